@@ -0,0 +1,173 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{ensure, Context, Result};
+use cugparck_cpu::{CompressedTable, EncodingStats, RainbowTable, RainbowTableStorage};
+use serde::Serialize;
+
+use crate::{map_table, print_json, AnalysisExportFormat, Analyze, OutputFormat, TableFormat};
+
+pub fn analyze(args: Analyze, output: OutputFormat) -> Result<()> {
+    ensure!(
+        TableFormat::from_path(&args.table) == Some(TableFormat::Compressed),
+        "Only compressed delta encoding tables (.rtcde) can be analyzed, use `compress` to convert the table first"
+    );
+
+    let mmap = map_table(&args.table)?;
+    let table = CompressedTable::load(&mmap)?;
+    let n = table.ctx().n;
+    let stats = table.encoding_stats();
+    let histogram = table.endpoint_histogram(args.buckets as usize);
+    let summary = AnalysisSummary::new(&stats, table.len());
+
+    match args.export {
+        Some(AnalysisExportFormat::Csv) => {
+            let stem = args
+                .table
+                .file_stem()
+                .context("The table should have a file name")?
+                .to_string_lossy();
+            fs::create_dir_all(&args.out_dir).context("Unable to create the output directory")?;
+
+            export_csv(&args.out_dir, &stem, &stats, &histogram, n)?;
+        }
+        None => match output {
+            OutputFormat::Json => print_json(&summary)?,
+            OutputFormat::Human => summary.print(),
+        },
+    }
+
+    Ok(())
+}
+
+/// Writes each statistic to a CSV file named after the table in the output directory.
+fn export_csv(
+    out_dir: &Path,
+    stem: &str,
+    stats: &EncodingStats,
+    histogram: &[u64],
+    n: usize,
+) -> Result<()> {
+    write_csv(
+        &out_dir.join(format!("{stem}_blocks.csv")),
+        "block,start,end,chains",
+        stats
+            .block_chains
+            .iter()
+            .enumerate()
+            .map(|(block, chains)| {
+                let start = block * stats.block_span;
+                let end = (start + stats.block_span).min(n);
+                format!("{block},{start},{end},{chains}")
+            }),
+    )?;
+
+    let buckets = histogram.len() as u128;
+    write_csv(
+        &out_dir.join(format!("{stem}_endpoints.csv")),
+        "bucket,start,end,endpoints",
+        histogram.iter().enumerate().map(|(bucket, count)| {
+            let start = (n as u128 * bucket as u128 / buckets) as usize;
+            let end = (n as u128 * (bucket + 1) as u128 / buckets) as usize;
+            format!("{bucket},{start},{end},{count}")
+        }),
+    )?;
+
+    // an endpoint costs at least the delimiter and the k remainder bits
+    let min_bits = stats.rice_parameter as usize + 1;
+    write_csv(
+        &out_dir.join(format!("{stem}_bit_costs.csv")),
+        "bits,quotient,endpoints",
+        stats
+            .bit_costs
+            .iter()
+            .enumerate()
+            .skip(min_bits)
+            .map(|(bits, count)| format!("{bits},{},{count}", bits - min_bits)),
+    )?;
+
+    write_csv(
+        &out_dir.join(format!("{stem}_rice.csv")),
+        "statistic,value",
+        [
+            format!("rice_parameter,{}", stats.rice_parameter),
+            format!(
+                "expected_bits_per_endpoint,{}",
+                stats.expected_bits_per_endpoint
+            ),
+            format!("bits_per_endpoint,{}", stats.bits_per_endpoint()),
+            format!("endpoint_bits,{}", stats.endpoint_bits()),
+            format!("mean_delta,{}", stats.mean_delta),
+            format!("block_span,{}", stats.block_span),
+        ]
+        .into_iter(),
+    )?;
+
+    println!(
+        "Exported the statistics to {}",
+        out_dir.join(format!("{stem}_*.csv")).display()
+    );
+
+    Ok(())
+}
+
+/// Writes a CSV file with the given header and rows.
+fn write_csv(path: &Path, header: &str, rows: impl Iterator<Item = String>) -> Result<()> {
+    let mut out = BufWriter::new(
+        File::create(path).with_context(|| format!("Unable to create {}", path.display()))?,
+    );
+
+    writeln!(out, "{header}")?;
+    for row in rows {
+        writeln!(out, "{row}")?;
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// A summary of the statistics of a table, printed when they aren't exported.
+#[derive(Serialize)]
+struct AnalysisSummary {
+    chains: usize,
+    blocks: usize,
+    empty_blocks: usize,
+    max_block_chains: usize,
+    rice_parameter: u8,
+    expected_bits_per_endpoint: f64,
+    bits_per_endpoint: f64,
+    mean_delta: f64,
+}
+
+impl AnalysisSummary {
+    fn new(stats: &EncodingStats, chains: usize) -> Self {
+        Self {
+            chains,
+            blocks: stats.block_chains.len(),
+            empty_blocks: stats.block_chains.iter().filter(|&&c| c == 0).count(),
+            max_block_chains: stats.block_chains.iter().copied().max().unwrap_or(0),
+            rice_parameter: stats.rice_parameter,
+            expected_bits_per_endpoint: stats.expected_bits_per_endpoint,
+            bits_per_endpoint: stats.bits_per_endpoint(),
+            mean_delta: stats.mean_delta,
+        }
+    }
+
+    /// Prints the summary in a human-readable way.
+    fn print(&self) {
+        println!("Chains: {}", self.chains);
+        println!(
+            "Blocks: {} ({} empty, at most {} chains per block)",
+            self.blocks, self.empty_blocks, self.max_block_chains
+        );
+        println!("Rice parameter: {}", self.rice_parameter);
+        println!(
+            "Bits per endpoint: {:.3} (expected {:.3})",
+            self.bits_per_endpoint, self.expected_bits_per_endpoint
+        );
+        println!("Mean endpoint difference: {:.1}", self.mean_delta);
+    }
+}
@@ -1,24 +1,717 @@
-use anyhow::{bail, Result};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context, Result};
 use crossterm::style::{style, Color, Stylize};
+use cugparck_commons::{Digest, HashType};
+use cugparck_cpu::{backend, search_wordlist, CugparckError, RainbowTable, SearchHit, TableHeader};
+use serde::Serialize;
 
-use crate::{load_tables_from_dir, search_tables, Attack};
+use crate::{
+    detect::{detect_digest, HashTypes},
+    false_alarm_filters, format_password, group_search_task_count, group_tables,
+    load_false_alarm_indexes, load_tables_from_dir, print_json, search_table_groups, Attack,
+    AvailableBackend, MappedTable, OutputFormat, TableGroup, TableSearchStats, TaskProgress,
+};
 
-pub fn attack(args: Attack) -> Result<()> {
-    let digest = hex::decode(args.digest)
-        .unwrap()
-        .as_slice()
-        .try_into()
-        .or_else(|_| bail!("The provided hexadecimal string is not a valid digest"))?;
+/// The number of digests of a file searched at once.
+/// The results are printed after each chunk.
+const DIGEST_CHUNK_SIZE: usize = 256;
 
-    let (mmaps, is_compressed) = load_tables_from_dir(&args.dir)?;
+pub fn attack(args: Attack, output: OutputFormat) -> Result<()> {
+    let tables = group_tables(load_tables_from_dir(&args.dir)?)?;
+    let indexes = tables
+        .iter()
+        .map(|tables| load_false_alarm_indexes(tables))
+        .collect::<Result<Vec<_>>>()?;
+    let mut groups = tables
+        .iter()
+        .zip(&indexes)
+        .map(|(tables, indexes)| TableGroup::new(tables, false_alarm_filters(indexes, tables)?))
+        .collect::<Result<Vec<_>>>()?;
 
-    let search = search_tables(digest, &mmaps, is_compressed, args.low_memory)?;
+    // only the tables generated with the given salt can contain the passwords
+    if let Some(salt) = &args.salt {
+        let salt = hex::decode(salt)?;
+        let salts = groups
+            .iter()
+            .map(|group| {
+                if group.ctx.salt.is_empty() {
+                    "no salt".to_owned()
+                } else {
+                    hex::encode(group.ctx.salt)
+                }
+            })
+            .collect::<BTreeSet<_>>();
 
-    if let Some(password) = search {
-        println!("{}", style(password).with(Color::Green));
-    } else {
-        eprintln!("{}", "No password found for the given digest".red());
+        groups.retain(|group| group.ctx.salt.as_slice() == salt);
+        ensure!(
+            !groups.is_empty(),
+            "The tables were generated with a different salt ({})",
+            salts.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+
+    // only the tables using the given hash function can contain the passwords
+    if let Some(hash_type) = args.hash_type {
+        let hash_type = HashType::from(hash_type);
+        let hash_types = table_hash_types(&groups);
+
+        groups.retain(|group| group.ctx.hash_type == hash_type);
+        ensure!(
+            !groups.is_empty(),
+            "The tables were generated with a different hash function ({hash_types})"
+        );
+    }
+
+    let wordlist = match &args.wordlist {
+        Some(path) => fs::read(path).context("Unable to read the wordlist")?,
+        None => Vec::new(),
+    };
+    let words = parse_wordlist(&wordlist);
+
+    let start = Instant::now();
+    let is_file = args.digest_file.is_some();
+    let mut stats = TableSearchStats::default();
+    let results = match (&args.digest, &args.digest_file) {
+        (Some(digest), _) => vec![attack_digest(digest, &args, &groups, &words, &mut stats)?],
+        (None, Some(file)) => attack_file(file, &args, &groups, &words, output, &mut stats)?,
+        (None, None) => unreachable!(),
+    };
+    let report = AttackReport::new(results, &stats, start.elapsed());
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Human if is_file => println!(
+            "Cracked {}/{} digests ({:.2}%)",
+            report.cracked,
+            report.results.len(),
+            report.success_rate() * 100.
+        ),
+        OutputFormat::Human => match &report.results[0].password {
+            Some(password) => {
+                println!("{}", style(password).with(Color::Green));
+                if args.verbose {
+                    println!("{}", report.results[0].location());
+                }
+            }
+            None => eprintln!("{}", "No password found for the given digest".red()),
+        },
+    }
+
+    if output == OutputFormat::Human && args.verbose {
+        report.print_breakdown();
     }
 
     Ok(())
 }
+
+/// A digest to attack.
+pub struct Target {
+    /// The user name of the digest in a digest file, or the digest itself.
+    pub label: String,
+    pub digest: Digest,
+    /// The hash functions that could have produced the digest.
+    pub hash_types: HashTypes,
+}
+
+impl Target {
+    /// Returns true if one of the groups could contain the password of the digest.
+    fn is_covered(&self, groups: &[TableGroup]) -> bool {
+        groups.iter().any(|group| {
+            group.ctx.could_contain_digest(&self.digest)
+                && self.hash_types.contains(group.ctx.hash_type)
+        })
+    }
+}
+
+/// Returns the hash functions used by the groups.
+fn table_hash_types(groups: &[TableGroup]) -> HashTypes {
+    groups.iter().fold(HashTypes::NONE, |hash_types, group| {
+        hash_types.union(HashTypes::only(group.ctx.hash_type))
+    })
+}
+
+/// Returns the hash function given with `--hash`, which replaces the detected ones.
+fn given_hash_types(args: &Attack) -> Option<HashTypes> {
+    args.hash_type
+        .map(|hash_type| HashTypes::only(hash_type.into()))
+}
+
+/// Returns the path of the table with the given table number.
+fn path_of(tables: &[MappedTable], table_number: usize) -> Option<&Path> {
+    tables
+        .iter()
+        .find(|table| {
+            matches!(TableHeader::from_bytes(&table.mmap), Ok(Some(header)) if header.ctx.tn == table_number)
+        })
+        .map(|table| table.path.as_path())
+}
+
+/// The results of an attack, printed with `--output json`.
+#[derive(Serialize)]
+pub struct AttackReport {
+    results: Vec<DigestResult>,
+    cracked: usize,
+    /// The searches of each table. Empty when the tables are searched with `--gpu`.
+    tables: Vec<TableSearchReport>,
+    duration_secs: f64,
+}
+
+/// The statistics of the searches of a table during an attack.
+#[derive(Serialize)]
+struct TableSearchReport {
+    table_number: usize,
+    path: PathBuf,
+    columns_searched: usize,
+    /// The matching endpoints whose chain didn't contain the password.
+    false_alarms: usize,
+    /// The false alarms skipped thanks to the false alarm index of the table.
+    filtered_false_alarms: usize,
+    /// The time spent searching the table, summed over the threads.
+    search_secs: f64,
+}
+
+impl AttackReport {
+    pub fn new(results: Vec<DigestResult>, stats: &TableSearchStats, duration: Duration) -> Self {
+        Self {
+            cracked: results
+                .iter()
+                .filter(|result| result.password.is_some())
+                .count(),
+            results,
+            tables: stats
+                .0
+                .iter()
+                .map(|(path, (table_number, stats))| TableSearchReport {
+                    table_number: *table_number,
+                    path: path.clone(),
+                    columns_searched: stats.columns,
+                    false_alarms: stats.false_alarms,
+                    filtered_false_alarms: stats.filtered_false_alarms,
+                    search_secs: stats.duration.as_secs_f64(),
+                })
+                .collect(),
+            duration_secs: duration.as_secs_f64(),
+        }
+    }
+
+    /// Prints the time spent on each table and the false alarms encountered, for the `--verbose` flag.
+    /// The tables are searched in parallel, so their search times add up to more than the total time.
+    fn print_breakdown(&self) {
+        for table in &self.tables {
+            let false_alarms = format!("{} false alarms", table.false_alarms);
+            println!(
+                "  {} ({}): {:.3}s, {} columns searched, {} ({} filtered)",
+                format!("table {}", table.table_number).cyan(),
+                table.path.display(),
+                table.search_secs,
+                table.columns_searched,
+                if table.false_alarms > 0 {
+                    false_alarms.yellow()
+                } else {
+                    false_alarms.green()
+                },
+                table.filtered_false_alarms,
+            );
+        }
+        println!(
+            "{}",
+            format!("Total time: {:.3}s", self.duration_secs).bold()
+        );
+    }
+
+    /// Returns the proportion of digests cracked.
+    fn success_rate(&self) -> f64 {
+        if self.results.is_empty() {
+            0.
+        } else {
+            self.cracked as f64 / self.results.len() as f64
+        }
+    }
+}
+
+/// How a password was found.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Method {
+    Table,
+    Wordlist,
+}
+
+/// The result of the attack of a single digest.
+#[derive(Serialize)]
+pub struct DigestResult {
+    /// The user name of the digest in a digest file, or the digest itself.
+    label: String,
+    digest: String,
+    password: Option<String>,
+    /// How the password was found.
+    method: Option<Method>,
+    /// The number of the table containing the password.
+    table_number: Option<usize>,
+    /// The column of the chain containing the password.
+    column: Option<usize>,
+    /// The path of the table containing the password.
+    table_path: Option<PathBuf>,
+}
+
+impl DigestResult {
+    /// `hit` is the hit of the digest along with the index of the group it was found in.
+    fn new(
+        label: String,
+        digest: &Digest,
+        hit: Option<(SearchHit, usize)>,
+        hex_output: bool,
+        groups: &[TableGroup],
+    ) -> Self {
+        let (hit, group) = match hit {
+            Some((hit, group)) => (Some(hit), Some(&groups[group])),
+            None => (None, None),
+        };
+
+        Self {
+            label,
+            digest: hex::encode(digest),
+            password: hit.map(|hit| format_password(hit.password, hex_output)),
+            method: hit.map(|_| Method::Table),
+            table_number: hit.map(|hit| hit.table_number),
+            column: hit.map(|hit| hit.column),
+            table_path: hit
+                .zip(group)
+                .and_then(|(hit, group)| path_of(group.tables, hit.table_number))
+                .map(Path::to_owned),
+        }
+    }
+
+    /// Sets the password to the word of the wordlist matching the digest.
+    fn set_word(&mut self, word: &[u8], hex_output: bool) {
+        self.password = Some(format_word(word, hex_output));
+        self.method = Some(Method::Wordlist);
+    }
+
+    /// Describes where the password was found, for the `--verbose` flag.
+    fn location(&self) -> String {
+        if self.method == Some(Method::Wordlist) {
+            return "  found in the wordlist".to_owned();
+        }
+
+        match (self.table_number, self.column, &self.table_path) {
+            (Some(table_number), Some(column), Some(path)) => format!(
+                "  found in table {table_number} ({}), column {column}",
+                path.display()
+            ),
+            (Some(table_number), Some(column), None) => {
+                format!("  found in table {table_number}, column {column}")
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// Attacks a single digest.
+fn attack_digest(
+    input: &str,
+    args: &Attack,
+    groups: &[TableGroup],
+    words: &[&[u8]],
+    stats: &mut TableSearchStats,
+) -> Result<DigestResult> {
+    let (digest, hash_types) = detect_digest(input)?;
+    let target = Target {
+        label: input.to_owned(),
+        digest,
+        hash_types: given_hash_types(args).unwrap_or(hash_types),
+    };
+
+    if !target.is_covered(groups) {
+        let warning = if target.hash_types.is_empty() {
+            format!(
+                "Warning: the digest is {} bytes long, which doesn't match any supported hash function",
+                digest.len()
+            )
+        } else {
+            format!(
+                "Warning: the digest looks like {}, but the tables use {}",
+                target.hash_types,
+                table_hash_types(groups)
+            )
+        };
+        eprintln!("{}", warning.yellow());
+    }
+
+    let hash_types = [target.hash_types];
+    let hit = if args.gpu {
+        search_tables_gpu(digest, target.hash_types, groups)?
+    } else {
+        let progress = TaskProgress::new(
+            group_search_task_count(groups, args.low_memory),
+            "Searching the tables",
+        );
+        search_table_groups(
+            &[digest],
+            Some(&hash_types),
+            groups,
+            args.low_memory,
+            &progress,
+            stats,
+        )?[0]
+    };
+
+    let mut result = DigestResult::new(target.label, &digest, hit, args.hex_output, groups);
+    if hit.is_none() {
+        if let Some(word) = search_words(words, &[digest], &hash_types, groups)[0] {
+            result.set_word(word, args.hex_output);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Attacks all the digests of a file.
+/// With the human output, the passwords are printed as they are found.
+fn attack_file(
+    file: &Path,
+    args: &Attack,
+    groups: &[TableGroup],
+    words: &[&[u8]],
+    output: OutputFormat,
+    stats: &mut TableSearchStats,
+) -> Result<Vec<DigestResult>> {
+    let content = fs::read_to_string(file).context("Unable to read the digest file")?;
+    let mut targets = parse_digest_file(&content);
+    if let Some(hash_types) = given_hash_types(args) {
+        for target in &mut targets {
+            target.hash_types = hash_types;
+        }
+    }
+
+    warn_uncovered(&targets, groups);
+
+    let progress = TaskProgress::new(
+        crack_task_count(targets.len(), groups, args.low_memory),
+        format!("Searching the tables for {} digests", targets.len()),
+    );
+
+    let (results, file_stats) = crack_targets(
+        &targets,
+        groups,
+        words,
+        args.low_memory,
+        args.hex_output,
+        &progress,
+        |result| {
+            if output == OutputFormat::Human {
+                progress.suspend(|| print_found(result, args));
+            }
+        },
+    )?;
+    *stats = file_stats;
+
+    Ok(results)
+}
+
+/// Returns the number of tasks of the progress of `crack_targets`.
+pub fn crack_task_count(targets: usize, groups: &[TableGroup], low_memory: bool) -> usize {
+    targets.div_ceil(DIGEST_CHUNK_SIZE) * group_search_task_count(groups, low_memory)
+}
+
+/// Searches for the passwords of the targets in the tables of the groups, then in the wordlist.
+/// The digests are searched by chunks, and `on_found` is called with each password as soon as it is found.
+/// Returns the results along with the statistics of the searches of each table.
+pub fn crack_targets(
+    targets: &[Target],
+    groups: &[TableGroup],
+    words: &[&[u8]],
+    low_memory: bool,
+    hex_output: bool,
+    progress: &TaskProgress,
+    mut on_found: impl FnMut(&DigestResult),
+) -> Result<(Vec<DigestResult>, TableSearchStats)> {
+    let mut results = Vec::with_capacity(targets.len());
+    let mut stats = TableSearchStats::default();
+    for chunk in targets.chunks(DIGEST_CHUNK_SIZE) {
+        let digests = chunk.iter().map(|target| target.digest).collect::<Vec<_>>();
+        let hash_types = chunk
+            .iter()
+            .map(|target| target.hash_types)
+            .collect::<Vec<_>>();
+        let found = search_table_groups(
+            &digests,
+            Some(&hash_types),
+            groups,
+            low_memory,
+            progress,
+            &mut stats,
+        )?;
+
+        for (target, hit) in chunk.iter().zip(found) {
+            let result = DigestResult::new(
+                target.label.clone(),
+                &target.digest,
+                hit,
+                hex_output,
+                groups,
+            );
+            if result.password.is_some() {
+                on_found(&result);
+            }
+            results.push(result);
+        }
+    }
+
+    // the wordlist is only hashed once, for all the digests missing from the tables
+    if !words.is_empty() {
+        let missed = (0..results.len())
+            .filter(|&i| results[i].password.is_none())
+            .collect::<Vec<_>>();
+        let digests = missed
+            .iter()
+            .map(|&i| targets[i].digest)
+            .collect::<Vec<_>>();
+        let hash_types = missed
+            .iter()
+            .map(|&i| targets[i].hash_types)
+            .collect::<Vec<_>>();
+        progress.set_message(format!(
+            "Searching the wordlist for {} digests",
+            missed.len()
+        ));
+
+        for (i, word) in missed
+            .into_iter()
+            .zip(search_words(words, &digests, &hash_types, groups))
+        {
+            if let Some(word) = word {
+                results[i].set_word(word, hex_output);
+                on_found(&results[i]);
+            }
+        }
+    }
+
+    Ok((results, stats))
+}
+
+/// Warns about the digests of a file that no table could contain,
+/// grouped by the hash functions they were detected as.
+fn warn_uncovered(targets: &[Target], groups: &[TableGroup]) {
+    let mut uncovered = BTreeMap::<_, usize>::new();
+    for target in targets.iter().filter(|target| !target.is_covered(groups)) {
+        *uncovered.entry(target.hash_types).or_default() += 1;
+    }
+
+    for (hash_types, count) in uncovered {
+        let warning = if hash_types.is_empty() {
+            format!("Warning: {count} digests don't match any supported hash function")
+        } else {
+            format!(
+                "Warning: {count} digests look like {hash_types}, but the tables use {}",
+                table_hash_types(groups)
+            )
+        };
+        eprintln!("{}", warning.yellow());
+    }
+}
+
+/// Prints the password of a digest of a file if it was found.
+fn print_found(result: &DigestResult, args: &Attack) {
+    if let Some(password) = &result.password {
+        println!("{}:{}", result.label, style(password).with(Color::Green));
+        if args.verbose {
+            println!("{}", result.location());
+        }
+    }
+}
+
+/// Searches the wordlist for the passwords of digests.
+/// Each digest is searched with the hash function and salt of every group of tables that could contain it,
+/// and the matching word is returned.
+fn search_words<'w>(
+    words: &[&'w [u8]],
+    digests: &[Digest],
+    hash_types: &[HashTypes],
+    groups: &[TableGroup],
+) -> Vec<Option<&'w [u8]>> {
+    let mut found = vec![None; digests.len()];
+    if words.is_empty() {
+        return found;
+    }
+
+    // the groups using the same hash function and salt only differ by their charset or lengths
+    let mut searched = Vec::new();
+    for group in groups {
+        let ctx = &group.ctx;
+        let key = (ctx.hash_type, ctx.salt, ctx.salt_position);
+        if searched.contains(&key) {
+            continue;
+        }
+        searched.push(key);
+
+        let remaining = (0..digests.len())
+            .filter(|&i| found[i].is_none() && hash_types[i].contains(ctx.hash_type))
+            .collect::<Vec<_>>();
+        let remaining_digests = remaining.iter().map(|&i| digests[i]).collect::<Vec<_>>();
+
+        for (i, word) in remaining
+            .into_iter()
+            .zip(search_wordlist(words, &remaining_digests, ctx))
+        {
+            found[i] = word.map(|word| words[word]);
+        }
+    }
+
+    found
+}
+
+/// Parses a wordlist containing one word per line.
+/// The words are kept as bytes, since wordlists are not always valid UTF-8.
+pub fn parse_wordlist(content: &[u8]) -> Vec<&[u8]> {
+    content
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Formats a word of the wordlist found for a digest, like `format_password` does for the passwords of the tables.
+fn format_word(word: &[u8], hex: bool) -> String {
+    match std::str::from_utf8(word) {
+        Ok(word) if !hex && !word.chars().any(char::is_control) => word.to_owned(),
+        _ => format!("$HEX[{}]", hex::encode(word)),
+    }
+}
+
+/// Searches for a digest in the tables of the groups that could contain it, one after the other,
+/// computing the endpoints of the digest on the fastest GPU backend available.
+/// Only the groups using one of the hash functions of the digest are searched.
+/// Returns the hit along with the index of the group it was found in.
+fn search_tables_gpu(
+    digest: Digest,
+    hash_types: HashTypes,
+    groups: &[TableGroup],
+) -> Result<Option<(SearchHit, usize)>> {
+    let backend = AvailableBackend::default();
+    ensure!(
+        backend != AvailableBackend::Cpu,
+        "Cugparck was built without any GPU backend"
+    );
+
+    let groups = groups.iter().enumerate().filter(|(_, group)| {
+        group.ctx.could_contain_digest(&digest) && hash_types.contains(group.ctx.hash_type)
+    });
+
+    for (group_index, group) in groups {
+        for table in group.tables {
+            if let Some(hit) = search_gpu(&table.table()?, digest, backend)? {
+                return Ok(Some((hit, group_index)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Searches for a digest in a table, computing the endpoints of the digest with the given backend.
+fn search_gpu<T: RainbowTable>(
+    table: &T,
+    digest: Digest,
+    backend: AvailableBackend,
+) -> Result<Option<SearchHit>, CugparckError> {
+    match backend {
+        AvailableBackend::Auto => search_gpu(table, digest, backend.resolve()),
+        AvailableBackend::Cpu => table.search_gpu::<backend::Cpu>(digest),
+        #[cfg(feature = "cuda")]
+        AvailableBackend::Cuda => table.search_gpu::<backend::Cuda>(digest),
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        AvailableBackend::Vulkan => table.search_gpu::<backend::Vulkan>(digest),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx12 => table.search_gpu::<backend::Dx12>(digest),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx11 => table.search_gpu::<backend::Dx11>(digest),
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        AvailableBackend::Metal => table.search_gpu::<backend::Metal>(digest),
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        AvailableBackend::OpenGL => table.search_gpu::<backend::OpenGL>(digest),
+    }
+}
+
+/// Parses a file containing one digest per line, optionally prefixed by a user name (`user:digest`).
+/// Returns the digests along with a label to display them, which is the user name if there is one,
+/// and the hash functions detected for them.
+/// Invalid lines are skipped with a warning.
+pub fn parse_digest_file(content: &str) -> Vec<Target> {
+    let mut targets = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (label, hex) = match line.rsplit_once(':') {
+            Some((user, hex)) => (user.to_owned(), hex),
+            None => (line.to_owned(), line),
+        };
+
+        match detect_digest(hex) {
+            Ok((digest, hash_types)) => targets.push(Target {
+                label,
+                digest,
+                hash_types,
+            }),
+            Err(_) => eprintln!(
+                "{}",
+                format!(
+                    "Warning: skipping line {}, which is not a valid digest",
+                    i + 1
+                )
+                .yellow()
+            ),
+        }
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_word, parse_digest_file, parse_wordlist};
+
+    #[test]
+    fn test_parse_digest_file() {
+        let content = "aabbcc\n\nadmin:DDEEFF\nnot a digest\nuser:with:colons:0x0102\n";
+        let targets = parse_digest_file(content);
+
+        let expected = [
+            ("aabbcc", vec![0xaa, 0xbb, 0xcc]),
+            ("admin", vec![0xdd, 0xee, 0xff]),
+            ("user:with:colons", vec![0x01, 0x02]),
+        ];
+
+        assert_eq!(expected.len(), targets.len());
+        for (target, (expected_label, expected_digest)) in targets.iter().zip(expected) {
+            assert_eq!(expected_label, target.label);
+            assert_eq!(expected_digest, target.digest.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_parse_wordlist() {
+        let words = parse_wordlist(b"password\r\n\n123456\nmot de passe \xe9\n");
+        assert_eq!(
+            vec![b"password".as_slice(), b"123456", b"mot de passe \xe9"],
+            words
+        );
+
+        assert_eq!("123456", format_word(words[1], false));
+        assert_eq!("$HEX[313233343536]", format_word(words[1], true));
+        assert_eq!(
+            "$HEX[6d6f7420646520706173736520e9]",
+            format_word(words[2], false)
+        );
+    }
+}
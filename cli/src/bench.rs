@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use crossterm::style::Stylize;
+use cugparck_commons::RainbowTableCtx;
+use cugparck_cpu::{backend, benchmark, BenchmarkResult, CugparckError, RainbowTableCtxBuilder};
+use indicatif::{HumanBytes, HumanDuration};
+
+use crate::{with_charset, AvailableBackend, Bench};
+
+pub fn bench(args: Bench) -> Result<()> {
+    let ctx = with_charset(RainbowTableCtxBuilder::new(), &args.charset, args.utf8)?
+        .hash(args.hash_type.into())
+        .chain_length(args.chain_length as usize)
+        .min_password_length(args.min_password_length)
+        .max_password_length(args.max_password_length)
+        .build()?;
+
+    let chains = args.chains as usize;
+    let columns = (args.columns as usize).min(ctx.t - 1);
+
+    println!("Computing {chains} chains of {columns} columns on each backend\n");
+
+    // every backend is measured, so there is nothing to choose automatically
+    for backend in AvailableBackend::value_variants()
+        .iter()
+        .filter(|&&backend| backend != AvailableBackend::Auto)
+    {
+        let name = backend.name();
+
+        match run_benchmark(*backend, ctx, chains, columns) {
+            Ok(result) => print_result(&result, &ctx),
+            Err(CugparckError::NoBackend { source, .. }) => {
+                println!("{}", format!("{name}: unavailable ({source})").dark_grey())
+            }
+            Err(err) => println!("{}", format!("{name}: {err}").red()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the benchmark on the given backend.
+pub fn run_benchmark(
+    backend: AvailableBackend,
+    ctx: RainbowTableCtx,
+    chains: usize,
+    columns: usize,
+) -> Result<BenchmarkResult, CugparckError> {
+    match backend {
+        AvailableBackend::Auto => run_benchmark(backend.resolve(), ctx, chains, columns),
+        AvailableBackend::Cpu => benchmark::<backend::Cpu>(ctx, chains, columns),
+        #[cfg(feature = "cuda")]
+        AvailableBackend::Cuda => benchmark::<backend::Cuda>(ctx, chains, columns),
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        AvailableBackend::Vulkan => benchmark::<backend::Vulkan>(ctx, chains, columns),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx12 => benchmark::<backend::Dx12>(ctx, chains, columns),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx11 => benchmark::<backend::Dx11>(ctx, chains, columns),
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        AvailableBackend::Metal => benchmark::<backend::Metal>(ctx, chains, columns),
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        AvailableBackend::OpenGL => benchmark::<backend::OpenGL>(ctx, chains, columns),
+    }
+}
+
+/// Prints the result of a benchmark.
+fn print_result(result: &BenchmarkResult, ctx: &RainbowTableCtx) {
+    println!("{}", result.backend.bold());
+    println!("  Time: {:.2?}", result.duration);
+    println!("  Hashes per second: {:.0}", result.hashes_per_second());
+    println!(
+        "  Memory bandwidth: {}/s",
+        HumanBytes(result.memory_bandwidth() as u64)
+    );
+
+    let estimated = result.estimated_generation_time(ctx);
+    println!(
+        "  Estimated generation time of a table: {}",
+        HumanDuration(estimated.max(Duration::from_secs(1)))
+    );
+}
@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use crossterm::style::{style, Color, Stylize};
+use cugparck_commons::{Digest, Password, RainbowTableCtx};
+use cugparck_cpu::{backend, brute_force, parse_mask, CugparckError, RainbowTableCtxBuilder};
+use indicatif::HumanCount;
+use serde::Serialize;
+
+use crate::{
+    attack::parse_digest_file, format_password, print_json, with_charset, AvailableBackend,
+    Bruteforce, OutputFormat, TaskProgress,
+};
+
+pub fn bruteforce(args: Bruteforce, output: OutputFormat) -> Result<()> {
+    let mut ctx_builder = with_charset(RainbowTableCtxBuilder::new(), &args.charset, args.utf8)?
+        .hash(args.hash_type.into())
+        .min_password_length(args.min_password_length)
+        .max_password_length(args.max_password_length);
+
+    if let Some(salt) = &args.salt {
+        ctx_builder = ctx_builder.salt(&hex::decode(salt)?, args.salt_position.into());
+    }
+
+    if let Some(mask) = &args.mask {
+        let charsets = parse_mask(mask)?;
+        ctx_builder =
+            ctx_builder.position_charsets(&charsets.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    }
+
+    let ctx = ctx_builder.build()?;
+
+    let targets = match (&args.digest, &args.digest_file) {
+        (Some(digest), _) => {
+            let digest: Digest = hex::decode(digest)?
+                .as_slice()
+                .try_into()
+                .or_else(|_| bail!("The provided hexadecimal string is not a valid digest"))?;
+            vec![(hex::encode(digest), digest)]
+        }
+        (None, Some(file)) => {
+            let content = fs::read_to_string(file).context("Unable to read the digest file")?;
+            parse_digest_file(&content)
+                .into_iter()
+                .map(|target| (target.label, target.digest))
+                .collect()
+        }
+        (None, None) => unreachable!(),
+    };
+
+    let wrong_length = targets
+        .iter()
+        .filter(|(_, digest)| !ctx.could_contain_digest(digest))
+        .count();
+    if wrong_length > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: {wrong_length} digest(s) are not {} bytes long and cannot be found",
+                ctx.hash_type.digest_size()
+            )
+            .yellow()
+        );
+    }
+
+    let backend = args.backend.resolve();
+    let progress = TaskProgress::new(
+        1,
+        format!(
+            "Hashing {} passwords on the {} backend",
+            HumanCount(ctx.n as u64),
+            backend.name()
+        ),
+    );
+
+    let start = Instant::now();
+    let digests = targets
+        .iter()
+        .map(|(_, digest)| *digest)
+        .collect::<Vec<_>>();
+    let found = run_brute_force(backend, &digests, ctx, &progress)?;
+    drop(progress);
+
+    let results = targets
+        .into_iter()
+        .zip(found)
+        .map(|((label, digest), password)| DigestResult {
+            label,
+            digest: hex::encode(digest),
+            password: password.map(|password| format_password(password, args.hex_output)),
+        })
+        .collect();
+    let report = BruteforceReport::new(results, start.elapsed(), ctx.n);
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Human => report.print(args.digest_file.is_some()),
+    }
+
+    Ok(())
+}
+
+/// Runs the brute force attack on the given backend.
+fn run_brute_force(
+    backend: AvailableBackend,
+    digests: &[Digest],
+    ctx: RainbowTableCtx,
+    progress: &TaskProgress,
+) -> Result<Vec<Option<Password>>, CugparckError> {
+    match backend {
+        AvailableBackend::Auto => run_brute_force(backend.resolve(), digests, ctx, progress),
+        AvailableBackend::Cpu => brute_force::<backend::Cpu>(digests, ctx, progress),
+        #[cfg(feature = "cuda")]
+        AvailableBackend::Cuda => brute_force::<backend::Cuda>(digests, ctx, progress),
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        AvailableBackend::Vulkan => brute_force::<backend::Vulkan>(digests, ctx, progress),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx12 => brute_force::<backend::Dx12>(digests, ctx, progress),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx11 => brute_force::<backend::Dx11>(digests, ctx, progress),
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        AvailableBackend::Metal => brute_force::<backend::Metal>(digests, ctx, progress),
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        AvailableBackend::OpenGL => brute_force::<backend::OpenGL>(digests, ctx, progress),
+    }
+}
+
+/// The results of a brute force attack, printed with `--output json`.
+#[derive(Serialize)]
+struct BruteforceReport {
+    results: Vec<DigestResult>,
+    cracked: usize,
+    /// The number of passwords in the search space.
+    search_space: usize,
+    duration_secs: f64,
+}
+
+/// The result of the brute force attack of a single digest.
+#[derive(Serialize)]
+struct DigestResult {
+    /// The user name of the digest in a digest file, or the digest itself.
+    label: String,
+    digest: String,
+    password: Option<String>,
+}
+
+impl BruteforceReport {
+    fn new(results: Vec<DigestResult>, duration: Duration, search_space: usize) -> Self {
+        Self {
+            cracked: results
+                .iter()
+                .filter(|result| result.password.is_some())
+                .count(),
+            results,
+            search_space,
+            duration_secs: duration.as_secs_f64(),
+        }
+    }
+
+    /// Prints the results in a human-readable way.
+    /// The results of a digest file are prefixed by their label.
+    fn print(&self, is_file: bool) {
+        if !is_file {
+            match &self.results[0].password {
+                Some(password) => println!("{}", style(password).with(Color::Green)),
+                None => eprintln!("{}", "No password found for the given digest".red()),
+            }
+            return;
+        }
+
+        for result in &self.results {
+            if let Some(password) = &result.password {
+                println!("{}:{}", result.label, style(password).with(Color::Green));
+            }
+        }
+        println!("Cracked {}/{} digests", self.cracked, self.results.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::{bruteforce::bruteforce, Cli, Commands, OutputFormat};
+
+    #[test]
+    fn test_bruteforce_cpu() {
+        // md5("cab")
+        let cli = Cli::try_parse_from([
+            "cugparck-cli",
+            "bruteforce",
+            "md5",
+            "16ecfd64586ec6c1ab212762c2c38a90",
+            "--charset",
+            "abc",
+            "--max-password-length",
+            "3",
+            "--backend",
+            "cpu",
+        ])
+        .unwrap();
+
+        match cli.commands {
+            Commands::Bruteforce(args) => bruteforce(args, OutputFormat::Json).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+}
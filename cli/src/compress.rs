@@ -1,27 +1,255 @@
-use crate::{create_dir_to_store_tables, load_tables_from_dir, Compress};
+use crate::{
+    compress_table, create_dir_to_store_tables, decompress_table, load_tables_from_dir,
+    replace_table, Compress, MappedTable, TableFormat, TaskProgress,
+};
+
+use std::path::Path;
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{ensure, Result};
+use cugparck_commons::RainbowTableCtx;
 use cugparck_cpu::{
-    CompressedTable, Deserialize, Infallible, RainbowTable, RainbowTableStorage, SimpleTable,
+    ArchivedSimpleTable, CompressedTable, CugparckError, RainbowTable, RainbowTableStorage,
+    SimpleTable, TableHeader, TruncatedTable,
 };
 
 pub fn compress(args: Compress) -> Result<()> {
-    create_dir_to_store_tables(&args.out_dir)?;
+    let in_dir = match (&args.in_place, &args.in_dir, &args.out_dir) {
+        (Some(dir), _, _) => dir,
+        (None, Some(in_dir), Some(out_dir)) => {
+            create_dir_to_store_tables(out_dir)?;
+            in_dir
+        }
+        _ => unreachable!(),
+    };
+
+    // the tables that are already compressed are left as they are.
+    let tables = load_tables_from_dir(in_dir)?
+        .into_iter()
+        .filter(|table| table.format == TableFormat::Simple)
+        .collect::<Vec<_>>();
+
+    ensure!(!tables.is_empty(), "The tables are already compressed");
 
-    let (mmaps, is_compressed) = load_tables_from_dir(&args.in_dir)?;
+    let progress = TaskProgress::new(tables.len(), "Compressing the tables");
 
-    ensure!(!is_compressed, "The tables are already compressed");
+    // the tables are converted straight from their memory map, so they never have to fit in memory.
+    for MappedTable {
+        path: table_path,
+        mmap,
+        ..
+    } in tables
+    {
+        // the table is already stored with zstd
+        if args.zstd_level.is_some()
+            && args.in_place.is_some()
+            && table_path.extension() == Some("rtz".as_ref())
+        {
+            progress.next_task();
+            continue;
+        }
 
-    for mmap in mmaps {
-        let ar = SimpleTable::load(&mmap)?;
-        let path = args.out_dir.join(format!("table_{}.rtcde", ar.ctx().tn));
+        progress.set_message(format!("Compressing {}", table_path.display()));
 
-        let table: SimpleTable = ar
-            .deserialize(&mut Infallible)
-            .context("Unable to deserialize the rainbow table")?;
+        let table = SimpleTable::load(&mmap)?;
+        let stats = TableHeader::from_bytes(&mmap)?.and_then(|header| header.stats);
 
-        table.into_rainbow_table::<CompressedTable>().store(&path)?;
+        if let Some(level) = args.zstd_level {
+            store_converted(
+                table,
+                &table_path,
+                "rtz",
+                &args,
+                |path| compress_table(&mmap, path, level),
+                |bytes| {
+                    let decompressed = decompress_table(bytes)?;
+                    let ar = SimpleTable::load(&decompressed)?;
+                    Ok((ar.len(), ar.ctx()))
+                },
+            )?;
+        } else if args.truncate {
+            store_converted(
+                table,
+                &table_path,
+                "rtt",
+                &args,
+                |path| {
+                    TruncatedTable::store_streamed(
+                        table,
+                        path,
+                        &path.with_extension("spill"),
+                        stats,
+                        &progress,
+                    )
+                },
+                |bytes| {
+                    let ar = TruncatedTable::load(bytes)?;
+                    Ok((ar.len(), ar.ctx()))
+                },
+            )?;
+        } else {
+            store_converted(
+                table,
+                &table_path,
+                "rtcde",
+                &args,
+                |path| {
+                    CompressedTable::store_streamed(
+                        table,
+                        path,
+                        &path.with_extension("spill"),
+                        args.endpoints_only,
+                        stats,
+                        &progress,
+                    )
+                },
+                |bytes| {
+                    let ar = CompressedTable::load(bytes)?;
+                    Ok((ar.len(), ar.ctx()))
+                },
+            )?;
+        }
+
+        progress.next_task();
     }
 
     Ok(())
 }
+
+/// Stores a converted rainbow table in the output directory or in place of the original table.
+/// `store` writes the converted table to the given path,
+/// and `load` loads it back from its bytes to return its number of chains and its context.
+fn store_converted(
+    table: &ArchivedSimpleTable,
+    table_path: &Path,
+    ext: &str,
+    args: &Compress,
+    store: impl FnOnce(&Path) -> Result<(), CugparckError>,
+    load: impl FnOnce(&[u8]) -> Result<(usize, RainbowTableCtx)>,
+) -> Result<()> {
+    let ctx = table.ctx();
+
+    // the converted table keeps the name of the original table
+    if let Some(out_dir) = &args.out_dir {
+        store(&out_dir.join(table_path.with_extension(ext).file_name().unwrap()))?;
+        return Ok(());
+    }
+
+    replace_table(table_path, ext, store, |bytes| {
+        let (len, converted_ctx) = load(bytes)?;
+        ensure!(
+            len == table.len() && converted_ctx == ctx,
+            "The compressed table doesn't match the original table"
+        );
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use cugparck_cpu::{
+        backend::Cpu, CompressedTable, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage,
+        SimpleTable, TruncatedTable,
+    };
+    use memmap2::Mmap;
+
+    use crate::{compress::compress, decompress::decompress, temp_path, Compress, Decompress};
+
+    fn chains<T: RainbowTable>(table: &T) -> HashSet<(usize, usize)> {
+        table
+            .iter()
+            .map(|chain| (chain.startpoint.get(), chain.endpoint.get()))
+            .collect()
+    }
+
+    fn mmap(path: &Path) -> Mmap {
+        // SAFETY: the file exists and is not being modified anywhere else.
+        unsafe { Mmap::map(&fs::File::open(path).unwrap()).unwrap() }
+    }
+
+    #[test]
+    fn test_convert_in_place() {
+        let dir = temp_path("in_place");
+        fs::create_dir_all(&dir).unwrap();
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .table_number(1)
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        table.store(&dir.join("table_1.rt")).unwrap();
+
+        compress(Compress {
+            out_dir: None,
+            in_dir: None,
+            in_place: Some(dir.clone()),
+            endpoints_only: false,
+            zstd_level: None,
+            truncate: false,
+        })
+        .unwrap();
+
+        assert!(!dir.join("table_1.rt").exists());
+        assert!(!dir.join("table_1.rtcde.tmp").exists());
+        let compressed = mmap(&dir.join("table_1.rtcde"));
+        assert_eq!(
+            chains(&table),
+            chains(CompressedTable::load(&compressed).unwrap())
+        );
+        drop(compressed);
+
+        decompress(Decompress {
+            out_dir: None,
+            in_dir: None,
+            in_place: Some(dir.clone()),
+        })
+        .unwrap();
+
+        assert!(!dir.join("table_1.rtcde").exists());
+        let decompressed = mmap(&dir.join("table_1.rt"));
+        assert_eq!(
+            chains(&table),
+            chains(SimpleTable::load(&decompressed).unwrap())
+        );
+        drop(decompressed);
+
+        compress(Compress {
+            out_dir: None,
+            in_dir: None,
+            in_place: Some(dir.clone()),
+            endpoints_only: false,
+            zstd_level: None,
+            truncate: true,
+        })
+        .unwrap();
+
+        assert!(!dir.join("table_1.rt").exists());
+        let truncated = mmap(&dir.join("table_1.rtt"));
+        assert_eq!(
+            chains(&table),
+            chains(TruncatedTable::load(&truncated).unwrap())
+        );
+        drop(truncated);
+
+        decompress(Decompress {
+            out_dir: None,
+            in_dir: None,
+            in_place: Some(dir.clone()),
+        })
+        .unwrap();
+
+        assert!(!dir.join("table_1.rtt").exists());
+        let decompressed = mmap(&dir.join("table_1.rt"));
+        assert_eq!(
+            chains(&table),
+            chains(SimpleTable::load(&decompressed).unwrap())
+        );
+        drop(decompressed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
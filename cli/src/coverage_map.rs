@@ -0,0 +1,37 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use anyhow::{Context, Result};
+use cugparck_cpu::RainbowTable;
+
+use crate::{load_tables_from_dir, CoverageMap};
+
+pub fn coverage_map(args: CoverageMap) -> Result<()> {
+    let tables = load_tables_from_dir(&args.dir)?;
+
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).context("Unable to create the output file")?,
+        )),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    writeln!(out, "table,bucket,start,end,endpoints")?;
+
+    for table in &tables {
+        let table = table.table()?;
+        let (ctx, map) = (table.ctx(), table.coverage_map(args.buckets as usize));
+
+        for (bucket, count) in map.into_iter().enumerate() {
+            let start = (ctx.n as u128 * bucket as u128 / args.buckets as u128) as usize;
+            let end = (ctx.n as u128 * (bucket + 1) as u128 / args.buckets as u128) as usize;
+            writeln!(out, "{},{bucket},{start},{end},{count}", ctx.tn)?;
+        }
+    }
+
+    out.flush()?;
+
+    Ok(())
+}
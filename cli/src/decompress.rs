@@ -1,26 +1,75 @@
-use crate::{create_dir_to_store_tables, load_tables_from_dir, Decompress};
+use crate::{
+    create_dir_to_store_tables, load_tables_from_dir, replace_table, Decompress, MappedTable,
+    TableFormat, TaskProgress,
+};
 
-use anyhow::{ensure, Context, Result};
+use anyhow::{ensure, Result};
 use cugparck_cpu::{
-    CompressedTable, Deserialize, Infallible, RainbowTable, RainbowTableStorage, SimpleTable,
+    CompressedTable, RainbowTable, RainbowTableStorage, SimpleTable, TableHeader, TruncatedTable,
 };
 
 pub fn decompress(args: Decompress) -> Result<()> {
-    create_dir_to_store_tables(&args.out_dir)?;
+    let in_dir = match (&args.in_place, &args.in_dir, &args.out_dir) {
+        (Some(dir), _, _) => dir,
+        (None, Some(in_dir), Some(out_dir)) => {
+            create_dir_to_store_tables(out_dir)?;
+            in_dir
+        }
+        _ => unreachable!(),
+    };
+
+    // the tables that are already decompressed are left as they are.
+    let tables = load_tables_from_dir(in_dir)?
+        .into_iter()
+        .filter(|table| table.format != TableFormat::Simple)
+        .collect::<Vec<_>>();
+
+    ensure!(!tables.is_empty(), "The tables are already decompressed");
 
-    let (mmaps, is_compressed) = load_tables_from_dir(&args.in_dir)?;
+    let progress = TaskProgress::new(tables.len(), "Decompressing the tables");
 
-    ensure!(is_compressed, "The tables are already decompressed");
+    for MappedTable {
+        path: table_path,
+        format,
+        mmap,
+    } in tables
+    {
+        progress.set_message(format!("Decompressing {}", table_path.display()));
+        let stats = TableHeader::from_bytes(&mmap)?.and_then(|header| header.stats);
 
-    for mmap in mmaps {
-        let ar = CompressedTable::load(&mmap)?;
-        let path = args.out_dir.join(format!("table_{}.rt", ar.ctx().tn));
+        // the chains are decoded straight from the memory-mapped table,
+        // so only the decompressed table has to fit in memory. It is still entirely built before being stored.
+        let table = if format == TableFormat::Compressed {
+            SimpleTable::from_rainbow_table_with_progress(CompressedTable::load(&mmap)?, &progress)
+        } else {
+            // the truncated endpoints can't be decoded, so they are computed again from the startpoints.
+            let ar = TruncatedTable::load(&mmap)?;
+            SimpleTable::from_vec(ar.par_chains(&progress), ar.ctx())
+        };
+        let table = table.with_stats(stats);
+        let ctx = table.ctx();
+        drop(mmap);
 
-        let table: CompressedTable = ar
-            .deserialize(&mut Infallible)
-            .context("Unable to deserialize the rainbow table")?;
+        if let Some(out_dir) = &args.out_dir {
+            table.store(&out_dir.join(table_path.with_extension("rt").file_name().unwrap()))?;
+            progress.next_task();
+            continue;
+        }
 
-        table.into_rainbow_table::<SimpleTable>().store(&path)?;
+        replace_table(
+            &table_path,
+            "rt",
+            |path| table.store(path),
+            |bytes| {
+                let ar = SimpleTable::load(bytes)?;
+                ensure!(
+                    ar.len() == table.len() && ar.ctx() == ctx,
+                    "The decompressed table doesn't match the original table"
+                );
+                Ok(())
+            },
+        )?;
+        progress.next_task();
     }
 
     Ok(())
@@ -0,0 +1,159 @@
+//! Heuristic detection of the hash function of a digest, from its length and its prefix.
+
+use std::fmt::{self, Display};
+
+use anyhow::{bail, Result};
+use cugparck_commons::{Digest, HashType};
+
+use crate::check_hex;
+
+/// The prefixes put in front of the digests of some hash functions, like John the Ripper and MySQL do.
+const PREFIXES: [(&str, HashType); 5] = [
+    ("$NT$", HashType::Ntlm),
+    ("$LM$", HashType::Lm),
+    ("$dynamic_0$", HashType::Md5),
+    ("$dynamic_2$", HashType::Md5Md5),
+    ("*", HashType::Mysql41),
+];
+
+/// A set of hash functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HashTypes(u32);
+
+impl HashTypes {
+    /// All the supported hash functions.
+    pub const ALL: Self = Self((1 << HashType::ALL.len()) - 1);
+
+    /// The empty set.
+    pub const NONE: Self = Self(0);
+
+    /// Returns the set containing a single hash function.
+    pub fn only(hash_type: HashType) -> Self {
+        Self(1 << hash_type as usize)
+    }
+
+    /// Returns the set of the hash functions producing digests of the given size.
+    pub fn with_digest_size(size: usize) -> Self {
+        HashType::ALL
+            .into_iter()
+            .filter(|hash_type| hash_type.digest_size() == size)
+            .fold(Self::NONE, |set, hash_type| {
+                set.union(Self::only(hash_type))
+            })
+    }
+
+    /// Returns the hash functions that are in both sets.
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns the hash functions that are in either set.
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn contains(self, hash_type: HashType) -> bool {
+        self.0 & Self::only(hash_type).0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterates over the hash functions of the set.
+    pub fn iter(self) -> impl Iterator<Item = HashType> {
+        HashType::ALL
+            .into_iter()
+            .filter(move |&hash_type| self.contains(hash_type))
+    }
+}
+
+impl Display for HashTypes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no known hash function");
+        }
+
+        let names = self
+            .iter()
+            .map(|hash_type| format!("{hash_type:?}"))
+            .collect::<Vec<_>>();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// Parses a digest in hexadecimal and detects the hash functions that could have produced it.
+/// The digest can be prefixed to tell its hash function, like `$NT$...` or `*...` for MySQL.
+/// Otherwise, only its length is used and every hash function with this digest size is returned.
+pub fn detect_digest(input: &str) -> Result<(Digest, HashTypes)> {
+    let input = input.trim();
+    let (hex, hash_types) = PREFIXES
+        .iter()
+        .find_map(|&(prefix, hash_type)| {
+            input
+                .strip_prefix(prefix)
+                .map(|hex| (hex, HashTypes::only(hash_type)))
+        })
+        .unwrap_or((input, HashTypes::ALL));
+
+    let digest: Digest = match hex::decode(check_hex(hex)?)?.as_slice().try_into() {
+        Ok(digest) => digest,
+        Err(_) => bail!("The provided hexadecimal string is not a valid digest"),
+    };
+
+    // a prefixed digest of the wrong length can't come from the hash function of its prefix
+    let hash_types = hash_types.intersection(HashTypes::with_digest_size(digest.len()));
+    Ok((digest, hash_types))
+}
+
+/// Validates a digest given on the command line, see `detect_digest`.
+pub fn check_digest(input: &str) -> Result<String> {
+    detect_digest(input)?;
+    Ok(input.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::HashType;
+
+    use super::{detect_digest, HashTypes};
+
+    #[test]
+    fn test_detect_digest() {
+        // md5("abc")
+        let (digest, hash_types) = detect_digest("900150983cd24fb0d6963f7d28e17f72").unwrap();
+        assert_eq!(16, digest.len());
+        assert_eq!(
+            vec![
+                HashType::Ntlm,
+                HashType::Md4,
+                HashType::Md5,
+                HashType::Md5Md5
+            ],
+            hash_types.iter().collect::<Vec<_>>()
+        );
+
+        let (_, hash_types) = detect_digest("$NT$0CB6948805F797BF2A82807973B89537").unwrap();
+        assert_eq!(HashTypes::only(HashType::Ntlm), hash_types);
+
+        let (_, hash_types) = detect_digest("*0D3CED9BEC10A777AEC23CCC353A8C08A633045E").unwrap();
+        assert_eq!(HashTypes::only(HashType::Mysql41), hash_types);
+
+        let (_, hash_types) = detect_digest("$LM$aad3b435b51404ee").unwrap();
+        assert_eq!(HashTypes::only(HashType::Lm), hash_types);
+
+        // the prefix doesn't match the length of the digest
+        let (_, hash_types) = detect_digest("$NT$aad3b435b51404ee").unwrap();
+        assert!(hash_types.is_empty());
+
+        let (_, hash_types) = detect_digest("0x0102").unwrap();
+        assert_eq!(HashTypes::NONE, hash_types);
+        assert_eq!("no known hash function", hash_types.to_string());
+
+        assert_eq!(
+            "Sha2_384, Sha3_384",
+            HashTypes::with_digest_size(48).to_string()
+        );
+        assert!(detect_digest("$NT$zz").is_err());
+    }
+}
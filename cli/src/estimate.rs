@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use cugparck_cpu::{
+    expected_success_rate, expected_success_rate_of_tables, expected_unique_chains, parse_mask,
+    CompressedTable, RainbowTableCtxBuilder, SimpleTable,
+};
+use indicatif::{HumanBytes, HumanDuration};
+use serde::Serialize;
+
+use crate::{bench::run_benchmark, print_json, with_charset, Estimate, OutputFormat};
+
+/// The number of chains computed by the benchmark used to extrapolate the generation time.
+const BENCHMARK_CHAINS: usize = 100_000;
+
+/// The number of columns computed by the benchmark used to extrapolate the generation time.
+const BENCHMARK_COLUMNS: usize = 50;
+
+pub fn estimate(args: Estimate, output: OutputFormat) -> Result<()> {
+    let mut ctx_builder = with_charset(RainbowTableCtxBuilder::new(), &args.charset, args.utf8)?
+        .hash(args.hash_type.into())
+        .alpha(args.alpha)
+        .startpoints(args.startpoints)
+        .chain_length(args.chain_length as usize)
+        .min_password_length(args.min_password_length)
+        .max_password_length(args.max_password_length);
+
+    if let Some(mask) = &args.mask {
+        let charsets = parse_mask(mask)?;
+        ctx_builder =
+            ctx_builder.position_charsets(&charsets.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    }
+
+    let ctx = ctx_builder.build()?;
+    let table_count = args.table_count as usize;
+    let unique_chains = expected_unique_chains(&ctx);
+
+    let result = run_benchmark(
+        args.backend,
+        ctx,
+        BENCHMARK_CHAINS,
+        BENCHMARK_COLUMNS.min(ctx.t - 1),
+    )?;
+
+    let report = EstimateReport {
+        search_space: ctx.n,
+        startpoints: ctx.m0,
+        expected_unique_chains: unique_chains,
+        table_count,
+        success_rate: expected_success_rate(&ctx),
+        success_rate_of_tables: expected_success_rate_of_tables(&ctx, table_count),
+        table_size: SimpleTable::stored_size(unique_chains) as u64,
+        compressed_table_size: CompressedTable::stored_size(&ctx, unique_chains) as u64,
+        backend: result.backend,
+        generation_time_secs: result.estimated_generation_time(&ctx).as_secs_f64(),
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Human => report.print(),
+    }
+
+    Ok(())
+}
+
+/// The estimates of a set of tables.
+#[derive(Serialize)]
+struct EstimateReport {
+    search_space: usize,
+    startpoints: usize,
+    expected_unique_chains: usize,
+    table_count: usize,
+    /// The expected success rate of a single table.
+    success_rate: f64,
+    /// The expected success rate of all the tables.
+    success_rate_of_tables: f64,
+    /// The expected size of a table, in bytes.
+    table_size: u64,
+    /// The expected size of a compressed table, in bytes.
+    compressed_table_size: u64,
+    /// The backend used to estimate the generation time.
+    backend: &'static str,
+    /// The expected generation time of a table.
+    generation_time_secs: f64,
+}
+
+impl EstimateReport {
+    /// Prints the estimates in a human-readable way.
+    fn print(&self) {
+        let table_count = self.table_count;
+        let total_size = |size: u64| HumanBytes(size * table_count as u64);
+        let table_time = Duration::from_secs_f64(self.generation_time_secs);
+
+        println!("Search space: {}", self.search_space);
+        println!("Startpoints (m0): {}", self.startpoints);
+        println!(
+            "Expected unique chains (mt): {}",
+            self.expected_unique_chains
+        );
+        println!(
+            "Expected success rate: {:.2}% for one table, {:.2}% for {table_count} tables",
+            self.success_rate * 100.,
+            self.success_rate_of_tables * 100.
+        );
+        println!(
+            "Expected size: {} per table ({} in total), {} per compressed table ({} in total)",
+            HumanBytes(self.table_size),
+            total_size(self.table_size),
+            HumanBytes(self.compressed_table_size),
+            total_size(self.compressed_table_size)
+        );
+        println!(
+            "Expected generation time with the {} backend: {} per table ({} in total)",
+            self.backend,
+            HumanDuration(table_time.max(Duration::from_secs(1))),
+            HumanDuration((table_time * table_count as u32).max(Duration::from_secs(1)))
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use std::{fs::File, io::BufWriter};
+
+use anyhow::{Context, Result};
+use cugparck_cpu::{export_rcrack, rcrack_file_name, RainbowTable};
+
+use crate::{create_dir_to_store_tables, load_tables_from_dir, Export};
+
+pub fn export(args: Export) -> Result<()> {
+    let tables = load_tables_from_dir(&args.in_dir)?;
+    create_dir_to_store_tables(&args.out_dir)?;
+
+    for table in &tables {
+        export_table(&table.table()?, &args)?;
+    }
+
+    Ok(())
+}
+
+/// Exports a table to the output directory, using the RainbowCrack file name convention.
+fn export_table<T: RainbowTable>(table: &T, args: &Export) -> Result<()> {
+    let path = args
+        .out_dir
+        .join(rcrack_file_name(&table.ctx(), table.len()));
+    let file = File::create(&path).context("Unable to create the exported table")?;
+
+    export_rcrack(table, BufWriter::new(file))
+        .context("Unable to write the exported table to the disk")?;
+    println!("Exported table {} to {}", table.ctx().tn, path.display());
+
+    Ok(())
+}
@@ -1,48 +1,295 @@
-use std::time::Duration;
+use std::{
+    fs::{self, File},
+    io::{stderr, stdin, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use clap::ValueEnum;
+use crossterm::{
+    event::{self as term_event, Event as TermEvent, KeyCode, KeyModifiers},
+    style::Stylize,
+    terminal,
+    tty::IsTty,
+};
+use cugparck_commons::RainbowTableCtx;
 use cugparck_cpu::{
-    backend, CompressedTable, Event, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage,
-    SimpleTable,
+    backend::{self, Backend},
+    ctx_warnings, expected_success_rate, expected_unique_chains, filtration_columns,
+    measured_success_rate, parse_mask, Checkpoint, CompressedTable, CugparckError, Event,
+    FalseAlarmIndex, GenerationController, GenerationSpeed, GenerationStats, RainbowTable,
+    RainbowTableCtxBuilder, RainbowTableStorage, Shard, SimpleTable, SimpleTableHandle,
+    MAX_CHECKPOINT_COUNT,
 };
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, HumanCount, HumanDuration, ProgressBar, ProgressStyle};
+use serde::Serialize;
 
-use crate::{create_dir_to_store_tables, AvailableBackend, Generate};
+use crate::{store_simple_table, with_charset, AvailableBackend, Generate};
 
 pub fn generate(args: Generate) -> Result<()> {
-    create_dir_to_store_tables(&args.dir)?;
+    // the directory can already contain tables generated with other parameters,
+    // as long as their names don't collide with the new tables.
+    fs::create_dir_all(&args.dir)
+        .context("Unable to create the specified directory to store the rainbow tables")?;
 
-    let ext = if args.compress { "rtcde" } else { "rt" };
+    let ext = match (args.compress, args.zstd_level) {
+        (true, _) => "rtcde",
+        (false, Some(_)) => "rtz",
+        (false, None) => "rt",
+    };
 
-    let ctx_builder = RainbowTableCtxBuilder::new()
+    let mut ctx_builder = with_charset(RainbowTableCtxBuilder::new(), &args.charset, args.utf8)?
         .hash(args.hash_type.into())
         .alpha(args.alpha)
         .startpoints(args.startpoints)
+        .startpoint_seed(args.startpoint_seed)
         .chain_length(args.chain_length as usize)
-        .charset(args.charset.as_bytes())
+        .min_password_length(args.min_password_length)
         .max_password_length(args.max_password_length);
 
+    if let Some(filters) = args.filters {
+        ctx_builder = ctx_builder.filter_count(filters as usize);
+    }
+
+    if let Some(salt) = &args.salt {
+        ctx_builder = ctx_builder.salt(&hex::decode(salt)?, args.salt_position.into());
+    }
+
+    if let Some(mask) = &args.mask {
+        let charsets = parse_mask(mask)?;
+        ctx_builder =
+            ctx_builder.position_charsets(&charsets.iter().map(Vec::as_slice).collect::<Vec<_>>());
+    }
+
+    let base_ctx = ctx_builder.build()?;
+    for warning in ctx_warnings(&base_ctx) {
+        eprintln!("{}", format!("Warning: {warning}").yellow());
+    }
+
+    if !args.resume {
+        for i in args.start_from..args.start_from + args.table_count {
+            let table_path = table_path(&args, &ctx_builder.table_number(i).build()?, ext);
+            ensure!(
+                !table_path.exists(),
+                "{} already exists. Use --resume to skip the tables already generated, or another --name-template",
+                table_path.display()
+            );
+        }
+    }
+
+    // the backends are probed once for all the tables
+    let backend = args.backend.resolve();
+    let mut table_reports = Vec::new();
+
     for i in args.start_from..args.start_from + args.table_count {
+        let start = Instant::now();
         let ctx = ctx_builder.table_number(i).build()?;
-        let table_path = args.dir.clone().join(format!("table_{i}.{ext}"));
-
-        let table_handle = match args.backend {
-            AvailableBackend::Cpu => SimpleTable::new_nonblocking::<backend::Cpu>(ctx)?,
-            #[cfg(feature = "cuda")]
-            AvailableBackend::Cuda => SimpleTable::new_nonblocking::<backend::Cuda>(ctx)?,
-            #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
-            AvailableBackend::Vulkan => SimpleTable::new_nonblocking::<backend::Vulkan>(ctx)?,
-            #[cfg(all(feature = "wgpu", target_os = "windows"))]
-            AvailableBackend::Dx12 => SimpleTable::new_nonblocking::<backend::Dx12>(ctx)?,
-            #[cfg(all(feature = "wgpu", target_os = "windows"))]
-            AvailableBackend::Dx11 => SimpleTable::new_nonblocking::<backend::Dx11>(ctx)?,
-            #[cfg(all(feature = "wgpu", target_os = "macos"))]
-            AvailableBackend::Metal => SimpleTable::new_nonblocking::<backend::Metal>(ctx)?,
-            #[cfg(all(feature = "wgpu", target_os = "linux"))]
-            AvailableBackend::OpenGL => SimpleTable::new_nonblocking::<backend::OpenGL>(ctx)?,
+        let stem = args.name_template.file_stem(&ctx);
+        let table_path = table_path(&args, &ctx, ext);
+        let checkpoint_path = args.dir.join(format!("{stem}.checkpoint"));
+
+        if args.resume && table_path.exists() {
+            println!("Table {i} is already generated, skipping it");
+            continue;
+        }
+
+        let resume_checkpoint = args.resume && checkpoint_path.exists();
+        if resume_checkpoint {
+            let checkpoint =
+                Checkpoint::load_from(&checkpoint_path).context("Unable to load the checkpoint")?;
+            ensure!(
+                checkpoint.ctx() == ctx,
+                "The checkpoint of table {i} was made with different parameters"
+            );
+            println!("Resuming table {i} from {:.0}%", checkpoint.progress());
+        } else if let Some(shard) = args.shard {
+            println!("Generating shard {shard} of table {i}");
+        } else {
+            println!("Generating table {i}");
+        }
+
+        let mode = if resume_checkpoint {
+            GenerationMode::Resume(checkpoint_path.clone())
+        } else if args.checkpoint || args.resume {
+            GenerationMode::Checkpoints(checkpoint_path.clone())
+        } else if let Some(max_host_memory) = args.max_host_memory {
+            GenerationMode::MaxHostMemory(
+                (max_host_memory * 1e9) as usize,
+                args.dir.join(format!("{stem}.spill")),
+            )
+        } else if let Some(shard) = args.shard {
+            GenerationMode::Shard(shard)
+        } else if let Some(batch_size) = args.batch_size {
+            GenerationMode::BatchSize(batch_size as usize)
+        } else if let Some(devices) = &args.devices {
+            GenerationMode::Devices(devices.clone())
+        } else {
+            GenerationMode::InMemory
         };
+        let table_handle = start_generation_with(backend, ctx, mode)?;
+        let mut progress = GenerationProgress::new(args.no_progress_bar || !stderr().is_tty());
+        let event_log_path = args
+            .event_log
+            .then(|| table_path.with_extension("events.jsonl"));
+        let (simple_table, stats) =
+            wait_for_table(table_handle, &mut progress, event_log_path.as_deref())?;
+
+        let disk_error = "Unable to store the generated rainbow table to the disk";
+        if args.compress {
+            let table_stats = simple_table.stats().cloned();
+            let table = simple_table
+                .into_rainbow_table::<CompressedTable>()
+                .with_stats(table_stats);
+            table.store(&table_path).context(disk_error)?;
+            store_false_alarm_index(&args, &table, &table_path, &progress)?;
+        } else {
+            store_simple_table(&simple_table, &table_path, args.zstd_level).context(disk_error)?;
+            store_false_alarm_index(&args, &simple_table, &table_path, &progress)?;
+        }
+
+        // the table is safely stored, the checkpoint is not needed anymore
+        if checkpoint_path.exists() {
+            fs::remove_file(checkpoint_path)?;
+        }
+
+        table_reports.push(TableReport {
+            table_number: ctx.tn,
+            file_size: fs::metadata(&table_path)?.len(),
+            path: table_path,
+            unique_chains: stats.unique_chains,
+            expected_unique_chains: expected_unique_chains(&ctx),
+            measured_coverage: measured_success_rate(&ctx, stats.unique_chains),
+            duration_secs: start.elapsed().as_secs_f64(),
+        });
+    }
+
+    if let Some(report_path) = args.report {
+        let report = GenerationReport::new(&base_ctx, table_reports);
+        let file = File::create(&report_path).context("Unable to create the report file")?;
+        serde_json::to_writer_pretty(file, &report).context("Unable to write the report")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the path of a generated table, with the `ext` extension.
+fn table_path(args: &Generate, ctx: &RainbowTableCtx, ext: &str) -> PathBuf {
+    let stem = args.name_template.file_stem(ctx);
+
+    match args.shard {
+        Some(shard) => args.dir.join(format!(
+            "{stem}.shard{}-{}.{ext}",
+            shard.index(),
+            shard.count()
+        )),
+        None => args.dir.join(format!("{stem}.{ext}")),
+    }
+}
+
+/// Stores the false alarm index of a generated table next to it, if `--false-alarm-index` is used.
+/// The index follows the order of the chains of the table, so it is created from the table as it is stored.
+fn store_false_alarm_index<T: RainbowTable>(
+    args: &Generate,
+    table: &T,
+    table_path: &Path,
+    progress: &GenerationProgress,
+) -> Result<()> {
+    if !args.false_alarm_index {
+        return Ok(());
+    }
+
+    progress.println("Computing the false alarm index");
+    FalseAlarmIndex::new(table, MAX_CHECKPOINT_COUNT)?
+        .store(&table_path.with_extension("fai"))
+        .context("Unable to store the false alarm index to the disk")
+}
+
+/// A summary of a generation, written when the `--report` flag is used.
+#[derive(Serialize)]
+struct GenerationReport {
+    hash_type: String,
+    charset: String,
+    min_password_length: usize,
+    max_password_length: usize,
+    chain_length: usize,
+    startpoints: usize,
+    search_space: usize,
+    filtration_columns: Vec<Range<usize>>,
+    expected_coverage: f64,
+    tables: Vec<TableReport>,
+}
+
+impl GenerationReport {
+    fn new(ctx: &RainbowTableCtx, tables: Vec<TableReport>) -> Self {
+        Self {
+            hash_type: format!("{:?}", ctx.hash_type),
+            charset: ctx.charset_chars(&ctx.charset).collect(),
+            min_password_length: ctx.min_password_length,
+            max_password_length: ctx.max_password_length,
+            chain_length: ctx.t,
+            startpoints: ctx.m0,
+            search_space: ctx.n,
+            filtration_columns: filtration_columns(ctx),
+            expected_coverage: expected_success_rate(ctx),
+            tables,
+        }
+    }
+}
+
+/// The summary of a single table of a generation.
+#[derive(Serialize)]
+struct TableReport {
+    table_number: usize,
+    path: PathBuf,
+    file_size: u64,
+    unique_chains: usize,
+    expected_unique_chains: usize,
+    measured_coverage: f64,
+    duration_secs: f64,
+}
+
+/// How a table is generated.
+pub enum GenerationMode {
+    /// All the chains are kept in memory.
+    InMemory,
+    /// A checkpoint is stored at this path after each filtration.
+    Checkpoints(PathBuf),
+    /// The generation is resumed from the checkpoint at this path.
+    Resume(PathBuf),
+    /// The chains use at most about this many bytes of memory, and are deduplicated on the disk in this directory otherwise.
+    MaxHostMemory(usize, PathBuf),
+    /// Only a part of the startpoints is used.
+    Shard(Shard),
+    /// At most this many chains are computed at once.
+    BatchSize(usize),
+    /// Each batch is split between these devices of the backend.
+    Devices(Vec<usize>),
+    /// The chains of this table are extended to the chain length of the context.
+    Rebuild(Box<SimpleTable>),
+}
 
-        println!("Generating table {i}");
+/// Displays the progress of the generation of a table.
+pub enum GenerationProgress {
+    /// A progress bar, for interactive terminals.
+    Bar(ProgressBar),
+    /// Plain lines, for logs and non-interactive terminals.
+    /// The last progress printed is kept, to print a line every 10% only.
+    Plain(u64),
+}
+
+impl GenerationProgress {
+    /// Creates a progress display, using plain lines if `plain` is true.
+    pub fn new(plain: bool) -> Self {
+        if plain {
+            return Self::Plain(0);
+        }
 
         let pb = ProgressBar::new(10_000).with_style(
             ProgressStyle::default_bar()
@@ -51,33 +298,595 @@ pub fn generate(args: Generate) -> Result<()> {
                 .progress_chars("#>-"),
         );
         pb.enable_steady_tick(Duration::from_millis(100));
+        Self::Bar(pb)
+    }
+
+    /// Returns true if the progress is displayed with a progress bar.
+    fn is_interactive(&self) -> bool {
+        matches!(self, Self::Bar(_))
+    }
 
-        while let Some(event) = table_handle.recv() {
-            match event {
-                Event::Progress(progress) => pb.set_position((progress * 100.) as u64),
-                Event::Batch {
-                    batch_number,
-                    batch_count,
-                    columns,
-                } => pb.set_message(format!(
-                    "Running batch {batch_number}/{batch_count} of columns {columns:?}"
-                )),
+    /// Sets the progress of the generation, in percent.
+    fn set_progress(&mut self, percent: f64) {
+        match self {
+            Self::Bar(pb) => pb.set_position((percent * 100.) as u64),
+            Self::Plain(last) => {
+                let step = percent as u64 / 10 * 10;
+                if step > *last {
+                    *last = step;
+                    println!("Progress: {step}%");
+                }
             }
         }
+    }
 
-        pb.finish_with_message("Done");
-        let simple_table = table_handle.join()?;
+    /// Sets the current step of the generation.
+    fn set_message(&mut self, message: String) {
+        match self {
+            Self::Bar(pb) => pb.set_message(message),
+            Self::Plain(_) => println!("{message}"),
+        }
+    }
 
-        let disk_error = "Unable to store the generated rainbow table to the disk";
-        if args.compress {
-            simple_table
-                .into_rainbow_table::<CompressedTable>()
-                .store(&table_path)
-                .context(disk_error)?
-        } else {
-            simple_table.store(&table_path).context(disk_error)?;
+    /// Prints a line without messing with the progress bar.
+    pub fn println(&self, line: &str) {
+        match self {
+            Self::Bar(pb) => pb.println(line),
+            Self::Plain(_) => println!("{line}"),
         }
     }
 
-    Ok(())
+    /// Marks the generation as done.
+    fn finish(&self) {
+        match self {
+            Self::Bar(pb) => pb.finish_with_message("Done"),
+            Self::Plain(_) => println!("Done"),
+        }
+    }
+}
+
+/// Controls a generation with the keyboard while its progress bar is displayed.
+/// `p` pauses the generation, `r` resumes it, and `q` or Ctrl+C cancels it.
+struct KeyboardControl {
+    done: Arc<AtomicBool>,
+    thread_handle: Option<JoinHandle<()>>,
+}
+
+impl KeyboardControl {
+    /// Starts reading the keys in another thread.
+    /// The terminal is in raw mode until the `KeyboardControl` is dropped.
+    fn new(controller: GenerationController) -> Result<Self> {
+        terminal::enable_raw_mode().context("Unable to read the keyboard")?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let thread_handle = thread::spawn(move || {
+            while !thread_done.load(Ordering::Relaxed) {
+                // the keys are polled with a timeout to stop shortly after the generation is done
+                if !term_event::poll(Duration::from_millis(100)).unwrap_or(false) {
+                    continue;
+                }
+
+                if let Ok(TermEvent::Key(key)) = term_event::read() {
+                    match key.code {
+                        KeyCode::Char('p') => controller.pause(),
+                        KeyCode::Char('r') => controller.resume(),
+                        KeyCode::Char('q') => controller.cancel(),
+                        // the raw mode disables the interrupt signal
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            controller.cancel()
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            done,
+            thread_handle: Some(thread_handle),
+        })
+    }
+}
+
+impl Drop for KeyboardControl {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Starts the generation of a table with the given backend.
+pub fn start_generation_with(
+    backend: AvailableBackend,
+    ctx: RainbowTableCtx,
+    mode: GenerationMode,
+) -> Result<SimpleTableHandle> {
+    match backend {
+        AvailableBackend::Auto => start_generation_with(backend.resolve(), ctx, mode),
+        AvailableBackend::Cpu => start_generation::<backend::Cpu>(ctx, mode),
+        #[cfg(feature = "cuda")]
+        AvailableBackend::Cuda => start_generation::<backend::Cuda>(ctx, mode),
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        AvailableBackend::Vulkan => start_generation::<backend::Vulkan>(ctx, mode),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx12 => start_generation::<backend::Dx12>(ctx, mode),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx11 => start_generation::<backend::Dx11>(ctx, mode),
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        AvailableBackend::Metal => start_generation::<backend::Metal>(ctx, mode),
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        AvailableBackend::OpenGL => start_generation::<backend::OpenGL>(ctx, mode),
+    }
+}
+
+/// Displays the events of a generation until it is done, and returns the generated table.
+/// The events are also written to the `event_log` file if there is one.
+pub fn wait_for_table(
+    table_handle: SimpleTableHandle,
+    progress: &mut GenerationProgress,
+    event_log: Option<&Path>,
+) -> Result<(SimpleTable, GenerationStats)> {
+    let mut event_log = event_log.map(EventLog::create).transpose()?;
+
+    // the keys can only be read when the progress bar is displayed
+    let keyboard_control = if progress.is_interactive() && stdin().is_tty() {
+        Some(KeyboardControl::new(table_handle.controller())?)
+    } else {
+        None
+    };
+
+    let mut device_memory = "n/a".to_owned();
+    let mut speed = "n/a".to_owned();
+    while let Some(event) = table_handle.recv() {
+        if let Some(event_log) = &mut event_log {
+            event_log.write(&event)?;
+        }
+
+        match event {
+            Event::Progress(percent) => progress.set_progress(percent),
+            Event::Batch {
+                batch_number,
+                batch_count,
+                columns,
+            } => progress.set_message(format!(
+                "Running batch {batch_number}/{batch_count} of columns {columns:?} (device memory: {device_memory}, speed: {speed})"
+            )),
+            Event::DeviceStats(stats) => {
+                device_memory = match stats {
+                    Some(memory) => format!(
+                        "{}/{}, {:.0}%",
+                        HumanBytes(memory.used as u64),
+                        HumanBytes(memory.total as u64),
+                        memory.utilization() * 100.
+                    ),
+                    None => "n/a".to_owned(),
+                }
+            }
+            Event::Speed(GenerationSpeed {
+                chains_per_sec,
+                hashes_per_sec,
+                eta,
+            }) => {
+                speed = format!(
+                    "{} chains/s, {} hashes/s, ETA: {}",
+                    HumanCount(chains_per_sec as u64),
+                    HumanCount(hashes_per_sec as u64),
+                    eta.map_or("n/a".to_owned(), |eta| HumanDuration(eta).to_string()),
+                )
+            }
+            Event::Filtration {
+                columns,
+                chains,
+                chains_left,
+            } => progress.set_message(format!(
+                "Filtered the chains of columns {columns:?}, {} chains left out of {}",
+                HumanCount(chains_left as u64),
+                HumanCount(chains as u64),
+            )),
+            Event::Paused => progress.set_message("Paused, press r to resume".to_owned()),
+            Event::Resumed => progress.set_message("Resumed".to_owned()),
+            Event::Cancelled => progress.set_message("Cancelled".to_owned()),
+        }
+    }
+
+    drop(keyboard_control);
+    if let Some(event_log) = event_log {
+        event_log.finish()?;
+    }
+    progress.finish();
+    let simple_table = match table_handle.join() {
+        Err(CugparckError::NoBackend { requested, source }) => bail!(
+            "The {requested} backend could not be initialized: {source}\nTry another backend with the `--backend` flag. Available backends are: {}",
+            available_backends()
+        ),
+        table => table?,
+    };
+
+    Ok(simple_table.shrink_and_finalize())
+}
+
+/// Writes the events of a generation to a JSONL file, one event per line.
+/// Each line is flushed once written, so the log can be followed during the generation and isn't lost if it is interrupted.
+struct EventLog {
+    writer: BufWriter<File>,
+    start: Instant,
+    /// When the current batch started, to log the duration of each batch.
+    batch_start: Option<Instant>,
+}
+
+/// An event of a generation, as written to the event log.
+#[derive(Serialize)]
+struct EventRecord {
+    /// The time elapsed since the start of the generation.
+    elapsed_secs: f64,
+    #[serde(flatten)]
+    event: LoggedEvent,
+}
+
+/// A serializable version of `Event`.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LoggedEvent {
+    /// The progress is sent once a batch is done, along with the duration of the batch.
+    Progress {
+        percent: f64,
+        batch_secs: Option<f64>,
+    },
+    Batch {
+        batch_number: usize,
+        batch_count: usize,
+        columns: Range<usize>,
+    },
+    DeviceStats {
+        memory_used: Option<usize>,
+        memory_total: Option<usize>,
+    },
+    Speed {
+        chains_per_sec: f64,
+        hashes_per_sec: f64,
+        eta_secs: Option<f64>,
+    },
+    Filtration {
+        columns: Range<usize>,
+        chains: usize,
+        chains_left: usize,
+    },
+    Paused,
+    Resumed,
+    Cancelled,
+}
+
+impl EventLog {
+    /// Creates the event log file, overwriting an existing one.
+    fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).context("Unable to create the event log")?;
+
+        Ok(Self {
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+            batch_start: None,
+        })
+    }
+
+    /// Writes an event to the log.
+    fn write(&mut self, event: &Event) -> Result<()> {
+        let event = match event {
+            Event::Progress(percent) => LoggedEvent::Progress {
+                percent: *percent,
+                batch_secs: self
+                    .batch_start
+                    .take()
+                    .map(|start| start.elapsed().as_secs_f64()),
+            },
+            Event::Batch {
+                batch_number,
+                batch_count,
+                columns,
+            } => {
+                self.batch_start = Some(Instant::now());
+                LoggedEvent::Batch {
+                    batch_number: *batch_number,
+                    batch_count: *batch_count,
+                    columns: columns.clone(),
+                }
+            }
+            Event::DeviceStats(memory) => LoggedEvent::DeviceStats {
+                memory_used: memory.map(|memory| memory.used),
+                memory_total: memory.map(|memory| memory.total),
+            },
+            Event::Speed(speed) => LoggedEvent::Speed {
+                chains_per_sec: speed.chains_per_sec,
+                hashes_per_sec: speed.hashes_per_sec,
+                eta_secs: speed.eta.map(|eta| eta.as_secs_f64()),
+            },
+            Event::Filtration {
+                columns,
+                chains,
+                chains_left,
+            } => LoggedEvent::Filtration {
+                columns: columns.clone(),
+                chains: *chains,
+                chains_left: *chains_left,
+            },
+            Event::Paused => LoggedEvent::Paused,
+            Event::Resumed => LoggedEvent::Resumed,
+            Event::Cancelled => LoggedEvent::Cancelled,
+        };
+
+        let record = EventRecord {
+            elapsed_secs: self.start.elapsed().as_secs_f64(),
+            event,
+        };
+        serde_json::to_writer(&mut self.writer, &record)
+            .context("Unable to write the event log")?;
+        writeln!(self.writer).context("Unable to write the event log")?;
+        self.writer.flush().context("Unable to write the event log")
+    }
+
+    /// Flushes the events written to the file.
+    fn finish(mut self) -> Result<()> {
+        self.writer.flush().context("Unable to write the event log")
+    }
+}
+
+/// Starts the generation of a table.
+fn start_generation<T: Backend>(
+    ctx: RainbowTableCtx,
+    mode: GenerationMode,
+) -> Result<SimpleTableHandle>
+where
+    T::Renderer: Send,
+{
+    let handle = match mode {
+        GenerationMode::InMemory => SimpleTable::new_nonblocking::<T>(ctx)?,
+        GenerationMode::Checkpoints(path) => {
+            SimpleTable::new_nonblocking_with_checkpoints::<T>(ctx, path)?
+        }
+        GenerationMode::Resume(path) => SimpleTable::resume::<T>(path)?,
+        GenerationMode::MaxHostMemory(max_host_memory, spill_dir) => {
+            SimpleTable::new_nonblocking_with_max_host_memory::<T>(ctx, max_host_memory, spill_dir)?
+        }
+        GenerationMode::Shard(shard) => SimpleTable::new_nonblocking_shard::<T>(ctx, shard)?,
+        GenerationMode::BatchSize(batch_size) => {
+            SimpleTable::new_nonblocking_with_batch_size::<T>(ctx, batch_size)?
+        }
+        GenerationMode::Devices(mut devices) => {
+            let names = T::devices()?;
+            if let Some(device) = devices.iter().find(|&&device| device >= names.len()) {
+                let names = names
+                    .iter()
+                    .enumerate()
+                    .map(|(i, name)| format!("  {i}: {name}"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                bail!(
+                    "There is no device {device} for the {} backend, the available devices are:\n{names}",
+                    T::NAME
+                );
+            }
+
+            // each device is used by a single renderer
+            devices.sort_unstable();
+            devices.dedup();
+            SimpleTable::new_nonblocking_with_devices::<T>(ctx, devices)?
+        }
+        GenerationMode::Rebuild(table) => table.rebuild_nonblocking::<T>(ctx.t)?,
+    };
+
+    Ok(handle)
+}
+
+/// Returns the names of the backends available with the current feature flags.
+fn available_backends() -> String {
+    AvailableBackend::value_variants()
+        .iter()
+        .filter_map(|backend| backend.to_possible_value())
+        .map(|value| value.get_name().to_owned())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs, path::Path};
+
+    use clap::Parser;
+    use cugparck_cpu::{
+        backend::Cpu, Event, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage, SimpleTable,
+    };
+    use memmap2::Mmap;
+    use serde_json::Value;
+
+    use crate::{generate::generate, temp_path, Cli, Commands};
+
+    use super::EventLog;
+
+    fn run(args: &[&str]) -> anyhow::Result<()> {
+        let cli = Cli::try_parse_from(["cugparck-cli", "generate"].iter().chain(args)).unwrap();
+        match cli.commands {
+            Commands::Generate(args) => generate(args),
+            _ => unreachable!(),
+        }
+    }
+
+    fn endpoints<T: RainbowTable>(table: &T) -> HashSet<usize> {
+        table.iter().map(|chain| chain.endpoint.get()).collect()
+    }
+
+    fn mmap(path: &Path) -> Mmap {
+        // SAFETY: the file exists and is not being modified anywhere else.
+        unsafe { Mmap::map(&fs::File::open(path).unwrap()).unwrap() }
+    }
+
+    #[test]
+    fn test_report() {
+        let dir = temp_path("report");
+        let report_path = dir.join("report.json");
+        fs::create_dir_all(&dir).unwrap();
+
+        run(&[
+            "ntlm",
+            dir.join("tables").to_str().unwrap(),
+            "-t",
+            "100",
+            "-l",
+            "4",
+            "-c",
+            "abcdef",
+            "-n",
+            "2",
+            "-b",
+            "cpu",
+            "--report",
+            report_path.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        let report: Value = serde_json::from_reader(fs::File::open(&report_path).unwrap()).unwrap();
+        let tables = report["tables"].as_array().unwrap();
+        assert_eq!(tables.len(), 2);
+
+        for table in tables {
+            let mmap = mmap(Path::new(table["path"].as_str().unwrap()));
+            let stored = SimpleTable::load(&mmap).unwrap();
+
+            assert_eq!(
+                table["unique_chains"].as_u64().unwrap() as usize,
+                stored.len()
+            );
+            assert_eq!(table["file_size"].as_u64().unwrap() as usize, mmap.len());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_log() {
+        let dir = temp_path("event_log");
+        fs::create_dir_all(&dir).unwrap();
+
+        run(&[
+            "ntlm",
+            dir.to_str().unwrap(),
+            "-t",
+            "100",
+            "-l",
+            "4",
+            "-c",
+            "abcdef",
+            "-n",
+            "1",
+            "-b",
+            "cpu",
+            "--event-log",
+        ])
+        .unwrap();
+
+        let events = fs::read_to_string(dir.join("table_1.events.jsonl"))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let of_kind = |kind: &str| {
+            events
+                .iter()
+                .filter(|event| event["event"] == kind)
+                .collect::<Vec<_>>()
+        };
+
+        let batches = of_kind("batch");
+        let filtrations = of_kind("filtration");
+        assert!(!batches.is_empty());
+        assert_eq!(batches.len(), filtrations.len());
+        assert!(of_kind("progress")
+            .iter()
+            .all(|event| event["batch_secs"].as_f64().is_some()));
+        assert!(
+            events
+                .windows(2)
+                .all(|events| events[0]["elapsed_secs"].as_f64()
+                    <= events[1]["elapsed_secs"].as_f64())
+        );
+
+        let table = mmap(&dir.join("table_1.rt"));
+        assert_eq!(
+            SimpleTable::load(&table).unwrap().len() as u64,
+            filtrations.last().unwrap()["chains_left"].as_u64().unwrap()
+        );
+        drop(table);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_log_flushed() {
+        let path = temp_path("event_log_flushed.jsonl");
+
+        // the events can be read while the generation is still running
+        let mut event_log = EventLog::create(&path).unwrap();
+        event_log.write(&Event::Paused).unwrap();
+        let events = fs::read_to_string(&path).unwrap();
+        assert_eq!(1, events.lines().count());
+        assert!(events.contains("\"paused\""));
+
+        event_log.finish().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_resume() {
+        let dir = temp_path("resume_cli");
+        fs::create_dir_all(&dir).unwrap();
+        let args = [
+            "ntlm",
+            dir.to_str().unwrap(),
+            "-t",
+            "100",
+            "-l",
+            "4",
+            "-c",
+            "abcdef",
+            "-n",
+            "1",
+            "-b",
+            "cpu",
+            "--resume",
+        ];
+
+        // simulate an interrupted generation by keeping the checkpoint but not the table
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .table_number(1)
+            .build()
+            .unwrap();
+        let checkpoint_path = dir.join("table_1.checkpoint");
+        let table =
+            SimpleTable::new_nonblocking_with_checkpoints::<Cpu>(ctx, checkpoint_path.clone())
+                .unwrap()
+                .join()
+                .unwrap();
+
+        // the checkpoint doesn't match other parameters
+        let mut other_args = args;
+        other_args[7] = "abc";
+        assert!(run(&other_args).is_err());
+
+        run(&args).unwrap();
+        assert!(!checkpoint_path.exists());
+
+        let resumed = mmap(&dir.join("table_1.rt"));
+        assert_eq!(
+            endpoints(&table),
+            endpoints(SimpleTable::load(&resumed).unwrap())
+        );
+        drop(resumed);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,59 @@
+use std::fs;
+
+use anyhow::{bail, ensure, Context, Result};
+use cugparck_cpu::{RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage, SimpleTable};
+
+use crate::{create_dir_to_store_tables, latin1_charset, Import};
+
+pub fn import(args: Import) -> Result<()> {
+    create_dir_to_store_tables(&args.out_dir)?;
+
+    let mut ctx_hints = RainbowTableCtxBuilder::new();
+    if let Some(hash_type) = args.hash_type {
+        ctx_hints = ctx_hints.hash(hash_type.into());
+    }
+    if let Some(charset) = &args.charset {
+        ctx_hints = ctx_hints.charset(&latin1_charset(charset)?);
+    }
+    if let Some(max_password_length) = args.max_password_length {
+        ctx_hints = ctx_hints.max_password_length(max_password_length);
+    }
+    if let Some(chain_length) = args.chain_length {
+        ctx_hints = ctx_hints.chain_length(chain_length as usize);
+    }
+
+    let mut imported = 0;
+    for file in fs::read_dir(&args.in_dir).context("Unable to open the specified directory")? {
+        let path = file?.path();
+
+        if path.extension().and_then(|ext| ext.to_str()) != Some("rt") {
+            continue;
+        }
+
+        let table = SimpleTable::from_rcrack(&path, ctx_hints)
+            .with_context(|| format!("Unable to import {}", path.display()))?;
+
+        let table_path = args
+            .out_dir
+            .join(format!("{}.rt", args.name_template.file_stem(&table.ctx())));
+        if table_path.exists() {
+            bail!(
+                "Several tables have the table number {}. Only one of them can be imported",
+                table.ctx().tn
+            );
+        }
+
+        table
+            .store(&table_path)
+            .context("Unable to store the imported rainbow table to the disk")?;
+        println!("Imported {} to {}", path.display(), table_path.display());
+        imported += 1;
+    }
+
+    ensure!(
+        imported > 0,
+        "No RainbowCrack table found in the given directory"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{ensure, Context, Result};
+use cugparck_commons::StartpointOrder;
+use cugparck_cpu::{
+    expected_success_rate, measured_success_rate, CugparckError, SimpleTable, TableHeader,
+    TableStats,
+};
+use indicatif::{HumanBytes, HumanDuration};
+use serde::Serialize;
+
+use crate::{print_json, Info, OutputFormat};
+
+pub fn info(args: Info, output: OutputFormat) -> Result<()> {
+    let mut paths = if args.path.is_dir() {
+        fs::read_dir(&args.path)
+            .context("Unable to open the specified directory")?
+            .map(|file| Ok(file?.path()))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        vec![args.path.clone()]
+    };
+
+    paths.retain(|path| is_table(path));
+    paths.sort();
+    ensure!(!paths.is_empty(), "No table found at the given path");
+
+    let infos = paths
+        .iter()
+        .map(|path| TableInfo::read(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    match output {
+        OutputFormat::Json => print_json(&infos)?,
+        OutputFormat::Human => {
+            for (i, info) in infos.iter().enumerate() {
+                if i != 0 {
+                    println!();
+                }
+                info.print();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the file is a rainbow table.
+fn is_table(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|s| s.to_str()),
+        Some("rt" | "rtz" | "rtcde" | "rtt")
+    )
+}
+
+/// The information about a single table.
+#[derive(Serialize)]
+struct TableInfo {
+    path: PathBuf,
+    format_version: u32,
+    hash_type: String,
+    charset: String,
+    /// How the characters of the charset are encoded in the passwords.
+    encoding: String,
+    /// The charset of each position, if the table was generated with a mask.
+    position_charsets: Vec<String>,
+    salt: Option<String>,
+    salt_position: String,
+    min_password_length: usize,
+    max_password_length: usize,
+    table_number: usize,
+    shard: Option<String>,
+    chain_length: usize,
+    startpoints: usize,
+    filter_count: usize,
+    /// The seed of the startpoints, if they were chosen from a seed.
+    startpoint_seed: Option<u64>,
+    chains: usize,
+    success_rate: f64,
+    expected_success_rate: f64,
+    size_on_disk: u64,
+    /// The size of the table once decompressed, if it is compressed.
+    estimated_decompressed_size: Option<u64>,
+    /// Statistics about the generation, if they were stored with the table.
+    generation: Option<GenerationInfo>,
+}
+
+/// The statistics about the generation of a table stored in its header.
+#[derive(Serialize)]
+struct GenerationInfo {
+    /// The number of unique chains at the end of the generation (m_t).
+    unique_chains: u64,
+    /// The number of unique chains after each filtration, as (column, chains) pairs.
+    filtration_curve: Vec<(u64, u64)>,
+    duration_secs: f64,
+    backend: String,
+    cugparck_version: String,
+}
+
+impl From<TableStats> for GenerationInfo {
+    fn from(stats: TableStats) -> Self {
+        Self {
+            unique_chains: stats.unique_chains,
+            filtration_curve: stats.filtration_curve,
+            duration_secs: stats.duration.as_secs_f64(),
+            backend: stats.backend,
+            cugparck_version: stats.cugparck_version,
+        }
+    }
+}
+
+impl TableInfo {
+    /// Reads the information about a table from its header.
+    fn read(path: &Path) -> Result<Self> {
+        let is_compressed = matches!(
+            path.extension().and_then(|s| s.to_str()),
+            Some("rtz" | "rtcde" | "rtt")
+        );
+        let size_on_disk = fs::metadata(path)
+            .context("Unable to open a rainbow table")?
+            .len();
+
+        let header = TableHeader::read(path)?.ok_or(CugparckError::UnsupportedVersion(0))?;
+        let ctx = header.ctx;
+        let chain_count = header.chain_count as usize;
+
+        let position_charsets = if ctx.has_position_charsets() {
+            ctx.charsets[..ctx.max_password_length]
+                .iter()
+                .map(|charset| ctx.charset_chars(charset).collect())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            format_version: header.version,
+            hash_type: format!("{:?}", ctx.hash_type),
+            charset: ctx.charset_chars(&ctx.charset).collect(),
+            encoding: format!("{:?}", ctx.encoding),
+            position_charsets,
+            salt: (!ctx.salt.is_empty()).then(|| hex::encode(ctx.salt)),
+            salt_position: format!("{:?}", ctx.salt_position),
+            min_password_length: ctx.min_password_length,
+            max_password_length: ctx.max_password_length,
+            table_number: ctx.tn,
+            shard: header.shard.map(|shard| shard.to_string()),
+            chain_length: ctx.t,
+            startpoints: ctx.m0,
+            filter_count: ctx.filter_count,
+            startpoint_seed: (ctx.startpoint_order == StartpointOrder::Seeded)
+                .then_some(ctx.startpoint_seed),
+            chains: chain_count,
+            success_rate: measured_success_rate(&ctx, chain_count),
+            expected_success_rate: expected_success_rate(&ctx),
+            size_on_disk,
+            estimated_decompressed_size: is_compressed
+                .then(|| SimpleTable::stored_size(chain_count) as u64),
+            generation: header.stats.map(GenerationInfo::from),
+        })
+    }
+
+    /// Prints the information in a human-readable way.
+    fn print(&self) {
+        println!("{}", self.path.display());
+        println!("  Format version: {}", self.format_version);
+        println!("  Hash function: {}", self.hash_type);
+        println!("  Charset: {} ({})", self.charset, self.encoding);
+        for (i, charset) in self.position_charsets.iter().enumerate() {
+            println!("    Position {}: {charset}", i + 1);
+        }
+        if let Some(salt) = &self.salt {
+            println!("  Salt: {salt} ({})", self.salt_position);
+        }
+        println!("  Minimum password length: {}", self.min_password_length);
+        println!("  Maximum password length: {}", self.max_password_length);
+        println!("  Table number: {}", self.table_number);
+        if let Some(shard) = &self.shard {
+            println!("  Shard: {shard}");
+        }
+        println!("  Chain length: {}", self.chain_length);
+        println!("  Startpoints: {}", self.startpoints);
+        if let Some(seed) = self.startpoint_seed {
+            println!("  Startpoint seed: {seed}");
+        }
+        println!("  Filtrations: {}", self.filter_count);
+        println!("  Chains: {}", self.chains);
+        println!(
+            "  Success rate: {:.2}% (expected {:.2}%)",
+            self.success_rate * 100.,
+            self.expected_success_rate * 100.,
+        );
+        println!("  Size on disk: {}", HumanBytes(self.size_on_disk));
+        if let Some(size) = self.estimated_decompressed_size {
+            println!("  Estimated decompressed size: {}", HumanBytes(size));
+        }
+        if let Some(generation) = &self.generation {
+            println!(
+                "  Generated by: cugparck {} ({} backend) in {}",
+                generation.cugparck_version,
+                generation.backend,
+                HumanDuration(Duration::from_secs_f64(generation.duration_secs)),
+            );
+            println!("  Unique chains generated: {}", generation.unique_chains);
+            println!("  Filtration curve:");
+            for (column, chains) in &generation.filtration_curve {
+                println!("    Column {column}: {chains} chains");
+            }
+        }
+    }
+}
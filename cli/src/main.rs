@@ -1,33 +1,89 @@
+mod analyze;
 mod attack;
+mod bench;
+mod bruteforce;
 mod compress;
+mod coverage_map;
 mod decompress;
+mod detect;
+mod estimate;
+mod export;
 mod generate;
+mod import;
+mod info;
+mod merge;
+mod rebuild;
+mod selftest;
+mod serve;
 mod stealdows;
+mod stealnix;
+mod verify;
 
 use std::{
-    collections::HashSet,
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
     fs::{self, File},
+    io::stderr,
+    net::SocketAddr,
     path::{Path, PathBuf},
     string::String,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
 };
 
-use clap::{clap_derive::ArgEnum, value_parser, Args, Parser, Subcommand};
+use clap::{clap_derive::ArgEnum, value_parser, Args, Parser, Subcommand, ValueEnum};
 
 use anyhow::{ensure, Context, Result};
 
-use crossterm::style::{style, Color, Stylize};
+use crossterm::{
+    style::{style, Color, Stylize},
+    tty::IsTty,
+};
 use cugparck_commons::{
-    Digest, HashType, Password, DEFAULT_APLHA, DEFAULT_CHAIN_LENGTH, DEFAULT_CHARSET,
-    DEFAULT_MAX_PASSWORD_LENGTH,
+    Digest, HashType, Password, RainbowTableCtx, SaltPosition, DEFAULT_APLHA, DEFAULT_CHAIN_LENGTH,
+    DEFAULT_CHARSET, DEFAULT_MAX_PASSWORD_LENGTH, MAX_FILTER_COUNT, MAX_PASSWORD_LENGTH_ALLOWED,
+};
+use cugparck_cpu::{
+    backend, parse_mask, probe, AnyTable, CugparckError, FalseAlarmFilter, FalseAlarmIndex,
+    NameTemplate, Progress, ProgressListener, RainbowTable, RainbowTableCtxBuilder,
+    RainbowTableStorage, SearchHit, SearchStats, Shard, SimpleTable, TableCluster,
+    TableSetValidator,
 };
-use cugparck_cpu::{CompressedTable, RainbowTable, RainbowTableStorage, SimpleTable, TableCluster};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+use tracing_subscriber::prelude::*;
 
+use analyze::analyze;
 use attack::attack;
+use bench::bench;
+use bruteforce::bruteforce;
 use compress::compress;
+use coverage_map::coverage_map;
 use decompress::decompress;
+use detect::{check_digest, HashTypes};
+use estimate::estimate;
+use export::export;
 use generate::generate;
+use import::import;
+use info::info;
 use memmap2::Mmap;
+use merge::merge;
+use rebuild::rebuild;
+use selftest::selftest;
+use serve::serve;
 use stealdows::stealdows;
+use stealnix::stealnix;
+use verify::verify;
+#[cfg(feature = "zstd")]
+use {
+    cugparck_cpu::{compress_zstd, decompress_zstd, zstd_decompressed_len},
+    memmap2::MmapMut,
+};
+
+/// The error returned when a table stored with zstd is used without the zstd feature.
+const ZSTD_UNAVAILABLE: &str =
+    "The tables stored with zstd require cugparck to be built with the zstd feature";
 
 /// All the hash types supported.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
@@ -44,6 +100,15 @@ enum HashTypeArg {
     Sha3_256,
     Sha3_384,
     Sha3_512,
+    Blake2b512,
+    Blake2s256,
+    Blake3,
+    /// A half of a LAN Manager hash, covering up to 7 uppercased characters.
+    Lm,
+    /// SHA1(SHA1(password)), used by MySQL 4.1+.
+    Mysql41,
+    /// MD5 of the hexadecimal MD5 of the password.
+    Md5Md5,
 }
 
 impl From<HashTypeArg> for HashType {
@@ -61,6 +126,29 @@ impl From<HashTypeArg> for HashType {
             HashTypeArg::Sha3_256 => HashType::Sha3_256,
             HashTypeArg::Sha3_384 => HashType::Sha3_384,
             HashTypeArg::Sha3_512 => HashType::Sha3_512,
+            HashTypeArg::Blake2b512 => HashType::Blake2b512,
+            HashTypeArg::Blake2s256 => HashType::Blake2s256,
+            HashTypeArg::Blake3 => HashType::Blake3,
+            HashTypeArg::Lm => HashType::Lm,
+            HashTypeArg::Mysql41 => HashType::Mysql41,
+            HashTypeArg::Md5Md5 => HashType::Md5Md5,
+        }
+    }
+}
+
+/// Where the salt is concatenated to the passwords.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Default)]
+enum SaltPositionArg {
+    Prefix,
+    #[default]
+    Suffix,
+}
+
+impl From<SaltPositionArg> for SaltPosition {
+    fn from(arg: SaltPositionArg) -> Self {
+        match arg {
+            SaltPositionArg::Prefix => SaltPosition::Prefix,
+            SaltPositionArg::Suffix => SaltPosition::Suffix,
         }
     }
 }
@@ -69,6 +157,9 @@ impl From<HashTypeArg> for HashType {
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Default)]
 pub enum AvailableBackend {
+    /// Probe the GPU backends from the fastest to the slowest, and use the first one that works.
+    /// The CPU is used if none of them works.
+    Auto,
     #[cfg_attr(not(any(feature = "cuda", feature = "wgpu")), default)]
     Cpu,
     #[cfg(feature = "cuda")]
@@ -95,10 +186,98 @@ pub enum AvailableBackend {
     OpenGL,
 }
 
+impl AvailableBackend {
+    /// Returns the backend to use.
+    /// With `Auto`, the GPU backends are probed in order and the first one computing correct chains is returned.
+    fn resolve(self) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+
+        let candidates: &[Self] = &[
+            #[cfg(feature = "cuda")]
+            Self::Cuda,
+            #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+            Self::Vulkan,
+            #[cfg(all(feature = "wgpu", target_os = "macos"))]
+            Self::Metal,
+            #[cfg(all(feature = "wgpu", target_os = "windows"))]
+            Self::Dx12,
+            #[cfg(all(feature = "wgpu", target_os = "windows"))]
+            Self::Dx11,
+            #[cfg(all(feature = "wgpu", target_os = "linux"))]
+            Self::OpenGL,
+        ];
+
+        let backend = candidates
+            .iter()
+            .copied()
+            .find(|backend| match backend.probe() {
+                Ok(()) => true,
+                Err(CugparckError::NoBackend { .. }) => false,
+                Err(err) => {
+                    eprintln!("{}", format!("Warning: {err}, skipping it").yellow());
+                    false
+                }
+            })
+            .unwrap_or(Self::Cpu);
+
+        eprintln!("Using the {} backend", backend.name());
+        backend
+    }
+
+    /// Checks that the backend is available and computes correct chains.
+    fn probe(self) -> Result<(), CugparckError> {
+        match self {
+            Self::Auto => self.resolve().probe(),
+            Self::Cpu => probe::<backend::Cpu>(),
+            #[cfg(feature = "cuda")]
+            Self::Cuda => probe::<backend::Cuda>(),
+            #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+            Self::Vulkan => probe::<backend::Vulkan>(),
+            #[cfg(all(feature = "wgpu", target_os = "windows"))]
+            Self::Dx12 => probe::<backend::Dx12>(),
+            #[cfg(all(feature = "wgpu", target_os = "windows"))]
+            Self::Dx11 => probe::<backend::Dx11>(),
+            #[cfg(all(feature = "wgpu", target_os = "macos"))]
+            Self::Metal => probe::<backend::Metal>(),
+            #[cfg(all(feature = "wgpu", target_os = "linux"))]
+            Self::OpenGL => probe::<backend::OpenGL>(),
+        }
+    }
+
+    /// Returns the name of the backend, as given to the `--backend` flag.
+    fn name(self) -> &'static str {
+        self.to_possible_value().unwrap().get_name()
+    }
+}
+
+/// The format of the results printed by the commands.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Default)]
+pub enum OutputFormat {
+    /// Human-readable text and tables.
+    #[default]
+    Human,
+    /// A single JSON document, to pipe the results into other tools.
+    Json,
+}
+
 /// Cugparck is a modern rainbow table library & CLI.
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    /// The format of the results of the `attack`, `stealdows`, `stealnix`, `info` and `estimate` commands.
+    /// It should be given before the command, such as `cugparck-cli --output json info tables`.
+    /// The warnings and the progress are still printed to the standard error.
+    #[clap(long, arg_enum, default_value_t, value_name = "FORMAT")]
+    output: OutputFormat,
+
+    /// Write a Chrome trace of the table generation to this file, such as `chrome-trace.json`.
+    /// It can be opened in `chrome://tracing` or Perfetto to see the time spent uploading the batches,
+    /// running the kernels, reading back the chains and filtering them.
+    #[clap(long, value_parser, value_name = "FILE")]
+    profile: Option<PathBuf>,
+
     #[clap(subcommand)]
     commands: Commands,
 }
@@ -110,14 +289,30 @@ enum Commands {
     Compress(Compress),
     Decompress(Decompress),
     Stealdows(Stealdows),
+    Stealnix(Stealnix),
+    CoverageMap(CoverageMap),
+    Export(Export),
+    Import(Import),
+    Info(Info),
+    Bench(Bench),
+    Estimate(Estimate),
+    Merge(Merge),
+    Verify(Verify),
+    Rebuild(Rebuild),
+    Selftest(Selftest),
+    Bruteforce(Bruteforce),
+    Analyze(Analyze),
+    Serve(Serve),
 }
 
 /// Find the password producing a certain hash digest.
 #[derive(Args)]
+#[clap(allow_missing_positional = true)]
 pub struct Attack {
     /// The digest to attack, in hexadecimal.
-    #[clap(value_parser = check_hex)]
-    digest: String,
+    /// It can be prefixed to tell its hash function, like `$NT$...`, `$LM$...` or `*...` for MySQL.
+    #[clap(value_parser = check_digest, required_unless_present = "digest-file")]
+    digest: Option<String>,
 
     /// The directory containing the rainbow table(s) to use.
     #[clap(value_parser)]
@@ -127,6 +322,42 @@ pub struct Attack {
     /// This is slower on average than searching with all the tables at once.
     #[clap(long, value_parser)]
     low_memory: bool,
+
+    /// Attack all the digests of a file instead of a single digest.
+    /// The file should contain one hexadecimal digest per line, optionally prefixed by a user name (`user:digest`).
+    #[clap(long, value_parser, value_name = "FILE", conflicts_with = "digest")]
+    digest_file: Option<PathBuf>,
+
+    /// The hash function of the digest(s).
+    /// When it isn't specified, it is detected from the length and the prefix of each digest,
+    /// and each digest is only searched in the tables using one of its detected hash functions.
+    #[clap(long = "hash", value_parser, value_name = "HASH")]
+    hash_type: Option<HashTypeArg>,
+
+    /// The salt of the digest(s), in hexadecimal.
+    /// The attack fails early if the tables were generated with another salt.
+    #[clap(long, value_parser = check_hex)]
+    salt: Option<String>,
+
+    /// Hash the words of this file, one per line, when a password isn't found in the tables.
+    /// The words are hashed with the hash function and salt of each set of tables that could contain the digest.
+    #[clap(long, value_parser, value_name = "FILE")]
+    wordlist: Option<PathBuf>,
+
+    /// Compute the endpoints of the digest on the fastest GPU backend available.
+    /// The false alarm indexes are not used in this mode.
+    #[clap(long, value_parser, conflicts_with = "digest-file")]
+    gpu: bool,
+
+    /// Print the passwords found in hexadecimal, in the `$HEX[...]` format.
+    /// The passwords that can't be printed as is are always printed this way.
+    #[clap(long, value_parser)]
+    hex_output: bool,
+
+    /// Print the table and the column of the chain in which each password was found,
+    /// then the time spent searching each table and the false alarms encountered.
+    #[clap(short, long, value_parser)]
+    verbose: bool,
 }
 
 /// Compress a set of rainbow tables using compressed delta encoding.
@@ -135,29 +366,442 @@ pub struct Attack {
 #[derive(Args)]
 pub struct Compress {
     /// The output directory of the compressed rainbow table(s).
-    #[clap(value_parser)]
-    out_dir: PathBuf,
+    #[clap(value_parser, required_unless_present = "in-place")]
+    out_dir: Option<PathBuf>,
 
     /// The input directory containing the rainbow table(s) to compress.
-    #[clap(value_parser)]
-    in_dir: PathBuf,
+    #[clap(value_parser, required_unless_present = "in-place")]
+    in_dir: Option<PathBuf>,
+
+    /// Compress the rainbow table(s) of this directory in place.
+    /// Each table is only removed once its compressed version is stored and verified.
+    #[clap(long, value_parser, value_name = "DIR", conflicts_with_all = &["out-dir", "in-dir"])]
+    in_place: Option<PathBuf>,
+
+    /// Only store the endpoints of the chains, for archival.
+    /// The tables are a lot smaller but much slower to search,
+    /// since the startpoints have to be recomputed when an endpoint matches.
+    #[clap(long, value_parser)]
+    endpoints_only: bool,
+
+    /// Compress the rainbow table(s) with zstd at this level, from 1 to 22, instead of using compressed delta encoding.
+    /// The tables are stored as `.rtz` files, and are as fast to search as uncompressed tables once loaded.
+    #[clap(long, value_parser = check_zstd_level, value_name = "LEVEL", conflicts_with = "endpoints-only")]
+    zstd_level: Option<i32>,
+
+    /// Store only the least significant bits of the endpoints that are needed to tell them apart,
+    /// instead of using compressed delta encoding.
+    /// The tables are stored as `.rtt` files, which are bigger than compressed tables but faster to search.
+    #[clap(long, value_parser, conflicts_with_all = &["endpoints-only", "zstd-level"])]
+    truncate: bool,
 }
 
 /// Decompress a set of compressed rainbow tables.
 ///
 /// Decompressed tables are bigger on the disk but faster to search.
+/// Each decompressed table is built in memory before being stored, so it has to fit in the RAM.
 #[derive(Args)]
 pub struct Decompress {
     /// The output directory of the rainbow table(s).
+    #[clap(value_parser, required_unless_present = "in-place")]
+    out_dir: Option<PathBuf>,
+
+    /// The input directory containing the compressed rainbow table(s) to decompress.
+    #[clap(value_parser, required_unless_present = "in-place")]
+    in_dir: Option<PathBuf>,
+
+    /// Decompress the rainbow table(s) of this directory in place.
+    /// Each table is only removed once its decompressed version is stored and verified.
+    #[clap(long, value_parser, value_name = "DIR", conflicts_with_all = &["out-dir", "in-dir"])]
+    in_place: Option<PathBuf>,
+}
+
+/// Export how the endpoints of a set of rainbow tables are spread over the search space, as CSV.
+///
+/// The search space is divided in equal slices and the number of endpoints in each slice is counted.
+#[derive(Args)]
+pub struct CoverageMap {
+    /// The directory containing the rainbow table(s).
+    #[clap(value_parser)]
+    dir: PathBuf,
+
+    /// The number of slices of the search space.
+    #[clap(short, long, value_parser = value_parser!(u64).range(1..), default_value_t = 100)]
+    buckets: u64,
+
+    /// Write the CSV to this file instead of the standard output.
+    #[clap(short, long, value_parser)]
+    output: Option<PathBuf>,
+}
+
+/// Export a set of rainbow tables to the file layout of the RainbowCrack `.rt` format.
+///
+/// The tables are named using the RainbowCrack convention.
+/// The exported tables are NOT compatible with RainbowCrack: they still use cugparck's reduction function
+/// and password indexing, so RainbowCrack can't search them. They can only be imported back with `import`.
+#[derive(Args)]
+pub struct Export {
+    /// The directory containing the rainbow table(s) to export.
+    #[clap(value_parser)]
+    in_dir: PathBuf,
+
+    /// The output directory of the exported rainbow table(s).
     #[clap(value_parser)]
     out_dir: PathBuf,
+}
 
-    /// The input directory containing the compressed rainbow table(s) to decompress.
+/// Merge tables generated in several parts into a single table.
+///
+/// The parts should have the same parameters and table number, but can use different numbers of startpoints.
+/// The chains are deduplicated by endpoint.
+#[derive(Args)]
+pub struct Merge {
+    /// The path of the merged table.
+    /// It is compressed if its extension is `.rtcde`, and stores truncated endpoints if its extension is `.rtt`.
+    #[clap(value_parser)]
+    out: PathBuf,
+
+    /// The tables to merge. They should all be stored in the same format.
+    #[clap(value_parser, required = true)]
+    tables: Vec<PathBuf>,
+}
+
+/// Print the parameters and statistics of a set of rainbow tables.
+///
+/// Only the headers of the tables are read, so this is fast even for big tables.
+#[derive(Args)]
+pub struct Info {
+    /// The rainbow table to inspect, or a directory containing the rainbow table(s) to inspect.
+    #[clap(value_parser)]
+    path: PathBuf,
+}
+
+/// Measure the success rate of rainbow tables.
+///
+/// Random passwords of the search space of the tables are hashed and attacked,
+/// to check that the tables perform as expected.
+#[derive(Args)]
+pub struct Verify {
+    /// The directory containing the rainbow table(s) to verify.
+    #[clap(value_parser)]
+    dir: PathBuf,
+
+    /// The number of random passwords to attack.
+    #[clap(long, value_parser = value_parser!(u64).range(1..), default_value_t = 10_000)]
+    samples: u64,
+
+    /// Don't load all the tables at the same time to save memory.
+    /// This is slower on average than searching with all the tables at once.
+    #[clap(long, value_parser)]
+    low_memory: bool,
+}
+
+/// Extend the chains of a set of rainbow tables to a larger chain length.
+///
+/// The chains are continued from their endpoints instead of being generated again from their startpoints,
+/// and the chains merging in the new columns are removed.
+/// The rebuilt tables are smaller but slower to search.
+///
+/// While the progress bar is displayed, press `p` to pause the rebuild, `r` to resume it and `q` to cancel it.
+#[derive(Args)]
+pub struct Rebuild {
+    /// The output directory of the rebuilt rainbow table(s).
+    #[clap(value_parser)]
+    out_dir: PathBuf,
+
+    /// The input directory containing the rainbow table(s) to rebuild.
     #[clap(value_parser)]
     in_dir: PathBuf,
+
+    /// The new chain length, greater than the chain length of the tables.
+    #[clap(short = 't', long, value_parser = value_parser!(u64).range(10..=1_000_000))]
+    chain_length: u64,
+
+    /// Force a backend to extend the chains.
+    #[clap(short, long, arg_enum, default_value_t)]
+    backend: AvailableBackend,
+
+    /// The template of the file names of the rebuilt tables, see `generate --name-template`.
+    #[clap(long, value_parser = check_name_template, default_value = NameTemplate::DEFAULT)]
+    name_template: NameTemplate,
+
+    /// Print the progress as plain lines instead of a progress bar, for logs and scripts.
+    /// This is the default when the standard error is not a terminal.
+    #[clap(long, value_parser)]
+    no_progress_bar: bool,
+}
+
+/// Check that a backend computes correct chains.
+///
+/// The known answers of every hash function and the conversions between counters and plaintexts
+/// are checked with the chains kernel of the backend.
+/// The command fails if any check fails.
+#[derive(Args)]
+pub struct Selftest {
+    /// The backend to check.
+    /// If not provided, the backend that `generate` would choose is checked.
+    #[clap(short, long, arg_enum, default_value = "auto")]
+    backend: AvailableBackend,
+}
+
+/// Find the passwords of digests by hashing every password of a search space.
+///
+/// This is faster than a table for small search spaces, such as the passwords shorter than the minimum length of a table.
+/// The passwords are hashed with the chains kernel of the backend.
+#[derive(Args)]
+pub struct Bruteforce {
+    /// The type of the hash.
+    #[clap(value_parser)]
+    hash_type: HashTypeArg,
+
+    /// The digest to attack, in hexadecimal.
+    #[clap(value_parser = check_hex, required_unless_present = "digest-file")]
+    digest: Option<String>,
+
+    /// Attack all the digests of a file instead of a single digest.
+    /// The file should contain one hexadecimal digest per line, optionally prefixed by a user name (`user:digest`).
+    #[clap(long, value_parser, value_name = "FILE", conflicts_with = "digest")]
+    digest_file: Option<PathBuf>,
+
+    /// The minimum password length.
+    #[clap(short = 'm', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = 0)]
+    min_password_length: u8,
+
+    /// The maximum password length.
+    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = 5)]
+    max_password_length: u8,
+
+    /// The charset to use.
+    #[clap(short, long, value_parser, default_value_t = String::from_utf8_lossy(DEFAULT_CHARSET).to_string())]
+    charset: String,
+
+    /// Encode the passwords in UTF-8, which allows any Unicode character in the charset.
+    /// Otherwise, the charset can only contain Latin-1 characters, which are encoded on a single byte.
+    #[clap(long, value_parser, conflicts_with = "mask")]
+    utf8: bool,
+
+    /// A mask giving the charset of each position of the passwords, such as `?u?l?l?l?d?d`.
+    /// It replaces the charset and the maximum password length.
+    #[clap(long, value_parser = check_mask, conflicts_with_all = &["charset", "max-password-length"])]
+    mask: Option<String>,
+
+    /// A salt concatenated to the passwords before hashing them, in hexadecimal.
+    #[clap(long, value_parser = check_hex)]
+    salt: Option<String>,
+
+    /// Where the salt is concatenated to the passwords.
+    #[clap(long, arg_enum, default_value_t, requires = "salt")]
+    salt_position: SaltPositionArg,
+
+    /// Force a backend to hash the passwords.
+    /// If not provided, the fastest backend computing correct chains is used.
+    #[clap(short, long, arg_enum, default_value = "auto")]
+    backend: AvailableBackend,
+
+    /// Print the passwords found in hexadecimal, in the `$HEX[...]` format.
+    /// The passwords that can't be printed as is are always printed this way.
+    #[clap(long, value_parser)]
+    hex_output: bool,
+}
+
+/// Analyze how the chains of a compressed table are distributed and encoded.
+///
+/// The chain count of each block, a histogram of the endpoints over the search space,
+/// the distribution of the bit costs of the delta encoding and the statistics of the Rice parameter are computed.
+/// Only the endpoints are decoded, so this also works quickly on tables storing only the endpoints.
+#[derive(Args)]
+pub struct Analyze {
+    /// The compressed delta encoding table (`.rtcde`) to analyze.
+    #[clap(value_parser)]
+    table: PathBuf,
+
+    /// Export all the statistics to files instead of printing a summary.
+    #[clap(long, arg_enum, value_name = "FORMAT")]
+    export: Option<AnalysisExportFormat>,
+
+    /// The directory where the exported files are written.
+    #[clap(long, value_parser, default_value = ".", requires = "export")]
+    out_dir: PathBuf,
+
+    /// The number of slices of the search space in the endpoint histogram.
+    #[clap(short, long, value_parser = value_parser!(u64).range(1..), default_value_t = 100)]
+    buckets: u64,
+}
+
+/// Load a set of rainbow tables once and crack digests over HTTP.
+///
+/// `GET /crack/<digest>` cracks a single digest, and `POST /crack` cracks the digests of the body,
+/// which is formatted like a digest file. Both answer with the JSON results of `attack --output json`.
+/// The requests are handled one at a time, each search using all the cores, so a request waits for the previous ones.
+#[derive(Args)]
+pub struct Serve {
+    /// The directory containing the rainbow table(s) to use.
+    #[clap(value_parser)]
+    dir: PathBuf,
+
+    /// The address to listen on.
+    #[clap(
+        long,
+        value_parser,
+        default_value = "127.0.0.1:8271",
+        value_name = "ADDRESS"
+    )]
+    listen: SocketAddr,
+
+    /// Don't load all the tables at the same time to save memory.
+    /// This is slower on average than searching with all the tables at once.
+    #[clap(long, value_parser)]
+    low_memory: bool,
+
+    /// Hash the words of this file, one per line, when a password isn't found in the tables.
+    #[clap(long, value_parser, value_name = "FILE")]
+    wordlist: Option<PathBuf>,
+
+    /// Return the passwords found in hexadecimal, in the `$HEX[...]` format.
+    #[clap(long, value_parser)]
+    hex_output: bool,
+}
+
+/// The format of the files exported by the `analyze` command.
+#[derive(Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum AnalysisExportFormat {
+    /// One CSV file per statistic, named after the table.
+    Csv,
+}
+
+/// Measure the speed of the table generation on each available backend.
+///
+/// A fixed number of chains is computed without filtration, and the throughput is used
+/// to estimate the time needed to generate a table with the given parameters.
+#[derive(Args)]
+pub struct Bench {
+    /// The type of the hash.
+    #[clap(value_parser, default_value = "ntlm")]
+    hash_type: HashTypeArg,
+
+    /// The chain length of the table to estimate.
+    #[clap(short = 't', long, value_parser = value_parser!(u64).range(10..=1_000_000), default_value_t = DEFAULT_CHAIN_LENGTH as u64)]
+    chain_length: u64,
+
+    /// The minimum password length of the table to estimate.
+    #[clap(short = 'm', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = 0)]
+    min_password_length: u8,
+
+    /// The maximum password length of the table to estimate.
+    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = DEFAULT_MAX_PASSWORD_LENGTH)]
+    max_password_length: u8,
+
+    /// The charset of the table to estimate.
+    #[clap(short, long, value_parser, default_value_t = String::from_utf8_lossy(DEFAULT_CHARSET).to_string())]
+    charset: String,
+
+    /// Encode the passwords in UTF-8, which allows any Unicode character in the charset.
+    /// Otherwise, the charset can only contain Latin-1 characters, which are encoded on a single byte.
+    #[clap(long, value_parser)]
+    utf8: bool,
+
+    /// The number of chains computed by each backend.
+    #[clap(long, value_parser = value_parser!(u64).range(1..), default_value_t = 100_000)]
+    chains: u64,
+
+    /// The number of columns computed for each chain.
+    /// It is capped to the chain length.
+    #[clap(long, value_parser = value_parser!(u64).range(1..), default_value_t = 100)]
+    columns: u64,
+}
+
+/// Predict the size, success rate and generation time of rainbow tables without generating them.
+///
+/// The generation time is extrapolated from a short benchmark of the backend.
+#[derive(Args)]
+pub struct Estimate {
+    /// The type of the hash.
+    #[clap(value_parser)]
+    hash_type: HashTypeArg,
+
+    /// The chain length.
+    #[clap(short = 't', long, value_parser = value_parser!(u64).range(10..=1_000_000), default_value_t = DEFAULT_CHAIN_LENGTH as u64)]
+    chain_length: u64,
+
+    /// The minimum password length in the table.
+    #[clap(short = 'm', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = 0)]
+    min_password_length: u8,
+
+    /// The maximum password length in the table.
+    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = DEFAULT_MAX_PASSWORD_LENGTH)]
+    max_password_length: u8,
+
+    /// The charset to use.
+    #[clap(short, long, value_parser, default_value_t = String::from_utf8_lossy(DEFAULT_CHARSET).to_string())]
+    charset: String,
+
+    /// Encode the passwords in UTF-8, which allows any Unicode character in the charset.
+    /// Otherwise, the charset can only contain Latin-1 characters, which are encoded on a single byte.
+    #[clap(long, value_parser, conflicts_with = "mask")]
+    utf8: bool,
+
+    /// A mask giving the charset of each position of the passwords, such as `?u?l?l?l?d?d`.
+    /// It replaces the charset and the maximum password length.
+    #[clap(long, value_parser = check_mask, conflicts_with_all = &["charset", "max-password-length"])]
+    mask: Option<String>,
+
+    /// The number of tables to generate.
+    #[clap(short = 'n', long, value_parser = value_parser!(u8).range(1..), default_value_t = 4)]
+    table_count: u8,
+
+    /// The backend used to estimate the generation time.
+    #[clap(short, long, arg_enum, default_value_t)]
+    backend: AvailableBackend,
+
+    /// Set the maximality factor (alpha).
+    #[clap(short, long, value_parser = check_alpha, default_value_t = DEFAULT_APLHA, group = "startpoint")]
+    alpha: f64,
+
+    /// The number of startpoints to use.
+    #[clap(short, long, value_parser = value_parser!(u64).range(1..), group = "startpoint")]
+    startpoints: Option<usize>,
+}
+
+/// Import a set of RainbowCrack `.rt` tables.
+///
+/// The parameters of the tables are read from their file name when it follows the RainbowCrack convention.
+/// The other parameters should be provided with the options.
+/// Only the tables exported by cugparck can be imported: the tables generated by RainbowCrack itself
+/// use a different reduction function and password indexing, and are rejected.
+#[derive(Args)]
+pub struct Import {
+    /// The directory containing the RainbowCrack table(s) to import.
+    #[clap(value_parser)]
+    in_dir: PathBuf,
+
+    /// The output directory of the imported rainbow table(s).
+    #[clap(value_parser)]
+    out_dir: PathBuf,
+
+    /// The type of the hash, if it isn't in the file names.
+    #[clap(long, value_parser)]
+    hash_type: Option<HashTypeArg>,
+
+    /// The charset, if it isn't a RainbowCrack charset.
+    #[clap(short, long, value_parser)]
+    charset: Option<String>,
+
+    /// The maximum password length, if it isn't in the file names.
+    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64))]
+    max_password_length: Option<u8>,
+
+    /// The chain length, if it isn't in the file names.
+    #[clap(short = 't', long, value_parser = value_parser!(u64).range(10..=1_000_000))]
+    chain_length: Option<u64>,
+
+    /// The template of the file names of the imported tables, see `generate --name-template`.
+    #[clap(long, value_parser = check_name_template, default_value = NameTemplate::DEFAULT)]
+    name_template: NameTemplate,
 }
 
 /// Generate a rainbow table.
+///
+/// While the progress bar is displayed, press `p` to pause the generation, `r` to resume it and `q` to cancel it.
 #[derive(Args)]
 pub struct Generate {
     /// The type of the hash.
@@ -174,14 +818,39 @@ pub struct Generate {
     #[clap(short = 't', long, value_parser = value_parser!(u64).range(10..=1_000_000), default_value_t = DEFAULT_CHAIN_LENGTH as u64)]
     chain_length: u64,
 
+    /// The minimum password length in the table.
+    /// Short passwords are quick to brute force, so they can be left out of the table.
+    #[clap(short = 'm', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = 0)]
+    min_password_length: u8,
+
     /// The maximum password length in the table.
-    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=10), default_value_t = DEFAULT_MAX_PASSWORD_LENGTH)]
+    #[clap(short = 'l', long, value_parser = value_parser!(u8).range(..=MAX_PASSWORD_LENGTH_ALLOWED as i64), default_value_t = DEFAULT_MAX_PASSWORD_LENGTH)]
     max_password_length: u8,
 
     /// The charset to use.
-    #[clap(short, long, value_parser = check_charset, default_value_t = String::from_utf8_lossy(DEFAULT_CHARSET).to_string())]
+    #[clap(short, long, value_parser, default_value_t = String::from_utf8_lossy(DEFAULT_CHARSET).to_string())]
     charset: String,
 
+    /// Encode the passwords in UTF-8, which allows any Unicode character in the charset.
+    /// Otherwise, the charset can only contain Latin-1 characters, which are encoded on a single byte.
+    #[clap(long, value_parser, conflicts_with = "mask")]
+    utf8: bool,
+
+    /// A mask giving the charset of each position of the passwords, such as `?u?l?l?l?d?d`.
+    /// It replaces the charset and the maximum password length.
+    /// The placeholders are `?l` (lowercase), `?u` (uppercase), `?d` (digit), `?s` (special), `?a` (any of them),
+    /// `?h` and `?H` (hexadecimal digit) and `??` (question mark). Other characters match themselves.
+    #[clap(long, value_parser = check_mask, conflicts_with_all = &["charset", "max-password-length"])]
+    mask: Option<String>,
+
+    /// A salt concatenated to the passwords before hashing them, in hexadecimal.
+    #[clap(long, value_parser = check_hex)]
+    salt: Option<String>,
+
+    /// Where the salt is concatenated to the passwords.
+    #[clap(long, arg_enum, default_value_t, requires = "salt")]
+    salt_position: SaltPositionArg,
+
     /// The number of tables to generate.
     /// A single table has a theorical success rate of 86.5%.
     /// Generating 4 tables allows to increase the success rate to 99.96%.
@@ -199,9 +868,14 @@ pub struct Generate {
     #[clap(long, value_parser)]
     compress: bool,
 
+    /// Compress the rainbow table(s) with zstd at this level, from 1 to 22, and store them as `.rtz` files.
+    /// The tables are decompressed in memory when they are loaded.
+    #[clap(long, value_parser = check_zstd_level, value_name = "LEVEL", conflicts_with = "compress")]
+    zstd_level: Option<i32>,
+
     /// Force a backend for the table generation.
-    /// If not provided, the fastest will be used.
-    #[clap(short, long, arg_enum, default_value_t)]
+    /// If not provided, the fastest backend computing correct chains is used.
+    #[clap(short, long, arg_enum, default_value = "auto")]
     backend: AvailableBackend,
 
     /// Set the maximality factor (alpha).
@@ -214,10 +888,84 @@ pub struct Generate {
     /// Prefer using alpha if you don't know what you're doing.
     #[clap(short, long, value_parser = value_parser!(u64).range(1..), group = "startpoint")]
     startpoints: Option<usize>,
+
+    /// Choose the startpoints pseudo-randomly in the search space from this seed.
+    /// Tables generated with the same parameters and seed are identical, so they can be reproduced independently.
+    #[clap(long, value_parser, value_name = "SEED")]
+    startpoint_seed: Option<u64>,
+
+    /// The number of filtrations of the chains during the generation.
+    /// More filtrations keep fewer chains in memory but make the generation slower.
+    /// It should be lower than the chain length minus one. Defaults to 20, or fewer for short chains.
+    #[clap(long, value_parser = value_parser!(u64).range(1..=MAX_FILTER_COUNT as u64))]
+    filters: Option<u64>,
+
+    /// The template of the file names of the tables.
+    /// `{hash}`, `{charset}` (a hash of the charset and salt), `{min}`, `{max}`, `{t}` and `{tn}`
+    /// are replaced by the parameters of each table.
+    /// Use `{hash}_{charset}#{min}-{max}_{t}_{tn}` to store tables generated with different parameters in the same directory.
+    #[clap(long, value_parser = check_name_template, default_value = NameTemplate::DEFAULT)]
+    name_template: NameTemplate,
+
+    /// Write a JSON summary of the generation to this file.
+    #[clap(long, value_parser)]
+    report: Option<PathBuf>,
+
+    /// Write the events of the generation of each table to a JSONL file next to it, such as `table_1.events.jsonl`.
+    /// Each line contains an event and the time elapsed since the start of the generation,
+    /// to analyze the batches and the filtrations afterwards.
+    #[clap(long, value_parser)]
+    event_log: bool,
+
+    /// Store a false alarm index next to each table.
+    /// It is used to skip most of the false alarms when attacking, which makes the attacks faster.
+    /// Computing it takes about as long as generating the table.
+    #[clap(long, value_parser)]
+    false_alarm_index: bool,
+
+    /// Store a checkpoint after each filtration of the chains.
+    /// An interrupted generation can then be continued with `--resume`.
+    #[clap(long, value_parser)]
+    checkpoint: bool,
+
+    /// The maximum memory used by the chains during the generation, in gigabytes.
+    /// If the chains don't fit, they are deduplicated on the disk after each filtration, which is slower.
+    /// The generated table itself still has to fit in memory.
+    #[clap(long, value_parser = check_max_host_memory, value_name = "GB", conflicts_with_all = &["checkpoint", "resume"])]
+    max_host_memory: Option<f64>,
+
+    /// Only generate a part of the startpoints of each table, such as `2/4` for the second quarter.
+    /// This splits the generation across several machines, and the parts are then assembled with the `merge` command.
+    #[clap(long, value_parser = check_shard, value_name = "i/N", conflicts_with_all = &["checkpoint", "resume", "compress", "max-host-memory", "false-alarm-index"])]
+    shard: Option<Shard>,
+
+    /// The maximum number of chains computed at once by the GPU.
+    /// By default, it is computed from the memory and the properties of the device.
+    #[clap(long, value_parser = value_parser!(u64).range(1..), conflicts_with_all = &["checkpoint", "resume", "max-host-memory", "shard"])]
+    batch_size: Option<u64>,
+
+    /// Split the generation between several devices of the backend, such as `0,1` for the first two GPUs.
+    /// Each device computes a part of every batch, proportional to its own batch size.
+    /// The devices are numbered in the order of the driver, as listed when a device doesn't exist.
+    #[clap(long, value_parser, value_delimiter = ',', value_name = "DEVICES", conflicts_with_all = &["checkpoint", "resume", "max-host-memory", "shard", "batch-size"])]
+    devices: Option<Vec<usize>>,
+
+    /// Print the progress as plain lines instead of a progress bar, for logs and scripts.
+    /// This is the default when the standard error is not a terminal.
+    #[clap(long, value_parser)]
+    no_progress_bar: bool,
+
+    /// Resume an interrupted generation in an existing directory.
+    /// The tables already generated are skipped, and the others are resumed from their checkpoint if there is one.
+    #[clap(long, value_parser)]
+    resume: bool,
 }
 
 /// Dump and crack NTLM hashes from Windows accounts.
 ///
+/// The hashes of the local accounts are read from the SAM registry file,
+/// and those of the domain accounts from a copy of the ntds.dit database of a domain controller.
+///
 /// Note that this cannot be used on a Windows machine to dump the hashes of the same Windows,
 /// because the required files are locked by the OS.
 #[derive(Args)]
@@ -228,7 +976,7 @@ pub struct Stealdows {
     user: Vec<String>,
 
     /// Attempts to crack the hashes dumped using the rainbow table(s) provided as an argument.
-    /// The hash type of the table(s) must be NTLM.
+    /// The hash type of the table(s) must be NTLM, or LM to crack the LM hashes of the accounts that have one.
     #[clap(long, value_parser, value_name = "TABLES_DIR")]
     crack: Option<PathBuf>,
 
@@ -240,23 +988,91 @@ pub struct Stealdows {
 
     /// The path to the SAM registry file. If not provided an attempt will be made to find it automatically.
     /// This path is usually `C:\Windows\System32\config\SAM`.
-    #[clap(long, value_parser, requires = "system")]
+    #[clap(long, value_parser, requires = "system", group = "database")]
     sam: Option<PathBuf>,
 
+    /// The path to an offline copy of the ntds.dit database of a domain controller, to dump the domain accounts.
+    /// This path is usually `C:\Windows\NTDS\ntds.dit`, and the SYSTEM registry file of the same domain controller is needed.
+    #[clap(long, value_parser, requires = "system", group = "database")]
+    ntds: Option<PathBuf>,
+
     /// The path to the SYSTEM registry file. If not provided an attempt will be made to find it automatically.
     /// This path is usually `C:\Windows\System32\config\SYSTEM`.
-    #[clap(long, value_parser, requires = "sam")]
+    #[clap(long, value_parser, requires = "database")]
     system: Option<PathBuf>,
+
+    /// Print the passwords found in hexadecimal, in the `$HEX[...]` format.
+    /// The passwords that can't be printed as is are always printed this way.
+    #[clap(long, value_parser, requires = "crack")]
+    hex_output: bool,
 }
 
-/// Checks if the charset is made of ASCII characters.
-fn check_charset(charset: &str) -> Result<String> {
-    ensure!(
-        charset.is_ascii(),
-        "The charset can only contain ASCII characters"
-    );
+/// Dump and crack password hashes from a Unix shadow file.
+///
+/// Only the raw MD5/SHA digests can be cracked with rainbow tables, unsalted or salted like the tables.
+/// The salted and iterated crypt formats (md5crypt, sha512crypt, yescrypt...) are reported but can't be cracked.
+#[derive(Args)]
+pub struct Stealnix {
+    /// The path to the shadow file.
+    #[clap(long, value_parser, default_value = "/etc/shadow")]
+    shadow: PathBuf,
+
+    /// Search for a specific user.
+    /// You can specify several users by using multiple times this flag.
+    #[clap(short, long, value_parser)]
+    user: Vec<String>,
+
+    /// Attempts to crack the digests dumped using the rainbow table(s) provided as an argument.
+    #[clap(long, value_parser, value_name = "TABLES_DIR")]
+    crack: Option<PathBuf>,
+
+    /// Don't load all the tables at the same time to save memory.
+    /// This is slower on average than searching with all the tables at once.
+    #[clap(long, value_parser, requires = "crack")]
+    low_memory: bool,
+
+    /// Print the passwords found in hexadecimal, in the `$HEX[...]` format.
+    /// The passwords that can't be printed as is are always printed this way.
+    #[clap(long, value_parser, requires = "crack")]
+    hex_output: bool,
+}
+
+/// Formats a password found in the tables, in hexadecimal if `hex` is true.
+fn format_password(password: Password, hex: bool) -> String {
+    if hex {
+        password.hex().to_string()
+    } else {
+        password.to_string()
+    }
+}
+
+/// Encodes a charset in Latin-1.
+fn latin1_charset(charset: &str) -> Result<Vec<u8>> {
+    charset
+        .chars()
+        .map(|c| u8::try_from(c).ok())
+        .collect::<Option<_>>()
+        .context("The charset can only contain Latin-1 characters. Use --utf8 to encode the passwords in UTF-8")
+}
+
+/// Sets the charset of a context, encoded in UTF-8 or in Latin-1.
+fn with_charset(
+    ctx_builder: RainbowTableCtxBuilder,
+    charset: &str,
+    utf8: bool,
+) -> Result<RainbowTableCtxBuilder> {
+    if utf8 {
+        Ok(ctx_builder.utf8_charset(charset))
+    } else {
+        Ok(ctx_builder.charset(&latin1_charset(charset)?))
+    }
+}
 
-    Ok(charset.to_owned())
+/// Checks if the mask is valid.
+fn check_mask(mask: &str) -> Result<String> {
+    parse_mask(mask)?;
+
+    Ok(mask.to_owned())
 }
 
 /// Checks if the alpha coefficient is a float between 0 and 1.
@@ -271,10 +1087,58 @@ fn check_alpha(alpha: &str) -> Result<f64> {
     Ok(alpha)
 }
 
+/// Checks if the maximum host memory is a positive number of gigabytes.
+fn check_max_host_memory(memory: &str) -> Result<f64> {
+    let memory = memory
+        .parse::<f64>()
+        .context("The maximum host memory should be a number")?;
+
+    ensure!(memory > 0., "The maximum host memory should be positive");
+
+    Ok(memory)
+}
+
+/// Checks if the zstd compression level is valid and if zstd is available.
+fn check_zstd_level(level: &str) -> Result<i32> {
+    ensure!(cfg!(feature = "zstd"), ZSTD_UNAVAILABLE);
+
+    let level = level
+        .parse::<i32>()
+        .context("The zstd level should be a number")?;
+
+    ensure!(
+        (1..=22).contains(&level),
+        "The zstd level should be between 1 and 22"
+    );
+
+    Ok(level)
+}
+
+/// Checks if the shard is valid.
+fn check_shard(shard: &str) -> Result<Shard> {
+    Ok(shard.parse()?)
+}
+
+/// Checks if the table name template is valid.
+fn check_name_template(template: &str) -> Result<NameTemplate> {
+    Ok(template.parse()?)
+}
+
 /// Checks if the digest is valid hexadecimal.
+/// The digest is normalized first, so that common formats such as `0xAABB` or `aa:bb` are accepted.
 fn check_hex(hex: &str) -> Result<String> {
-    hex::decode(hex).context("The digest is not valid hexadecimal")?;
-    Ok(hex.to_owned())
+    let hex = hex.trim();
+    let hex = hex
+        .strip_prefix("0x")
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex)
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect::<String>()
+        .to_lowercase();
+
+    hex::decode(&hex).context("The digest is not valid hexadecimal")?;
+    Ok(hex)
 }
 
 fn main() {
@@ -286,30 +1150,274 @@ fn main() {
 
 fn try_main() -> Result<()> {
     let cli = Cli::parse();
+    // the trace is written when the guard is dropped
+    let _profile_guard = cli.profile.as_deref().map(start_profiling);
 
     match cli.commands {
-        Commands::Attack(args) => attack(args)?,
+        Commands::Attack(args) => attack(args, cli.output)?,
         Commands::Generate(args) => generate(args)?,
         Commands::Compress(args) => compress(args)?,
         Commands::Decompress(args) => decompress(args)?,
-        Commands::Stealdows(args) => stealdows(args)?,
+        Commands::Stealdows(args) => stealdows(args, cli.output)?,
+        Commands::Stealnix(args) => stealnix(args, cli.output)?,
+        Commands::CoverageMap(args) => coverage_map(args)?,
+        Commands::Export(args) => export(args)?,
+        Commands::Import(args) => import(args)?,
+        Commands::Info(args) => info(args, cli.output)?,
+        Commands::Bench(args) => bench(args)?,
+        Commands::Estimate(args) => estimate(args, cli.output)?,
+        Commands::Merge(args) => merge(args)?,
+        Commands::Verify(args) => verify(args, cli.output)?,
+        Commands::Rebuild(args) => rebuild(args)?,
+        Commands::Selftest(args) => selftest(args, cli.output)?,
+        Commands::Bruteforce(args) => bruteforce(args, cli.output)?,
+        Commands::Analyze(args) => analyze(args, cli.output)?,
+        Commands::Serve(args) => serve(args)?,
     }
 
     Ok(())
 }
 
+/// Helper function to print the results of a command as JSON.
+fn print_json(results: &impl Serialize) -> Result<()> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(results).context("Unable to serialize the results")?
+    );
+    Ok(())
+}
+
 /// Helper function to create a directory where will be stored rainbow tables.
 fn create_dir_to_store_tables(dir: &Path) -> Result<()> {
     fs::create_dir(dir)
         .context("Unable to create the specified directory to store the rainbow tables")
 }
 
+/// Helper function to store a converted rainbow table in place of the original one.
+/// The converted table is first stored to a temporary file and checked.
+/// It is then renamed with the `ext` extension, and only then is the original table removed.
+fn replace_table(
+    original: &Path,
+    ext: &str,
+    store: impl FnOnce(&Path) -> Result<(), CugparckError>,
+    check: impl FnOnce(&[u8]) -> Result<()>,
+) -> Result<()> {
+    let new_path = original.with_extension(ext);
+    let tmp_path = original.with_extension(format!("{ext}.tmp"));
+
+    store(&tmp_path).context("Unable to store the converted rainbow table to the disk")?;
+
+    let file = File::open(&tmp_path).context("Unable to open the converted rainbow table")?;
+    // SAFETY: the file exists and is not being modified anywhere else.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    if let Err(err) = check(&mmap) {
+        drop(mmap);
+        fs::remove_file(&tmp_path)?;
+        return Err(err.context("The converted rainbow table is invalid"));
+    }
+
+    drop(mmap);
+    fs::rename(&tmp_path, &new_path)
+        .context("Unable to replace the rainbow table by the converted one")?;
+    fs::remove_file(original).context("Unable to remove the original rainbow table")?;
+
+    Ok(())
+}
+
+/// The resolution of the progress of a single task.
+const TASK_PROGRESS_SCALE: u64 = 10_000;
+
+/// Displays the progress of conversions or searches on the standard error,
+/// so that the output of the commands is not mixed with it.
+/// Several tasks, such as the tables converted one after the other, share the same bar.
+/// Nothing is displayed if the standard error is not an interactive terminal.
+struct TaskProgress {
+    bar: ProgressBar,
+    /// The index of the task in progress.
+    task: AtomicU64,
+}
+
+impl TaskProgress {
+    /// Creates a progress bar for `tasks` tasks.
+    fn new(tasks: usize, message: impl Into<Cow<'static, str>>) -> Self {
+        if !stderr().is_tty() {
+            return Self::hidden();
+        }
+
+        let bar = ProgressBar::new(tasks as u64 * TASK_PROGRESS_SCALE).with_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} {msg} [{elapsed_precise}] [{wide_bar:.cyan/blue}]")
+                .unwrap()
+                .progress_chars("#>-"),
+        );
+        bar.set_message(message);
+        bar.enable_steady_tick(Duration::from_millis(100));
+
+        Self {
+            bar,
+            task: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a progress that is never displayed.
+    fn hidden() -> Self {
+        Self {
+            bar: ProgressBar::hidden(),
+            task: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the message displayed next to the bar.
+    fn set_message(&self, message: impl Into<Cow<'static, str>>) {
+        self.bar.set_message(message);
+    }
+
+    /// Marks the task in progress as done and moves to the next one.
+    fn next_task(&self) {
+        let task = self.task.fetch_add(1, Ordering::Relaxed) + 1;
+        self.bar.set_position(task * TASK_PROGRESS_SCALE);
+    }
+
+    /// Hides the bar while `f` prints to the terminal.
+    fn suspend<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.bar.suspend(f)
+    }
+}
+
+impl ProgressListener for TaskProgress {
+    fn progress(&self, progress: Progress) {
+        let (Progress::Chains { done, total }
+        | Progress::Columns { done, total }
+        | Progress::Passwords { done, total }) = progress;
+        if total == 0 {
+            return;
+        }
+
+        let task = self.task.load(Ordering::Relaxed);
+        self.bar.set_position(
+            task * TASK_PROGRESS_SCALE + done as u64 * TASK_PROGRESS_SCALE / total as u64,
+        );
+    }
+}
+
+impl Drop for TaskProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+/// Helper function to memory map a rainbow table.
+/// The tables stored with zstd (`.rtz`) are decompressed in memory.
+fn map_table(path: &Path) -> Result<Mmap> {
+    let file = File::open(path).context("Unable to open a rainbow table")?;
+    // SAFETY: the file exists and is not being modified anywhere else.
+    let mmap = unsafe { Mmap::map(&file)? };
+
+    if path.extension() == Some("rtz".as_ref()) {
+        decompress_table(&mmap)
+    } else {
+        Ok(mmap)
+    }
+}
+
+/// Helper function to decompress a table stored with zstd.
+/// The table is decompressed to an anonymous memory map, so that it can be used like the other tables.
+#[cfg(feature = "zstd")]
+fn decompress_table(bytes: &[u8]) -> Result<Mmap> {
+    let mut decompressed = MmapMut::map_anon(zstd_decompressed_len(bytes)?)?;
+    decompress_zstd(bytes, &mut decompressed)?;
+
+    Ok(decompressed.make_read_only()?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_table(_bytes: &[u8]) -> Result<Mmap> {
+    anyhow::bail!("{ZSTD_UNAVAILABLE}")
+}
+
+/// Helper function to compress the bytes of a stored simple table with zstd, without deserializing it.
+#[cfg(feature = "zstd")]
+fn compress_table(bytes: &[u8], path: &Path, level: i32) -> Result<(), CugparckError> {
+    compress_zstd(bytes, path, level)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_table(_bytes: &[u8], _path: &Path, _level: i32) -> Result<(), CugparckError> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, ZSTD_UNAVAILABLE).into())
+}
+
+/// Helper function to store a simple rainbow table, compressed with zstd if a level is given.
+fn store_simple_table(
+    table: &SimpleTable,
+    path: &Path,
+    zstd_level: Option<i32>,
+) -> Result<(), CugparckError> {
+    match zstd_level {
+        #[cfg(feature = "zstd")]
+        Some(level) => table.store_zstd(path, level),
+        _ => table.store(path),
+    }
+}
+
+/// The on-disk format of a rainbow table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TableFormat {
+    /// A `.rt` table, or a `.rtz` table once decompressed.
+    Simple,
+    /// A `.rtcde` table, using compressed delta encoding.
+    Compressed,
+    /// A `.rtt` table, storing truncated endpoints.
+    Truncated,
+}
+
+impl TableFormat {
+    /// Returns the format of a table from its extension, or `None` if the file isn't a table.
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("rt" | "rtz") => Some(Self::Simple),
+            Some("rtcde") => Some(Self::Compressed),
+            Some("rtt") => Some(Self::Truncated),
+            _ => None,
+        }
+    }
+}
+
+/// A rainbow table memory mapped from a file.
+struct MappedTable {
+    path: PathBuf,
+    format: TableFormat,
+    mmap: Mmap,
+}
+
+impl MappedTable {
+    /// Returns the archived table.
+    fn table(&self) -> Result<AnyTable<'_>, CugparckError> {
+        match self.format {
+            TableFormat::Simple => AnyTable::load_simple(&self.mmap),
+            TableFormat::Compressed => AnyTable::load_compressed(&self.mmap),
+            TableFormat::Truncated => AnyTable::load_truncated(&self.mmap),
+        }
+    }
+}
+
+/// Records the tracing spans to a Chrome trace file.
+/// The file is written when the returned guard is dropped.
+fn start_profiling(path: &Path) -> FlushGuard {
+    let (chrome_layer, guard) = ChromeLayerBuilder::new()
+        .file(path)
+        .include_args(true)
+        .build();
+    tracing_subscriber::registry().with(chrome_layer).init();
+
+    guard
+}
+
 /// Helper function to load rainbow tables from a directory.
-/// Returns a vector of memory mapped rainbow tables and true if the tables loaded are compressed.
-fn load_tables_from_dir(dir: &Path) -> Result<(Vec<Mmap>, bool)> {
-    let mut mmaps = Vec::new();
-    let mut is_simple_tables = false;
-    let mut is_compressed_tables = false;
+/// The tables can be stored in different formats and generated with different parameters,
+/// see `group_tables` to find the tables that can be searched together.
+fn load_tables_from_dir(dir: &Path) -> Result<Vec<MappedTable>> {
+    let mut tables = Vec::new();
 
     for file in fs::read_dir(&dir).context("Unable to open the specified directory")? {
         let file = file?;
@@ -318,105 +1426,392 @@ fn load_tables_from_dir(dir: &Path) -> Result<(Vec<Mmap>, bool)> {
             continue;
         }
 
-        match file.path().extension().and_then(|s| s.to_str()) {
-            Some("rt") => is_simple_tables = true,
-            Some("rtcde") => is_compressed_tables = true,
-            _ => continue,
+        let path = file.path();
+        let format = match TableFormat::from_path(&path) {
+            Some(format) => format,
+            None => continue,
         };
 
-        let file = File::open(file.path()).context("Unable to open a rainbow table")?;
-
-        // SAFETY: the file exists and is not being modified anywhere else.
-        unsafe { mmaps.push(Mmap::map(&file)?) };
+        let mmap = map_table(&path)?;
+        tables.push(MappedTable { path, format, mmap });
     }
 
-    ensure!(!mmaps.is_empty(), "No table found in the given directory");
+    ensure!(!tables.is_empty(), "No table found in the given directory");
 
-    ensure!(
-        !(is_simple_tables && is_compressed_tables),
-        "All tables in the directory should be of the same type",
-    );
+    Ok(tables)
+}
+
+/// Groups the tables generated with compatible parameters, which can be searched together.
+/// Each table is added to the first group it is compatible with, so the groups keep the order of the tables.
+/// Returns an error if two compatible tables have the same table number.
+fn group_tables(tables: Vec<MappedTable>) -> Result<Vec<Vec<MappedTable>>> {
+    let mut groups: Vec<(TableSetValidator, Vec<MappedTable>)> = Vec::new();
 
-    // check that the tables in the directory are all compatible.
     // since we're mmaping our files, we shouldn't run out of memory.
-    let all_ctx = if is_compressed_tables {
-        mmaps
-            .iter()
-            .map(|mmap| Ok(CompressedTable::load(mmap)?.ctx()))
-            .collect::<Result<Vec<_>>>()?
-    } else {
-        mmaps
-            .iter()
-            .map(|mmap| Ok(SimpleTable::load(mmap)?.ctx()))
-            .collect::<Result<Vec<_>>>()?
-    };
+    'tables: for table in tables {
+        let ctx = table.table()?.ctx();
+
+        for (validator, group) in &mut groups {
+            match validator.add(ctx) {
+                Ok(()) => {
+                    group.push(table);
+                    continue 'tables;
+                }
+                Err(CugparckError::IncompatibleTable { .. }) => (),
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!(
+                            "{} can't be used with the other tables",
+                            table.path.display()
+                        )
+                    })
+                }
+            }
+        }
 
-    let table_numbers = all_ctx.iter().map(|ctx| ctx.tn).collect::<HashSet<_>>();
+        let mut validator = TableSetValidator::new();
+        validator.add(ctx)?;
+        groups.push((validator, vec![table]));
+    }
 
-    ensure!(
-        table_numbers.len() == mmaps.len(),
-        "All tables in the directory should have a different table number",
-    );
+    Ok(groups.into_iter().map(|(_, group)| group).collect())
+}
+
+/// Tables generated with compatible parameters, which are searched together.
+struct TableGroup<'a> {
+    tables: &'a [MappedTable],
+    /// The false alarm filters of the tables, in the same order. It can be empty if there are none.
+    filters: Vec<&'a dyn FalseAlarmFilter>,
+    /// The context of the first table of the group.
+    ctx: RainbowTableCtx,
+}
 
-    let ctx_spaces_and_hash_types = all_ctx
+impl<'a> TableGroup<'a> {
+    fn new(tables: &'a [MappedTable], filters: Vec<&'a dyn FalseAlarmFilter>) -> Result<Self> {
+        Ok(Self {
+            ctx: tables[0].table()?.ctx(),
+            tables,
+            filters,
+        })
+    }
+}
+
+/// Returns the sizes of the digests that can be found in the groups, such as `16 or 20`.
+fn digest_sizes(groups: &[TableGroup]) -> String {
+    let sizes = groups
         .iter()
-        .map(|ctx| (ctx.charset, ctx.max_password_length, ctx.hash_type))
-        .collect::<HashSet<_>>();
+        .map(|group| group.ctx.hash_type.digest_size())
+        .collect::<BTreeSet<_>>();
+
+    sizes
+        .into_iter()
+        .map(|size| size.to_string())
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
 
-    ensure!(
-        ctx_spaces_and_hash_types.len() == 1,
-        "All tables in the directory should use the same charset, maximum password length and hash function"
-    );
+/// Helper function to load the false alarm indexes stored next to the tables.
+/// Returns the memory mapped index of each table, or `None` if the table doesn't have one.
+fn load_false_alarm_indexes(tables: &[MappedTable]) -> Result<Vec<Option<Mmap>>> {
+    tables
+        .iter()
+        .map(|table| {
+            let path = table.path.with_extension("fai");
+            if !path.exists() {
+                return Ok(None);
+            }
+
+            let file = File::open(&path).context("Unable to open a false alarm index")?;
+            // SAFETY: the file exists and is not being modified anywhere else.
+            Ok(Some(unsafe { Mmap::map(&file)? }))
+        })
+        .collect()
+}
+
+/// Returns the false alarm filters of the tables from their memory mapped indexes.
+/// The indexes that don't match their table are ignored.
+fn false_alarm_filters<'a>(
+    indexes: &'a [Option<Mmap>],
+    tables: &[MappedTable],
+) -> Result<Vec<&'a dyn FalseAlarmFilter>> {
+    indexes
+        .iter()
+        .zip(tables)
+        .map(|(index, table)| {
+            let index = match index {
+                Some(index) => FalseAlarmIndex::load(index)?,
+                None => return Ok(&() as &dyn FalseAlarmFilter),
+            };
+
+            let table = table.table()?;
+
+            // the index is only valid for the table it was created from, not for a converted table
+            if !index.matches(&table) {
+                eprintln!(
+                    "{}",
+                    format!("Warning: the false alarm index of table {} doesn't match the table and is ignored", table.ctx().tn).yellow()
+                );
+                return Ok(&() as &dyn FalseAlarmFilter);
+            }
 
-    Ok((mmaps, is_compressed_tables))
+            Ok(index as &dyn FalseAlarmFilter)
+        })
+        .collect()
 }
 
-/// Searches for a digest from the tables at a given path, table after table.
-/// If `low memory` is true, the tables aren't loaded at the same time to be searched in parallel.
+/// Searches for the passwords of digests in the given tables.
+/// `filters` are the false alarm filters of the tables, in the same order. It can be empty if there are none.
+/// If `low memory` is true, the tables are searched one after the other,
+/// and only for the digests that haven't been found yet.
 /// This slows the search but saves memory.
+/// Each search is a task of `progress`, see `search_task_count`, and its statistics are added to `stats`.
 fn search_tables(
-    digest: Digest,
-    mmaps: &[Mmap],
-    is_compressed: bool,
+    digests: &[Digest],
+    tables: &[MappedTable],
+    filters: &[&dyn FalseAlarmFilter],
     low_memory: bool,
-) -> Result<Option<Password>> {
-    match (is_compressed, low_memory) {
-        (true, true) => {
-            for mmap in mmaps {
-                if let Some(digest) = CompressedTable::load(mmap)?.search(digest) {
-                    return Ok(Some(digest));
-                }
-            }
+    progress: &TaskProgress,
+    stats: &mut TableSearchStats,
+) -> Result<Vec<Option<SearchHit>>> {
+    let filters = filters_or_none(filters, tables.len());
+
+    if low_memory {
+        return search_one_by_one(tables, filters, digests, progress, stats);
+    }
+
+    let archived = tables
+        .iter()
+        .map(MappedTable::table)
+        .collect::<Result<Vec<_>, _>>()?;
+    let archived = archived.iter().collect::<Vec<_>>();
+
+    let (found, table_stats) = TableCluster::new(&archived)
+        .with_false_alarm_filters(filters)
+        .with_progress(progress)
+        .search_batch_stats(digests);
+    progress.next_task();
+
+    for ((table, archived), table_stats) in tables.iter().zip(archived).zip(table_stats) {
+        stats.add(table, archived.ctx().tn, table_stats);
+    }
 
-            Ok(None)
+    Ok(found)
+}
+
+/// The statistics of the searches of each table, keyed by the path of the table.
+/// They are accumulated over all the searches of a command, along with the table number of each table.
+#[derive(Default)]
+struct TableSearchStats(BTreeMap<PathBuf, (usize, SearchStats)>);
+
+impl TableSearchStats {
+    /// Adds the statistics of a search of a table.
+    fn add(&mut self, table: &MappedTable, table_number: usize, stats: SearchStats) {
+        self.0
+            .entry(table.path.clone())
+            .or_insert((table_number, SearchStats::default()))
+            .1 += stats;
+    }
+}
+
+/// Searches for the passwords of digests in groups of tables.
+/// Each group is only searched for the digests it could contain and that weren't found in the previous groups.
+/// When `hash_types` is given, each digest is also only searched in the groups using one of its hash functions.
+/// Returns the hits along with the index of the group they were found in.
+/// Each search is a task of `progress`, see `group_search_task_count`.
+fn search_table_groups(
+    digests: &[Digest],
+    hash_types: Option<&[HashTypes]>,
+    groups: &[TableGroup],
+    low_memory: bool,
+    progress: &TaskProgress,
+    stats: &mut TableSearchStats,
+) -> Result<Vec<Option<(SearchHit, usize)>>> {
+    let mut found = vec![None; digests.len()];
+
+    for (group_index, group) in groups.iter().enumerate() {
+        let remaining = (0..digests.len())
+            .filter(|&i| {
+                found[i].is_none()
+                    && group.ctx.could_contain_digest(&digests[i])
+                    && hash_types
+                        .is_none_or(|hash_types| hash_types[i].contains(group.ctx.hash_type))
+            })
+            .collect::<Vec<_>>();
+        let remaining_digests = remaining.iter().map(|&i| digests[i]).collect::<Vec<_>>();
+
+        let hits = search_tables(
+            &remaining_digests,
+            group.tables,
+            &group.filters,
+            low_memory,
+            progress,
+            stats,
+        )?;
+        for (i, hit) in remaining.into_iter().zip(hits) {
+            found[i] = hit.map(|hit| (hit, group_index));
         }
+    }
 
-        (true, false) => {
-            let tables = mmaps
-                .iter()
-                .map(|mmap| CompressedTable::load(mmap))
-                .collect::<Result<Vec<_>, _>>()?;
+    Ok(found)
+}
+
+/// Returns the number of searches made by `search_table_groups` for a single batch of digests.
+fn group_search_task_count(groups: &[TableGroup], low_memory: bool) -> usize {
+    groups
+        .iter()
+        .map(|group| search_task_count(group.tables, low_memory))
+        .sum()
+}
+
+/// Returns the number of searches made by `search_tables` for a single batch of digests.
+fn search_task_count(tables: &[MappedTable], low_memory: bool) -> usize {
+    if low_memory {
+        tables.len()
+    } else {
+        1
+    }
+}
 
-            Ok(TableCluster::new(&tables).search(digest))
+/// Searches the tables one after the other for the digests that haven't been found yet.
+fn search_one_by_one(
+    tables: &[MappedTable],
+    filters: Vec<&dyn FalseAlarmFilter>,
+    digests: &[Digest],
+    progress: &TaskProgress,
+    stats: &mut TableSearchStats,
+) -> Result<Vec<Option<SearchHit>>> {
+    let mut found = vec![None; digests.len()];
+
+    for (mapped, filter) in tables.iter().zip(filters) {
+        let remaining = (0..digests.len())
+            .filter(|&i| found[i].is_none())
+            .collect::<Vec<_>>();
+        let remaining_digests = remaining.iter().map(|&i| digests[i]).collect::<Vec<_>>();
+
+        let table = mapped.table()?;
+        let table = [&table];
+        let cluster = TableCluster::new(&table)
+            .with_false_alarm_filters(vec![filter])
+            .with_progress(progress);
+        let (hits, table_stats) = cluster.search_batch_stats(&remaining_digests);
+        for (i, hit) in remaining.into_iter().zip(hits) {
+            found[i] = hit;
         }
+        stats.add(mapped, table[0].ctx().tn, table_stats[0]);
+        progress.next_task();
+    }
 
-        (false, true) => {
-            for mmap in mmaps {
-                if let Some(digest) = SimpleTable::load(mmap)?.search(digest) {
-                    return Ok(Some(digest));
-                }
+    Ok(found)
+}
+
+/// Returns the false alarm filters to use for `len` tables.
+/// If no filter is provided, a filter that doesn't detect any false alarm is used for each table.
+fn filters_or_none<'a>(
+    filters: &[&'a dyn FalseAlarmFilter],
+    len: usize,
+) -> Vec<&'a dyn FalseAlarmFilter> {
+    if filters.is_empty() {
+        vec![&(); len]
+    } else {
+        filters.to_vec()
+    }
+}
+
+/// Returns a path in the temporary directory, unique to this test process and to each call,
+/// so that tests running in parallel or from several processes never share a file.
+#[cfg(test)]
+fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("cugparck_test_{}_{id}_{name}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use clap::{CommandFactory, Parser};
+    use cugparck_cpu::{
+        backend::Cpu, NameTemplate, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage,
+        SimpleTable,
+    };
+
+    use crate::{check_hex, group_tables, load_tables_from_dir, temp_path, Cli, Commands};
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_attack_digest_file() {
+        let cli = Cli::try_parse_from(["cugparck-cli", "attack", "--digest-file", "file", "dir"])
+            .unwrap();
+        match cli.commands {
+            Commands::Attack(args) => {
+                assert!(args.digest.is_none());
+                assert_eq!(Some(PathBuf::from("file")), args.digest_file);
+                assert_eq!(PathBuf::from("dir"), args.dir);
             }
+            _ => unreachable!(),
+        }
+    }
 
-            Ok(None)
+    #[test]
+    fn test_group_tables() {
+        let dir = temp_path("group");
+        fs::create_dir_all(&dir).unwrap();
+
+        let template: NameTemplate = NameTemplate::DESCRIPTIVE.parse().unwrap();
+        for (charset, table_number) in [(b"abc", 1), (b"abd", 1), (b"abc", 2)] {
+            let ctx = RainbowTableCtxBuilder::new()
+                .chain_length(10)
+                .max_password_length(3)
+                .charset(charset)
+                .table_number(table_number)
+                .build()
+                .unwrap();
+            let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+            table
+                .store(&dir.join(format!("{}.rt", template.file_stem(&ctx))))
+                .unwrap();
         }
 
-        (false, false) => {
-            let tables = mmaps
+        let groups = group_tables(load_tables_from_dir(&dir).unwrap()).unwrap();
+        let mut sizes = groups.iter().map(Vec::len).collect::<Vec<_>>();
+        sizes.sort_unstable();
+        assert_eq!(vec![1, 2], sizes);
+
+        for group in &groups {
+            let ctx = group[0].table().unwrap().ctx();
+            assert!(group
                 .iter()
-                .map(|mmap| SimpleTable::load(mmap))
-                .collect::<Result<Vec<_>, _>>()?;
+                .all(|table| table.table().unwrap().ctx().charset == ctx.charset));
+        }
 
-            Ok(TableCluster::new(&tables).search(digest))
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_hex() {
+        let expected = "aabbcc";
+
+        for hex in [
+            "aabbcc",
+            "AABBCC",
+            "AaBbCc",
+            "0xAABBCC",
+            "0Xaabbcc",
+            "AA:BB:CC",
+            "aa bb cc",
+            " aabbcc\n",
+        ] {
+            assert_eq!(expected, check_hex(hex).unwrap());
         }
+
+        assert!(check_hex("aabbc").is_err());
+        assert!(check_hex("0xzz").is_err());
     }
 }
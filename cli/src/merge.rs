@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+
+use anyhow::{ensure, Context, Result};
+use crossterm::style::Stylize;
+use cugparck_cpu::{
+    CompressedTable, RainbowTable, RainbowTableStorage, Shard, SimpleTable, TableHeader,
+    TruncatedTable,
+};
+
+use crate::{map_table, Merge, TableFormat};
+
+pub fn merge(args: Merge) -> Result<()> {
+    let input_format = TableFormat::from_path(&args.tables[0]);
+    ensure!(
+        input_format.is_some()
+            && args
+                .tables
+                .iter()
+                .all(|path| TableFormat::from_path(path) == input_format),
+        "The tables to merge should all be rainbow tables of the same type"
+    );
+    ensure!(!args.out.exists(), "The output file already exists");
+    ensure!(
+        args.out.extension() != Some("rtz".as_ref()),
+        "The merged table can't be stored with zstd directly, use `compress --zstd-level` afterwards"
+    );
+
+    let mmaps = args
+        .tables
+        .iter()
+        .map(|path| {
+            map_table(path).with_context(|| format!("Unable to open the table {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let shards = mmaps
+        .iter()
+        .map(|mmap| Ok(TableHeader::from_bytes(mmap)?.and_then(|header| header.shard)))
+        .collect::<Result<Vec<_>>>()?;
+    check_shards(&shards)?;
+
+    let merged = match input_format.unwrap() {
+        TableFormat::Simple => {
+            let tables = mmaps
+                .iter()
+                .map(|mmap| SimpleTable::load(mmap))
+                .collect::<Result<Vec<_>, _>>()?;
+            SimpleTable::merge(&tables)?
+        }
+        TableFormat::Compressed => {
+            let tables = mmaps
+                .iter()
+                .map(|mmap| CompressedTable::load(mmap))
+                .collect::<Result<Vec<_>, _>>()?;
+            SimpleTable::merge(&tables)?
+        }
+        TableFormat::Truncated => {
+            let tables = mmaps
+                .iter()
+                .map(|mmap| TruncatedTable::load(mmap))
+                .collect::<Result<Vec<_>, _>>()?;
+            SimpleTable::merge(&tables)?
+        }
+    };
+    drop(mmaps);
+
+    let chain_count = merged.len();
+    let disk_error = "Unable to store the merged table to the disk";
+    match TableFormat::from_path(&args.out) {
+        Some(TableFormat::Compressed) => merged
+            .into_rainbow_table::<CompressedTable>()
+            .store(&args.out)
+            .context(disk_error)?,
+        Some(TableFormat::Truncated) => merged
+            .into_rainbow_table::<TruncatedTable>()
+            .store(&args.out)
+            .context(disk_error)?,
+        _ => merged.store(&args.out).context(disk_error)?,
+    }
+
+    println!(
+        "Merged {} tables into {} ({chain_count} unique chains)",
+        args.tables.len(),
+        args.out.display()
+    );
+
+    Ok(())
+}
+
+/// Checks that the shards of the tables to merge belong together, and warns if some shards are missing.
+fn check_shards(shards: &[Option<Shard>]) -> Result<()> {
+    let shards = shards.iter().flatten().collect::<Vec<_>>();
+    let count = match shards.first() {
+        Some(shard) => shard.count(),
+        None => return Ok(()),
+    };
+
+    ensure!(
+        shards.iter().all(|shard| shard.count() == count),
+        "The shards to merge should split the table in the same number of parts"
+    );
+
+    let indexes = shards
+        .iter()
+        .map(|shard| shard.index())
+        .collect::<BTreeSet<_>>();
+    ensure!(
+        indexes.len() == shards.len(),
+        "The same shard is merged more than once"
+    );
+
+    let missing = (1..=count)
+        .filter(|index| !indexes.contains(index))
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: the shard(s) {} out of {count} are missing, the merged table will be incomplete",
+                missing.join(", ")
+            )
+            .yellow()
+        );
+    }
+
+    Ok(())
+}
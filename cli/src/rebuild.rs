@@ -0,0 +1,99 @@
+use std::io::stderr;
+
+use anyhow::{ensure, Context, Result};
+use crossterm::tty::IsTty;
+use cugparck_commons::RainbowTableCtx;
+use cugparck_cpu::{
+    measured_success_rate, CompressedTable, RainbowTable, RainbowTableStorage, SimpleTable,
+    TruncatedTable,
+};
+use indicatif::HumanBytes;
+
+use crate::{
+    create_dir_to_store_tables,
+    generate::{start_generation_with, wait_for_table, GenerationMode, GenerationProgress},
+    load_tables_from_dir, MappedTable, Rebuild, TableFormat,
+};
+
+pub fn rebuild(args: Rebuild) -> Result<()> {
+    create_dir_to_store_tables(&args.out_dir)?;
+
+    let tables = load_tables_from_dir(&args.in_dir)?;
+    let chain_length = args.chain_length as usize;
+
+    for MappedTable { format, mmap, .. } in tables {
+        let table = match format {
+            TableFormat::Simple => SimpleTable::from_rainbow_table(SimpleTable::load(&mmap)?),
+            TableFormat::Compressed => {
+                let ar = CompressedTable::load(&mmap)?;
+                ensure!(
+                    !ar.is_endpoints_only(),
+                    "The tables storing only their endpoints can't be rebuilt"
+                );
+                SimpleTable::from_rainbow_table(ar)
+            }
+            TableFormat::Truncated => {
+                let ar = TruncatedTable::load(&mmap)?;
+                SimpleTable::from_vec(ar.par_chains(&()), ar.ctx())
+            }
+        };
+        drop(mmap);
+
+        let old_ctx = table.ctx();
+        let old_len = table.len();
+        let ctx = RainbowTableCtx {
+            t: chain_length,
+            ..old_ctx
+        };
+        println!(
+            "Rebuilding table {} from a chain length of {} to {chain_length}",
+            ctx.tn, old_ctx.t
+        );
+
+        let table_handle =
+            start_generation_with(args.backend, ctx, GenerationMode::Rebuild(Box::new(table)))?;
+        let mut progress = GenerationProgress::new(args.no_progress_bar || !stderr().is_tty());
+        let (table, stats) = wait_for_table(table_handle, &mut progress, None)?;
+
+        let disk_error = "Unable to store the rebuilt rainbow table to the disk";
+        let stem = args.name_template.file_stem(&ctx);
+        let size = match format {
+            TableFormat::Simple => {
+                table
+                    .store(&args.out_dir.join(format!("{stem}.rt")))
+                    .context(disk_error)?;
+                stats.bytes
+            }
+            TableFormat::Compressed => {
+                let table_stats = table.stats().cloned();
+                let table = table
+                    .into_rainbow_table::<CompressedTable>()
+                    .with_stats(table_stats);
+                table
+                    .store(&args.out_dir.join(format!("{stem}.rtcde")))
+                    .context(disk_error)?;
+                CompressedTable::stored_size(&ctx, table.len())
+            }
+            TableFormat::Truncated => {
+                let table_stats = table.stats().cloned();
+                let table = table
+                    .into_rainbow_table::<TruncatedTable>()
+                    .with_stats(table_stats);
+                table
+                    .store(&args.out_dir.join(format!("{stem}.rtt")))
+                    .context(disk_error)?;
+                TruncatedTable::stored_size(&ctx, table.len())
+            }
+        };
+
+        progress.println(&format!(
+            "{old_len} chains merged into {} ({}), success rate from {:.2}% to {:.2}%",
+            stats.unique_chains,
+            HumanBytes(size as u64),
+            measured_success_rate(&old_ctx, old_len) * 100.,
+            measured_success_rate(&ctx, stats.unique_chains) * 100.,
+        ));
+    }
+
+    Ok(())
+}
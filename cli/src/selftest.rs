@@ -0,0 +1,107 @@
+use anyhow::{ensure, Result};
+use crossterm::style::Stylize;
+use cugparck_cpu::{backend, self_test, CugparckError, SelfTestCheck};
+use serde::Serialize;
+
+use crate::{print_json, AvailableBackend, OutputFormat, Selftest};
+
+pub fn selftest(args: Selftest, output: OutputFormat) -> Result<()> {
+    let backend = args.backend.resolve();
+    let checks = run_self_test(backend)?;
+    let failed = checks.iter().filter(|check| !check.passed).count();
+
+    let report = SelftestReport {
+        backend: backend.name(),
+        checks: checks.into_iter().map(CheckReport::from).collect(),
+        passed: failed == 0,
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Human => report.print(),
+    }
+
+    ensure!(
+        failed == 0,
+        "{failed} checks failed on the {} backend, the tables it generates would be wrong",
+        report.backend
+    );
+
+    Ok(())
+}
+
+/// Runs the self-test on the given backend.
+fn run_self_test(backend: AvailableBackend) -> Result<Vec<SelfTestCheck>, CugparckError> {
+    match backend {
+        AvailableBackend::Auto => run_self_test(backend.resolve()),
+        AvailableBackend::Cpu => self_test::<backend::Cpu>(),
+        #[cfg(feature = "cuda")]
+        AvailableBackend::Cuda => self_test::<backend::Cuda>(),
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        AvailableBackend::Vulkan => self_test::<backend::Vulkan>(),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx12 => self_test::<backend::Dx12>(),
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        AvailableBackend::Dx11 => self_test::<backend::Dx11>(),
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        AvailableBackend::Metal => self_test::<backend::Metal>(),
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        AvailableBackend::OpenGL => self_test::<backend::OpenGL>(),
+    }
+}
+
+/// The results of the self-test of a backend.
+#[derive(Serialize)]
+struct SelftestReport {
+    backend: &'static str,
+    checks: Vec<CheckReport>,
+    /// True if all the checks passed.
+    passed: bool,
+}
+
+/// The result of a single check.
+#[derive(Serialize)]
+struct CheckReport {
+    name: String,
+    passed: bool,
+}
+
+impl From<SelfTestCheck> for CheckReport {
+    fn from(check: SelfTestCheck) -> Self {
+        Self {
+            name: check.name,
+            passed: check.passed,
+        }
+    }
+}
+
+impl SelftestReport {
+    /// Prints the results in a human-readable way.
+    fn print(&self) {
+        println!("Self-test of the {} backend", self.backend);
+        for check in &self.checks {
+            let result = if check.passed {
+                "passed".green()
+            } else {
+                "FAILED".red()
+            };
+            println!("  {}: {result}", check.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{selftest::selftest, AvailableBackend, OutputFormat, Selftest};
+
+    #[test]
+    fn test_selftest_cpu() {
+        selftest(
+            Selftest {
+                backend: AvailableBackend::Cpu,
+            },
+            OutputFormat::Json,
+        )
+        .unwrap();
+    }
+}
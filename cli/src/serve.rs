@@ -0,0 +1,256 @@
+use std::{fs, io::Read, time::Instant};
+
+use anyhow::{anyhow, Context, Result};
+use crossterm::style::Stylize;
+use serde::Serialize;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    attack::{crack_targets, parse_digest_file, parse_wordlist, AttackReport, Target},
+    detect::detect_digest,
+    false_alarm_filters, group_tables, load_false_alarm_indexes, load_tables_from_dir, Serve,
+    TableGroup, TaskProgress,
+};
+
+/// The maximum size of the body of a request, in bytes.
+const MAX_BODY_SIZE: u64 = 16 << 20;
+
+pub fn serve(args: Serve) -> Result<()> {
+    let tables = group_tables(load_tables_from_dir(&args.dir)?)?;
+    let indexes = tables
+        .iter()
+        .map(|tables| load_false_alarm_indexes(tables))
+        .collect::<Result<Vec<_>>>()?;
+    let groups = tables
+        .iter()
+        .zip(&indexes)
+        .map(|(tables, indexes)| TableGroup::new(tables, false_alarm_filters(indexes, tables)?))
+        .collect::<Result<Vec<_>>>()?;
+
+    let wordlist = match &args.wordlist {
+        Some(path) => fs::read(path).context("Unable to read the wordlist")?,
+        None => Vec::new(),
+    };
+    let words = parse_wordlist(&wordlist);
+
+    let cracker = Cracker {
+        groups: &groups,
+        words: &words,
+        low_memory: args.low_memory,
+        hex_output: args.hex_output,
+    };
+
+    let server = Server::http(args.listen)
+        .map_err(|err| anyhow!("Unable to listen on {}: {err}", args.listen))?;
+    eprintln!(
+        "Serving {} table(s) on http://{}",
+        tables.iter().map(Vec::len).sum::<usize>(),
+        args.listen
+    );
+
+    // the requests are handled one after the other: a search already uses all the cores,
+    // and the tables of several searches at a time may not fit in memory.
+    for mut request in server.incoming_requests() {
+        let (status, content) = match read_body(request.body_length(), request.as_reader()) {
+            Ok(body) => cracker.handle(request.method(), request.url(), &body),
+            Err(response) => response,
+        };
+        eprintln!("{} {} {status}", request.method(), request.url());
+
+        let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+        let response = Response::from_string(content)
+            .with_status_code(status)
+            .with_header(header);
+        if let Err(err) = request.respond(response) {
+            eprintln!("{}", format!("Warning: unable to respond: {err}").yellow());
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the body of a request, or returns the error response to send if it is too large or isn't UTF-8.
+/// `body_length` is the length announced by the client, if any.
+fn read_body(body_length: Option<usize>, reader: impl Read) -> Result<String, (u16, String)> {
+    let too_large = || {
+        error_response(
+            413,
+            &format!("The body should be at most {MAX_BODY_SIZE} bytes long"),
+        )
+    };
+
+    if body_length.is_some_and(|len| len as u64 > MAX_BODY_SIZE) {
+        return Err(too_large());
+    }
+
+    // one more byte is read to tell a body of the maximum size from a longer one.
+    let mut body = Vec::new();
+    reader
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|_| error_response(400, "Unable to read the body"))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(too_large());
+    }
+
+    String::from_utf8(body)
+        .map_err(|_| error_response(400, "The body should be a digest file encoded in UTF-8"))
+}
+
+/// Answers the crack requests with the tables loaded once.
+struct Cracker<'a> {
+    groups: &'a [TableGroup<'a>],
+    words: &'a [&'a [u8]],
+    low_memory: bool,
+    hex_output: bool,
+}
+
+impl Cracker<'_> {
+    /// Handles a request, and returns the status code and the JSON content of the response.
+    fn handle(&self, method: &Method, url: &str, body: &str) -> (u16, String) {
+        let path = url.split_once('?').map_or(url, |(path, _)| path);
+
+        let targets = match (method, path.strip_prefix("/crack/")) {
+            (Method::Post, _) if path == "/crack" => parse_digest_file(body),
+            (Method::Get, Some(input)) if !input.is_empty() => match detect_digest(input) {
+                Ok((digest, hash_types)) => vec![Target {
+                    label: input.to_owned(),
+                    digest,
+                    hash_types,
+                }],
+                Err(err) => return error_response(400, &err.to_string()),
+            },
+            _ => {
+                return error_response(404, "Use `GET /crack/<digest>` or `POST /crack`");
+            }
+        };
+
+        let start = Instant::now();
+        let results = crack_targets(
+            &targets,
+            self.groups,
+            self.words,
+            self.low_memory,
+            self.hex_output,
+            &TaskProgress::hidden(),
+            |_| (),
+        );
+
+        match results {
+            Ok((results, stats)) => (
+                200,
+                serde_json::to_string(&AttackReport::new(results, &stats, start.elapsed()))
+                    .unwrap(),
+            ),
+            Err(err) => error_response(500, &format!("{err:#}")),
+        }
+    }
+}
+
+/// The content of the responses to the requests that failed.
+#[derive(Serialize)]
+struct ErrorResponse<'a> {
+    error: &'a str,
+}
+
+/// Returns the status code and the JSON content of an error response.
+fn error_response(status: u16, error: &str) -> (u16, String) {
+    (
+        status,
+        serde_json::to_string(&ErrorResponse { error }).unwrap(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cugparck_commons::HashType;
+    use cugparck_cpu::{
+        backend::Cpu, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage, SimpleTable,
+    };
+    use serde_json::Value;
+    use tiny_http::Method;
+
+    use crate::{group_tables, load_tables_from_dir, temp_path, TableGroup};
+
+    use super::{read_body, Cracker, MAX_BODY_SIZE};
+
+    #[test]
+    fn test_serve_crack() {
+        let dir = temp_path("serve");
+        fs::create_dir_all(&dir).unwrap();
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .hash(HashType::Md5)
+            .chain_length(10)
+            .max_password_length(3)
+            .charset(b"abc")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        table.store(&dir.join("table.rt")).unwrap();
+
+        let tables = group_tables(load_tables_from_dir(&dir).unwrap()).unwrap();
+        let groups = tables
+            .iter()
+            .map(|tables| TableGroup::new(tables, Vec::new()).unwrap())
+            .collect::<Vec<_>>();
+        let cracker = Cracker {
+            groups: &groups,
+            words: &[],
+            low_memory: false,
+            hex_output: false,
+        };
+
+        // the startpoints of the chains are always found
+        let password = table.iter().next().unwrap().startpoint.into_password(&ctx);
+        let digest = hex::encode(ctx.hash(&password));
+        let (status, content) = cracker.handle(&Method::Get, &format!("/crack/{digest}"), "");
+        let report: Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(200, status);
+        assert_eq!(password.to_string(), report["results"][0]["password"]);
+        assert_eq!(1, report["tables"].as_array().unwrap().len());
+        assert!(report["tables"][0]["columns_searched"].as_u64().unwrap() > 0);
+
+        // the SHA-1 digest can't be found in an MD5 table
+        let body = format!("admin:{digest}\nroot:a9993e364706816aba3e25717850c26c9cd0d89d\n");
+        let (status, content) = cracker.handle(&Method::Post, "/crack", &body);
+        let report: Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(200, status);
+        assert_eq!(1, report["cracked"]);
+        assert_eq!("admin", report["results"][0]["label"]);
+        assert_eq!(Value::Null, report["results"][1]["password"]);
+
+        assert_eq!(400, cracker.handle(&Method::Get, "/crack/xyz", "").0);
+        assert_eq!(404, cracker.handle(&Method::Get, "/crack", "").0);
+        assert_eq!(404, cracker.handle(&Method::Delete, "/crack", "").0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_body() {
+        let max = MAX_BODY_SIZE as usize;
+
+        assert_eq!(
+            "admin:abc",
+            read_body(None, b"admin:abc".as_slice()).unwrap()
+        );
+        assert_eq!(
+            max,
+            read_body(None, vec![b'a'; max].as_slice()).unwrap().len()
+        );
+
+        // too large bodies are rejected instead of being truncated
+        assert_eq!(
+            413,
+            read_body(None, vec![b'a'; max + 1].as_slice())
+                .unwrap_err()
+                .0
+        );
+        assert_eq!(413, read_body(Some(max + 1), b"".as_slice()).unwrap_err().0);
+
+        assert_eq!(400, read_body(None, b"\xff".as_slice()).unwrap_err().0);
+    }
+}
@@ -1,11 +1,21 @@
-//! Dumps NTLM hashes from a Windows drive.
+//! Dumps NTLM and LM hashes from a Windows drive.
 //!
 //! This module is based off the https://www.insecurity.be/blog/2018/01/21/retrieving-ntlm-hashes-and-what-changed-technical-writeup/ blogpost
 //! The implementation was made possible thanks to the accompanying code: https://github.com/tijldeneut/Security/blob/master/DumpSomeHashes/DumpSomeHashes.py
 
-use std::{collections::HashMap, fs, path::Path};
+mod ese;
+mod ntds;
 
-use crate::{load_tables_from_dir, search_tables, Stealdows};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    format_password, group_tables, load_tables_from_dir, print_json, search_table_groups,
+    OutputFormat, Stealdows, TableGroup, TableSearchStats, TaskProgress,
+};
 
 use aes::{
     cipher::{generic_array::GenericArray, BlockDecrypt, BlockDecryptMut, KeyIvInit},
@@ -15,11 +25,13 @@ use anyhow::{ensure, Context, Result};
 use cbc::Decryptor;
 use comfy_table::{presets::UTF8_BORDERS_ONLY, Cell, Color, Table};
 use crossterm::style::Stylize;
-use cugparck_commons::{Digest, Password};
+use cugparck_commons::{Digest, HashType, Password};
+use cugparck_cpu::SearchHit;
 use des::Des;
 use md5::{Digest as _, Md5};
 use nt_hive::{Hive, KeyNode, NtHiveError, NtHiveNameString};
 use rc4::{KeyInit, Rc4, StreamCipher};
+use serde::Serialize;
 use sysinfo::{DiskExt, RefreshKind, System, SystemExt};
 
 /// The default path of the SAM file.
@@ -31,6 +43,9 @@ const SYSTEM_PATH: &str = "Windows/System32/config/SYSTEM";
 /// The offset which identifies how is encrypted the hash.
 const HASH_TYPE_OFFSET: usize = 0xAC;
 
+/// The offset which identifies how is encrypted the LM hash.
+const LM_HASH_TYPE_OFFSET: usize = 0xA0;
+
 /// The hash is encrypted using RC4.
 const HASH_TYPE_RC4: u8 = 0x14;
 
@@ -40,6 +55,9 @@ const HASH_TYPE_AES: u8 = 0x38;
 /// The offset of the hash.
 const HASH_OFFSET: usize = 0xA8;
 
+/// The offset of the LM hash.
+const LM_HASH_OFFSET: usize = 0x9C;
+
 /// The lengfth of the offset.
 const HASH_OFFSET_LENGTH: usize = 0x4;
 
@@ -100,6 +118,9 @@ const STRING_2: &[u8] = b"0123456789012345678901234567890123456789\0";
 /// The "NTPASSWORD" static string.
 const NTPASSWORD: &[u8] = b"NTPASSWORD\0";
 
+/// The "LMPASSWORD" static string.
+const LMPASSWORD: &[u8] = b"LMPASSWORD\0";
+
 /// Odd parity array for the DES key derivation algorithm.
 const ODD_PARITY: [u8; 256] = [
     1, 1, 2, 2, 4, 4, 7, 7, 8, 8, 11, 11, 13, 13, 14, 14, 16, 16, 19, 19, 21, 21, 22, 22, 25, 25,
@@ -125,6 +146,41 @@ type Aes128CbcDec = Decryptor<Aes128>;
 struct Account {
     username: String,
     hash: Option<Digest>,
+    /// The LM hash, which is only stored by old Windows versions or when enabled by a group policy.
+    lm_hash: Option<Digest>,
+}
+
+/// The hashes stored for an account.
+#[derive(Clone, Copy)]
+enum HashKind {
+    Nt,
+    Lm,
+}
+
+impl HashKind {
+    /// Returns the offset identifying how the hash is encrypted.
+    fn type_offset(self) -> usize {
+        match self {
+            HashKind::Nt => HASH_TYPE_OFFSET,
+            HashKind::Lm => LM_HASH_TYPE_OFFSET,
+        }
+    }
+
+    /// Returns the offset of the hash.
+    fn offset(self) -> usize {
+        match self {
+            HashKind::Nt => HASH_OFFSET,
+            HashKind::Lm => LM_HASH_OFFSET,
+        }
+    }
+
+    /// Returns the static string used to derive the RC4 key of the hash.
+    fn rc4_string(self) -> &'static [u8] {
+        match self {
+            HashKind::Nt => NTPASSWORD,
+            HashKind::Lm => LMPASSWORD,
+        }
+    }
 }
 
 /// Returns the class name of a registry key.
@@ -162,17 +218,17 @@ fn permute<const N: usize>(array: &[u8], permutations: [u8; N]) -> [u8; N] {
     res
 }
 
-/// Returns the double-encrypted NTLM hash when the RC4 cipher is used.
-fn rc4_double_encrypted_hash(v: &[u8]) -> &[u8] {
-    let nt_offset_slice = &v[HASH_OFFSET..HASH_OFFSET + HASH_OFFSET_LENGTH];
+/// Returns the double-encrypted hash when the RC4 cipher is used.
+fn rc4_double_encrypted_hash(v: &[u8], kind: HashKind) -> &[u8] {
+    let nt_offset_slice = &v[kind.offset()..kind.offset() + HASH_OFFSET_LENGTH];
     let nt_offset = (u32::from_le_bytes(nt_offset_slice.try_into().unwrap()) + OFFSET_ADD) as usize;
 
     &v[nt_offset + RC4_ENCRYPTED_HASH_START..nt_offset + RC4_ENCRYPTED_HASH_START + HASH_LENGTH]
 }
 
-/// Returns the double-encrypted NTLM hash and its IV if the AES cipher is used.
-fn aes_double_encrypted_hash(v: &[u8]) -> (&[u8], &[u8]) {
-    let nt_offset_slice = &v[HASH_OFFSET..HASH_OFFSET + HASH_OFFSET_LENGTH];
+/// Returns the double-encrypted hash and its IV if the AES cipher is used.
+fn aes_double_encrypted_hash(v: &[u8], kind: HashKind) -> (&[u8], &[u8]) {
+    let nt_offset_slice = &v[kind.offset()..kind.offset() + HASH_OFFSET_LENGTH];
     let nt_offset = (u32::from_le_bytes(nt_offset_slice.try_into().unwrap()) + OFFSET_ADD) as usize;
 
     let iv = &v[nt_offset + AES_IV_START..nt_offset + AES_IV_START + HASH_LENGTH];
@@ -248,12 +304,17 @@ fn aes_decrypt_syskey(f: &[u8], bootkey: &[u8]) -> [u8; HASH_LENGTH] {
 }
 
 /// Decrypts an RC4-DES-encrypted hash.
-fn rc4_decrypt_hash(double_enc_hash: &[u8], syskey: &[u8], rid: &[u8]) -> [u8; HASH_LENGTH] {
+fn rc4_decrypt_hash(
+    double_enc_hash: &[u8],
+    syskey: &[u8],
+    rid: &[u8],
+    kind: HashKind,
+) -> [u8; HASH_LENGTH] {
     // RC4 key derivation
     let mut md5 = Md5::new();
     md5.update(syskey);
     md5.update(rid);
-    md5.update(NTPASSWORD);
+    md5.update(kind.rc4_string());
     let rc4_key = md5.finalize();
 
     // decryption
@@ -277,7 +338,7 @@ fn aes_decrypt_hash(double_enc_hash: &[u8], syskey: &[u8], iv: &[u8]) -> [u8; HA
     enc_hash
 }
 
-/// Decrypts a DES-encrypted NTLM hash.
+/// Decrypts a DES-encrypted hash.
 fn des_decrypt_hash(enc_hash: &[u8], rid: &[u8]) -> Digest {
     let des_source_1 = permute(rid, SHIFT_ARRAY_2);
     let des_source_2 = permute(rid, SHIFT_ARRAY_3);
@@ -302,19 +363,19 @@ fn des_decrypt_hash(enc_hash: &[u8], rid: &[u8]) -> Digest {
     hash
 }
 
-/// Gets the RC4-encrypted NTLM hash (Windows < 1607).
-fn rc4_encrypted_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8]) -> Digest {
-    let double_enc_hash = rc4_double_encrypted_hash(v);
+/// Gets an RC4-encrypted hash (Windows < 1607).
+fn rc4_encrypted_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8], kind: HashKind) -> Digest {
+    let double_enc_hash = rc4_double_encrypted_hash(v, kind);
     let syskey = rc4_decrypt_syskey(f, bootkey);
 
-    let enc_hash = rc4_decrypt_hash(double_enc_hash, &syskey, rid);
+    let enc_hash = rc4_decrypt_hash(double_enc_hash, &syskey, rid, kind);
 
     des_decrypt_hash(&enc_hash, rid)
 }
 
-/// Gets the AES-encrypted NTLM hash (Windows >= 1607).
-fn aes_encrypted_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8]) -> Digest {
-    let (double_enc_hash, aes_iv) = aes_double_encrypted_hash(v);
+/// Gets an AES-encrypted hash (Windows >= 1607).
+fn aes_encrypted_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8], kind: HashKind) -> Digest {
+    let (double_enc_hash, aes_iv) = aes_double_encrypted_hash(v, kind);
 
     let syskey = if f[SYSKEY_ENCRYPTION_OFFSET] == AES_ENCRYPTED_SYSKEY {
         aes_decrypt_syskey(f, bootkey)
@@ -327,6 +388,15 @@ fn aes_encrypted_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8]) -> Digest
     des_decrypt_hash(&enc_hash, rid)
 }
 
+/// Gets a hash of an account, or `None` if the account doesn't have this hash.
+fn account_hash(rid: &[u8], v: &[u8], f: &[u8], bootkey: &[u8], kind: HashKind) -> Option<Digest> {
+    match v[kind.type_offset()] {
+        HASH_TYPE_RC4 => Some(rc4_encrypted_hash(rid, v, f, bootkey, kind)),
+        HASH_TYPE_AES => Some(aes_encrypted_hash(rid, v, f, bootkey, kind)),
+        _ => None,
+    }
+}
+
 /// Returns the username of a RID.
 fn username(v: &[u8]) -> String {
     let username_offset =
@@ -344,44 +414,55 @@ fn parse_rid(unordered_rid: &str) -> [u8; 4] {
     u32::from_le_bytes(hex.try_into().unwrap()).to_be_bytes()
 }
 
-/// Returns a vec of the accounts and their hashes present in the given SAM file.
-fn decrypt_accounts(sam: &Path, system: &Path) -> Result<Vec<Account>> {
-    let sam = fs::read(sam).context("Unable to read the SAM file")?;
-    let system = fs::read(system).context("Unable to read the SYSTEM file")?;
-
+/// Opens the SYSTEM hive, and returns whether its header could be verified.
+fn open_system_hive(system: &[u8]) -> Result<(Hive<&[u8]>, bool)> {
     // If the Windows partition is in fast-startup mode, the hive will be considered "dirty".
     // We can still extract the hashes, but we need to ignore the header verifications.
-    let (system_hive, sam_hive) = match Hive::new(system.as_ref()) {
-        Ok(system_hive) => (system_hive, Hive::new(sam.as_ref())?),
+    match Hive::new(system) {
+        Ok(system_hive) => Ok((system_hive, true)),
 
         Err(NtHiveError::SequenceNumberMismatch { primary, secondary })
             if primary == secondary + 1 =>
         {
-            println!(
+            eprintln!(
                 "{}",
                 "The Windows partition is using fast-startup, disabling header verification"
                     .with(Color::Yellow)
             );
-            (
-                Hive::without_validation(system.as_ref())?,
-                Hive::without_validation(sam.as_ref())?,
-            )
+            Ok((Hive::without_validation(system)?, false))
         }
 
-        Err(e) => return Err(e.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the bootkey stored in the SYSTEM hive.
+fn bootkey<'a>(system_root: &KeyNode<&Hive<&'a [u8]>, &'a [u8]>) -> Result<[u8; HASH_LENGTH]> {
+    let jd = class_name(system_root, "ControlSet001\\Control\\LSA\\JD")?;
+    let skew1 = class_name(system_root, "ControlSet001\\Control\\LSA\\Skew1")?;
+    let gbg = class_name(system_root, "ControlSet001\\Control\\LSA\\GBG")?;
+    let data = class_name(system_root, "ControlSet001\\Control\\LSA\\Data")?;
+
+    Ok(derive_bootkey(&jd, &skew1, &gbg, &data))
+}
+
+/// Returns a vec of the accounts and their hashes present in the given SAM file.
+fn decrypt_accounts(sam: &Path, system: &Path) -> Result<Vec<Account>> {
+    let sam = fs::read(sam).context("Unable to read the SAM file")?;
+    let system = fs::read(system).context("Unable to read the SYSTEM file")?;
+
+    let (system_hive, verified) = open_system_hive(&system)?;
+    let sam_hive = if verified {
+        Hive::new(sam.as_ref())?
+    } else {
+        Hive::without_validation(sam.as_ref())?
     };
 
     let sam_root = sam_hive.root_key_node()?;
     let system_root = system_hive.root_key_node()?;
 
     let f = key_value(&sam_root, "SAM\\Domains\\Account", "F")?;
-
-    // derive the bootkey
-    let jd = class_name(&system_root, "ControlSet001\\Control\\LSA\\JD")?;
-    let skew1 = class_name(&system_root, "ControlSet001\\Control\\LSA\\Skew1")?;
-    let gbg = class_name(&system_root, "ControlSet001\\Control\\LSA\\GBG")?;
-    let data = class_name(&system_root, "ControlSet001\\Control\\LSA\\Data")?;
-    let bootkey = derive_bootkey(&jd, &skew1, &gbg, &data);
+    let bootkey = bootkey(&system_root)?;
 
     let user_rid_key = sam_root.subpath("SAM\\Domains\\Account\\Users").unwrap()?;
 
@@ -399,91 +480,210 @@ fn decrypt_accounts(sam: &Path, system: &Path) -> Result<Vec<Account>> {
         let unordered_rid = account.name()?;
         let rid = parse_rid(&unordered_rid.to_string());
 
-        let hash = match v[HASH_TYPE_OFFSET] {
-            HASH_TYPE_RC4 => Some(rc4_encrypted_hash(&rid, &v, &f, &bootkey)),
-            HASH_TYPE_AES => Some(aes_encrypted_hash(&rid, &v, &f, &bootkey)),
-            _ => None,
-        };
+        let hash = account_hash(&rid, &v, &f, &bootkey, HashKind::Nt);
+        let lm_hash = account_hash(&rid, &v, &f, &bootkey, HashKind::Lm);
 
-        accounts.push(Account { username, hash });
+        accounts.push(Account {
+            username,
+            hash,
+            lm_hash,
+        });
     }
 
     Ok(accounts)
 }
 
-/// Dumps the hashes of the specified acounts.
-fn dump_accounts(accounts: Vec<Account>) {
-    let mut display_table = Table::new();
-    display_table.load_preset(UTF8_BORDERS_ONLY);
-    display_table.set_header(vec!["Username", "Hash"]);
-
-    for account in accounts {
-        let username = Cell::new(account.username);
-
-        let hash = account
-            .hash
-            .map(|hash| Cell::new(hex::encode(hash)).fg(Color::Green))
-            .unwrap_or_else(|| Cell::new("No hash found").fg(Color::Grey));
+/// The result of the dump of an account, printed with `--output json`.
+#[derive(Serialize)]
+struct AccountResult {
+    username: String,
+    hash: Option<String>,
+    lm_hash: Option<String>,
+    /// The password, uppercased when it was found with the LM hash.
+    password: Option<String>,
+    /// The number of the table containing the password, `None` when it was found with the LM hash.
+    table_number: Option<usize>,
+    /// The column of the chain containing the password, `None` when it was found with the LM hash.
+    column: Option<usize>,
+}
 
-        display_table.add_row(vec![username, hash]);
+impl AccountResult {
+    fn new(account: Account, hit: Option<SearchHit>, hex_output: bool) -> Self {
+        Self {
+            username: account.username,
+            hash: account.hash.map(hex::encode),
+            lm_hash: account.lm_hash.map(hex::encode),
+            password: hit.map(|hit| format_password(hit.password, hex_output)),
+            table_number: hit.map(|hit| hit.table_number),
+            column: hit.map(|hit| hit.column),
+        }
     }
+}
 
-    println!("{display_table}");
+/// Dumps the hashes of the specified acounts.
+fn dump_accounts(accounts: Vec<Account>) -> Vec<AccountResult> {
+    accounts
+        .into_iter()
+        .map(|account| AccountResult::new(account, None, false))
+        .collect()
 }
 
 /// Dumps the hashes of the specified accounts and tries to crack them.
-fn crack_accounts(accounts: Vec<Account>, dir: &Path, low_memory: bool) -> Result<()> {
-    let (mmaps, is_compressed) = load_tables_from_dir(dir)?;
-
-    let mut display_table = Table::new();
-    display_table.load_preset(UTF8_BORDERS_ONLY);
-    display_table.set_header(vec!["Username", "Hash", "Password"]);
+fn crack_accounts(
+    accounts: Vec<Account>,
+    dir: &Path,
+    low_memory: bool,
+    hex_output: bool,
+) -> Result<Vec<AccountResult>> {
+    let tables = group_tables(load_tables_from_dir(dir)?)?;
+    let groups = tables
+        .iter()
+        .map(|tables| TableGroup::new(tables, Vec::new()))
+        .collect::<Result<Vec<_>>>()?;
+    let (lm_groups, ntlm_groups): (Vec<_>, Vec<_>) = groups
+        .into_iter()
+        .filter(|group| matches!(group.ctx.hash_type, HashType::Ntlm | HashType::Lm))
+        .partition(|group| group.ctx.hash_type == HashType::Lm);
+    ensure!(
+        !lm_groups.is_empty() || !ntlm_groups.is_empty(),
+        "The directory doesn't contain any NTLM or LM table"
+    );
 
-    // we use a hashmap so if we have two times the same hash we don't attack it twice.
-    let mut passwords: HashMap<Digest, Option<Password>> = HashMap::from_iter(
+    // the LM tables only contain halves of passwords, so both halves of the LM hashes are searched.
+    let ntlm_hits = search_hashes(
+        accounts.iter().filter_map(|account| account.hash),
+        &ntlm_groups,
+        low_memory,
+    )?;
+    let lm_hits = search_hashes(
         accounts
             .iter()
-            .filter_map(|account| Some((account.hash?, None))),
+            .filter_map(|account| account.lm_hash)
+            .flat_map(lm_halves),
+        &lm_groups,
+        low_memory,
+    )?;
+
+    Ok(accounts
+        .into_iter()
+        .map(|account| {
+            let hit = account.hash.and_then(|hash| ntlm_hits[&hash]);
+            if hit.is_some() || lm_groups.is_empty() {
+                return AccountResult::new(account, hit, hex_output);
+            }
+
+            let password = account.lm_hash.and_then(|hash| {
+                let [first, second] = lm_halves(hash).map(|half| lm_hits[&half]);
+                Some(join_lm_halves(
+                    first?.password,
+                    second?.password,
+                    hex_output,
+                ))
+            });
+
+            AccountResult {
+                password,
+                ..AccountResult::new(account, None, hex_output)
+            }
+        })
+        .collect())
+}
+
+/// Searches for hashes in groups of tables.
+fn search_hashes(
+    hashes: impl Iterator<Item = Digest>,
+    groups: &[TableGroup],
+    low_memory: bool,
+) -> Result<HashMap<Digest, Option<SearchHit>>> {
+    // we use a hashmap so if we have two times the same hash we don't attack it twice.
+    let mut hits: HashMap<Digest, Option<SearchHit>> =
+        hashes.map(|digest| (digest, None)).collect();
+
+    let hashes = hits.keys().copied().collect::<Vec<_>>();
+    let found = search_table_groups(
+        &hashes,
+        None,
+        groups,
+        low_memory,
+        &TaskProgress::hidden(),
+        &mut TableSearchStats::default(),
+    )?;
+    hits.extend(
+        hashes
+            .into_iter()
+            .zip(found.into_iter().map(|hit| hit.map(|(hit, _)| hit))),
     );
 
-    for (hash, password) in &mut passwords {
-        *password = search_tables(*hash, &mmaps, is_compressed, low_memory)?;
+    Ok(hits)
+}
+
+/// Splits an LM hash into the digests of its two halves, which are hashed separately.
+fn lm_halves(hash: Digest) -> [Digest; 2] {
+    let (first, second) = hash.split_at(HASH_LENGTH / 2);
+
+    [first.try_into().unwrap(), second.try_into().unwrap()]
+}
+
+/// Joins the two halves of a password found with an LM hash.
+fn join_lm_halves(first: Password, second: Password, hex_output: bool) -> String {
+    if hex_output || !first.is_printable() || !second.is_printable() {
+        format!("$HEX[{}{}]", hex::encode(first), hex::encode(second))
+    } else {
+        format!("{first}{second}")
     }
+}
+
+/// Prints the accounts in a table, along with their passwords if `cracked` is true.
+/// The LM hashes are only printed if an account has one.
+fn print_accounts(results: &[AccountResult], cracked: bool) {
+    let has_lm_hashes = results.iter().any(|result| result.lm_hash.is_some());
 
-    for account in accounts {
-        let username = Cell::new(account.username);
-
-        let hash = account
-            .hash
-            .map(|account| Cell::new(hex::encode(account)).fg(Color::Green))
-            .unwrap_or_else(|| Cell::new("No hash found").fg(Color::Grey));
-
-        let password = account
-            .hash
-            .map(|hash| {
-                passwords
-                    .get(&hash)
-                    .unwrap()
-                    .map(|password| Cell::new(password).fg(Color::Green))
-                    .unwrap_or_else(|| Cell::new("No password found").fg(Color::Red))
-            })
-            .unwrap_or_else(|| Cell::new("No password found").fg(Color::Grey));
-
-        display_table.add_row(vec![username, hash, password]);
+    let mut header = vec!["Username", "Hash"];
+    if has_lm_hashes {
+        header.push("LM hash");
+    }
+    if cracked {
+        header.push("Password");
     }
 
-    println!("{display_table}");
+    let mut display_table = Table::new();
+    display_table.load_preset(UTF8_BORDERS_ONLY);
+    display_table.set_header(header);
 
-    Ok(())
+    let hash_cell = |hash: &Option<String>| {
+        hash.as_ref()
+            .map(|hash| Cell::new(hash).fg(Color::Green))
+            .unwrap_or_else(|| Cell::new("No hash found").fg(Color::Grey))
+    };
+
+    for result in results {
+        let mut row = vec![Cell::new(&result.username), hash_cell(&result.hash)];
+        if has_lm_hashes {
+            row.push(hash_cell(&result.lm_hash));
+        }
+
+        if cracked {
+            row.push(match (&result.password, &result.hash, &result.lm_hash) {
+                (Some(password), _, _) => Cell::new(password).fg(Color::Green),
+                (None, None, None) => Cell::new("No password found").fg(Color::Grey),
+                (None, _, _) => Cell::new("No password found").fg(Color::Red),
+            });
+        }
+
+        display_table.add_row(row);
+    }
+
+    println!("{display_table}");
 }
 
-pub fn stealdows(args: Stealdows) -> Result<()> {
+/// Returns the paths of the SAM and SYSTEM files, looking for them on the mounted disks if they aren't provided.
+fn find_hives(args: &Stealdows) -> Result<(PathBuf, PathBuf)> {
     let sam;
     let system;
 
     if args.sam.is_some() {
-        sam = args.sam.unwrap();
-        system = args.system.unwrap();
+        sam = args.sam.clone().unwrap();
+        system = args.system.clone().unwrap();
     } else {
         let sys = System::new_with_specifics(RefreshKind::new().with_disks().with_disks_list());
         let mut sam_try = None;
@@ -510,17 +710,32 @@ pub fn stealdows(args: Stealdows) -> Result<()> {
         system = system_try.unwrap();
     }
 
-    let mut accounts = decrypt_accounts(&sam, &system)
-        .context("Error when decrypting the SAM or the SYSTEM file")?;
+    Ok((sam, system))
+}
+
+pub fn stealdows(args: Stealdows, output: OutputFormat) -> Result<()> {
+    let mut accounts = match &args.ntds {
+        Some(ntds) => ntds::decrypt_domain_accounts(ntds, args.system.as_ref().unwrap())
+            .context("Error when decrypting the ntds.dit or the SYSTEM file")?,
+        None => {
+            let (sam, system) = find_hives(&args)?;
+            decrypt_accounts(&sam, &system)
+                .context("Error when decrypting the SAM or the SYSTEM file")?
+        }
+    };
 
     if !args.user.is_empty() {
         accounts.retain(|account| args.user.contains(&account.username));
     }
 
-    if let Some(dir) = args.crack {
-        crack_accounts(accounts, &dir, args.low_memory)?;
-    } else {
-        dump_accounts(accounts);
+    let results = match &args.crack {
+        Some(dir) => crack_accounts(accounts, dir, args.low_memory, args.hex_output)?,
+        None => dump_accounts(accounts),
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&results)?,
+        OutputFormat::Human => print_accounts(&results, args.crack.is_some()),
     }
 
     Ok(())
@@ -546,9 +761,11 @@ mod tests {
     const IV_TEST: &str = "6d59cbe78a9468f4853c654e078bcd46";
     const HASH_TEST: &str = "32ed87bdb5fdc5e9cba88547376818d4";
 
+    use cugparck_commons::{Digest, Password};
+
     use super::{
-        aes_decrypt_hash, aes_double_encrypted_hash, derive_bootkey, derive_des_key,
-        des_decrypt_hash, username,
+        account_hash, aes_decrypt_hash, aes_double_encrypted_hash, derive_bootkey, derive_des_key,
+        des_decrypt_hash, join_lm_halves, lm_halves, username, HashKind,
     };
     use crate::stealdows::{parse_rid, rc4_decrypt_syskey};
 
@@ -586,7 +803,7 @@ mod tests {
     fn test_aes_double_encrypted_hash() {
         let v_test = hex::decode(V_TEST).unwrap();
 
-        let (hash, iv) = aes_double_encrypted_hash(&v_test);
+        let (hash, iv) = aes_double_encrypted_hash(&v_test, HashKind::Nt);
 
         assert_eq!(DOUBLE_ENC_HASH_TEST, hex::encode(hash));
         assert_eq!(IV_TEST, hex::encode(iv));
@@ -627,4 +844,38 @@ mod tests {
 
         assert_eq!(HASH_TEST, hex::encode(hash));
     }
+
+    #[test]
+    fn test_no_lm_hash() {
+        let lm_hash = account_hash(
+            &hex::decode(RID_TEST).unwrap(),
+            &hex::decode(V_TEST).unwrap(),
+            &hex::decode(F_TEST).unwrap(),
+            &hex::decode(BOOTKEY_TEST).unwrap(),
+            HashKind::Lm,
+        );
+
+        assert_eq!(None, lm_hash);
+    }
+
+    #[test]
+    fn test_lm_halves() {
+        let hash: Digest = hex::decode("e52cac67419a9a224a3b108f3fa6cb6d")
+            .unwrap()
+            .as_slice()
+            .try_into()
+            .unwrap();
+        let [first, second] = lm_halves(hash);
+
+        assert_eq!("e52cac67419a9a22", hex::encode(first));
+        assert_eq!("4a3b108f3fa6cb6d", hex::encode(second));
+
+        let first = Password::new(b"PASSWOR");
+        let second = Password::new(b"D");
+        assert_eq!("PASSWORD", join_lm_halves(first, second, false));
+        assert_eq!(
+            "$HEX[50415353574f5244]",
+            join_lm_halves(first, second, true)
+        );
+    }
 }
@@ -0,0 +1,678 @@
+//! Reads the tables of an ESE database, the format of the ntds.dit database of Active Directory.
+//!
+//! Only the records of the tables are read, by walking the B-tree of each table.
+//! The values stored apart from their record (long values), and the compressed or multi-valued ones, are skipped.
+//! The format is described in https://github.com/libyal/libesedb/blob/main/documentation/Extensible%20Storage%20Engine%20(ESE)%20Database%20File%20(EDB)%20format.asciidoc
+
+use std::{collections::HashMap, ops::Range};
+
+use anyhow::{ensure, Context, Result};
+
+/// The error returned when the database can't be read.
+const CORRUPTED: &str = "The database is corrupted";
+
+/// The signature of an ESE database.
+const SIGNATURE: u32 = 0x89ABCDEF;
+
+/// The offset of the signature in the file header.
+const SIGNATURE_OFFSET: usize = 0x4;
+
+/// The offset of the format revision in the file header.
+const FORMAT_REVISION_OFFSET: usize = 0xE8;
+
+/// The offset of the page size in the file header.
+const PAGE_SIZE_OFFSET: usize = 0xEC;
+
+/// The first format revision storing the pages bigger than 8 KiB differently.
+const LARGE_PAGES_FORMAT_REVISION: u32 = 0x11;
+
+/// The root page of the catalog, which describes the tables and their columns.
+const CATALOG_ROOT_PAGE: u32 = 4;
+
+/// The size of the header of a page.
+const PAGE_HEADER_SIZE: usize = 40;
+
+/// The size of the header of a large page.
+const LARGE_PAGE_HEADER_SIZE: usize = 80;
+
+/// The offset of the number of tags in the header of a page.
+const TAG_COUNT_OFFSET: usize = 34;
+
+/// The offset of the flags in the header of a page.
+const PAGE_FLAGS_OFFSET: usize = 36;
+
+/// The page is a leaf of its B-tree, and its entries are records.
+const PAGE_FLAG_LEAF: u32 = 0x2;
+
+/// The entry of the tag was deleted.
+const TAG_FLAG_DEFUNCT: u8 = 0x2;
+
+/// The key of the entry is prefixed by the size of the part of the key common to the page.
+const TAG_FLAG_COMMON_KEY: u8 = 0x4;
+
+/// The maximum depth of a B-tree, to stop on a corrupted database instead of looping forever.
+const MAX_TREE_DEPTH: usize = 16;
+
+/// The offset of the values of the fixed size columns in a record.
+const FIXED_VALUES_OFFSET: usize = 4;
+
+/// The largest identifier of a fixed size column.
+const LAST_FIXED_COLUMN: u32 = 127;
+
+/// The smallest identifier of a variable size column.
+const FIRST_VARIABLE_COLUMN: u32 = 128;
+
+/// The largest identifier of a variable size column. The columns with a larger identifier are tagged.
+const LAST_VARIABLE_COLUMN: u32 = 255;
+
+/// The value of a variable size column is empty.
+const VARIABLE_VALUE_EMPTY: u16 = 0x8000;
+
+/// The tagged value is prefixed by flags, on the pages that aren't large pages.
+const TAGGED_VALUE_HAS_FLAGS: u16 = 0x4000;
+
+/// The flags of the tagged values that can't be read from the record:
+/// compressed values, long values and multi-valued values.
+const TAGGED_VALUE_UNSUPPORTED_FLAGS: u8 = 0x2 | 0x4 | 0x8 | 0x10;
+
+/// The catalog entry describes a table.
+const CATALOG_TYPE_TABLE: u16 = 1;
+
+/// The catalog entry describes a column.
+const CATALOG_TYPE_COLUMN: u16 = 2;
+
+/// The sizes of the fixed size columns of the catalog used to read it.
+const CATALOG_FIXED_SIZES: [usize; 5] = [4, 2, 4, 4, 4];
+
+/// The column of the catalog containing the identifier of the table of the entry.
+const CATALOG_TABLE_ID: u32 = 1;
+
+/// The column of the catalog containing the type of the entry.
+const CATALOG_TYPE: u32 = 2;
+
+/// The column of the catalog containing the identifier of the entry.
+const CATALOG_ID: u32 = 3;
+
+/// The column of the catalog containing the root page of a table.
+const CATALOG_ROOT_PAGE_COLUMN: u32 = 4;
+
+/// The column of the catalog containing the size of a column.
+const CATALOG_COLUMN_SIZE: u32 = 5;
+
+/// The column of the catalog containing the name of the entry.
+const CATALOG_NAME: u32 = FIRST_VARIABLE_COLUMN;
+
+/// Reads a little endian `u16`.
+fn read_u16(data: &[u8], offset: usize) -> Result<u16> {
+    let bytes = data.get(offset..offset + 2).context(CORRUPTED)?;
+
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a little endian `u32`.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data.get(offset..offset + 4).context(CORRUPTED)?;
+
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A table of a database.
+struct Table {
+    /// The root page of the B-tree of the records.
+    root_page: u32,
+    /// The identifiers of the columns, by name.
+    columns: HashMap<String, u32>,
+    /// The sizes of the fixed size columns, starting from the column 1.
+    fixed_sizes: Vec<usize>,
+}
+
+/// A record of a table.
+struct Record<'a> {
+    data: &'a [u8],
+    /// The identifier of the last fixed size column stored in the record.
+    last_fixed: u32,
+    /// The position of the values of the variable size columns, `None` for the empty ones.
+    variable_values: Vec<Option<Range<usize>>>,
+    /// The offset of the tagged columns, after the variable size ones.
+    tagged_offset: usize,
+}
+
+impl<'a> Record<'a> {
+    /// Parses the layout of a record.
+    fn new(data: &'a [u8]) -> Result<Self> {
+        let last_fixed = *data.first().context(CORRUPTED)? as u32;
+        let last_variable = *data.get(1).context(CORRUPTED)? as u32;
+        let variable_offset = read_u16(data, 2)? as usize;
+
+        // the end offsets of the variable size values are followed by the values,
+        // an empty value keeping the end offset of the previous one
+        let variable_count = (last_variable + 1).saturating_sub(FIRST_VARIABLE_COLUMN) as usize;
+        let values_offset = variable_offset + 2 * variable_count;
+        let mut variable_values = Vec::with_capacity(variable_count);
+        let mut previous_end = 0;
+        for i in 0..variable_count {
+            let end = read_u16(data, variable_offset + 2 * i)?;
+            if end & VARIABLE_VALUE_EMPTY != 0 {
+                variable_values.push(None);
+            } else {
+                variable_values.push(Some(
+                    values_offset + previous_end..values_offset + end as usize,
+                ));
+                previous_end = end as usize;
+            }
+        }
+
+        Ok(Self {
+            data,
+            last_fixed,
+            variable_values,
+            tagged_offset: values_offset + previous_end,
+        })
+    }
+
+    /// Returns the value of a column, or `None` if it isn't stored in the record or can't be read.
+    fn value(
+        &self,
+        column: u32,
+        fixed_sizes: &[usize],
+        large_pages: bool,
+    ) -> Result<Option<&'a [u8]>> {
+        match column {
+            1..=LAST_FIXED_COLUMN => {
+                if column > self.last_fixed {
+                    return Ok(None);
+                }
+
+                let index = column as usize - 1;
+                let size = *fixed_sizes.get(index).context(CORRUPTED)?;
+                let start = FIXED_VALUES_OFFSET + fixed_sizes[..index].iter().sum::<usize>();
+
+                self.data
+                    .get(start..start + size)
+                    .context(CORRUPTED)
+                    .map(Some)
+            }
+
+            FIRST_VARIABLE_COLUMN..=LAST_VARIABLE_COLUMN => {
+                let index = (column - FIRST_VARIABLE_COLUMN) as usize;
+
+                match self.variable_values.get(index) {
+                    Some(Some(range)) => self.data.get(range.clone()).context(CORRUPTED).map(Some),
+                    _ => Ok(None),
+                }
+            }
+
+            _ => self.tagged_value(column, large_pages),
+        }
+    }
+
+    /// Returns the value of a tagged column.
+    fn tagged_value(&self, column: u32, large_pages: bool) -> Result<Option<&'a [u8]>> {
+        let data = self.data.get(self.tagged_offset..).context(CORRUPTED)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+
+        // the tagged values start with the identifiers of the columns and the offsets of their values,
+        // so the offset of the first value is the size of this array
+        let offset_mask = if large_pages { 0x7FFF } else { 0x3FFF };
+        let array_size = (read_u16(data, 2)? & offset_mask) as usize;
+        ensure!(array_size > 0 && array_size.is_multiple_of(4), CORRUPTED);
+
+        for i in 0..array_size / 4 {
+            if read_u16(data, 4 * i)? as u32 != column {
+                continue;
+            }
+
+            let offset = read_u16(data, 4 * i + 2)?;
+            let start = (offset & offset_mask) as usize;
+            let end = if 4 * (i + 1) < array_size {
+                (read_u16(data, 4 * (i + 1) + 2)? & offset_mask) as usize
+            } else {
+                data.len()
+            };
+            let mut value = data.get(start..end).context(CORRUPTED)?;
+
+            // the flags are always present on the large pages
+            if large_pages || offset & TAGGED_VALUE_HAS_FLAGS != 0 {
+                let (&flags, rest) = value.split_first().context(CORRUPTED)?;
+                if flags & TAGGED_VALUE_UNSUPPORTED_FLAGS != 0 {
+                    return Ok(None);
+                }
+                value = rest;
+            }
+
+            return Ok(Some(value));
+        }
+
+        Ok(None)
+    }
+}
+
+/// An ESE database, read from memory.
+pub struct Database<'a> {
+    data: &'a [u8],
+    page_size: usize,
+    /// Whether the pages are bigger than 8 KiB, and store the flags of their entries in the entries.
+    large_pages: bool,
+    tables: HashMap<String, Table>,
+}
+
+impl<'a> Database<'a> {
+    /// Reads the header and the catalog of a database.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        ensure!(
+            read_u32(data, SIGNATURE_OFFSET)? == SIGNATURE,
+            "The file is not an ESE database"
+        );
+
+        let page_size = read_u32(data, PAGE_SIZE_OFFSET)? as usize;
+        ensure!(
+            matches!(page_size, 2048 | 4096 | 8192 | 16384 | 32768),
+            "The page size of the database is invalid"
+        );
+        let large_pages = page_size > 8192
+            && read_u32(data, FORMAT_REVISION_OFFSET)? >= LARGE_PAGES_FORMAT_REVISION;
+
+        let mut database = Self {
+            data,
+            page_size,
+            large_pages,
+            tables: HashMap::new(),
+        };
+        database.tables = database.read_catalog()?;
+
+        Ok(database)
+    }
+
+    /// Reads the tables described in the catalog.
+    fn read_catalog(&self) -> Result<HashMap<String, Table>> {
+        let mut tables = HashMap::new();
+        let mut columns: HashMap<u32, Vec<(String, u32, usize)>> = HashMap::new();
+
+        self.walk(CATALOG_ROOT_PAGE, 0, &mut |data| {
+            let record = Record::new(data)?;
+            let value = |column| {
+                record
+                    .value(column, &CATALOG_FIXED_SIZES, self.large_pages)?
+                    .context(CORRUPTED)
+            };
+
+            let table_id = read_u32(value(CATALOG_TABLE_ID)?, 0)?;
+            let name = String::from_utf8_lossy(value(CATALOG_NAME)?).into_owned();
+            match read_u16(value(CATALOG_TYPE)?, 0)? {
+                CATALOG_TYPE_TABLE => {
+                    let root_page = read_u32(value(CATALOG_ROOT_PAGE_COLUMN)?, 0)?;
+                    tables.insert(table_id, (name, root_page));
+                }
+                CATALOG_TYPE_COLUMN => {
+                    let id = read_u32(value(CATALOG_ID)?, 0)?;
+                    let size = read_u32(value(CATALOG_COLUMN_SIZE)?, 0)? as usize;
+                    columns.entry(table_id).or_default().push((name, id, size));
+                }
+                // the indexes and the long values aren't needed to read the records
+                _ => (),
+            }
+
+            Ok(())
+        })?;
+
+        Ok(tables
+            .into_iter()
+            .map(|(table_id, (name, root_page))| {
+                let columns = columns.remove(&table_id).unwrap_or_default();
+
+                let mut fixed_sizes = Vec::new();
+                for (_, id, size) in &columns {
+                    if (1..=LAST_FIXED_COLUMN).contains(id) {
+                        let index = *id as usize - 1;
+                        if fixed_sizes.len() <= index {
+                            fixed_sizes.resize(index + 1, 0);
+                        }
+                        fixed_sizes[index] = *size;
+                    }
+                }
+
+                let table = Table {
+                    root_page,
+                    columns: columns
+                        .into_iter()
+                        .map(|(name, id, _)| (name, id))
+                        .collect(),
+                    fixed_sizes,
+                };
+
+                (name, table)
+            })
+            .collect())
+    }
+
+    /// Calls `f` with the values of the given columns for each record of a table.
+    /// A value is `None` if it is empty, if it can't be read or if the table doesn't have the column.
+    pub fn read_table(
+        &self,
+        table: &str,
+        columns: &[&str],
+        mut f: impl FnMut(&[Option<&'a [u8]>]) -> Result<()>,
+    ) -> Result<()> {
+        let table = self
+            .tables
+            .get(table)
+            .with_context(|| format!("The database doesn't contain the {table} table"))?;
+        let ids = columns
+            .iter()
+            .map(|&name| table.columns.get(name).copied())
+            .collect::<Vec<_>>();
+
+        let mut values = Vec::with_capacity(ids.len());
+        self.walk(table.root_page, 0, &mut |data| {
+            let record = Record::new(data)?;
+
+            values.clear();
+            for id in &ids {
+                values.push(match id {
+                    Some(id) => record.value(*id, &table.fixed_sizes, self.large_pages)?,
+                    None => None,
+                });
+            }
+
+            f(&values)
+        })
+    }
+
+    /// Returns a page of the database.
+    fn page(&self, number: u32) -> Result<&'a [u8]> {
+        // the header of the database and its copy come before the first page
+        let start = (number as usize + 1) * self.page_size;
+
+        self.data
+            .get(start..start + self.page_size)
+            .with_context(|| format!("The page {number} is not in the database"))
+    }
+
+    /// Returns the flags and the value of a tag of a page.
+    fn tag(&self, page: &'a [u8], tag: usize) -> Result<(u8, &'a [u8])> {
+        // the tags are stored backwards from the end of the page
+        let tag_offset = self
+            .page_size
+            .checked_sub(4 * (tag + 1))
+            .context(CORRUPTED)?;
+        let size = read_u16(page, tag_offset)?;
+        let raw_offset = read_u16(page, tag_offset + 2)?;
+
+        let (header_size, size, offset) = if self.large_pages {
+            (
+                LARGE_PAGE_HEADER_SIZE,
+                (size & 0x7FFF) as usize,
+                (raw_offset & 0x7FFF) as usize,
+            )
+        } else {
+            (
+                PAGE_HEADER_SIZE,
+                (size & 0x1FFF) as usize,
+                (raw_offset & 0x1FFF) as usize,
+            )
+        };
+        let value = page
+            .get(header_size + offset..header_size + offset + size)
+            .context(CORRUPTED)?;
+
+        // the flags are stored in the highest bits of the offset, or of the value on the large pages
+        let flags = if self.large_pages {
+            value.get(1).map_or(0, |byte| byte >> 5)
+        } else {
+            (raw_offset >> 13) as u8
+        };
+
+        Ok((flags, value))
+    }
+
+    /// Returns the data of an entry of a page, after its key.
+    fn entry_data(&self, flags: u8, entry: &'a [u8]) -> Result<&'a [u8]> {
+        let key_size_offset = if flags & TAG_FLAG_COMMON_KEY != 0 {
+            2
+        } else {
+            0
+        };
+
+        let mut key_size = read_u16(entry, key_size_offset)?;
+        // the flags of the large pages are stored in the highest bits of the first size
+        if self.large_pages && key_size_offset == 0 {
+            key_size &= 0x1FFF;
+        }
+
+        entry
+            .get(key_size_offset + 2 + key_size as usize..)
+            .context(CORRUPTED)
+    }
+
+    /// Calls `f` with the data of each record of the B-tree whose root is `page_number`.
+    fn walk<F: FnMut(&'a [u8]) -> Result<()>>(
+        &self,
+        page_number: u32,
+        depth: usize,
+        f: &mut F,
+    ) -> Result<()> {
+        ensure!(depth < MAX_TREE_DEPTH, CORRUPTED);
+
+        let page = self.page(page_number)?;
+        let is_leaf = read_u32(page, PAGE_FLAGS_OFFSET)? & PAGE_FLAG_LEAF != 0;
+
+        // the first tag contains the header of the page instead of an entry
+        for tag in 1..read_u16(page, TAG_COUNT_OFFSET)? as usize {
+            let (flags, entry) = self.tag(page, tag)?;
+            if flags & TAG_FLAG_DEFUNCT != 0 {
+                continue;
+            }
+
+            let data = self.entry_data(flags, entry)?;
+            if is_leaf {
+                f(data)?;
+            } else {
+                // the entries of the other pages point to the child pages
+                self.walk(read_u32(data, 0)?, depth + 1, f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Database, SIGNATURE};
+
+    const PAGE_SIZE: usize = 8192;
+
+    /// Builds a record from the values of its fixed size, variable size and tagged columns.
+    /// The tagged values are given with their flags, if they have some.
+    fn record(
+        fixed: &[&[u8]],
+        variable: &[Option<&[u8]>],
+        tagged: &[(u16, Option<u8>, &[u8])],
+    ) -> Vec<u8> {
+        let mut record = vec![fixed.len() as u8, 127 + variable.len() as u8, 0, 0];
+        for value in fixed {
+            record.extend_from_slice(value);
+        }
+
+        let variable_offset = record.len() as u16;
+        record[2..4].copy_from_slice(&variable_offset.to_le_bytes());
+        let mut end = 0u16;
+        for value in variable {
+            match value {
+                Some(value) => {
+                    end += value.len() as u16;
+                    record.extend_from_slice(&end.to_le_bytes());
+                }
+                None => record.extend_from_slice(&(end | 0x8000).to_le_bytes()),
+            }
+        }
+        for value in variable.iter().flatten() {
+            record.extend_from_slice(value);
+        }
+
+        let mut offset = 4 * tagged.len() as u16;
+        let mut values = Vec::new();
+        for (id, flags, value) in tagged {
+            record.extend_from_slice(&id.to_le_bytes());
+            let flag_bit = if flags.is_some() { 0x4000 } else { 0 };
+            record.extend_from_slice(&(offset | flag_bit).to_le_bytes());
+            values.extend(flags);
+            values.extend_from_slice(value);
+            offset = 4 * tagged.len() as u16 + values.len() as u16;
+        }
+        record.extend(values);
+
+        record
+    }
+
+    /// Builds a page from its flags and its entries, along with the flags of their tags.
+    fn page(flags: u32, entries: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut page = vec![0; PAGE_SIZE];
+        page[34..36].copy_from_slice(&(entries.len() as u16 + 1).to_le_bytes());
+        page[36..40].copy_from_slice(&flags.to_le_bytes());
+
+        // the first tag is the empty header of the page
+        let mut offset = 0;
+        for (i, (tag_flags, data)) in entries.iter().enumerate() {
+            // the key of the entries is empty
+            let mut entry = vec![0, 0];
+            entry.extend_from_slice(data);
+            page[40 + offset..40 + offset + entry.len()].copy_from_slice(&entry);
+
+            let tag = PAGE_SIZE - 4 * (i + 2);
+            page[tag..tag + 2].copy_from_slice(&(entry.len() as u16).to_le_bytes());
+            let offset_flags = offset as u16 | (*tag_flags as u16) << 13;
+            page[tag + 2..tag + 4].copy_from_slice(&offset_flags.to_le_bytes());
+            offset += entry.len();
+        }
+
+        page
+    }
+
+    /// Builds a catalog entry.
+    fn catalog_entry(
+        kind: u16,
+        id: u32,
+        root_page_or_type: u32,
+        size: u32,
+        name: &str,
+    ) -> (u8, Vec<u8>) {
+        let fixed: [&[u8]; 5] = [
+            &5u32.to_le_bytes(),
+            &kind.to_le_bytes(),
+            &id.to_le_bytes(),
+            &root_page_or_type.to_le_bytes(),
+            &size.to_le_bytes(),
+        ];
+
+        (0, record(&fixed, &[Some(name.as_bytes())], &[]))
+    }
+
+    #[test]
+    fn test_read_table() {
+        let mut data = vec![0; PAGE_SIZE];
+        data[4..8].copy_from_slice(&SIGNATURE.to_le_bytes());
+        data[0xEC..0xF0].copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+
+        let catalog = page(
+            0x3,
+            &[
+                catalog_entry(1, 5, 5, 0, "datatable"),
+                catalog_entry(2, 1, 4, 4, "number"),
+                catalog_entry(2, 2, 4, 2, "short"),
+                catalog_entry(2, 128, 10, 0, "text"),
+                catalog_entry(2, 129, 10, 0, "other text"),
+                catalog_entry(2, 256, 9, 0, "binary"),
+                catalog_entry(2, 257, 9, 0, "long"),
+            ],
+        );
+        // the root of the table points to two leaves
+        let root = page(
+            0x1,
+            &[
+                (0, 6u32.to_le_bytes().to_vec()),
+                (0, 7u32.to_le_bytes().to_vec()),
+            ],
+        );
+        let first_leaf = page(
+            0x2,
+            &[
+                (
+                    0,
+                    record(
+                        &[&1u32.to_le_bytes(), &2u16.to_le_bytes()],
+                        &[Some(b"abc"), Some(b"de")],
+                        &[(256, None, b"\x01\x02")],
+                    ),
+                ),
+                // a deleted record
+                (0x2, record(&[&9u32.to_le_bytes()], &[], &[])),
+            ],
+        );
+        let second_leaf = page(
+            0x2,
+            &[(
+                0,
+                record(
+                    &[&3u32.to_le_bytes()],
+                    &[None, Some(b"f")],
+                    &[(256, Some(0x1), b"\x03"), (257, Some(0x4), b"\x04")],
+                ),
+            )],
+        );
+
+        data.extend(vec![0; PAGE_SIZE * 4]);
+        for page in [catalog, root, first_leaf, second_leaf] {
+            data.extend(page);
+        }
+
+        let database = Database::new(&data).unwrap();
+        let mut records = Vec::new();
+        database
+            .read_table(
+                "datatable",
+                &[
+                    "number",
+                    "short",
+                    "text",
+                    "other text",
+                    "binary",
+                    "long",
+                    "missing",
+                ],
+                |values| {
+                    records.push(values.to_vec());
+                    Ok(())
+                },
+            )
+            .unwrap();
+
+        let expected: [[Option<&[u8]>; 7]; 2] = [
+            [
+                Some(&[1, 0, 0, 0]),
+                Some(&[2, 0]),
+                Some(b"abc"),
+                Some(b"de"),
+                Some(&[1, 2]),
+                None,
+                None,
+            ],
+            // the long values aren't read
+            [
+                Some(&[3, 0, 0, 0]),
+                None,
+                None,
+                Some(b"f"),
+                Some(&[3]),
+                None,
+                None,
+            ],
+        ];
+        assert_eq!(expected.map(|values| values.to_vec()).to_vec(), records);
+
+        assert!(database.read_table("sd_table", &[], |_| Ok(())).is_err());
+        assert!(Database::new(&[0; PAGE_SIZE]).is_err());
+    }
+}
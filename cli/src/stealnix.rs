@@ -0,0 +1,334 @@
+//! Dumps password hashes from a Unix shadow file.
+//!
+//! Most modern systems use salted and iterated crypt formats (sha512crypt, yescrypt...) that can't be attacked
+//! with rainbow tables, but raw MD5/SHA digests are still found on some legacy systems and appliances.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Cell, Color, Table};
+use crossterm::style::Stylize;
+use cugparck_commons::Digest;
+use cugparck_cpu::SearchHit;
+use serde::Serialize;
+
+use crate::{
+    check_hex, digest_sizes, format_password, group_tables, load_tables_from_dir, print_json,
+    search_table_groups, OutputFormat, Stealnix, TableGroup, TableSearchStats, TaskProgress,
+};
+
+/// The hash of an account in a shadow file.
+#[derive(Debug, PartialEq, Eq)]
+enum ShadowHash {
+    /// The account has no usable password.
+    NoPassword,
+    /// A raw digest, which can be searched in rainbow tables.
+    Digest(Digest),
+    /// A salted and iterated crypt hash, which can't be searched in rainbow tables.
+    Crypt(&'static str),
+}
+
+/// An account of a shadow file.
+struct ShadowAccount {
+    username: String,
+    hash: ShadowHash,
+}
+
+/// The result of the dump of an account, printed with `--output json`.
+#[derive(Serialize)]
+struct AccountResult {
+    username: String,
+    /// The format of the hash, such as `digest` or `sha512crypt`.
+    format: &'static str,
+    hash: Option<String>,
+    password: Option<String>,
+    /// The number of the table containing the password.
+    table_number: Option<usize>,
+    /// The column of the chain containing the password.
+    column: Option<usize>,
+}
+
+impl AccountResult {
+    fn new(account: &ShadowAccount, hit: Option<SearchHit>, hex_output: bool) -> Self {
+        let (format, hash) = match &account.hash {
+            ShadowHash::NoPassword => ("none", None),
+            ShadowHash::Digest(digest) => ("digest", Some(hex::encode(digest))),
+            ShadowHash::Crypt(format) => (*format, None),
+        };
+
+        Self {
+            username: account.username.clone(),
+            format,
+            hash,
+            password: hit.map(|hit| format_password(hit.password, hex_output)),
+            table_number: hit.map(|hit| hit.table_number),
+            column: hit.map(|hit| hit.column),
+        }
+    }
+}
+
+/// Returns the name of a crypt format from its identifier, the `id` in `$id$salt$hash`.
+fn crypt_format(id: &str) -> &'static str {
+    match id {
+        "1" => "md5crypt",
+        "apr1" => "apr1",
+        "2a" | "2b" | "2x" | "2y" => "bcrypt",
+        "5" => "sha256crypt",
+        "6" => "sha512crypt",
+        "7" => "scrypt",
+        "y" => "yescrypt",
+        "gy" => "gost-yescrypt",
+        _ => "unknown crypt",
+    }
+}
+
+/// Parses the password field of a shadow file.
+fn parse_hash(field: &str) -> ShadowHash {
+    // a leading `!` locks the account without changing its hash
+    let field = field.trim_start_matches('!');
+
+    if field.is_empty() || field.starts_with('*') {
+        return ShadowHash::NoPassword;
+    }
+
+    if let Some(rest) = field.strip_prefix('$') {
+        let id = rest.split('$').next().unwrap_or_default();
+        return ShadowHash::Crypt(crypt_format(id));
+    }
+
+    let digest = check_hex(field)
+        .ok()
+        .and_then(|hex| hex::decode(hex).ok()?.as_slice().try_into().ok());
+
+    match digest {
+        Some(digest) => ShadowHash::Digest(digest),
+        None if field.len() == 13 => ShadowHash::Crypt("descrypt"),
+        None => ShadowHash::Crypt("unknown crypt"),
+    }
+}
+
+/// Parses the accounts of a shadow file, where each line looks like `user:hash:...`.
+fn parse_shadow(content: &str) -> Vec<ShadowAccount> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let username = fields.next()?.to_owned();
+            let hash = parse_hash(fields.next()?);
+            Some(ShadowAccount { username, hash })
+        })
+        .collect()
+}
+
+/// Warns about the accounts whose hash can't be cracked with rainbow tables.
+/// `groups` are the tables the accounts are cracked with, if any.
+fn warn_incompatible(accounts: &[ShadowAccount], groups: &[TableGroup]) {
+    let mut crypt_formats = HashMap::<_, usize>::new();
+    let mut wrong_length = 0;
+
+    for account in accounts {
+        match &account.hash {
+            ShadowHash::Crypt(format) => *crypt_formats.entry(*format).or_default() += 1,
+            ShadowHash::Digest(digest) if !could_contain(groups, digest) => wrong_length += 1,
+            _ => (),
+        }
+    }
+
+    let mut crypt_formats = crypt_formats.into_iter().collect::<Vec<_>>();
+    crypt_formats.sort();
+    for (format, count) in crypt_formats {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: {count} account(s) use {format}, which is salted and iterated and can't be cracked with rainbow tables"
+            )
+            .yellow()
+        );
+    }
+
+    if !groups.is_empty() && wrong_length > 0 {
+        eprintln!(
+            "{}",
+            format!(
+                "Warning: {wrong_length} digest(s) are not {} bytes long and cannot be found in the tables",
+                digest_sizes(groups)
+            )
+            .yellow()
+        );
+    }
+}
+
+/// Returns true if a digest could be found in one of the groups of tables.
+fn could_contain(groups: &[TableGroup], digest: &Digest) -> bool {
+    groups
+        .iter()
+        .any(|group| group.ctx.could_contain_digest(digest))
+}
+
+/// Tries to crack the digests of the accounts with the tables of a directory.
+fn crack_accounts(
+    accounts: &[ShadowAccount],
+    dir: &Path,
+    low_memory: bool,
+    hex_output: bool,
+) -> Result<Vec<AccountResult>> {
+    let tables = group_tables(load_tables_from_dir(dir)?)?;
+    let groups = tables
+        .iter()
+        .map(|tables| TableGroup::new(tables, Vec::new()))
+        .collect::<Result<Vec<_>>>()?;
+    warn_incompatible(accounts, &groups);
+
+    // we use a hashmap so if we have two times the same hash we don't attack it twice.
+    let mut hits: HashMap<Digest, Option<SearchHit>> = accounts
+        .iter()
+        .filter_map(|account| match account.hash {
+            ShadowHash::Digest(digest) if could_contain(&groups, &digest) => Some((digest, None)),
+            _ => None,
+        })
+        .collect();
+
+    let digests = hits.keys().copied().collect::<Vec<_>>();
+    let found = search_table_groups(
+        &digests,
+        None,
+        &groups,
+        low_memory,
+        &TaskProgress::hidden(),
+        &mut TableSearchStats::default(),
+    )?;
+    hits.extend(
+        digests
+            .into_iter()
+            .zip(found.into_iter().map(|hit| hit.map(|(hit, _)| hit))),
+    );
+
+    Ok(accounts
+        .iter()
+        .map(|account| {
+            let hit = match &account.hash {
+                ShadowHash::Digest(digest) => hits.get(digest).copied().flatten(),
+                _ => None,
+            };
+            AccountResult::new(account, hit, hex_output)
+        })
+        .collect())
+}
+
+/// Prints the accounts in a table, along with their passwords if `cracked` is true.
+fn print_accounts(results: &[AccountResult], cracked: bool) {
+    let mut display_table = Table::new();
+    display_table.load_preset(UTF8_BORDERS_ONLY);
+    if cracked {
+        display_table.set_header(vec!["Username", "Hash", "Password"]);
+    } else {
+        display_table.set_header(vec!["Username", "Hash"]);
+    }
+
+    for result in results {
+        let username = Cell::new(&result.username);
+
+        let hash = match (&result.hash, result.format) {
+            (Some(hash), _) => Cell::new(hash).fg(Color::Green),
+            (None, "none") => Cell::new("No password").fg(Color::Grey),
+            (None, format) => Cell::new(format!("{format} (incompatible)")).fg(Color::Yellow),
+        };
+
+        if !cracked {
+            display_table.add_row(vec![username, hash]);
+            continue;
+        }
+
+        let password = match (&result.hash, &result.password) {
+            (Some(_), Some(password)) => Cell::new(password).fg(Color::Green),
+            (Some(_), None) => Cell::new("No password found").fg(Color::Red),
+            (None, _) => Cell::new("No password found").fg(Color::Grey),
+        };
+
+        display_table.add_row(vec![username, hash, password]);
+    }
+
+    println!("{display_table}");
+}
+
+pub fn stealnix(args: Stealnix, output: OutputFormat) -> Result<()> {
+    let content = fs::read_to_string(&args.shadow).context("Unable to read the shadow file")?;
+    let mut accounts = parse_shadow(&content);
+
+    if !args.user.is_empty() {
+        accounts.retain(|account| args.user.contains(&account.username));
+    }
+
+    let results = match &args.crack {
+        Some(dir) => crack_accounts(&accounts, dir, args.low_memory, args.hex_output)?,
+        None => {
+            warn_incompatible(&accounts, &[]);
+            accounts
+                .iter()
+                .map(|account| AccountResult::new(account, None, false))
+                .collect()
+        }
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&results)?,
+        OutputFormat::Human => print_accounts(&results, args.crack.is_some()),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_shadow, ShadowHash};
+
+    #[test]
+    fn test_parse_shadow() {
+        let content = "\
+root:$6$saltsalt$hash:19000:0:99999:7:::
+daemon:*:19000:0:99999:7:::
+admin:5F4DCC3B5AA765D61D8327DEB882CF99:19000::::::
+locked:!d8578edf8458ce06fbc5bb76a58c5ca4:19000::::::
+nopass::19000::::::
+legacy:abJnggxhB/yWI:19000::::::
+
+bcrypt:$2b$10$hash:19000::::::
+";
+        let accounts = parse_shadow(content);
+
+        let expected = [
+            ("root", ShadowHash::Crypt("sha512crypt")),
+            ("daemon", ShadowHash::NoPassword),
+            (
+                "admin",
+                ShadowHash::Digest(
+                    hex::decode("5f4dcc3b5aa765d61d8327deb882cf99")
+                        .unwrap()
+                        .as_slice()
+                        .try_into()
+                        .unwrap(),
+                ),
+            ),
+            (
+                "locked",
+                ShadowHash::Digest(
+                    hex::decode("d8578edf8458ce06fbc5bb76a58c5ca4")
+                        .unwrap()
+                        .as_slice()
+                        .try_into()
+                        .unwrap(),
+                ),
+            ),
+            ("nopass", ShadowHash::NoPassword),
+            ("legacy", ShadowHash::Crypt("descrypt")),
+            ("bcrypt", ShadowHash::Crypt("bcrypt")),
+        ];
+
+        assert_eq!(expected.len(), accounts.len());
+        for (account, (username, hash)) in accounts.iter().zip(expected) {
+            assert_eq!(username, account.username);
+            assert_eq!(hash, account.hash);
+        }
+    }
+}
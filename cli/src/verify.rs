@@ -0,0 +1,146 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Result};
+use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+use cugparck_cpu::{measured_success_rate, RainbowTable};
+use indicatif::HumanDuration;
+use serde::Serialize;
+
+use crate::{
+    false_alarm_filters, group_tables, load_false_alarm_indexes, load_tables_from_dir, print_json,
+    search_tables, OutputFormat, TableSearchStats, TaskProgress, Verify,
+};
+
+pub fn verify(args: Verify, output: OutputFormat) -> Result<()> {
+    let mut groups = group_tables(load_tables_from_dir(&args.dir)?)?;
+    ensure!(
+        groups.len() == 1,
+        "The directory contains tables generated with {} different sets of parameters, which should be verified separately",
+        groups.len()
+    );
+    let tables = groups.pop().unwrap();
+    let indexes = load_false_alarm_indexes(&tables)?;
+    let filters = false_alarm_filters(&indexes, &tables)?;
+
+    // the tables are independent, so the probability to miss a password is the product of the probabilities to miss it in each table
+    let mut miss_rate = 1.;
+    let mut ctx = None;
+    for table in &tables {
+        let table = table.table()?;
+        let (table_ctx, len) = (table.ctx(), table.len());
+        miss_rate *= 1. - measured_success_rate(&table_ctx, len);
+        ctx = Some(table_ctx);
+    }
+    let ctx = ctx.unwrap();
+
+    let passwords = sample_passwords(&ctx, args.samples as usize);
+    let digests = passwords
+        .iter()
+        .map(|password| ctx.hash(&password.into_password(&ctx)))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let hits = search_tables(
+        &digests,
+        &tables,
+        &filters,
+        args.low_memory,
+        &TaskProgress::hidden(),
+        &mut TableSearchStats::default(),
+    )?;
+    let duration = start.elapsed();
+
+    let cracked = hits.iter().filter(|hit| hit.is_some()).count();
+    let report = VerifyReport {
+        table_count: tables.len(),
+        samples: passwords.len(),
+        cracked,
+        success_rate: cracked as f64 / passwords.len() as f64,
+        expected_success_rate: 1. - miss_rate,
+        duration_secs: duration.as_secs_f64(),
+        average_time_secs: duration.as_secs_f64() / passwords.len() as f64,
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&report)?,
+        OutputFormat::Human => report.print(),
+    }
+
+    Ok(())
+}
+
+/// Returns `samples` passwords picked at random in the search space of the tables.
+fn sample_passwords(ctx: &RainbowTableCtx, samples: usize) -> Vec<CompressedPassword> {
+    let state = RandomState::new();
+
+    (0..samples)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_usize(i);
+            // maps the random 64-bit value to the search space without a modulo bias
+            let counter = (hasher.finish() as u128 * ctx.n as u128) >> 64;
+            CompressedPassword::from(counter as usize)
+        })
+        .collect()
+}
+
+/// The result of the verification of a set of tables.
+#[derive(Serialize)]
+struct VerifyReport {
+    table_count: usize,
+    samples: usize,
+    cracked: usize,
+    /// The proportion of the random passwords found in the tables.
+    success_rate: f64,
+    /// The success rate of the tables computed from their number of chains.
+    expected_success_rate: f64,
+    duration_secs: f64,
+    /// The average time needed to crack a digest, in seconds.
+    average_time_secs: f64,
+}
+
+impl VerifyReport {
+    /// Prints the report in a human-readable way.
+    fn print(&self) {
+        println!("Tables: {}", self.table_count);
+        println!("Cracked: {}/{}", self.cracked, self.samples);
+        println!(
+            "Success rate: {:.2}% (expected {:.2}%)",
+            self.success_rate * 100.,
+            self.expected_success_rate * 100.,
+        );
+        println!(
+            "Duration: {} ({:.2} ms per digest)",
+            HumanDuration(Duration::from_secs_f64(self.duration_secs)),
+            self.average_time_secs * 1000.,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_cpu::RainbowTableCtxBuilder;
+
+    use super::sample_passwords;
+
+    #[test]
+    fn test_sample_passwords() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .max_password_length(3)
+            .charset(b"abc")
+            .build()
+            .unwrap();
+
+        let passwords = sample_passwords(&ctx, 1000);
+        assert_eq!(1000, passwords.len());
+        assert!(passwords.iter().all(|password| password.get() < ctx.n));
+
+        // the passwords are spread over the search space
+        assert!(passwords.iter().any(|password| password.get() < ctx.n / 2));
+        assert!(passwords.iter().any(|password| password.get() >= ctx.n / 2));
+    }
+}
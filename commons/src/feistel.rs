@@ -0,0 +1,114 @@
+/// The number of rounds of the Feistel network.
+const ROUNDS: u64 = 8;
+
+/// A permutation of `0..n` derived from a seed, used to choose the startpoints of a table.
+/// It is a Feistel network on the smallest even number of bits that can represent `n - 1`,
+/// combined with cycle walking so that the values above `n` are mapped back into `0..n`.
+#[derive(Clone, Copy)]
+pub struct FeistelPermutation {
+    n: u64,
+    seed: u64,
+    half_bits: u32,
+}
+
+impl FeistelPermutation {
+    /// Creates the permutation of `0..n` of the given seed.
+    pub fn new(n: usize, seed: u64) -> Self {
+        let bits = u64::BITS - (n as u64).saturating_sub(1).leading_zeros();
+
+        Self {
+            n: n as u64,
+            seed,
+            half_bits: bits.div_ceil(2).max(1),
+        }
+    }
+
+    /// Returns the image of `x`, which should be smaller than `n`.
+    pub fn permute(&self, x: usize) -> usize {
+        let mut y = self.encrypt(x as u64);
+        while y >= self.n {
+            y = self.encrypt(y);
+        }
+
+        y as usize
+    }
+
+    /// Returns the value whose image is `y`, which should be smaller than `n`.
+    pub fn invert(&self, y: usize) -> usize {
+        let mut x = self.decrypt(y as u64);
+        while x >= self.n {
+            x = self.decrypt(x);
+        }
+
+        x as usize
+    }
+
+    /// Returns the mask of a half of a value.
+    #[inline]
+    fn mask(&self) -> u64 {
+        (1 << self.half_bits) - 1
+    }
+
+    /// The round function, mixing a half of a value with the seed and the round number (SplitMix64 finalizer).
+    #[inline]
+    fn round(&self, round: u64, half: u64) -> u64 {
+        let mut z = self
+            .seed
+            .wrapping_add(round.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+            ^ half;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+
+        (z ^ (z >> 31)) & self.mask()
+    }
+
+    fn encrypt(&self, x: u64) -> u64 {
+        let (mut left, mut right) = (x >> self.half_bits, x & self.mask());
+        for round in 0..ROUNDS {
+            (left, right) = (right, left ^ self.round(round, right));
+        }
+
+        (left << self.half_bits) | right
+    }
+
+    fn decrypt(&self, y: u64) -> u64 {
+        let (mut left, mut right) = (y >> self.half_bits, y & self.mask());
+        for round in (0..ROUNDS).rev() {
+            (left, right) = (right ^ self.round(round, left), left);
+        }
+
+        (left << self.half_bits) | right
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::FeistelPermutation;
+
+    #[test]
+    fn test_permutation() {
+        for n in [1, 2, 3, 100, 1000, 4096] {
+            let permutation = FeistelPermutation::new(n, 42);
+            let mut images = (0..n).map(|x| permutation.permute(x)).collect::<Vec<_>>();
+
+            for (x, &y) in images.iter().enumerate() {
+                assert_eq!(x, permutation.invert(y));
+            }
+
+            images.sort_unstable();
+            assert!(images.into_iter().eq(0..n));
+        }
+
+        // the seed changes the permutation.
+        let a = FeistelPermutation::new(1000, 1);
+        let b = FeistelPermutation::new(1000, 2);
+        assert!((0..1000).any(|x| a.permute(x) != b.permute(x)));
+
+        // the permutation works on the whole range of usize.
+        let permutation = FeistelPermutation::new(usize::MAX, 7);
+        let y = permutation.permute(usize::MAX - 1);
+        assert_eq!(usize::MAX - 1, permutation.invert(y));
+    }
+}
@@ -3,9 +3,22 @@
 #[cfg(not(any(target_os = "cuda", target_arch = "spirv")))]
 extern crate std;
 
+#[cfg(feature = "serde")]
+extern crate serde_crate as serde;
+
+mod feistel;
+mod lm;
 mod ntlm;
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+pub use feistel::FeistelPermutation;
+pub use lm::LM_HALF_LENGTH;
 
-use ntlm::ntlm;
+use lm::lm;
+use ntlm::{ntlm, ntlm_utf8};
+#[cfg(feature = "serde")]
+pub use serde_impls::serde_digest;
 pub use tinyvec::ArrayVec;
 
 use core::{
@@ -13,6 +26,7 @@ use core::{
     ops::{Deref, DerefMut, Range},
 };
 
+use blake2::{Blake2b512, Blake2s256};
 use md4::{Digest as _, Md4};
 use md5::Md5;
 use sha1::Sha1;
@@ -29,6 +43,9 @@ use {
 /// "Precomputation for Rainbow Tables has Never Been so Fast" figure 3 shows that 20 is a reasonable number.
 pub const DEFAULT_FILTER_COUNT: usize = 20;
 
+/// The maximum number of filters.
+pub const MAX_FILTER_COUNT: usize = 1000;
+
 /// The default chain length.
 pub const DEFAULT_CHAIN_LENGTH: usize = 10_000;
 
@@ -45,8 +62,9 @@ pub const DEFAULT_CHARSET: &[u8] =
 /// The default table number.
 pub const DEFAULT_TABLE_NUMBER: u8 = 8;
 
-/// The maximum password size allowed.
-pub const MAX_PASSWORD_LENGTH_ALLOWED: usize = 10;
+/// The maximum password size allowed, in bytes.
+/// The search space must still fit in 64 bits, so only small charsets can be used with the longest passwords.
+pub const MAX_PASSWORD_LENGTH_ALLOWED: usize = 16;
 
 /// The maximum digest size allowed.
 pub const MAX_DIGEST_LENGTH_ALLOWED: usize = 64;
@@ -54,7 +72,16 @@ pub const MAX_DIGEST_LENGTH_ALLOWED: usize = 64;
 /// The maximum charset length allowed.
 pub const MAX_CHARSET_LENGTH_ALLOWED: usize = 126;
 
-/// An ASCII password stored in a stack-allocated vector.
+/// The maximum salt length allowed.
+pub const MAX_SALT_LENGTH_ALLOWED: usize = 62;
+
+/// A charset stored in a stack-allocated vector.
+pub type Charset = ArrayVec<[u8; MAX_CHARSET_LENGTH_ALLOWED]>;
+
+/// A salt stored in a stack-allocated vector.
+pub type Salt = ArrayVec<[u8; MAX_SALT_LENGTH_ALLOWED]>;
+
+/// A password stored in a stack-allocated vector.
 #[repr(transparent)]
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub struct Password(ArrayVec<[u8; MAX_PASSWORD_LENGTH_ALLOWED]>);
@@ -64,6 +91,30 @@ impl Password {
     pub fn new(text: &[u8]) -> Self {
         Password(text.try_into().unwrap())
     }
+
+    /// Returns true if the password is valid UTF-8 without control characters, so it can be printed as is.
+    pub fn is_printable(&self) -> bool {
+        matches!(core::str::from_utf8(&self.0), Ok(password) if !password.chars().any(char::is_control))
+    }
+
+    /// Returns a displayable hexadecimal representation of the password,
+    /// in the `$HEX[...]` format used by hashcat and John the Ripper.
+    pub fn hex(&self) -> HexPassword<'_> {
+        HexPassword(self)
+    }
+}
+
+/// The hexadecimal representation of a password, returned by `Password::hex`.
+pub struct HexPassword<'a>(&'a Password);
+
+impl Display for HexPassword<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "$HEX[")?;
+        for byte in self.0.iter() {
+            write!(f, "{byte:02x}")?;
+        }
+        write!(f, "]")
+    }
 }
 
 impl AsRef<[u8]> for Password {
@@ -88,9 +139,12 @@ impl DerefMut for Password {
 
 impl Display for Password {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}", core::str::from_utf8(&self.0).unwrap())?;
-
-        Ok(())
+        if self.is_printable() {
+            // SAFETY: The password was checked to be valid UTF-8.
+            write!(f, "{}", unsafe { core::str::from_utf8_unchecked(&self.0) })
+        } else {
+            write!(f, "{}", self.hex())
+        }
     }
 }
 
@@ -129,11 +183,12 @@ impl CompressedPassword {
     }
 
     pub fn continue_chain(&mut self, columns: Range<usize>, ctx: &RainbowTableCtx) {
-        let hash = ctx.hash_type.hash_function();
+        let hash = ctx.hash_function();
 
         for i in columns {
             let plaintext = self.into_password(ctx);
-            let digest = hash(plaintext);
+            let digest = ctx.hash_with(hash, &plaintext);
+            debug_assert_eq!(digest.len(), ctx.hash_type.digest_size());
             *self = reduce(digest, i, ctx);
         }
     }
@@ -202,79 +257,151 @@ pub enum HashType {
     Sha3_256,
     Sha3_384,
     Sha3_512,
+    Blake2b512,
+    Blake2s256,
+    Blake3,
+    /// A half of a LAN Manager hash, which covers up to 7 uppercased characters.
+    Lm,
+    /// The hash of MySQL 4.1+, `SHA1(SHA1(password))`, where the inner digest is hashed as raw bytes.
+    Mysql41,
+    /// `MD5(MD5(password))`, where the inner digest is hashed as lowercase hexadecimal like in hashcat.
+    Md5Md5,
 }
 
 impl HashType {
+    /// All the supported hash functions.
+    pub const ALL: [HashType; 18] = [
+        HashType::Ntlm,
+        HashType::Md4,
+        HashType::Md5,
+        HashType::Sha1,
+        HashType::Sha2_224,
+        HashType::Sha2_256,
+        HashType::Sha2_384,
+        HashType::Sha2_512,
+        HashType::Sha3_224,
+        HashType::Sha3_256,
+        HashType::Sha3_384,
+        HashType::Sha3_512,
+        HashType::Blake2b512,
+        HashType::Blake2s256,
+        HashType::Blake3,
+        HashType::Lm,
+        HashType::Mysql41,
+        HashType::Md5Md5,
+    ];
+
+    /// Returns the maximum length of the passwords of a table using this hash function.
+    pub fn max_password_length(&self) -> usize {
+        match self {
+            // a table only contains one half of the LM passwords
+            HashType::Lm => LM_HALF_LENGTH,
+            _ => MAX_PASSWORD_LENGTH_ALLOWED,
+        }
+    }
+
     /// Gets the right hash function.
-    pub fn hash_function(&self) -> fn(Password) -> Digest {
+    pub fn hash_function(&self) -> fn(&[u8]) -> Digest {
         // SAFETY: The digests are guaranteed to be smaller or of the same size than the maximum digest size allowed.
         unsafe {
             match self {
                 HashType::Ntlm => {
-                    |password| ntlm(&password).as_slice().try_into().unwrap_unchecked()
+                    |password| ntlm(password).as_slice().try_into().unwrap_unchecked()
                 }
                 HashType::Md4 => |password| {
-                    Md4::digest(&password)
+                    Md4::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Md5 => |password| {
-                    Md5::digest(&password)
+                    Md5::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha1 => |password| {
-                    Sha1::digest(&password)
+                    Sha1::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha2_224 => |password| {
-                    Sha224::digest(&password)
+                    Sha224::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha2_256 => |password| {
-                    Sha256::digest(&password)
+                    Sha256::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha2_384 => |password| {
-                    Sha384::digest(&password)
+                    Sha384::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha2_512 => |password| {
-                    Sha512::digest(&password)
+                    Sha512::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha3_224 => |password| {
-                    Sha3_224::digest(&password)
+                    Sha3_224::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha3_256 => |password| {
-                    Sha3_256::digest(&password)
+                    Sha3_256::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha3_384 => |password| {
-                    Sha3_384::digest(&password)
+                    Sha3_384::digest(password)
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
                 },
                 HashType::Sha3_512 => |password| {
-                    Sha3_512::digest(&password)
+                    Sha3_512::digest(password)
+                        .as_slice()
+                        .try_into()
+                        .unwrap_unchecked()
+                },
+                HashType::Blake2b512 => |password| {
+                    Blake2b512::digest(password)
+                        .as_slice()
+                        .try_into()
+                        .unwrap_unchecked()
+                },
+                HashType::Blake2s256 => |password| {
+                    Blake2s256::digest(password)
+                        .as_slice()
+                        .try_into()
+                        .unwrap_unchecked()
+                },
+                HashType::Blake3 => |password| {
+                    blake3::hash(password)
+                        .as_bytes()
+                        .as_slice()
+                        .try_into()
+                        .unwrap_unchecked()
+                },
+                HashType::Lm => |password| lm(password).as_slice().try_into().unwrap_unchecked(),
+                HashType::Mysql41 => |password| {
+                    Sha1::digest(Sha1::digest(password))
+                        .as_slice()
+                        .try_into()
+                        .unwrap_unchecked()
+                },
+                HashType::Md5Md5 => |password| {
+                    Md5::digest(lowercase_hex(&Md5::digest(password).into()))
                         .as_slice()
                         .try_into()
                         .unwrap_unchecked()
@@ -298,10 +425,141 @@ impl HashType {
             HashType::Sha3_256 => Sha3_256::output_size(),
             HashType::Sha3_384 => Sha3_384::output_size(),
             HashType::Sha3_512 => Sha3_512::output_size(),
+            HashType::Blake2b512 => Blake2b512::output_size(),
+            HashType::Blake2s256 => Blake2s256::output_size(),
+            HashType::Blake3 => blake3::OUT_LEN,
+            HashType::Lm => 8,
+            HashType::Mysql41 => Sha1::output_size(),
+            HashType::Md5Md5 => Md5::output_size(),
+        }
+    }
+
+    /// Returns a password and its reference digest in hexadecimal,
+    /// to check the implementations of the hash function at runtime.
+    pub fn known_answer(&self) -> (&'static [u8], &'static str) {
+        match self {
+            HashType::Ntlm => (b"password", "8846f7eaee8fb117ad06bdd830b7586c"),
+            HashType::Md4 => (b"abc", "a448017aaf21d8525fc10ae87aa6729d"),
+            HashType::Md5 => (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            HashType::Sha1 => (b"abc", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+            HashType::Sha2_224 => (
+                b"abc",
+                "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7",
+            ),
+            HashType::Sha2_256 => (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            HashType::Sha2_384 => (
+                b"abc",
+                "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            ),
+            HashType::Sha2_512 => (
+                b"abc",
+                "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            ),
+            HashType::Sha3_224 => (
+                b"abc",
+                "e642824c3f8cf24ad09234ee7d3c766fc9a3a5168d0c94ad73b46fdf",
+            ),
+            HashType::Sha3_256 => (
+                b"abc",
+                "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            ),
+            HashType::Sha3_384 => (
+                b"abc",
+                "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25",
+            ),
+            HashType::Sha3_512 => (
+                b"abc",
+                "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0",
+            ),
+            HashType::Blake2b512 => (
+                b"abc",
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+            ),
+            HashType::Blake2s256 => (
+                b"abc",
+                "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982",
+            ),
+            HashType::Blake3 => (
+                b"abc",
+                "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            ),
+            // the first half of the LM hash of "password"
+            HashType::Lm => (b"PASSWOR", "e52cac67419a9a22"),
+            HashType::Mysql41 => (b"password", "2470c0c06dee42fd1618bb99005adca2ec9d1e19"),
+            HashType::Md5Md5 => (b"password", "696d29e0940a4957748fe3fc9efd22a3"),
         }
     }
 }
 
+/// Encodes a digest in lowercase hexadecimal, to hash it again.
+#[inline]
+fn lowercase_hex<const N: usize>(
+    digest: &[u8; N],
+) -> ArrayVec<[u8; MAX_DIGEST_LENGTH_ALLOWED * 2]> {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    let mut hex = ArrayVec::new();
+    for byte in digest {
+        hex.push(DIGITS[(byte >> 4) as usize]);
+        hex.push(DIGITS[(byte & 0xF) as usize]);
+    }
+
+    hex
+}
+
+/// Where the salt is concatenated to the password before hashing it.
+#[cfg_attr(
+    not(any(target_os = "cuda", target_arch = "spirv")),
+    derive(Archive, Deserialize, Serialize),
+    archive_attr(derive(CheckBytes))
+)]
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SaltPosition {
+    /// The salt is put before the password.
+    Prefix,
+    /// The salt is put after the password.
+    #[default]
+    Suffix,
+}
+
+/// How the characters of the charsets are encoded in the passwords.
+#[cfg_attr(
+    not(any(target_os = "cuda", target_arch = "spirv")),
+    derive(Archive, Deserialize, Serialize),
+    archive_attr(derive(CheckBytes))
+)]
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PasswordEncoding {
+    /// Each character of the charsets is a byte of the passwords, e.g. an ASCII or a Latin-1 character.
+    #[default]
+    Bytes,
+    /// Each character of the charsets is the index of a code point of the context,
+    /// which is encoded in UTF-8 in the passwords.
+    Utf8,
+}
+
+/// How the startpoints of a table are chosen among the passwords of the search space.
+#[cfg_attr(
+    not(any(target_os = "cuda", target_arch = "spirv")),
+    derive(Archive, Deserialize, Serialize),
+    archive_attr(derive(CheckBytes))
+)]
+#[repr(usize)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StartpointOrder {
+    /// The startpoints are the first passwords of the search space.
+    #[default]
+    Sequential,
+    /// The startpoints are the images of `0..m0` by a pseudo-random permutation of the search space,
+    /// derived from the seed of the context.
+    Seeded,
+}
+
 /// Context used to store all parameters used to generate a rainbow table.
 #[repr(C)]
 #[cfg_attr(
@@ -309,25 +567,193 @@ impl HashType {
     derive(Archive, Deserialize, Serialize),
     archive_attr(derive(CheckBytes))
 )]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct RainbowTableCtx {
     /// The number of starting chains to generate.
     pub m0: usize,
     /// The type of the hash function used.
     pub hash_type: HashType,
     /// The charset used.
-    pub charset: ArrayVec<[u8; MAX_CHARSET_LENGTH_ALLOWED]>,
+    /// When a different charset is used for each position, this is the union of all the charsets.
+    pub charset: Charset,
+    /// The charset used for each position of a password.
+    /// The charsets after the maximum password length are empty.
+    pub charsets: [Charset; MAX_PASSWORD_LENGTH_ALLOWED],
     /// The length of a chain.
     pub t: usize,
+    /// The minimum password length.
+    pub min_password_length: usize,
     /// The maximum password length.
     pub max_password_length: usize,
     /// The size of the total search space.
     pub n: usize,
     /// A rainbow table has to search through passwords of a variable length.
     /// This is used to determine the search space for each password length.
+    /// The search space of a length is empty if it is below the minimum password length.
     pub search_spaces: ArrayVec<[usize; MAX_PASSWORD_LENGTH_ALLOWED + 1]>,
     /// The table number.
     pub tn: usize,
+    /// The salt concatenated to the passwords before hashing them, empty if there is no salt.
+    pub salt: Salt,
+    /// Where the salt is concatenated to the passwords.
+    pub salt_position: SaltPosition,
+    /// The number of filtrations of the chains during the generation.
+    /// More filtrations use less memory but make the generation slower.
+    pub filter_count: usize,
+    /// How the characters of the charsets are encoded in the passwords.
+    pub encoding: PasswordEncoding,
+    /// The sorted code points of the characters of the charsets, when the passwords are encoded in UTF-8.
+    /// Only the first `charset.len()` code points are used.
+    pub code_points: [u32; MAX_CHARSET_LENGTH_ALLOWED],
+    /// How the startpoints are chosen.
+    pub startpoint_order: StartpointOrder,
+    /// The seed of the permutation choosing the startpoints, when they are seeded.
+    pub startpoint_seed: u64,
+}
+
+impl RainbowTableCtx {
+    /// Returns true if the plaintext is in the search space of the context,
+    /// that is if its length is in the range of the context and each character is in the charset of its position.
+    pub fn could_contain(&self, plaintext: &Password) -> bool {
+        let plaintext = match self.decode(plaintext) {
+            Some(plaintext) => plaintext,
+            None => return false,
+        };
+
+        (self.min_password_length..=self.max_password_length).contains(&plaintext.len())
+            && plaintext
+                .iter()
+                .zip(&self.charsets)
+                .all(|(c, charset)| charset.contains(c))
+    }
+
+    /// Hashes a password with the hash function of the context, after concatenating the salt to it.
+    #[inline]
+    pub fn hash(&self, password: &Password) -> Digest {
+        self.hash_with(self.hash_function(), password)
+    }
+
+    /// Gets the hash function of the context.
+    /// It differs from the one of the hash type for NTLM, which has to decode the passwords encoded in UTF-8.
+    pub fn hash_function(&self) -> fn(&[u8]) -> Digest {
+        match (self.hash_type, self.encoding) {
+            // SAFETY: The digest is smaller than the maximum digest size allowed.
+            (HashType::Ntlm, PasswordEncoding::Utf8) => {
+                |password| unsafe { ntlm_utf8(password).as_slice().try_into().unwrap_unchecked() }
+            }
+            _ => self.hash_type.hash_function(),
+        }
+    }
+
+    /// Same as `RainbowTableCtx::hash`, but with the hash function already retrieved.
+    #[inline]
+    pub fn hash_with(&self, hash: fn(&[u8]) -> Digest, password: &Password) -> Digest {
+        if self.salt.is_empty() {
+            return hash(password);
+        }
+
+        let mut salted =
+            ArrayVec::<[u8; MAX_PASSWORD_LENGTH_ALLOWED + MAX_SALT_LENGTH_ALLOWED]>::new();
+        match self.salt_position {
+            SaltPosition::Prefix => {
+                salted.extend_from_slice(&self.salt);
+                salted.extend_from_slice(password);
+            }
+            SaltPosition::Suffix => {
+                salted.extend_from_slice(password);
+                salted.extend_from_slice(&self.salt);
+            }
+        }
+
+        hash(&salted)
+    }
+
+    /// Returns the startpoint of the chain at the given index, which should be smaller than `m0`.
+    pub fn startpoint(&self, index: usize) -> CompressedPassword {
+        match self.startpoint_order {
+            StartpointOrder::Sequential => index.into(),
+            StartpointOrder::Seeded => FeistelPermutation::new(self.n, self.startpoint_seed)
+                .permute(index)
+                .into(),
+        }
+    }
+
+    /// Returns the index of a startpoint of the context, the inverse of `RainbowTableCtx::startpoint`.
+    pub fn startpoint_index(&self, startpoint: CompressedPassword) -> usize {
+        match self.startpoint_order {
+            StartpointOrder::Sequential => startpoint.get(),
+            StartpointOrder::Seeded => {
+                FeistelPermutation::new(self.n, self.startpoint_seed).invert(startpoint.get())
+            }
+        }
+    }
+
+    /// Returns true if a different charset is used for some positions of a password.
+    pub fn has_position_charsets(&self) -> bool {
+        self.charsets[..self.max_password_length]
+            .iter()
+            .any(|charset| *charset != self.charset)
+    }
+
+    /// Returns true if the digest has the size of the digests produced by the hash function of the context.
+    pub fn could_contain_digest(&self, digest: &[u8]) -> bool {
+        digest.len() == self.hash_type.digest_size()
+    }
+
+    /// Returns the characters of a charset of the context.
+    pub fn charset_chars<'a>(&'a self, charset: &'a [u8]) -> impl Iterator<Item = char> + 'a {
+        charset.iter().map(move |&c| match self.encoding {
+            PasswordEncoding::Bytes => c as char,
+            PasswordEncoding::Utf8 => {
+                char::from_u32(self.code_points[c as usize]).unwrap_or(char::REPLACEMENT_CHARACTER)
+            }
+        })
+    }
+
+    /// Converts an encoded password to the characters of the charsets.
+    /// Returns `None` if the password contains a character that can't be in a charset of the context.
+    fn decode(&self, plaintext: &Password) -> Option<Password> {
+        if self.encoding == PasswordEncoding::Bytes {
+            return Some(*plaintext);
+        }
+
+        let code_points = &self.code_points[..self.charset.len()];
+        let mut decoded = Password::default();
+        for c in core::str::from_utf8(plaintext).ok()?.chars() {
+            decoded.push(code_points.binary_search(&(c as u32)).ok()? as u8);
+        }
+
+        Some(decoded)
+    }
+
+    /// Appends a character of a charset to a password, encoded with the encoding of the context.
+    #[inline]
+    fn push_encoded(&self, plaintext: &mut Password, c: u8) {
+        let code_point = match self.encoding {
+            PasswordEncoding::Bytes => {
+                plaintext.push(c);
+                return;
+            }
+            PasswordEncoding::Utf8 => self.code_points[c as usize],
+        };
+
+        // the encoding is done by hand so that it can run on the GPU
+        if code_point < 0x80 {
+            plaintext.push(code_point as u8);
+        } else if code_point < 0x800 {
+            plaintext.push(0xC0 | (code_point >> 6) as u8);
+            plaintext.push(0x80 | (code_point & 0x3F) as u8);
+        } else if code_point < 0x10000 {
+            plaintext.push(0xE0 | (code_point >> 12) as u8);
+            plaintext.push(0x80 | ((code_point >> 6) & 0x3F) as u8);
+            plaintext.push(0x80 | (code_point & 0x3F) as u8);
+        } else {
+            plaintext.push(0xF0 | (code_point >> 18) as u8);
+            plaintext.push(0x80 | ((code_point >> 12) & 0x3F) as u8);
+            plaintext.push(0x80 | ((code_point >> 6) & 0x3F) as u8);
+            plaintext.push(0x80 | (code_point & 0x3F) as u8);
+        }
+    }
 }
 
 // SAFETY: All fields can be initialized to 0.
@@ -409,11 +835,22 @@ impl ArchivedRainbowChain {
 #[inline]
 pub fn reduce(digest: Digest, iteration: usize, ctx: &RainbowTableCtx) -> CompressedPassword {
     // we can use the 8 first bytes of the digest as the seed, since it is pseudo-random.
+    debug_assert!(digest.len() >= 8);
     // SAFETY: The digest is at least 8 bytes long.
     let first_bytes = unsafe { usize::from_le_bytes(digest[0..8].try_into().unwrap_unchecked()) };
     (first_bytes.wrapping_add(iteration.wrapping_mul(ctx.tn as usize)) % ctx.n).into()
 }
 
+/// Returns the endpoint of the chain containing the digest at the given column.
+/// This is what the online phase looks up in the table for each column.
+#[inline]
+pub fn column_endpoint(digest: Digest, column: usize, ctx: &RainbowTableCtx) -> CompressedPassword {
+    let mut endpoint = reduce(digest, column, ctx);
+    endpoint.continue_chain(column + 1..ctx.t - 1, ctx);
+
+    endpoint
+}
+
 /// Creates a plaintext from a counter.
 #[inline]
 pub fn counter_to_plaintext(mut counter: usize, ctx: &RainbowTableCtx) -> Password {
@@ -430,9 +867,12 @@ pub fn counter_to_plaintext(mut counter: usize, ctx: &RainbowTableCtx) -> Passwo
     counter -= ctx.search_spaces[len];
 
     let mut plaintext = Password::default();
-    for _ in 0..len {
-        plaintext.push(charset_to_ascii(counter % ctx.charset.len(), &ctx.charset));
-        counter /= ctx.charset.len();
+    for charset in &ctx.charsets[..len] {
+        ctx.push_encoded(
+            &mut plaintext,
+            charset_to_ascii(counter % charset.len(), charset),
+        );
+        counter /= charset.len();
     }
 
     plaintext
@@ -441,9 +881,12 @@ pub fn counter_to_plaintext(mut counter: usize, ctx: &RainbowTableCtx) -> Passwo
 /// Creates a counter from a plaintext.
 #[inline]
 fn plaintext_to_counter(plaintext: Password, ctx: &RainbowTableCtx) -> usize {
+    let plaintext = ctx.decode(&plaintext).unwrap();
     let mut counter = ctx.search_spaces[plaintext.len()];
-    for (i, &c) in plaintext.iter().enumerate() {
-        counter += ascii_to_charset(c, &ctx.charset) as usize * ctx.charset.len().pow(i as u32);
+    let mut weight = 1;
+    for (&c, charset) in plaintext.iter().zip(&ctx.charsets) {
+        counter += ascii_to_charset(c, charset) as usize * weight;
+        weight *= charset.len();
     }
 
     counter
@@ -453,22 +896,224 @@ fn plaintext_to_counter(plaintext: Password, ctx: &RainbowTableCtx) -> usize {
 mod tests {
     use tinyvec::array_vec;
 
+    use std::{format, string::String};
+
     use crate::{
-        ascii_to_charset, counter_to_plaintext, plaintext_to_counter, HashType, Password,
-        RainbowTableCtx, DEFAULT_CHAIN_LENGTH, DEFAULT_CHARSET, DEFAULT_MAX_PASSWORD_LENGTH,
-        DEFAULT_TABLE_NUMBER,
+        ascii_to_charset, column_endpoint, counter_to_plaintext, plaintext_to_counter, Charset,
+        CompressedPassword, HashType, Password, PasswordEncoding, RainbowTableCtx, Salt,
+        SaltPosition, StartpointOrder, DEFAULT_CHAIN_LENGTH, DEFAULT_CHARSET, DEFAULT_FILTER_COUNT,
+        DEFAULT_MAX_PASSWORD_LENGTH, DEFAULT_TABLE_NUMBER, MAX_CHARSET_LENGTH_ALLOWED,
+        MAX_PASSWORD_LENGTH_ALLOWED,
     };
 
+    /// Tests that a hash function produces the expected hexadecimal digest for a password.
+    macro_rules! test_hash_function {
+        ($name:ident, $hash_type:expr, $password:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let digest = $hash_type.hash_function()($password);
+                let hex = digest
+                    .iter()
+                    .map(|byte| format!("{byte:02x}"))
+                    .collect::<String>();
+
+                assert_eq!($expected, hex);
+            }
+        };
+    }
+
+    test_hash_function!(
+        test_md5,
+        HashType::Md5,
+        b"abc",
+        "900150983cd24fb0d6963f7d28e17f72"
+    );
+
+    test_hash_function!(
+        test_md5_empty,
+        HashType::Md5,
+        b"",
+        "d41d8cd98f00b204e9800998ecf8427e"
+    );
+
+    test_hash_function!(
+        test_sha1,
+        HashType::Sha1,
+        b"abc",
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+
+    test_hash_function!(
+        test_sha1_empty,
+        HashType::Sha1,
+        b"",
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+    );
+
+    test_hash_function!(
+        test_sha2_224,
+        HashType::Sha2_224,
+        b"abc",
+        "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7"
+    );
+
+    test_hash_function!(
+        test_sha2_256,
+        HashType::Sha2_256,
+        b"abc",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+
+    test_hash_function!(
+        test_sha2_384,
+        HashType::Sha2_384,
+        b"abc",
+        "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+    );
+
+    test_hash_function!(
+        test_sha2_512,
+        HashType::Sha2_512,
+        b"abc",
+        "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+    );
+
+    test_hash_function!(
+        test_sha3_224,
+        HashType::Sha3_224,
+        b"abc",
+        "e642824c3f8cf24ad09234ee7d3c766fc9a3a5168d0c94ad73b46fdf"
+    );
+
+    // a password of the maximum length allowed
+    test_hash_function!(
+        test_sha3_224_max_length,
+        HashType::Sha3_224,
+        b"0123456789abcdef",
+        "bbb7d56cc80a8c80e907f7d9240edc0be264aa173266b30918bc1065"
+    );
+
+    test_hash_function!(
+        test_sha3_256,
+        HashType::Sha3_256,
+        b"abc",
+        "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+    );
+
+    test_hash_function!(
+        test_sha3_256_max_length,
+        HashType::Sha3_256,
+        b"0123456789abcdef",
+        "a5df4caae9fdb5dbacf667075b709a2f30a115c43168af332062b42d4b0da01f"
+    );
+
+    test_hash_function!(
+        test_sha3_384,
+        HashType::Sha3_384,
+        b"abc",
+        "ec01498288516fc926459f58e2c6ad8df9b473cb0fc08c2596da7cf0e49be4b298d88cea927ac7f539f1edf228376d25"
+    );
+
+    test_hash_function!(
+        test_sha3_384_max_length,
+        HashType::Sha3_384,
+        b"0123456789abcdef",
+        "56f351f754c418892eab4009e5f85c8d5436a591014503563e9395b8955264130e43758d01bd153e0a29e4e099a67998"
+    );
+
+    test_hash_function!(
+        test_sha3_512,
+        HashType::Sha3_512,
+        b"abc",
+        "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"
+    );
+
+    test_hash_function!(
+        test_sha3_512_max_length,
+        HashType::Sha3_512,
+        b"0123456789abcdef",
+        "59d06155d25dffdb982729de8dce9d7855ca094d8bab8124b347c40668477056b3c27ccb7d71b54043d207ccd187642bf9c8466f9a8d0dbefb4c41633a7e39ef"
+    );
+
+    test_hash_function!(
+        test_blake2b512,
+        HashType::Blake2b512,
+        b"abc",
+        "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d17d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+    );
+
+    test_hash_function!(
+        test_blake2s256,
+        HashType::Blake2s256,
+        b"abc",
+        "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"
+    );
+
+    test_hash_function!(
+        test_blake3,
+        HashType::Blake3,
+        b"abc",
+        "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+    );
+
+    test_hash_function!(
+        test_mysql41,
+        HashType::Mysql41,
+        b"password",
+        "2470c0c06dee42fd1618bb99005adca2ec9d1e19"
+    );
+
+    test_hash_function!(
+        test_md5_md5,
+        HashType::Md5Md5,
+        b"password",
+        "696d29e0940a4957748fe3fc9efd22a3"
+    );
+
+    #[test]
+    fn test_known_answers() {
+        for hash_type in HashType::ALL {
+            let (password, expected) = hash_type.known_answer();
+            let hex = hash_type.hash_function()(password)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+
+            assert_eq!(expected, hex, "{hash_type:?}");
+        }
+    }
+
+    #[test]
+    fn test_digest_size() {
+        for hash_type in HashType::ALL {
+            let digest = hash_type.hash_function()(b"password");
+            assert_eq!(hash_type.digest_size(), digest.len(), "{hash_type:?}");
+        }
+    }
+
     fn build_ctx() -> RainbowTableCtx {
+        let charset: Charset = b"abc".as_slice().try_into().unwrap();
+        let mut charsets = <[Charset; MAX_PASSWORD_LENGTH_ALLOWED]>::default();
+        charsets[..DEFAULT_MAX_PASSWORD_LENGTH as usize].fill(charset);
+
         RainbowTableCtx {
             hash_type: HashType::Ntlm,
             search_spaces: array_vec![0, 1, 4, 13, 40, 121, 364],
-            charset: b"abc".as_slice().try_into().unwrap(),
+            charset,
+            charsets,
+            min_password_length: 0,
             max_password_length: DEFAULT_MAX_PASSWORD_LENGTH as usize,
             t: DEFAULT_CHAIN_LENGTH,
             tn: DEFAULT_TABLE_NUMBER as usize,
             m0: 0,
             n: 0,
+            salt: Salt::new(),
+            salt_position: SaltPosition::Suffix,
+            filter_count: DEFAULT_FILTER_COUNT,
+            encoding: PasswordEncoding::Bytes,
+            code_points: [0; MAX_CHARSET_LENGTH_ALLOWED],
+            startpoint_order: StartpointOrder::Sequential,
+            startpoint_seed: 0,
         }
     }
 
@@ -530,4 +1175,175 @@ mod tests {
 
         assert!(expected.into_iter().eq(counters));
     }
+
+    #[test]
+    fn test_salt() {
+        let mut ctx = build_ctx();
+        ctx.hash_type = HashType::Md5;
+        ctx.salt = b"abc".as_slice().try_into().unwrap();
+        let password = Password::new(b"def");
+
+        ctx.salt_position = SaltPosition::Prefix;
+        assert_eq!(
+            HashType::Md5.hash_function()(b"abcdef"),
+            ctx.hash(&password)
+        );
+
+        ctx.salt_position = SaltPosition::Suffix;
+        assert_eq!(
+            HashType::Md5.hash_function()(b"defabc"),
+            ctx.hash(&password)
+        );
+
+        ctx.salt = Salt::new();
+        assert_eq!(HashType::Md5.hash_function()(b"def"), ctx.hash(&password));
+    }
+
+    #[test]
+    fn test_column_endpoint() {
+        let mut ctx = build_ctx();
+        ctx.n = 364;
+        ctx.t = 100;
+
+        let startpoint = CompressedPassword::from(42);
+        let mut endpoint = startpoint;
+        endpoint.continue_chain(0..ctx.t - 1, &ctx);
+
+        // the digest of the password at each column leads to the endpoint of the chain
+        let mut password = startpoint;
+        for column in 0..ctx.t - 1 {
+            let digest = ctx.hash(&password.into_password(&ctx));
+            assert_eq!(endpoint, column_endpoint(digest, column, &ctx));
+            password.continue_chain(column..column + 1, &ctx);
+        }
+    }
+
+    #[test]
+    fn test_min_password_length() {
+        let mut ctx = build_ctx();
+        ctx.min_password_length = 2;
+        ctx.max_password_length = 3;
+        ctx.search_spaces = array_vec![0, 0, 0, 9];
+
+        let expected = [
+            Password::new(b"aa"),
+            Password::new(b"ba"),
+            Password::new(b"ca"),
+            Password::new(b"ab"),
+        ];
+        assert!(expected
+            .into_iter()
+            .eq((0..4).map(|i| counter_to_plaintext(i, &ctx))));
+        assert_eq!(Password::new(b"aaa"), counter_to_plaintext(9, &ctx));
+        assert_eq!(9, plaintext_to_counter(Password::new(b"aaa"), &ctx));
+
+        assert!(!ctx.could_contain(&Password::new(b"a")));
+        assert!(ctx.could_contain(&Password::new(b"ab")));
+    }
+
+    #[test]
+    fn test_position_charsets() {
+        let mut ctx = build_ctx();
+        ctx.max_password_length = 2;
+        ctx.search_spaces = array_vec![0, 1, 3, 9];
+        ctx.charset = b"01ab".as_slice().try_into().unwrap();
+        ctx.charsets = Default::default();
+        ctx.charsets[0] = b"ab".as_slice().try_into().unwrap();
+        ctx.charsets[1] = b"012".as_slice().try_into().unwrap();
+
+        let plaintexts = (0..9).map(|i| counter_to_plaintext(i, &ctx));
+        let expected = [
+            Password::new(b""),
+            Password::new(b"a"),
+            Password::new(b"b"),
+            Password::new(b"a0"),
+            Password::new(b"b0"),
+            Password::new(b"a1"),
+            Password::new(b"b1"),
+            Password::new(b"a2"),
+            Password::new(b"b2"),
+        ];
+
+        assert!(expected.into_iter().eq(plaintexts));
+        assert!(expected
+            .into_iter()
+            .map(|plaintext| plaintext_to_counter(plaintext, &ctx))
+            .eq(0..9));
+
+        assert!(ctx.has_position_charsets());
+        assert!(ctx.could_contain(&Password::new(b"b2")));
+        assert!(!ctx.could_contain(&Password::new(b"2b")));
+    }
+
+    #[test]
+    fn test_could_contain() {
+        let mut ctx = build_ctx();
+        ctx.max_password_length = 3;
+
+        assert!(ctx.could_contain(&Password::new(b"")));
+        assert!(ctx.could_contain(&Password::new(b"cab")));
+        assert!(!ctx.could_contain(&Password::new(b"abca")));
+        assert!(!ctx.could_contain(&Password::new(b"abd")));
+    }
+
+    #[test]
+    fn test_utf8() {
+        let mut ctx = build_ctx();
+        ctx.max_password_length = 2;
+        ctx.search_spaces = array_vec![0, 1, 4, 13];
+        ctx.encoding = PasswordEncoding::Utf8;
+        ctx.code_points[..3].copy_from_slice(&['a' as u32, '\u{e9}' as u32, '\u{20ac}' as u32]);
+        ctx.charset = array_vec![0, 1, 2];
+        ctx.charsets = Default::default();
+        ctx.charsets[..2].fill(ctx.charset);
+
+        let expected = [
+            Password::new(b""),
+            Password::new(b"a"),
+            Password::new("\u{e9}".as_bytes()),
+            Password::new("\u{20ac}".as_bytes()),
+            Password::new(b"aa"),
+            Password::new("\u{e9}a".as_bytes()),
+            Password::new("\u{20ac}a".as_bytes()),
+            Password::new("a\u{e9}".as_bytes()),
+            Password::new("\u{e9}\u{e9}".as_bytes()),
+            Password::new("\u{20ac}\u{e9}".as_bytes()),
+            Password::new("a\u{20ac}".as_bytes()),
+            Password::new("\u{e9}\u{20ac}".as_bytes()),
+            Password::new("\u{20ac}\u{20ac}".as_bytes()),
+        ];
+
+        assert!(expected
+            .into_iter()
+            .eq((0..13).map(|i| counter_to_plaintext(i, &ctx))));
+        assert!(expected
+            .into_iter()
+            .map(|plaintext| plaintext_to_counter(plaintext, &ctx))
+            .eq(0..13));
+
+        assert!(ctx.could_contain(&Password::new("\u{20ac}a".as_bytes())));
+        assert!(!ctx.could_contain(&Password::new("\u{fc}".as_bytes())));
+        assert!(!ctx.could_contain(&Password::new(b"\xe9")));
+        assert!(!ctx.could_contain(&Password::new(b"aaa")));
+    }
+
+    #[test]
+    fn test_password_display() {
+        assert_eq!(
+            "gr\u{fc}n",
+            format!("{}", Password::new("gr\u{fc}n".as_bytes()))
+        );
+        assert_eq!("$HEX[6772fc6e]", format!("{}", Password::new(b"gr\xfcn")));
+        assert_eq!("$HEX[610a]", format!("{}", Password::new(b"a\n")));
+        assert_eq!("$HEX[]", format!("{}", Password::new(b"").hex()));
+        assert_eq!("$HEX[616263]", format!("{}", Password::new(b"abc").hex()));
+    }
+
+    #[test]
+    fn test_could_contain_digest() {
+        let ctx = build_ctx();
+
+        assert!(ctx.could_contain_digest(&[0; 16]));
+        assert!(!ctx.could_contain_digest(&[0; 20]));
+    }
 }
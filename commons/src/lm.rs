@@ -0,0 +1,60 @@
+use des::{
+    cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit},
+    Des,
+};
+
+/// The constant encrypted with each half of the password.
+const LM_MAGIC: &[u8; 8] = b"KGS!@#$%";
+
+/// The length of a half of an LM password.
+pub const LM_HALF_LENGTH: usize = 7;
+
+/// Spreads the 56 bits of a half of a password over the 8 bytes of a DES key.
+/// The parity bits are left to 0 since DES ignores them.
+#[inline]
+fn des_key(half: &[u8; LM_HALF_LENGTH]) -> [u8; 8] {
+    [
+        half[0] & 0xFE,
+        (half[0] << 7) | ((half[1] >> 1) & 0x7E),
+        (half[1] << 6) | ((half[2] >> 2) & 0x3E),
+        (half[2] << 5) | ((half[3] >> 3) & 0x1E),
+        (half[3] << 4) | ((half[4] >> 4) & 0x0E),
+        (half[4] << 3) | ((half[5] >> 5) & 0x06),
+        (half[5] << 2) | ((half[6] >> 6) & 0x02),
+        half[6] << 1,
+    ]
+}
+
+/// Hashes a half of a password using LM.
+/// LM uppercases the password and splits it into two halves of 7 characters, which are hashed separately.
+/// A table can only contain one half, so the characters after the 7th are ignored.
+#[inline]
+pub fn lm(password: &[u8]) -> [u8; 8] {
+    let mut half = [0; LM_HALF_LENGTH];
+    for (c, p) in half.iter_mut().zip(password) {
+        *c = p.to_ascii_uppercase();
+    }
+
+    let mut block = GenericArray::clone_from_slice(LM_MAGIC);
+    Des::new(&des_key(&half).into()).encrypt_block(&mut block);
+
+    block.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lm;
+
+    #[test]
+    fn test_lm() {
+        // the two halves of the LM hash of "password", e52cac67419a9a224a3b108f3fa6cb6d
+        assert_eq!(
+            [0xe5, 0x2c, 0xac, 0x67, 0x41, 0x9a, 0x9a, 0x22],
+            lm(b"passwor")
+        );
+        assert_eq!([0x4a, 0x3b, 0x10, 0x8f, 0x3f, 0xa6, 0xcb, 0x6d], lm(b"D"));
+
+        // the half of an empty password
+        assert_eq!([0xaa, 0xd3, 0xb4, 0x35, 0xb5, 0x14, 0x04, 0xee], lm(b""));
+    }
+}
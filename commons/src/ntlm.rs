@@ -2,11 +2,14 @@ use md4::{digest::generic_array::GenericArray, digest::OutputSizeUser, Digest as
 
 use tinyvec::ArrayVec;
 
-use crate::MAX_PASSWORD_LENGTH_ALLOWED;
+use crate::{MAX_PASSWORD_LENGTH_ALLOWED, MAX_SALT_LENGTH_ALLOWED};
 
-/// UTF-16LE encodes an ASCII password.
+/// UTF-16LE encodes a Latin-1 password, which may be salted.
+/// ASCII being a subset of Latin-1, ASCII passwords are encoded correctly too.
 #[inline]
-fn utf16_le(password: &[u8]) -> ArrayVec<[u8; MAX_PASSWORD_LENGTH_ALLOWED * 2]> {
+fn utf16_le(
+    password: &[u8],
+) -> ArrayVec<[u8; (MAX_PASSWORD_LENGTH_ALLOWED + MAX_SALT_LENGTH_ALLOWED) * 2]> {
     let mut buf = ArrayVec::new();
 
     for el in password {
@@ -17,15 +20,59 @@ fn utf16_le(password: &[u8]) -> ArrayVec<[u8; MAX_PASSWORD_LENGTH_ALLOWED * 2]>
     buf
 }
 
+/// UTF-16LE encodes a UTF-8 password, which may be salted.
+/// The bytes that are not valid UTF-8, for instance in the salt, are encoded as Latin-1 characters.
+#[inline]
+fn utf8_to_utf16_le(
+    mut password: &[u8],
+) -> ArrayVec<[u8; (MAX_PASSWORD_LENGTH_ALLOWED + MAX_SALT_LENGTH_ALLOWED) * 2]> {
+    let mut buf = ArrayVec::new();
+
+    while !password.is_empty() {
+        let (valid, rest) = match core::str::from_utf8(password) {
+            Ok(valid) => (valid, [].as_slice()),
+            Err(err) => {
+                let (valid, rest) = password.split_at(err.valid_up_to());
+                // SAFETY: The bytes up to `valid_up_to` are valid UTF-8.
+                (unsafe { core::str::from_utf8_unchecked(valid) }, rest)
+            }
+        };
+
+        for unit in valid.encode_utf16() {
+            buf.extend_from_slice(&unit.to_le_bytes());
+        }
+
+        password = match rest.split_first() {
+            Some((&invalid, rest)) => {
+                buf.push(invalid);
+                buf.push(0);
+                rest
+            }
+            None => rest,
+        };
+    }
+
+    buf
+}
+
 /// Hashes a password using NTLM.
 #[inline]
 pub fn ntlm(password: &[u8]) -> GenericArray<u8, <Md4 as OutputSizeUser>::OutputSize> {
     Md4::digest(utf16_le(password))
 }
 
+/// Hashes a password encoded in UTF-8 using NTLM.
+#[inline]
+pub fn ntlm_utf8(password: &[u8]) -> GenericArray<u8, <Md4 as OutputSizeUser>::OutputSize> {
+    Md4::digest(utf8_to_utf16_le(password))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{ntlm, Password};
+    use md4::{Digest, Md4};
+    use std::{string::String, vec::Vec};
+
+    use crate::{ntlm, ntlm_utf8, Password, MAX_PASSWORD_LENGTH_ALLOWED};
 
     #[test]
     fn test_ntlm() {
@@ -37,4 +84,37 @@ mod tests {
         let actual = ntlm(&password);
         assert_eq!(expected, actual.as_slice());
     }
+
+    #[test]
+    fn test_ntlm_reference() {
+        let charset = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!*-_";
+
+        for len in 0..=MAX_PASSWORD_LENGTH_ALLOWED {
+            let password = Password::new(&charset[len..len * 2]);
+
+            let utf16 = String::from_utf8(password.to_vec())
+                .unwrap()
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect::<Vec<_>>();
+            let expected = Md4::digest(utf16);
+
+            assert_eq!(expected, ntlm(&password));
+        }
+    }
+
+    #[test]
+    fn test_ntlm_non_ascii() {
+        let utf16 = "gr\u{fc}n\u{20ac}"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let expected = Md4::digest(utf16);
+
+        assert_eq!(expected, ntlm_utf8("gr\u{fc}n\u{20ac}".as_bytes()));
+
+        // Latin-1 passwords are hashed the same way as their UTF-8 counterparts
+        assert_eq!(ntlm(b"gr\xfcn"), ntlm_utf8("gr\u{fc}n".as_bytes()));
+        assert_eq!(ntlm(b"gr\xfcn"), ntlm_utf8(b"gr\xfcn"));
+    }
 }
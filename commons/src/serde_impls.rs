@@ -0,0 +1,152 @@
+//! Serde support for the types of this crate.
+
+use core::fmt;
+use std::string::String;
+
+use serde::{
+    de::{self, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::Password;
+
+/// The key used to store a password which is not valid UTF-8.
+const BASE64_KEY: &str = "base64";
+
+/// A password is serialized as a string if it is valid UTF-8,
+/// and as a `{ "base64": "..." }` map otherwise.
+impl Serialize for Password {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match core::str::from_utf8(self) {
+            Ok(password) => serializer.serialize_str(password),
+            Err(_) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(BASE64_KEY, &base64::encode(&self[..]))?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Password {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(PasswordVisitor)
+    }
+}
+
+struct PasswordVisitor;
+
+impl PasswordVisitor {
+    fn password<E: de::Error>(bytes: &[u8]) -> Result<Password, E> {
+        bytes
+            .try_into()
+            .map(Password)
+            .map_err(|_| E::invalid_length(bytes.len(), &PasswordVisitor))
+    }
+}
+
+impl<'de> Visitor<'de> for PasswordVisitor {
+    type Value = Password;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a password of at most {} bytes",
+            crate::MAX_PASSWORD_LENGTH_ALLOWED
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Self::password(v.as_bytes())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let (key, value) = map
+            .next_entry::<String, String>()?
+            .ok_or_else(|| de::Error::missing_field(BASE64_KEY))?;
+
+        if key != BASE64_KEY {
+            return Err(de::Error::unknown_field(&key, &[BASE64_KEY]));
+        }
+
+        let bytes = base64::decode(value).map_err(de::Error::custom)?;
+        Self::password(&bytes)
+    }
+}
+
+/// Serializes a digest as a hexadecimal string.
+/// Use it with `#[serde(with = "cugparck_commons::serde_digest")]`.
+pub mod serde_digest {
+    use std::string::String;
+
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::Digest;
+
+    pub fn serialize<S: Serializer>(digest: &Digest, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(digest))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Digest, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let bytes = hex::decode(hex).map_err(de::Error::custom)?;
+
+        bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| de::Error::invalid_length(bytes.len(), &"a digest of at most 64 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use crate::{Digest, HashType, Password};
+
+    #[test]
+    fn test_password_round_trip() {
+        let password = Password::new(b"p4ssw0rd");
+        let json = serde_json::to_value(password).unwrap();
+
+        assert_eq!(json!("p4ssw0rd"), json);
+        assert_eq!(password, serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn test_non_utf8_password_round_trip() {
+        let password = Password::new(&[0xff, 0x00, b'a']);
+        let json = serde_json::to_value(password).unwrap();
+
+        assert_eq!(json!({ "base64": "/wBh" }), json);
+        assert_eq!(password, serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn test_password_too_long() {
+        assert!(serde_json::from_value::<Password>(json!("01234567890")).is_err());
+    }
+
+    #[test]
+    fn test_digest_round_trip() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        #[serde(crate = "serde")]
+        struct Hash {
+            #[serde(with = "crate::serde_digest")]
+            digest: Digest,
+        }
+
+        let hash = Hash {
+            digest: HashType::Md5.hash_function()(b"abc"),
+        };
+        let json = serde_json::to_value(&hash).unwrap();
+
+        assert_eq!(
+            json!({ "digest": "900150983cd24fb0d6963f7d28e17f72" }),
+            json
+        );
+        assert_eq!(hash, serde_json::from_value(json).unwrap());
+    }
+}
@@ -0,0 +1,33 @@
+use std::mem;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use cugparck_commons::CompressedPassword;
+use cugparck_cpu::{backend::Cpu, RainbowTableCtxBuilder, SimpleTable};
+
+fn generation(c: &mut Criterion) {
+    let ctx = RainbowTableCtxBuilder::new()
+        .chain_length(1000)
+        .max_password_length(4)
+        .charset(b"abcdefghijklmnopqrstuvwxyz")
+        .build()
+        .unwrap();
+
+    // the throughput is reported in chains per second
+    let mut group = c.benchmark_group("generation");
+    group.throughput(Throughput::Elements(ctx.m0 as u64));
+    group.sample_size(10);
+    group.bench_function("cpu", |b| {
+        b.iter(|| SimpleTable::new_blocking::<Cpu>(ctx).unwrap())
+    });
+    group.finish();
+
+    // the chain map is the largest allocation, and is the largest right before being shrunk
+    let (_, stats) = SimpleTable::new_blocking::<Cpu>(ctx)
+        .unwrap()
+        .shrink_and_finalize();
+    let peak_memory = stats.capacity_before_shrink * mem::size_of::<[CompressedPassword; 2]>();
+    println!("generation/cpu: chain map peak memory is about {peak_memory} bytes");
+}
+
+criterion_group!(benches, generation);
+criterion_main!(benches);
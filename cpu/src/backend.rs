@@ -8,13 +8,39 @@ pub use crate::renderer::wgpu::{Dx11, Dx12, Metal, OpenGL, Vulkan};
 
 pub use crate::renderer::cpu::Cpu;
 
-use crate::{error::CugparckResult, renderer::Renderer};
+use crate::{
+    error::{CugparckError, CugparckResult},
+    renderer::Renderer,
+};
 
 /// A backend that can be used to generate rainbow tables.
 pub trait Backend {
+    /// The name of the backend.
+    const NAME: &'static str;
+
     /// The renderer that produces this backend.
     type Renderer: Renderer;
 
     /// Returns the renderer.
-    fn renderer(chains_len: usize) -> CugparckResult<Self::Renderer>;
+    /// `max_batch_size` limits the number of chains computed at once, on the renderers working with batches.
+    fn renderer(chains_len: usize, max_batch_size: Option<usize>)
+        -> CugparckResult<Self::Renderer>;
+
+    /// Returns the names of the devices the backend can use, numbered from 0.
+    /// The backends only able to use the default device have a single one.
+    fn devices() -> CugparckResult<Vec<String>> {
+        Ok(vec![Self::NAME.to_owned()])
+    }
+
+    /// Returns the renderer of a device, numbered like in `Backend::devices`.
+    fn device_renderer(
+        device: usize,
+        chains_len: usize,
+        max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self::Renderer> {
+        match device {
+            0 => Self::renderer(chains_len, max_batch_size),
+            _ => Err(CugparckError::NoDevice(device)),
+        }
+    }
 }
@@ -0,0 +1,114 @@
+//! A benchmark of the chains kernel of the backends.
+
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    expected_hash_count,
+    renderer::{BatchInformation, KernelHandle, Renderer, StagingHandleSync},
+};
+
+/// The result of a benchmark of a backend.
+#[derive(Clone, Copy, Debug)]
+pub struct BenchmarkResult {
+    /// The name of the backend.
+    pub backend: &'static str,
+    /// The number of chains computed.
+    pub chains: usize,
+    /// The number of columns computed for each chain.
+    pub columns: usize,
+    /// The time taken to compute the chains.
+    pub duration: Duration,
+}
+
+impl BenchmarkResult {
+    /// Returns the number of hashes and reductions computed per second.
+    pub fn hashes_per_second(&self) -> f64 {
+        (self.chains * self.columns) as f64 / self.duration.as_secs_f64()
+    }
+
+    /// Returns the number of bytes of midpoints read and written per second.
+    /// For the GPU backends, this is the bandwidth used between the host and the device.
+    pub fn memory_bandwidth(&self) -> f64 {
+        (self.chains * 2 * mem::size_of::<CompressedPassword>()) as f64
+            / self.duration.as_secs_f64()
+    }
+
+    /// Estimates the time needed by this backend to generate a table with the given context.
+    pub fn estimated_generation_time(&self, ctx: &RainbowTableCtx) -> Duration {
+        Duration::from_secs_f64(expected_hash_count(ctx) / self.hashes_per_second())
+    }
+}
+
+/// Benchmarks the chains kernel of a backend, by computing `columns` columns of `chains` chains without filtration.
+pub fn benchmark<T: Backend>(
+    ctx: RainbowTableCtx,
+    chains: usize,
+    columns: usize,
+) -> CugparckResult<BenchmarkResult> {
+    let mut midpoints = (0..chains)
+        .map(|i| CompressedPassword::from(i % ctx.n))
+        .collect::<Vec<_>>();
+
+    let mut renderer = T::renderer(chains, None).map_err(|err| CugparckError::NoBackend {
+        requested: T::NAME,
+        source: Box::new(err),
+    })?;
+    let mut batch_buf = Vec::new();
+    batch_buf.try_reserve_exact(renderer.max_staged_buffer_len(chains)?)?;
+
+    let start = Instant::now();
+    for batch_info in renderer.batch_iter(chains)? {
+        let batch = &mut midpoints[batch_info.range()];
+
+        if let KernelHandle::Staged(mut staging_handle) =
+            renderer.start_kernel(batch, &batch_info, 0..columns, ctx)?
+        {
+            staging_handle.sync(&mut batch_buf)?;
+        }
+    }
+
+    Ok(BenchmarkResult {
+        backend: T::NAME,
+        chains,
+        columns,
+        duration: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::Cpu, benchmark, RainbowTableCtxBuilder};
+
+    #[test]
+    fn test_benchmark() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(1000)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let result = benchmark::<Cpu>(ctx, 1000, 10).unwrap();
+        assert_eq!("cpu", result.backend);
+        assert!(result.hashes_per_second() > 0.);
+
+        // a longer chain length requires more hashes
+        let longer_ctx = RainbowTableCtxBuilder::new()
+            .chain_length(2000)
+            .startpoints(Some(ctx.m0))
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        assert!(
+            result.estimated_generation_time(&longer_ctx) > result.estimated_generation_time(&ctx)
+        );
+    }
+}
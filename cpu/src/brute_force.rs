@@ -0,0 +1,125 @@
+//! Brute force attacks, to find the passwords of search spaces too small to be worth a table.
+
+use std::collections::HashMap;
+
+use cugparck_commons::{reduce, CompressedPassword, Digest, Password, RainbowTableCtx};
+
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    event::{Progress, ProgressCounter, ProgressListener},
+    parallel::*,
+    renderer::continue_chains,
+};
+
+/// The number of passwords hashed at once by the backend.
+const BRUTE_FORCE_CHUNK_SIZE: usize = 1 << 22;
+
+/// Searches for the passwords of digests by hashing every password of the search space of a context on a backend.
+/// The charset, password lengths, hash function and salt of the context are used,
+/// and the passwords are enumerated with the same counters as the tables.
+///
+/// The chains kernel doesn't return the digests, so each password is hashed and reduced in a chain of a single column,
+/// and the passwords whose reduction matches the reduction of a digest are hashed again on the CPU to rule out the false alarms.
+/// Returns the password of each digest, or `None` if it isn't in the search space.
+pub fn brute_force<T: Backend>(
+    digests: &[Digest],
+    ctx: RainbowTableCtx,
+    progress: &dyn ProgressListener,
+) -> CugparckResult<Vec<Option<Password>>> {
+    let mut found = vec![None; digests.len()];
+
+    let mut targets = HashMap::<CompressedPassword, Vec<usize>>::new();
+    for (i, digest) in digests.iter().enumerate() {
+        if ctx.could_contain_digest(digest) {
+            targets.entry(reduce(*digest, 0, &ctx)).or_default().push(i);
+        }
+    }
+
+    if targets.is_empty() {
+        return Ok(found);
+    }
+
+    let chunk_size = BRUTE_FORCE_CHUNK_SIZE.min(ctx.n);
+    let mut renderer = T::renderer(chunk_size, None).map_err(|err| CugparckError::NoBackend {
+        requested: T::NAME,
+        source: Box::new(err),
+    })?;
+    let total = ctx.n;
+    let counter = ProgressCounter::new(progress, total, chunk_size, move |done| {
+        Progress::Passwords { done, total }
+    });
+
+    let mut midpoints = Vec::new();
+    midpoints.try_reserve_exact(chunk_size)?;
+
+    for start in (0..ctx.n).step_by(chunk_size) {
+        let end = ctx.n.min(start + chunk_size);
+        midpoints.clear();
+        midpoints.extend((start..end).map(CompressedPassword::from));
+
+        continue_chains(&mut renderer, &mut midpoints, 0..1, ctx)?;
+
+        let hits = midpoints
+            .par_iter()
+            .enumerate()
+            .filter_map(|(i, midpoint)| {
+                let digest_indexes = targets.get(midpoint)?;
+                let password = CompressedPassword::from(start + i).into_password(&ctx);
+                let digest = ctx.hash(&password);
+
+                Some(
+                    digest_indexes
+                        .iter()
+                        .filter(|&&j| digests[j] == digest)
+                        .map(|&j| (j, password))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+
+        for (j, password) in hits {
+            found[j].get_or_insert(password);
+        }
+        counter.add(end - start);
+
+        // all the digests are found, no need to hash the rest of the search space
+        if targets.values().flatten().all(|&j| found[j].is_some()) {
+            break;
+        }
+    }
+
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::{HashType, Password};
+
+    use crate::{backend::Cpu, brute_force, RainbowTableCtxBuilder};
+
+    #[test]
+    fn test_brute_force() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .hash(HashType::Sha1)
+            .charset(b"abc123")
+            .min_password_length(2)
+            .max_password_length(4)
+            .build()
+            .unwrap();
+
+        let passwords = [b"ab".as_slice(), b"c3a1", b"cab", b"abcd", b"a"];
+        let digests = passwords.map(|password| ctx.hash(&Password::new(password)));
+
+        let found = brute_force::<Cpu>(&digests, ctx, &()).unwrap();
+        let expected = [
+            Some(Password::new(b"ab")),
+            Some(Password::new(b"c3a1")),
+            Some(Password::new(b"cab")),
+            None,
+            None,
+        ];
+        assert_eq!(expected.as_slice(), found);
+    }
+}
@@ -1,17 +1,38 @@
-use std::{collections::TryReserveError, io};
+use std::{collections::TryReserveError, io, path::PathBuf};
 use thiserror::Error;
 
 pub type CugparckResult<T> = std::result::Result<T, CugparckError>;
 
 #[derive(Error, Debug)]
 pub enum CugparckError {
+    #[error("The {backend} backend computed wrong chains")]
+    BackendMismatch { backend: &'static str },
+
     #[cfg(feature = "wgpu")]
     #[error("An error occured inside of wgpu")]
     BufferAsync(#[from] wgpu::BufferAsyncError),
 
+    #[error("The generation was cancelled")]
+    Cancelled,
+
+    #[error("The chains can only be extended to a chain length greater than {0}")]
+    ChainLengthTooShort(usize),
+
+    #[error("The chain length should be at least {0}")]
+    ChainLength(usize),
+
+    #[error("The charset can contain at most {0} characters")]
+    CharsetTooLong(usize),
+
     #[error("Failed to validate the rainbow table. Is the file corrupted?")]
     Check,
 
+    #[error("A false alarm index can have at most {0} checkpoints")]
+    CheckpointCount(usize),
+
+    #[error("The checksum of the file doesn't match its content. Is the file corrupted?")]
+    Checksum,
+
     #[cfg(feature = "cuda")]
     #[error("A CUDA-related error occured")]
     Cuda(#[from] cust::error::CudaError),
@@ -21,18 +42,79 @@ pub enum CugparckError {
     )]
     Io(#[from] io::Error),
 
+    #[error("Several tables have the table number {0}")]
+    DuplicateTableNumber(usize),
+
+    #[error("A charset can't be empty")]
+    EmptyCharset,
+
+    #[error("The number of filtrations should be between 1 and {0}")]
+    FilterCount(usize),
+
+    #[error("Some startpoints of the table aren't startpoints of its context. Was it imported from another program?")]
+    ForeignStartpoints,
+
+    #[error("Table {tn} uses a different {parameter} than the other tables")]
+    IncompatibleTable { tn: usize, parameter: &'static str },
+
     #[error("Not enough memory available to start the computation. Try increasing the chain size")]
     IndexMapOutOfMemory,
 
+    #[error("The mask is invalid: {0}")]
+    InvalidMask(String),
+
+    #[error("The table name template is invalid: {0}")]
+    InvalidNameTemplate(String),
+
+    #[error("The shard is invalid: {0}")]
+    InvalidShard(String),
+
+    #[error("The RainbowCrack table is invalid: {0}")]
+    InvalidRcrackTable(&'static str),
+
+    #[error("The generation requires at least {0} bytes of host memory")]
+    MaxHostMemoryTooLow(usize),
+
+    #[error("The {requested} backend could not be initialized")]
+    NoBackend {
+        requested: &'static str,
+        #[source]
+        source: Box<CugparckError>,
+    },
+
+    #[error("The minimum password length can't be greater than the maximum password length")]
+    MinPasswordLength,
+
+    #[error("A false alarm index can't be created for a table whose startpoints are missing")]
+    MissingStartpoints,
+
+    #[error("There is no device {0} for this backend")]
+    NoDevice(usize),
+
     #[error("No suitable GPU found for the calcuation")]
     NoGpu,
 
     #[error("Not enough memory available to start the computation. Try increasing the chain size")]
     OutOfMemory(#[from] TryReserveError),
 
+    #[error("The maximum password length can be at most {0}")]
+    PasswordTooLong(usize),
+
+    #[error("The salt can contain at most {0} bytes")]
+    SaltTooLong(usize),
+
     #[error("Failed to serialize the rainbow table")]
     Serialize,
 
-    #[error("Cugparck only supports spaces up to 2^64, but the provided space is {0}")]
+    #[error("{} isn't a rainbow table file", .0.display())]
+    UnknownTableFormat(PathBuf),
+
+    #[error("The file was stored by an incompatible version of cugparck (format version {0}). It should be generated again")]
+    UnsupportedVersion(u32),
+
+    #[error("Cugparck only supports spaces up to 2^64, but the provided space is about 2^{0}. Use a smaller charset, a shorter maximum password length or a mask")]
     Space(u8),
+
+    #[error("The passwords can't be encoded in UTF-8 when a different charset is used for each position")]
+    Utf8PositionCharsets,
 }
@@ -1,8 +1,13 @@
-use std::{ops::Range, thread::JoinHandle};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
 
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
 
-use crate::{error::CugparckResult, SimpleTable};
+use crate::{error::CugparckResult, CugparckError, SimpleTable};
 
 /// An event to track the progress of the generation of a rainbow table.
 pub enum Event {
@@ -14,23 +19,448 @@ pub enum Event {
         batch_count: usize,
         columns: Range<usize>,
     },
+    /// Memory usage of the device generating the table.
+    /// `None` if the backend is unable to report it.
+    DeviceStats(Option<DeviceMemory>),
+    /// The speed of the generation, sent after each batch along with the progress.
+    Speed(GenerationSpeed),
+    /// The chains computed until the end of `columns` were filtered,
+    /// keeping `chains_left` unique chains out of `chains`.
+    Filtration {
+        columns: Range<usize>,
+        chains: usize,
+        chains_left: usize,
+    },
+    /// The generation is paused with `GenerationController::pause`.
+    Paused,
+    /// The generation is resumed with `GenerationController::resume`.
+    Resumed,
+    /// The generation was cancelled with `GenerationController::cancel`.
+    /// This is the last event sent.
+    Cancelled,
+}
+
+/// The speed of a generation, averaged since it started.
+/// The time spent paused is not taken into account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenerationSpeed {
+    /// The number of chains computed per second.
+    /// A chain is counted once for each filtration step it goes through.
+    pub chains_per_sec: f64,
+    /// The number of hashes computed per second.
+    pub hashes_per_sec: f64,
+    /// The estimated time until the generation is done.
+    /// `None` until enough work is done to estimate it.
+    pub eta: Option<Duration>,
+}
+
+/// Memory usage of a device, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceMemory {
+    pub used: usize,
+    pub total: usize,
+}
+
+impl DeviceMemory {
+    /// Returns the fraction of the device memory in use, between 0 and 1.
+    pub fn utilization(&self) -> f64 {
+        if self.total == 0 {
+            return 0.;
+        }
+
+        self.used.min(self.total) as f64 / self.total as f64
+    }
+}
+
+/// The progress of a long operation on existing tables, such as a conversion or an attack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// Chains processed by a conversion between two table formats.
+    /// A chain is counted once for each pass the conversion makes over the chains.
+    Chains { done: usize, total: usize },
+    /// Columns searched by an attack, for all the digests and tables of a cluster.
+    Columns { done: usize, total: usize },
+    /// Passwords hashed by a brute force attack.
+    Passwords { done: usize, total: usize },
+}
+
+/// Receives the progress of a conversion or an attack.
+/// The progress can be reported from several threads at once.
+pub trait ProgressListener: Sync {
+    /// Called regularly while the operation runs, and once when all the work is done.
+    fn progress(&self, progress: Progress);
+}
+
+/// Ignores the progress.
+impl ProgressListener for () {
+    fn progress(&self, _: Progress) {}
+}
+
+/// The number of chains processed between two progress reports.
+/// The step is much smaller in the tests, so that the small tables tested are reported several times.
+#[cfg(not(test))]
+const CHAINS_PROGRESS_STEP: usize = 1 << 14;
+#[cfg(test)]
+const CHAINS_PROGRESS_STEP: usize = 10;
+
+/// Counts the work done by an operation, possibly from several threads,
+/// and reports it to a listener each time a step is crossed.
+pub(crate) struct ProgressCounter<'a> {
+    listener: &'a dyn ProgressListener,
+    report: Box<dyn Fn(usize) -> Progress + Sync + 'a>,
+    done: AtomicUsize,
+    step: usize,
+    total: usize,
+}
+
+impl<'a> ProgressCounter<'a> {
+    /// Creates a counter reporting `report(done)` every `step` units of work.
+    pub(crate) fn new(
+        listener: &'a dyn ProgressListener,
+        total: usize,
+        step: usize,
+        report: impl Fn(usize) -> Progress + Sync + 'a,
+    ) -> Self {
+        Self {
+            listener,
+            report: Box::new(report),
+            done: AtomicUsize::new(0),
+            step: step.max(1),
+            total,
+        }
+    }
+
+    /// Creates a counter for the chains processed by a conversion.
+    pub(crate) fn chains(listener: &'a dyn ProgressListener, total: usize) -> Self {
+        Self::new(listener, total, CHAINS_PROGRESS_STEP, move |done| {
+            Progress::Chains { done, total }
+        })
+    }
+
+    /// Adds `count` units of work done.
+    pub(crate) fn add(&self, count: usize) {
+        let done = self.done.fetch_add(count, Ordering::Relaxed) + count;
+        if done / self.step != (done - count) / self.step || done == self.total {
+            self.listener.progress((self.report)(done));
+        }
+    }
+}
+
+/// A command sent to a generation.
+enum Control {
+    Pause,
+    Resume,
+    Cancel,
+}
+
+/// The generation side of a `SimpleTableHandle`, used to send the events and to receive the commands.
+pub(crate) struct GenerationChannel {
+    sender: Sender<Event>,
+    control: Receiver<Control>,
+}
+
+impl GenerationChannel {
+    /// Sends an event to the handle.
+    /// The events are dropped if the handle is not listening anymore.
+    pub(crate) fn send(&self, event: Event) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Handles the commands received since the last batch, and blocks while the generation is paused.
+    /// Returns `CugparckError::Cancelled` if the generation should be stopped.
+    pub(crate) fn wait_between_batches(&self) -> CugparckResult<()> {
+        let mut paused = false;
+
+        loop {
+            let control = if paused {
+                self.control.recv().ok()
+            } else {
+                match self.control.try_recv() {
+                    Ok(control) => Some(control),
+                    Err(TryRecvError::Empty) => return Ok(()),
+                    Err(TryRecvError::Disconnected) => None,
+                }
+            };
+
+            match control {
+                Some(Control::Pause) if !paused => {
+                    paused = true;
+                    self.send(Event::Paused);
+                }
+                Some(Control::Resume) if paused => {
+                    paused = false;
+                    self.send(Event::Resumed);
+                }
+                Some(Control::Pause | Control::Resume) => (),
+                // nobody can resume or join the generation once all the controllers are dropped
+                Some(Control::Cancel) | None => {
+                    self.send(Event::Cancelled);
+                    return Err(CugparckError::Cancelled);
+                }
+            }
+        }
+    }
+}
+
+/// Controls a generation from any thread.
+/// The commands are handled between two batches of chains.
+#[derive(Clone)]
+pub struct GenerationController {
+    control: Sender<Control>,
+}
+
+impl GenerationController {
+    /// Pauses the generation once the current batch is finished.
+    /// An `Event::Paused` is sent when the generation is paused.
+    pub fn pause(&self) {
+        let _ = self.control.send(Control::Pause);
+    }
+
+    /// Resumes a paused generation.
+    /// An `Event::Resumed` is sent when the generation is resumed.
+    pub fn resume(&self) {
+        let _ = self.control.send(Control::Resume);
+    }
+
+    /// Cancels the generation, even if it is paused.
+    /// The generation stops before computing the next batch of chains, and an `Event::Cancelled` is sent.
+    pub fn cancel(&self) {
+        let _ = self.control.send(Control::Cancel);
+    }
 }
 
+/// An handle to a rainbow table being generated in another thread.
+/// The generation is cancelled if the handle and its controllers are dropped before the table is finished.
 pub struct SimpleTableHandle {
-    pub(crate) thread_handle: JoinHandle<CugparckResult<SimpleTable>>,
-    pub(crate) receiver: Receiver<Event>,
+    thread_handle: JoinHandle<CugparckResult<SimpleTable>>,
+    receiver: Receiver<Event>,
+    controller: GenerationController,
 }
 
 impl SimpleTableHandle {
+    /// Runs a generation in a new thread.
+    pub(crate) fn spawn(
+        generation: impl FnOnce(GenerationChannel) -> CugparckResult<SimpleTable> + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = unbounded();
+        let (control_sender, control) = unbounded();
+
+        let channel = GenerationChannel { sender, control };
+        let thread_handle = thread::spawn(move || generation(channel));
+
+        Self {
+            thread_handle,
+            receiver,
+            controller: GenerationController {
+                control: control_sender,
+            },
+        }
+    }
+
     /// Returns the generated rainbow table.
     /// Blocks until the table is finished.
+    /// If the generation was cancelled, `CugparckError::Cancelled` is returned.
     pub fn join(self) -> CugparckResult<SimpleTable> {
         self.thread_handle.join().unwrap()
     }
 
+    /// Returns a controller to pause, resume or cancel the generation from another thread.
+    pub fn controller(&self) -> GenerationController {
+        self.controller.clone()
+    }
+
+    /// Pauses the generation, see `GenerationController::pause`.
+    pub fn pause(&self) {
+        self.controller.pause();
+    }
+
+    /// Resumes the generation, see `GenerationController::resume`.
+    pub fn resume(&self) {
+        self.controller.resume();
+    }
+
+    /// Cancels the generation, see `GenerationController::cancel`.
+    pub fn cancel(&self) {
+        self.controller.cancel();
+    }
+
     /// Blocks until an event is received.
     /// Returns `None` if the rainbow table is finished.
+    /// `None` is only returned once the generation thread is done, so all the events
+    /// are received when looping until then.
     pub fn recv(&self) -> Option<Event> {
         self.receiver.recv().ok()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use super::{DeviceMemory, Event, Progress, ProgressListener};
+    use crate::{
+        backend::Cpu, temp_path, CugparckError, FiltrationIterator, RainbowTable,
+        RainbowTableCtxBuilder, SimpleTable, TruncatedTable,
+    };
+
+    #[test]
+    fn test_device_memory_utilization() {
+        let stats = [
+            (DeviceMemory { used: 0, total: 8 }, 0.),
+            (DeviceMemory { used: 2, total: 8 }, 0.25),
+            (DeviceMemory { used: 8, total: 8 }, 1.),
+            (DeviceMemory { used: 9, total: 8 }, 1.),
+            (DeviceMemory { used: 1, total: 0 }, 0.),
+        ];
+
+        for (memory, utilization) in stats {
+            assert_eq!(utilization, memory.utilization());
+        }
+    }
+
+    #[test]
+    fn test_conversion_progress() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<Progress>>);
+
+        impl ProgressListener for Recorder {
+            fn progress(&self, progress: Progress) {
+                self.0.lock().unwrap().push(progress);
+            }
+        }
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let simple = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let path = temp_path("conversion_progress.rtt");
+        let recorder = Recorder::default();
+        TruncatedTable::store_streamed(
+            &simple,
+            &path,
+            &path.with_extension("spill"),
+            None,
+            &recorder,
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // the conversion goes over the chains three times
+        let total = simple.len() * 3;
+        let events = recorder.0.into_inner().unwrap();
+        assert!(events.len() > 1);
+        assert!(events.windows(2).all(|events| match events {
+            [Progress::Chains { done: a, .. }, Progress::Chains { done: b, .. }] => a < b,
+            _ => false,
+        }));
+        assert_eq!(
+            Some(&Progress::Chains { done: total, total }),
+            events.last()
+        );
+    }
+
+    #[test]
+    fn test_no_event_missed() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let handle = SimpleTable::new_nonblocking::<Cpu>(ctx).unwrap();
+
+        let mut batches = 0;
+        let mut filtrations = 0;
+        let mut last_progress = 0.;
+        let mut last_speed = None;
+        let mut last_event = None;
+        while let Some(event) = handle.recv() {
+            match event {
+                Event::Batch { .. } => batches += 1,
+                Event::Filtration { .. } => filtrations += 1,
+                Event::Progress(progress) => last_progress = progress,
+                Event::Speed(speed) => last_speed = Some(speed),
+                _ => (),
+            }
+            last_event = Some(event);
+        }
+
+        // the CPU renderer computes every filtration step in a single batch
+        assert_eq!(FiltrationIterator::new(ctx).count(), batches);
+        assert_eq!(batches, filtrations);
+        assert_eq!((ctx.t - 1) as f64 / ctx.t as f64 * 100., last_progress);
+
+        // nothing is left to compute after the last batch
+        let speed = last_speed.unwrap();
+        assert!(speed.hashes_per_sec > speed.chains_per_sec);
+        assert_eq!(Some(Duration::ZERO), speed.eta);
+
+        let table = handle.join().unwrap();
+        assert!(!table.is_empty());
+
+        // the last filtration gives the chains of the table
+        match last_event {
+            Some(Event::Filtration {
+                columns,
+                chains,
+                chains_left,
+            }) => {
+                assert_eq!(ctx.t - 1, columns.end);
+                assert!(chains >= chains_left);
+                assert_eq!(table.len(), chains_left);
+            }
+            _ => panic!("the last event should be the last filtration"),
+        }
+    }
+
+    #[test]
+    fn test_cancel() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(10_000)
+            .max_password_length(4)
+            .build()
+            .unwrap();
+
+        let handle = SimpleTable::new_nonblocking::<Cpu>(ctx).unwrap();
+        handle.cancel();
+
+        let mut last_event = None;
+        while let Some(event) = handle.recv() {
+            last_event = Some(event);
+        }
+
+        assert!(matches!(last_event, Some(Event::Cancelled)));
+        assert!(matches!(handle.join(), Err(CugparckError::Cancelled)));
+    }
+
+    #[test]
+    fn test_pause() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let handle = SimpleTable::new_nonblocking::<Cpu>(ctx).unwrap();
+        handle.pause();
+
+        let mut events = Vec::new();
+        while let Some(event) = handle.recv() {
+            if let Event::Paused = event {
+                handle.resume();
+            }
+            events.push(event);
+        }
+
+        // the generation is paused before the first batch
+        assert!(matches!(events[..2], [Event::Paused, Event::Resumed]));
+        assert!(matches!(events.last(), Some(Event::Speed(_))));
+        assert!(!handle.join().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+use crate::parallel::*;
+use bitvec::prelude::*;
+use bytecheck::CheckBytes;
+use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+use rkyv::{Archive, Deserialize, Infallible, Serialize};
+
+use crate::{
+    error::{CugparckError, CugparckResult},
+    rainbow_table::stream::StreamBits,
+    RainbowTable, RainbowTableStorage,
+};
+
+/// The maximum number of checkpoints of a false alarm index.
+pub const MAX_CHECKPOINT_COUNT: usize = 8;
+
+/// The number of endpoints sampled to check that an index was created from a given table.
+const SAMPLE_COUNT: usize = 16;
+
+/// Something that can tell if a matching endpoint is a false alarm, before the chain is regenerated.
+pub trait FalseAlarmFilter: Sync {
+    /// Returns the number of checkpoints.
+    fn checkpoint_count(&self) -> usize;
+
+    /// Returns the checkpoint bits of the chain at this position of the table.
+    fn checkpoint_bits(&self, position: usize) -> Option<u8>;
+}
+
+/// A filter that never detects false alarms.
+impl FalseAlarmFilter for () {
+    fn checkpoint_count(&self) -> usize {
+        0
+    }
+
+    fn checkpoint_bits(&self, _: usize) -> Option<u8> {
+        None
+    }
+}
+
+/// Returns the columns of the checkpoints, spread evenly over the chains.
+pub(crate) fn checkpoint_columns(t: usize, checkpoint_count: usize) -> impl Iterator<Item = usize> {
+    (1..=checkpoint_count).map(move |k| k * (t - 1) / (checkpoint_count + 1))
+}
+
+/// Returns the positions of the chains whose endpoints are sampled, spread evenly over the table.
+fn sample_positions(chain_count: usize) -> impl Iterator<Item = usize> {
+    let sample_count = SAMPLE_COUNT.min(chain_count);
+    (0..sample_count).map(move |i| i * chain_count / sample_count)
+}
+
+/// Returns the checkpoint bits of the chain at this position, stored one chain after the other.
+#[inline]
+fn chain_bits<S: BitStore>(
+    bits: &BitSlice<S>,
+    checkpoint_count: usize,
+    position: usize,
+) -> Option<u8> {
+    let bits = bits.get(position * checkpoint_count..(position + 1) * checkpoint_count)?;
+    Some(
+        bits.iter()
+            .by_vals()
+            .enumerate()
+            .fold(0, |acc, (k, bit)| acc | (bit as u8) << k),
+    )
+}
+
+/// An index storing, for each chain of a table, the parity of the passwords at a few checkpoint columns.
+/// When searching a table, a matching endpoint whose checkpoint bits differ from the ones computed
+/// from the digest is a false alarm, so the chain doesn't have to be regenerated.
+/// It is described in "Rainbow Tables: Time-Memory Trade-Offs Optimized" (Avoine, Junod, Oechslin).
+///
+/// The bits are packed in the order of the chains of the table, so that they are found from
+/// the position of a chain without storing its endpoint. An index is thus only valid for the table
+/// it was created from, and not for the same chains stored in another kind of table.
+#[derive(Archive, Deserialize, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct FalseAlarmIndex {
+    /// The checkpoint bits of the chains, `checkpoint_count` bits per chain.
+    #[with(StreamBits)]
+    bits: BitVec,
+    /// The number of checkpoints.
+    checkpoint_count: usize,
+    /// The number of chains of the indexed table.
+    chain_count: usize,
+    /// A few endpoints of the indexed table, at the positions given by `sample_positions`.
+    samples: Vec<CompressedPassword>,
+    /// The context of the indexed table.
+    ctx: RainbowTableCtx,
+}
+
+impl FalseAlarmIndex {
+    /// Creates the false alarm index of a table.
+    /// Each chain is computed again, so this takes about as long as generating the table.
+    /// The table should store its startpoints, so that all its chains can be computed.
+    pub fn new<T: RainbowTable>(table: &T, checkpoint_count: usize) -> CugparckResult<Self> {
+        if checkpoint_count > MAX_CHECKPOINT_COUNT {
+            return Err(CugparckError::CheckpointCount(MAX_CHECKPOINT_COUNT));
+        }
+
+        let ctx = table.ctx();
+        let columns = checkpoint_columns(ctx.t, checkpoint_count).collect::<Vec<_>>();
+
+        // the position of a chain is its position in the iterator,
+        // which skips the chains whose startpoints can't be recovered
+        let chains = table.iter().collect::<Vec<_>>();
+        if chains.len() != table.len() {
+            return Err(CugparckError::MissingStartpoints);
+        }
+
+        let chain_bits = chains
+            .par_iter()
+            .map(|chain| {
+                let mut password = chain.startpoint;
+                let mut current_column = 0;
+                let mut bits = 0u8;
+
+                for (k, &column) in columns.iter().enumerate() {
+                    password.continue_chain(current_column..column, &ctx);
+                    current_column = column;
+                    bits |= ((password.get() & 1) as u8) << k;
+                }
+
+                bits
+            })
+            .collect::<Vec<_>>();
+
+        let mut bits = BitVec::with_capacity(chains.len() * checkpoint_count);
+        for chain_bits in chain_bits {
+            bits.extend_from_bitslice(&chain_bits.view_bits::<Lsb0>()[..checkpoint_count]);
+        }
+
+        Ok(Self {
+            bits,
+            checkpoint_count,
+            chain_count: chains.len(),
+            samples: sample_positions(chains.len())
+                .map(|i| chains[i].endpoint)
+                .collect(),
+            ctx,
+        })
+    }
+
+    /// Returns the context of the indexed table.
+    pub fn ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    /// Returns true if the index was created from this table.
+    /// The context and the number of chains are compared, and the sampled endpoints should be found
+    /// at the same positions in the table, so an index isn't used with a table whose chains were reordered.
+    pub fn matches<T: RainbowTable>(&self, table: &T) -> bool {
+        self.ctx == table.ctx()
+            && self.chain_count == table.len()
+            && samples_match(table, self.samples.iter().copied())
+    }
+}
+
+impl ArchivedFalseAlarmIndex {
+    /// Returns the context of the indexed table.
+    pub fn ctx(&self) -> RainbowTableCtx {
+        self.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    /// Returns true if the index was created from this table, see `FalseAlarmIndex::matches`.
+    pub fn matches<T: RainbowTable>(&self, table: &T) -> bool {
+        self.ctx() == table.ctx()
+            && self.chain_count as usize == table.len()
+            && samples_match(table, self.samples.iter().map(|&sample| sample.into()))
+    }
+}
+
+/// Returns true if the chains ending with the sampled endpoints are at the sampled positions of the table.
+fn samples_match<T: RainbowTable>(
+    table: &T,
+    samples: impl Iterator<Item = CompressedPassword>,
+) -> bool {
+    sample_positions(table.len())
+        .zip(samples)
+        .all(|(i, endpoint)| table.search_chain_position(endpoint) == Some(i))
+}
+
+impl FalseAlarmFilter for FalseAlarmIndex {
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoint_count
+    }
+
+    fn checkpoint_bits(&self, position: usize) -> Option<u8> {
+        chain_bits(&self.bits, self.checkpoint_count, position)
+    }
+}
+
+impl FalseAlarmFilter for ArchivedFalseAlarmIndex {
+    fn checkpoint_count(&self) -> usize {
+        self.checkpoint_count as usize
+    }
+
+    fn checkpoint_bits(&self, position: usize) -> Option<u8> {
+        chain_bits(&self.bits[..], self.checkpoint_count as usize, position)
+    }
+}
+
+impl RainbowTableStorage for FalseAlarmIndex {
+    fn header_ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx {
+        archived.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn header_chain_count(&self) -> usize {
+        self.chain_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cugparck_commons::CompressedPassword;
+
+    use crate::{
+        backend::Cpu, temp_path, CompressedTable, CugparckError, FalseAlarmFilter, FalseAlarmIndex,
+        RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage, SimpleTable, TableCluster,
+        MAX_CHECKPOINT_COUNT,
+    };
+
+    #[test]
+    fn test_false_alarm_index() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let index = FalseAlarmIndex::new(&table, 8).unwrap();
+        assert!(index.matches(&table));
+        let tables = [&table];
+        let cluster = TableCluster::new(&tables).with_false_alarm_filters(vec![&index]);
+
+        for i in 0..ctx.n {
+            let password = CompressedPassword::from(i).into_password(&ctx);
+            let digest = ctx.hash(&password);
+
+            // the index only discards false alarms, so the same passwords are found
+            assert_eq!(table.search(digest), cluster.search(digest));
+        }
+
+        let digests = (0..ctx.n)
+            .map(|i| ctx.hash(&CompressedPassword::from(i).into_password(&ctx)))
+            .collect::<Vec<_>>();
+        let (_, unfiltered) = TableCluster::new(&tables).search_batch_stats(&digests);
+        let (_, filtered) = cluster.search_batch_stats(&digests);
+
+        // the digests are searched until they are found, so at most every column is searched
+        for stats in [unfiltered[0], filtered[0]] {
+            assert!(stats.columns > 0);
+            assert!(stats.columns <= digests.len() * (ctx.t - 1));
+            assert!(stats.false_alarms >= stats.filtered_false_alarms);
+        }
+        assert!(unfiltered[0].false_alarms > 0);
+        assert_eq!(0, unfiltered[0].filtered_false_alarms);
+        assert!(filtered[0].filtered_false_alarms > 0);
+
+        let (password, stats) = table.search_with_stats(digests[0]);
+        assert_eq!(table.search(digests[0]), password);
+        assert!(stats.columns > 0);
+    }
+
+    #[test]
+    fn test_archived_false_alarm_index() {
+        let path = temp_path("fai");
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let index = FalseAlarmIndex::new(&table, 4).unwrap();
+        index.store(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let mut aligned = rkyv::AlignedVec::new();
+        aligned.extend_from_slice(&bytes);
+        let archived = FalseAlarmIndex::load(&aligned).unwrap();
+
+        assert!(archived.ctx() == ctx);
+        assert_eq!(4, archived.checkpoint_count());
+        assert!(archived.matches(&table));
+        for i in 0..table.len() {
+            assert!(index.checkpoint_bits(i).is_some());
+            assert_eq!(index.checkpoint_bits(i), archived.checkpoint_bits(i));
+        }
+        assert_eq!(None, archived.checkpoint_bits(table.len()));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_false_alarm_index() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let compressed = CompressedTable::from_rainbow_table(&table);
+        let index = FalseAlarmIndex::new(&compressed, 8).unwrap();
+
+        // the chains of a compressed table are sorted by endpoint, so they aren't at the same positions
+        assert!(index.matches(&compressed));
+        assert!(!index.matches(&table));
+        assert!(!FalseAlarmIndex::new(&table, 8)
+            .unwrap()
+            .matches(&compressed));
+
+        let tables = [&compressed];
+        let cluster = TableCluster::new(&tables).with_false_alarm_filters(vec![&index]);
+        let digests = (0..ctx.n)
+            .map(|i| ctx.hash(&CompressedPassword::from(i).into_password(&ctx)))
+            .collect::<Vec<_>>();
+
+        let (found, stats) = cluster.search_batch_stats(&digests);
+        let unfiltered = TableCluster::new(&tables).search_batch(&digests);
+        assert_eq!(
+            unfiltered,
+            found
+                .into_iter()
+                .map(|hit| hit.map(|hit| hit.password))
+                .collect::<Vec<_>>()
+        );
+        assert!(stats[0].filtered_false_alarms > 0);
+    }
+
+    #[test]
+    fn test_false_alarm_index_checkpoint_count() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        assert!(matches!(
+            FalseAlarmIndex::new(&table, MAX_CHECKPOINT_COUNT + 1),
+            Err(CugparckError::CheckpointCount(MAX_CHECKPOINT_COUNT))
+        ));
+
+        // an index without checkpoints never filters anything
+        let index = FalseAlarmIndex::new(&table, 0).unwrap();
+        assert_eq!(0, index.checkpoint_count());
+        assert!(index.matches(&table));
+    }
+}
@@ -0,0 +1,680 @@
+//! The header stored at the beginning of the files written by `RainbowTableStorage::store`.
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    time::Duration,
+};
+
+use cugparck_commons::{
+    HashType, PasswordEncoding, RainbowTableCtx, SaltPosition, StartpointOrder,
+    MAX_CHARSET_LENGTH_ALLOWED, MAX_PASSWORD_LENGTH_ALLOWED,
+};
+
+use crate::{
+    error::{CugparckError, CugparckResult},
+    RainbowTableCtxBuilder, Shard,
+};
+
+/// The magic number identifying the files written by cugparck.
+pub const MAGIC: &[u8; 8] = b"CUGPARCK";
+
+/// The version of the file format.
+/// It is incremented once per release changing the layout of the header or of a stored structure,
+/// so that the files stored by the previous release are rejected instead of being misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The size of the header, in bytes.
+/// It is a multiple of 16 so that the archived data that follows stays aligned.
+pub const HEADER_SIZE: usize = 4672;
+
+// The offsets of the fields in the header.
+// The integers are stored in little endian, and the strings and charsets are preceded by their length on one byte.
+const VERSION_OFFSET: usize = 8;
+const CHECKSUM_OFFSET: usize = 12;
+const PAYLOAD_LEN_OFFSET: usize = 16;
+const HASH_TYPE_OFFSET: usize = 24;
+const CHAIN_LENGTH_OFFSET: usize = 32;
+const TABLE_NUMBER_OFFSET: usize = 40;
+const STARTPOINTS_OFFSET: usize = 48;
+const MAX_PASSWORD_LENGTH_OFFSET: usize = 56;
+const CHARSET_OFFSET: usize = 57;
+const CHAIN_COUNT_OFFSET: usize = 184;
+const HAS_POSITION_CHARSETS_OFFSET: usize = 192;
+const POSITION_CHARSET_LENGTHS_OFFSET: usize = 193;
+const SALT_POSITION_OFFSET: usize = 1464;
+const SALT_OFFSET: usize = 1465;
+const MIN_PASSWORD_LENGTH_OFFSET: usize = 1528;
+const ENCODING_OFFSET: usize = 1529;
+const SHARD_INDEX_OFFSET: usize = 1530;
+const SHARD_COUNT_OFFSET: usize = 1532;
+const FILTER_COUNT_OFFSET: usize = 1534;
+const CODE_POINTS_OFFSET: usize = 1536;
+const HAS_STATS_OFFSET: usize = 2048;
+const BACKEND_OFFSET: usize = 2049;
+const CUGPARCK_VERSION_OFFSET: usize = 2066;
+const UNIQUE_CHAINS_OFFSET: usize = 2104;
+const DURATION_OFFSET: usize = 2112;
+const CURVE_LENGTH_OFFSET: usize = 2120;
+const CURVE_OFFSET: usize = 2128;
+const HAS_STARTPOINT_SEED_OFFSET: usize = 2640;
+const STARTPOINT_SEED_OFFSET: usize = 2648;
+/// The charsets of all the positions are stored one after the other,
+/// with room for `MAX_PASSWORD_LENGTH_ALLOWED` charsets of `MAX_CHARSET_LENGTH_ALLOWED` bytes.
+const POSITION_CHARSETS_OFFSET: usize = 2656;
+
+/// The maximum number of samples of the filtration curve stored in the header.
+/// Longer curves are downsampled when they are stored.
+pub const MAX_FILTRATION_SAMPLES: usize = 32;
+
+/// The maximum length of the name of the backend stored in the header, in bytes.
+const MAX_BACKEND_LENGTH: usize = 16;
+
+/// The maximum length of the version of cugparck stored in the header, in bytes.
+const MAX_VERSION_LENGTH: usize = 32;
+
+/// Writes `data` in the header at the given offset.
+fn write_at(bytes: &mut [u8], offset: usize, data: &[u8]) {
+    bytes[offset..offset + data.len()].copy_from_slice(data);
+}
+
+/// Statistics about the generation of a table, stored in the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableStats {
+    /// The number of unique chains at the end of the generation (m_t).
+    pub unique_chains: u64,
+    /// The number of unique chains after each filtration, as (column, chains) pairs.
+    pub filtration_curve: Vec<(u64, u64)>,
+    /// The duration of the generation, without the pauses.
+    pub duration: Duration,
+    /// The name of the backend used for the generation.
+    pub backend: String,
+    /// The version of cugparck used for the generation.
+    pub cugparck_version: String,
+}
+
+impl TableStats {
+    /// Returns at most `MAX_FILTRATION_SAMPLES` evenly spaced samples of the filtration curve,
+    /// always keeping the first and the last one.
+    fn sampled_curve(&self) -> Vec<(u64, u64)> {
+        let curve = &self.filtration_curve;
+        if curve.len() <= MAX_FILTRATION_SAMPLES {
+            return curve.clone();
+        }
+
+        (0..MAX_FILTRATION_SAMPLES)
+            .map(|i| curve[i * (curve.len() - 1) / (MAX_FILTRATION_SAMPLES - 1)])
+            .collect()
+    }
+}
+
+/// The header of a stored file.
+/// It identifies the file, describes the context of the table it contains and
+/// allows to check the integrity of the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableHeader {
+    /// The version of the file format.
+    pub version: u32,
+    /// The CRC32 of the data following the header.
+    pub checksum: u32,
+    /// The length of the data following the header, in bytes.
+    pub payload_len: u64,
+    /// The number of chains of the stored table.
+    pub chain_count: u64,
+    /// The context of the stored table.
+    pub ctx: RainbowTableCtx,
+    /// The shard of the table, if it is only a part of a table.
+    pub shard: Option<Shard>,
+    /// Statistics about the generation of the table, if they are known.
+    /// Tables converted or merged from other tables don't have any.
+    pub stats: Option<TableStats>,
+}
+
+impl TableHeader {
+    /// Serializes the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        let ctx = &self.ctx;
+
+        write_at(&mut bytes, 0, MAGIC);
+        write_at(&mut bytes, VERSION_OFFSET, &self.version.to_le_bytes());
+        write_at(&mut bytes, CHECKSUM_OFFSET, &self.checksum.to_le_bytes());
+        write_at(
+            &mut bytes,
+            PAYLOAD_LEN_OFFSET,
+            &self.payload_len.to_le_bytes(),
+        );
+        write_at(
+            &mut bytes,
+            HASH_TYPE_OFFSET,
+            &(ctx.hash_type as u64).to_le_bytes(),
+        );
+        write_at(
+            &mut bytes,
+            CHAIN_LENGTH_OFFSET,
+            &(ctx.t as u64).to_le_bytes(),
+        );
+        write_at(
+            &mut bytes,
+            TABLE_NUMBER_OFFSET,
+            &(ctx.tn as u64).to_le_bytes(),
+        );
+        write_at(
+            &mut bytes,
+            STARTPOINTS_OFFSET,
+            &(ctx.m0 as u64).to_le_bytes(),
+        );
+        bytes[MAX_PASSWORD_LENGTH_OFFSET] = ctx.max_password_length as u8;
+        bytes[CHARSET_OFFSET] = ctx.charset.len() as u8;
+        write_at(&mut bytes, CHARSET_OFFSET + 1, &ctx.charset);
+        write_at(
+            &mut bytes,
+            CHAIN_COUNT_OFFSET,
+            &self.chain_count.to_le_bytes(),
+        );
+
+        bytes[SALT_POSITION_OFFSET] = ctx.salt_position as u8;
+        bytes[SALT_OFFSET] = ctx.salt.len() as u8;
+        write_at(&mut bytes, SALT_OFFSET + 1, &ctx.salt);
+        bytes[MIN_PASSWORD_LENGTH_OFFSET] = ctx.min_password_length as u8;
+        bytes[ENCODING_OFFSET] = ctx.encoding as u8;
+
+        // a shard count of 0 means the table is complete
+        if let Some(shard) = self.shard {
+            write_at(&mut bytes, SHARD_INDEX_OFFSET, &shard.index().to_le_bytes());
+            write_at(&mut bytes, SHARD_COUNT_OFFSET, &shard.count().to_le_bytes());
+        }
+        write_at(
+            &mut bytes,
+            FILTER_COUNT_OFFSET,
+            &(ctx.filter_count as u16).to_le_bytes(),
+        );
+
+        if ctx.encoding == PasswordEncoding::Utf8 {
+            for (i, code_point) in ctx.code_points[..ctx.charset.len()].iter().enumerate() {
+                write_at(
+                    &mut bytes,
+                    CODE_POINTS_OFFSET + i * 4,
+                    &code_point.to_le_bytes(),
+                );
+            }
+        }
+
+        if ctx.startpoint_order == StartpointOrder::Seeded {
+            bytes[HAS_STARTPOINT_SEED_OFFSET] = 1;
+            write_at(
+                &mut bytes,
+                STARTPOINT_SEED_OFFSET,
+                &ctx.startpoint_seed.to_le_bytes(),
+            );
+        }
+
+        if let Some(stats) = &self.stats {
+            let backend = &stats.backend.as_bytes()[..stats.backend.len().min(MAX_BACKEND_LENGTH)];
+            let version = &stats.cugparck_version.as_bytes()
+                [..stats.cugparck_version.len().min(MAX_VERSION_LENGTH)];
+            let curve = stats.sampled_curve();
+
+            bytes[HAS_STATS_OFFSET] = 1;
+            bytes[BACKEND_OFFSET] = backend.len() as u8;
+            write_at(&mut bytes, BACKEND_OFFSET + 1, backend);
+            bytes[CUGPARCK_VERSION_OFFSET] = version.len() as u8;
+            write_at(&mut bytes, CUGPARCK_VERSION_OFFSET + 1, version);
+            write_at(
+                &mut bytes,
+                UNIQUE_CHAINS_OFFSET,
+                &stats.unique_chains.to_le_bytes(),
+            );
+            write_at(
+                &mut bytes,
+                DURATION_OFFSET,
+                &(stats.duration.as_nanos() as u64).to_le_bytes(),
+            );
+            write_at(
+                &mut bytes,
+                CURVE_LENGTH_OFFSET,
+                &(curve.len() as u16).to_le_bytes(),
+            );
+            for (i, (column, chains)) in curve.into_iter().enumerate() {
+                write_at(&mut bytes, CURVE_OFFSET + i * 16, &column.to_le_bytes());
+                write_at(&mut bytes, CURVE_OFFSET + i * 16 + 8, &chains.to_le_bytes());
+            }
+        }
+
+        if ctx.has_position_charsets() {
+            bytes[HAS_POSITION_CHARSETS_OFFSET] = 1;
+            let mut offset = POSITION_CHARSETS_OFFSET;
+            for (i, charset) in ctx.charsets[..ctx.max_password_length].iter().enumerate() {
+                bytes[POSITION_CHARSET_LENGTHS_OFFSET + i] = charset.len() as u8;
+                write_at(&mut bytes, offset, charset);
+                offset += charset.len();
+            }
+        }
+
+        bytes
+    }
+
+    /// Parses the header at the beginning of a file.
+    /// Returns `None` if the file doesn't start with a header, which is the case of the files stored
+    /// by older versions of cugparck. Such files can't be loaded anymore.
+    pub fn from_bytes(bytes: &[u8]) -> CugparckResult<Option<Self>> {
+        if !bytes.starts_with(MAGIC) {
+            return Ok(None);
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes(bytes[i..i + 2].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        if bytes.len() < CHECKSUM_OFFSET {
+            return Err(CugparckError::Check);
+        }
+
+        // the archived data depends on the layout of the structures, so other versions can't be loaded.
+        let version = u32_at(VERSION_OFFSET);
+        if version != FORMAT_VERSION {
+            return Err(CugparckError::UnsupportedVersion(version));
+        }
+
+        if bytes.len() < HEADER_SIZE {
+            return Err(CugparckError::Check);
+        }
+
+        let hash_type = *HashType::ALL
+            .get(u64_at(HASH_TYPE_OFFSET) as usize)
+            .ok_or(CugparckError::Check)?;
+        let charset_len = bytes[CHARSET_OFFSET] as usize;
+        let charset = bytes
+            .get(CHARSET_OFFSET + 1..CHARSET_OFFSET + 1 + charset_len)
+            .ok_or(CugparckError::Check)?;
+
+        let mut ctx_builder = RainbowTableCtxBuilder::new()
+            .hash(hash_type)
+            .chain_length(u64_at(CHAIN_LENGTH_OFFSET) as usize)
+            .table_number(u64_at(TABLE_NUMBER_OFFSET) as u8)
+            .startpoints(Some(u64_at(STARTPOINTS_OFFSET) as usize))
+            .min_password_length(bytes[MIN_PASSWORD_LENGTH_OFFSET])
+            .max_password_length(bytes[MAX_PASSWORD_LENGTH_OFFSET])
+            .charset(charset)
+            .filter_count(u16_at(FILTER_COUNT_OFFSET) as usize)
+            .startpoint_seed(match bytes[HAS_STARTPOINT_SEED_OFFSET] {
+                0 => None,
+                1 => Some(u64_at(STARTPOINT_SEED_OFFSET)),
+                _ => return Err(CugparckError::Check),
+            });
+
+        let salt_position = match bytes[SALT_POSITION_OFFSET] {
+            0 => SaltPosition::Prefix,
+            1 => SaltPosition::Suffix,
+            _ => return Err(CugparckError::Check),
+        };
+        let salt = bytes
+            .get(SALT_OFFSET + 1..SALT_OFFSET + 1 + bytes[SALT_OFFSET] as usize)
+            .ok_or(CugparckError::Check)?;
+        ctx_builder = ctx_builder.salt(salt, salt_position);
+
+        match bytes[ENCODING_OFFSET] {
+            0 => (),
+            1 if charset_len <= MAX_CHARSET_LENGTH_ALLOWED => {
+                // the charset of the UTF-8 mode contains the indexes of its code points
+                let charset = (0..charset_len)
+                    .map(|i| {
+                        char::from_u32(u32_at(CODE_POINTS_OFFSET + i * 4))
+                            .ok_or(CugparckError::Check)
+                    })
+                    .collect::<CugparckResult<String>>()?;
+                ctx_builder = ctx_builder.utf8_charset(&charset);
+            }
+            _ => return Err(CugparckError::Check),
+        }
+
+        if bytes[HAS_POSITION_CHARSETS_OFFSET] == 1 {
+            let mut charsets = Vec::new();
+            let mut offset = POSITION_CHARSETS_OFFSET;
+            let max_len = bytes[MAX_PASSWORD_LENGTH_OFFSET] as usize;
+            for i in 0..max_len.min(MAX_PASSWORD_LENGTH_ALLOWED) {
+                let len = bytes[POSITION_CHARSET_LENGTHS_OFFSET + i] as usize;
+                charsets.push(
+                    bytes
+                        .get(offset..offset + len)
+                        .ok_or(CugparckError::Check)?,
+                );
+                offset += len;
+            }
+            ctx_builder = ctx_builder.position_charsets(&charsets);
+        }
+
+        let ctx = ctx_builder.build().map_err(|_| CugparckError::Check)?;
+
+        let shard = match u16_at(SHARD_COUNT_OFFSET) {
+            0 => None,
+            count => Some(
+                Shard::new(u16_at(SHARD_INDEX_OFFSET), count).map_err(|_| CugparckError::Check)?,
+            ),
+        };
+
+        let stats = match bytes[HAS_STATS_OFFSET] {
+            0 => None,
+            1 => {
+                let string_at = |i: usize, max_len: usize| {
+                    let len = (bytes[i] as usize).min(max_len);
+                    String::from_utf8_lossy(&bytes[i + 1..i + 1 + len]).into_owned()
+                };
+                let samples = (u16_at(CURVE_LENGTH_OFFSET) as usize).min(MAX_FILTRATION_SAMPLES);
+
+                Some(TableStats {
+                    unique_chains: u64_at(UNIQUE_CHAINS_OFFSET),
+                    filtration_curve: (0..samples)
+                        .map(|i| {
+                            (
+                                u64_at(CURVE_OFFSET + i * 16),
+                                u64_at(CURVE_OFFSET + i * 16 + 8),
+                            )
+                        })
+                        .collect(),
+                    duration: Duration::from_nanos(u64_at(DURATION_OFFSET)),
+                    backend: string_at(BACKEND_OFFSET, MAX_BACKEND_LENGTH),
+                    cugparck_version: string_at(CUGPARCK_VERSION_OFFSET, MAX_VERSION_LENGTH),
+                })
+            }
+            _ => return Err(CugparckError::Check),
+        };
+
+        Ok(Some(Self {
+            version,
+            checksum: u32_at(CHECKSUM_OFFSET),
+            payload_len: u64_at(PAYLOAD_LEN_OFFSET),
+            chain_count: u64_at(CHAIN_COUNT_OFFSET),
+            ctx,
+            shard,
+            stats,
+        }))
+    }
+
+    /// Reads the header of a stored file, without reading the rest of the file.
+    /// Returns `None` if the file doesn't have a header.
+    pub fn read(path: &Path) -> CugparckResult<Option<Self>> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        File::open(path)?
+            .take(HEADER_SIZE as u64)
+            .read_to_end(&mut bytes)?;
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Splits a stored file into its header and its payload, checking the integrity of the payload.
+pub(crate) fn split_payload(bytes: &[u8]) -> CugparckResult<(TableHeader, &[u8])> {
+    // files stored by the versions without header aren't supported anymore: their archived layout
+    // doesn't match the current one, so they are reported as the version 0 of the format.
+    let header = TableHeader::from_bytes(bytes)?.ok_or(CugparckError::UnsupportedVersion(0))?;
+
+    let payload = &bytes[HEADER_SIZE..];
+    if payload.len() as u64 != header.payload_len || crc32fast::hash(payload) != header.checksum {
+        return Err(CugparckError::Checksum);
+    }
+
+    Ok((header, payload))
+}
+
+/// A writer computing the CRC32 and the length of the data written.
+pub struct ChecksumWriter<W: Write> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            len: 0,
+        }
+    }
+
+    /// Returns the inner writer, the checksum and the length of the data written.
+    pub fn finalize(self) -> (W, u32, u64) {
+        (self.inner, self.hasher.finalize(), self.len)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.len += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use rkyv::AlignedVec;
+
+    use cugparck_commons::SaltPosition;
+
+    use crate::{
+        backend::Cpu, temp_path, CugparckError, RainbowTable, RainbowTableCtxBuilder,
+        RainbowTableStorage, Shard, SimpleTable, TableHeader, FORMAT_VERSION,
+    };
+
+    use super::{TableStats, HEADER_SIZE, MAX_FILTRATION_SAMPLES};
+
+    fn table() -> SimpleTable {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        SimpleTable::new_blocking::<Cpu>(ctx).unwrap()
+    }
+
+    fn aligned(bytes: &[u8]) -> AlignedVec {
+        let mut aligned = AlignedVec::new();
+        aligned.extend_from_slice(bytes);
+        aligned
+    }
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = TableHeader {
+            version: FORMAT_VERSION,
+            checksum: 0xdeadbeef,
+            payload_len: 1234,
+            chain_count: 42,
+            ctx: RainbowTableCtxBuilder::new()
+                .chain_length(100)
+                .max_password_length(4)
+                .charset(b"abcdef")
+                .table_number(3)
+                .build()
+                .unwrap(),
+            shard: None,
+            stats: None,
+        };
+
+        assert_eq!(
+            Some(header.clone()),
+            TableHeader::from_bytes(&header.to_bytes()).unwrap()
+        );
+        assert_eq!(None, TableHeader::from_bytes(b"no header").unwrap());
+
+        let header = TableHeader {
+            ctx: RainbowTableCtxBuilder::new()
+                .position_charsets(&[b"ABC", b"abc", b"abc", b"0123456789"])
+                .salt(b"pepper", SaltPosition::Prefix)
+                .min_password_length(2)
+                .filter_count(7)
+                .startpoint_seed(Some(0xc0ffee))
+                .build()
+                .unwrap(),
+            shard: Some(Shard::new(2, 3).unwrap()),
+            ..header
+        };
+
+        assert_eq!(
+            Some(header.clone()),
+            TableHeader::from_bytes(&header.to_bytes()).unwrap()
+        );
+
+        // the charsets of all the positions fit in the header
+        let digits = b"0123456789".as_slice();
+        let header = TableHeader {
+            ctx: RainbowTableCtxBuilder::new()
+                .position_charsets(&[[b"abcdef".as_slice()].as_slice(), &[digits; 15]].concat())
+                .build()
+                .unwrap(),
+            shard: None,
+            ..header
+        };
+
+        assert_eq!(
+            Some(header.clone()),
+            TableHeader::from_bytes(&header.to_bytes()).unwrap()
+        );
+
+        let header = TableHeader {
+            ctx: RainbowTableCtxBuilder::new()
+                .utf8_charset("a\u{e4}\u{f6}\u{fc}\u{df}\u{20ac}")
+                .max_password_length(3)
+                .build()
+                .unwrap(),
+            shard: None,
+            ..header
+        };
+
+        assert_eq!(
+            Some(header.clone()),
+            TableHeader::from_bytes(&header.to_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stats_roundtrip() {
+        let stats = TableStats {
+            unique_chains: 1000,
+            filtration_curve: vec![(10, 5000), (100, 2000), (999, 1000)],
+            duration: Duration::from_millis(1234),
+            backend: "cuda".to_owned(),
+            cugparck_version: "0.4.3".to_owned(),
+        };
+        let header = TableHeader {
+            version: FORMAT_VERSION,
+            checksum: 0,
+            payload_len: 0,
+            chain_count: 1000,
+            ctx: RainbowTableCtxBuilder::new().build().unwrap(),
+            shard: None,
+            stats: Some(stats.clone()),
+        };
+
+        assert_eq!(
+            Some(header.clone()),
+            TableHeader::from_bytes(&header.to_bytes()).unwrap()
+        );
+
+        // long filtration curves are downsampled, keeping both ends.
+        let header = TableHeader {
+            stats: Some(TableStats {
+                filtration_curve: (0..1000).map(|i| (i, 1000 - i)).collect(),
+                ..stats
+            }),
+            ..header
+        };
+        let curve = TableHeader::from_bytes(&header.to_bytes())
+            .unwrap()
+            .unwrap()
+            .stats
+            .unwrap()
+            .filtration_curve;
+
+        assert_eq!(MAX_FILTRATION_SAMPLES, curve.len());
+        assert_eq!(Some(&(0, 1000)), curve.first());
+        assert_eq!(Some(&(999, 1)), curve.last());
+    }
+
+    #[test]
+    fn test_store_load() {
+        let path = temp_path("header.rt");
+        let table = table();
+        table.store(&path).unwrap();
+
+        let header = TableHeader::read(&path).unwrap().unwrap();
+        assert_eq!(FORMAT_VERSION, header.version);
+        assert_eq!(table.ctx(), header.ctx);
+        assert_eq!(table.len() as u64, header.chain_count);
+
+        let stats = header.stats.clone().unwrap();
+        assert_eq!(table.len() as u64, stats.unique_chains);
+        assert_eq!("cpu", stats.backend);
+        assert_eq!(table.stats(), Some(&stats));
+
+        let mut bytes = aligned(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(bytes.len() as u64, HEADER_SIZE as u64 + header.payload_len);
+        assert_eq!(table.len(), SimpleTable::load(&bytes).unwrap().len());
+
+        // a header describing another table is detected.
+        let mut other = header.clone();
+        other.ctx.tn += 1;
+        let mut mismatched = bytes.clone();
+        mismatched[..HEADER_SIZE].copy_from_slice(&other.to_bytes());
+        assert!(matches!(
+            SimpleTable::load(&mismatched),
+            Err(CugparckError::Check)
+        ));
+
+        // a corrupted payload is detected.
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            SimpleTable::load(&bytes),
+            Err(CugparckError::Checksum)
+        ));
+    }
+
+    #[test]
+    fn test_load_other_version() {
+        let table = table();
+
+        // files stored without a header.
+        let bytes = rkyv::to_bytes::<_, 4096>(&table).unwrap();
+        assert_eq!(None, TableHeader::from_bytes(&bytes).unwrap());
+        assert!(matches!(
+            SimpleTable::load(&bytes),
+            Err(CugparckError::UnsupportedVersion(0))
+        ));
+
+        let mut header = TableHeader {
+            version: FORMAT_VERSION - 1,
+            checksum: 0,
+            payload_len: 0,
+            chain_count: 0,
+            ctx: table.ctx(),
+            shard: None,
+            stats: None,
+        }
+        .to_bytes();
+        assert!(matches!(
+            SimpleTable::load(&header),
+            Err(CugparckError::UnsupportedVersion(version)) if version == FORMAT_VERSION - 1
+        ));
+
+        header[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            TableHeader::from_bytes(&header),
+            Err(CugparckError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+    }
+}
@@ -3,53 +3,128 @@
 #[cfg(feature = "wgpu")]
 extern crate wgpu_crate as wgpu;
 
+#[cfg(feature = "zstd")]
+extern crate zstd_crate as zstd;
+
 pub mod backend;
+mod benchmark;
+mod brute_force;
 mod error;
 mod event;
+mod false_alarm;
+mod header;
+mod mask;
+mod naming;
+mod parallel;
 mod rainbow_table;
+mod rcrack;
 mod renderer;
+mod self_test;
+mod shard;
+#[cfg(not(target_arch = "wasm32"))]
+mod table_cache;
 mod table_cluster;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod wordlist;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use table_cache::{CachedTable, TableCache};
+
+#[cfg(feature = "zstd")]
+pub use rainbow_table::{compress_zstd, decompress_zstd, zstd_decompressed_len};
 
 pub use {
+    benchmark::{benchmark, BenchmarkResult},
+    brute_force::brute_force,
     error::CugparckError,
-    event::{Event, SimpleTableHandle},
-    rainbow_table::{CompressedTable, RainbowTable, RainbowTableStorage, SimpleTable},
+    event::{
+        DeviceMemory, Event, GenerationController, GenerationSpeed, Progress, ProgressListener,
+        SimpleTableHandle,
+    },
+    false_alarm::{
+        ArchivedFalseAlarmIndex, FalseAlarmFilter, FalseAlarmIndex, MAX_CHECKPOINT_COUNT,
+    },
+    header::{TableHeader, TableStats, FORMAT_VERSION, HEADER_SIZE, MAX_FILTRATION_SAMPLES},
+    mask::parse_mask,
+    naming::NameTemplate,
+    rainbow_table::{
+        AnyTable, AnyTableIterator, ArchivedCompressedTable, ArchivedSimpleTable,
+        ArchivedTruncatedTable, Checkpoint, CompressedTable, EncodingStats, GenerationStats,
+        RainbowTable, RainbowTableStorage, SearchHit, SearchStats, SimpleTable, TruncatedTable,
+    },
+    rcrack::{export_rcrack, rcrack_file_name, RCRACK_CHAIN_SIZE},
     rkyv::{Deserialize, Infallible, Serialize},
-    table_cluster::TableCluster,
+    self_test::{probe, self_test, SelfTestCheck},
+    shard::Shard,
+    table_cluster::{
+        CachedTableCluster, ClusterSearch, MergedTableCluster, TableCluster, TableSetValidator,
+    },
+    wordlist::{max_word_length, search_wordlist},
 };
 
-use std::ops::Range;
+use std::{
+    fmt::{self, Display},
+    ops::Range,
+};
 
 use cugparck_commons::{
-    ArrayVec, HashType, RainbowTableCtx, DEFAULT_APLHA, DEFAULT_CHAIN_LENGTH, DEFAULT_CHARSET,
-    DEFAULT_FILTER_COUNT, DEFAULT_MAX_PASSWORD_LENGTH, DEFAULT_TABLE_NUMBER,
-    MAX_CHARSET_LENGTH_ALLOWED,
+    ArrayVec, Charset, HashType, PasswordEncoding, RainbowTableCtx, Salt, SaltPosition,
+    StartpointOrder, DEFAULT_APLHA, DEFAULT_CHAIN_LENGTH, DEFAULT_CHARSET, DEFAULT_FILTER_COUNT,
+    DEFAULT_MAX_PASSWORD_LENGTH, DEFAULT_TABLE_NUMBER, MAX_CHARSET_LENGTH_ALLOWED,
+    MAX_FILTER_COUNT, MAX_PASSWORD_LENGTH_ALLOWED, MAX_SALT_LENGTH_ALLOWED,
 };
 
 use error::CugparckResult;
 
+/// The minimum chain length of a context.
+/// The last column of a chain is its endpoint, so shorter chains don't contain any password to search.
+pub const MIN_CHAIN_LENGTH: usize = 2;
+
 /// A builder for a rainbow table context.
 #[derive(Clone, Copy)]
 pub struct RainbowTableCtxBuilder {
     hash_type: HashType,
-    charset: ArrayVec<[u8; MAX_CHARSET_LENGTH_ALLOWED]>,
+    /// `None` if the charset provided is too long.
+    charset: Option<Charset>,
+    /// The charset of each position, empty if the same charset is used everywhere.
+    /// An element is `None` if the charset provided is too long.
+    position_charsets: ArrayVec<[Option<Charset>; MAX_PASSWORD_LENGTH_ALLOWED]>,
     t: usize,
     tn: usize,
+    min_password_length: usize,
     max_password_length: usize,
     m0: Option<usize>,
     alpha: f64,
+    /// `None` if the salt provided is too long.
+    salt: Option<Salt>,
+    salt_position: SaltPosition,
+    /// `None` to use the default filter count of the chain length.
+    filter_count: Option<usize>,
+    encoding: PasswordEncoding,
+    /// The sorted code points of the charset in the UTF-8 mode, empty otherwise.
+    code_points: ArrayVec<[u32; MAX_CHARSET_LENGTH_ALLOWED]>,
+    startpoint_seed: Option<u64>,
 }
 
 impl Default for RainbowTableCtxBuilder {
     fn default() -> Self {
         Self {
             hash_type: HashType::Ntlm,
-            charset: DEFAULT_CHARSET.try_into().unwrap(),
+            charset: Some(DEFAULT_CHARSET.try_into().unwrap()),
+            position_charsets: ArrayVec::new(),
+            min_password_length: 0,
             max_password_length: DEFAULT_MAX_PASSWORD_LENGTH as usize,
             t: DEFAULT_CHAIN_LENGTH,
             tn: DEFAULT_TABLE_NUMBER as usize,
             m0: None,
             alpha: DEFAULT_APLHA,
+            salt: Some(Salt::new()),
+            salt_position: SaltPosition::default(),
+            filter_count: None,
+            encoding: PasswordEncoding::Bytes,
+            code_points: ArrayVec::new(),
+            startpoint_seed: None,
         }
     }
 }
@@ -68,10 +143,56 @@ impl RainbowTableCtxBuilder {
     }
 
     /// Sets the charset of the context.
+    /// Each byte of the charset is a character of the passwords, which allows ASCII or Latin-1 charsets.
+    /// The charset can contain at most `MAX_CHARSET_LENGTH_ALLOWED` characters.
     pub fn charset(mut self, charset: &[u8]) -> Self {
-        self.charset = charset.try_into().expect(&format!(
-            "Charset should be < {MAX_CHARSET_LENGTH_ALLOWED} chars"
-        ));
+        self.charset = charset.try_into().ok();
+        self.encoding = PasswordEncoding::Bytes;
+        self.code_points = ArrayVec::new();
+
+        self
+    }
+
+    /// Sets a charset of Unicode characters, and encodes the passwords in UTF-8.
+    /// The password lengths are counted in characters, but the longest passwords must still fit
+    /// in `MAX_PASSWORD_LENGTH_ALLOWED` bytes once encoded.
+    /// The charset can contain at most `MAX_CHARSET_LENGTH_ALLOWED` characters.
+    pub fn utf8_charset(mut self, charset: &str) -> Self {
+        let mut code_points = charset.chars().map(u32::from).collect::<Vec<_>>();
+        code_points.sort_unstable();
+        code_points.dedup();
+
+        self.charset = (0..code_points.len())
+            .map(|i| i as u8)
+            .collect::<Vec<_>>()
+            .as_slice()
+            .try_into()
+            .ok();
+        self.encoding = PasswordEncoding::Utf8;
+        self.code_points = code_points.as_slice().try_into().unwrap_or_default();
+
+        self
+    }
+
+    /// Sets a different charset for each position of the passwords, instead of using the same charset everywhere.
+    /// The maximum password length becomes the number of charsets provided.
+    /// Passwords shorter than the maximum length use the first charsets.
+    pub fn position_charsets(mut self, charsets: &[&[u8]]) -> Self {
+        self.max_password_length = charsets.len();
+        self.position_charsets = charsets
+            .iter()
+            .take(MAX_PASSWORD_LENGTH_ALLOWED)
+            .map(|charset| (*charset).try_into().ok())
+            .collect();
+
+        self
+    }
+
+    /// Sets a salt concatenated to the passwords before hashing them.
+    /// The salt can contain at most `MAX_SALT_LENGTH_ALLOWED` bytes.
+    pub fn salt(mut self, salt: &[u8], position: SaltPosition) -> Self {
+        self.salt = salt.try_into().ok();
+        self.salt_position = position;
 
         self
     }
@@ -79,12 +200,21 @@ impl RainbowTableCtxBuilder {
     /// Sets the length of the chain of the context.
     /// Increasing the chain length will reduce the memory used
     /// to store the table but increase the time taken to attack.
+    /// The chain length should be at least `MIN_CHAIN_LENGTH`.
     pub fn chain_length(mut self, chain_length: usize) -> Self {
         self.t = chain_length;
 
         self
     }
 
+    /// Sets the minimum password length of the context.
+    /// Shorter passwords are not part of the search space, which is useful since they are quick to brute force.
+    pub fn min_password_length(mut self, min_password_length: u8) -> Self {
+        self.min_password_length = min_password_length as usize;
+
+        self
+    }
+
     /// Sets the maximum password length of the context.
     pub fn max_password_length(mut self, max_password_length: u8) -> Self {
         self.max_password_length = max_password_length as usize;
@@ -109,6 +239,15 @@ impl RainbowTableCtxBuilder {
         self
     }
 
+    /// Sets the seed of the permutation choosing the startpoints of the context.
+    /// With a seed, the startpoints are spread pseudo-randomly over the search space instead of being its first passwords,
+    /// and the same seed always gives the same startpoints, so that a table can be reproduced exactly.
+    pub fn startpoint_seed(mut self, seed: Option<u64>) -> Self {
+        self.startpoint_seed = seed;
+
+        self
+    }
+
     /// Sets the maximality factor (alpha) of the context.
     /// The maximality factor is used to determine the number of startpoints.
     /// It is an indicator of how well the table will perform compared to a maximum table.
@@ -118,18 +257,115 @@ impl RainbowTableCtxBuilder {
         self
     }
 
+    /// Sets the number of filtrations of the chains during the generation.
+    /// More filtrations use less memory but make the generation slower.
+    /// The filter count can be at most `MAX_FILTER_COUNT`, and should be lower than `t - 1`
+    /// so that every filtration happens on a different column.
+    /// It defaults to `DEFAULT_FILTER_COUNT`, lowered for short chains.
+    pub fn filter_count(mut self, filter_count: usize) -> Self {
+        self.filter_count = Some(filter_count);
+
+        self
+    }
+
     /// Builds a RainbowTableCtx with the specified parameters.
-    pub fn build(mut self) -> CugparckResult<RainbowTableCtx> {
+    pub fn build(self) -> CugparckResult<RainbowTableCtx> {
+        if self.t < MIN_CHAIN_LENGTH {
+            return Err(CugparckError::ChainLength(MIN_CHAIN_LENGTH));
+        }
+
+        let max_password_length = self.hash_type.max_password_length();
+        if self.max_password_length > max_password_length {
+            return Err(CugparckError::PasswordTooLong(max_password_length));
+        }
+
+        if self.min_password_length > self.max_password_length {
+            return Err(CugparckError::MinPasswordLength);
+        }
+
+        // a chain of length t has t - 1 columns to filter, the last one being followed by the final filtration.
+        // Chains of length 2 still need one filtration.
+        let max_filter_count = MAX_FILTER_COUNT.min(self.t - 2).max(1);
+        let filter_count = self
+            .filter_count
+            .unwrap_or_else(|| DEFAULT_FILTER_COUNT.min(max_filter_count));
+        if !(1..=max_filter_count).contains(&filter_count) {
+            return Err(CugparckError::FilterCount(max_filter_count));
+        }
+
+        let salt = self
+            .salt
+            .ok_or(CugparckError::SaltTooLong(MAX_SALT_LENGTH_ALLOWED))?;
+
+        let mut charsets = <[Charset; MAX_PASSWORD_LENGTH_ALLOWED]>::default();
+        let charset = if self.position_charsets.is_empty() {
+            let mut charset = self
+                .charset
+                .ok_or(CugparckError::CharsetTooLong(MAX_CHARSET_LENGTH_ALLOWED))?;
+            charset.sort_unstable();
+            charsets[..self.max_password_length].fill(charset);
+
+            charset
+        } else {
+            let mut union = Vec::new();
+            for (position_charset, &charset) in charsets.iter_mut().zip(&self.position_charsets) {
+                *position_charset =
+                    charset.ok_or(CugparckError::CharsetTooLong(MAX_CHARSET_LENGTH_ALLOWED))?;
+                position_charset.sort_unstable();
+                union.extend_from_slice(position_charset);
+            }
+
+            union.sort_unstable();
+            union.dedup();
+            union
+                .as_slice()
+                .try_into()
+                .map_err(|_| CugparckError::CharsetTooLong(MAX_CHARSET_LENGTH_ALLOWED))?
+        };
+
+        if charsets[..self.max_password_length]
+            .iter()
+            .any(|charset| charset.is_empty())
+        {
+            return Err(CugparckError::EmptyCharset);
+        }
+
+        let mut code_points = [0; MAX_CHARSET_LENGTH_ALLOWED];
+        if self.encoding == PasswordEncoding::Utf8 {
+            if !self.position_charsets.is_empty() {
+                return Err(CugparckError::Utf8PositionCharsets);
+            }
+
+            // the code points are valid since they come from a string
+            let max_char_len = self
+                .code_points
+                .iter()
+                .map(|&c| char::from_u32(c).unwrap().len_utf8())
+                .max()
+                .unwrap_or(1);
+            if self.max_password_length * max_char_len > MAX_PASSWORD_LENGTH_ALLOWED {
+                return Err(CugparckError::PasswordTooLong(
+                    MAX_PASSWORD_LENGTH_ALLOWED / max_char_len,
+                ));
+            }
+
+            code_points[..self.code_points.len()].copy_from_slice(&self.code_points);
+        }
+
         // create the search spaces
         let mut n: u128 = 0;
+        let mut space: u128 = 1;
         let mut search_spaces = ArrayVec::new();
 
         search_spaces.push(n as usize);
-        for i in 0..self.max_password_length {
-            n += self.charset.len().pow(i as u32) as u128;
+        for (len, position_charset) in charsets[..self.max_password_length].iter().enumerate() {
+            if len >= self.min_password_length {
+                n += space;
+            }
             search_spaces.push(n as usize);
+            space *= position_charset.len() as u128;
         }
-        n += self.charset.len().pow(self.max_password_length as u32) as u128;
+        n += space;
 
         // make sure the search space is <= 2^64
         if n > usize::MAX as u128 {
@@ -152,21 +388,146 @@ impl RainbowTableCtxBuilder {
             }
         };
 
-        self.charset.sort_unstable();
-
         Ok(RainbowTableCtx {
             search_spaces,
             m0,
             n,
             hash_type: self.hash_type,
-            charset: self.charset,
+            charset,
+            charsets,
+            min_password_length: self.min_password_length,
             max_password_length: self.max_password_length,
             t: self.t,
             tn: self.tn,
+            salt,
+            salt_position: self.salt_position,
+            filter_count,
+            encoding: self.encoding,
+            code_points,
+            startpoint_order: match self.startpoint_seed {
+                Some(_) => StartpointOrder::Seeded,
+                None => StartpointOrder::Sequential,
+            },
+            startpoint_seed: self.startpoint_seed.unwrap_or_default(),
         })
     }
 }
 
+/// A warning about a context whose parameters are valid but likely to waste work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtxWarning {
+    /// The chain length is larger than the search space.
+    ChainLengthLargerThanSpace,
+    /// Almost all the chains are expected to merge, leaving few unique endpoints.
+    FewUniqueChains { expected: usize },
+    /// The charset contains lowercase letters, which LM hashes like uppercase letters.
+    LmLowercaseCharset,
+}
+
+impl Display for CtxWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChainLengthLargerThanSpace => write!(
+                f,
+                "The chain length is larger than the search space. Try decreasing the chain length"
+            ),
+            Self::FewUniqueChains { expected } => write!(
+                f,
+                "Only {expected} unique chains are expected at the end of the generation. Try decreasing the chain length"
+            ),
+            Self::LmLowercaseCharset => write!(
+                f,
+                "LM uppercases the passwords, so the lowercase letters of the charset are redundant. Try removing them"
+            ),
+        }
+    }
+}
+
+/// Returns the warnings about a context.
+pub fn ctx_warnings(ctx: &RainbowTableCtx) -> Vec<CtxWarning> {
+    let mut warnings = Vec::new();
+
+    if ctx.t > ctx.n {
+        warnings.push(CtxWarning::ChainLengthLargerThanSpace);
+    }
+
+    let expected = expected_unique_chains(ctx);
+
+    if expected < ctx.m0 / 100 {
+        warnings.push(CtxWarning::FewUniqueChains { expected });
+    }
+
+    if ctx.hash_type == HashType::Lm
+        && ctx.encoding == PasswordEncoding::Bytes
+        && ctx.charset.iter().any(u8::is_ascii_lowercase)
+    {
+        warnings.push(CtxWarning::LmLowercaseCharset);
+    }
+
+    warnings
+}
+
+/// Returns the number of unique chains expected at the end of the generation of a table.
+pub fn expected_unique_chains(ctx: &RainbowTableCtx) -> usize {
+    // from "Making a Faster Cryptanalytic Time-Memory Trade-Off" (Oechslin), the number of
+    // unique chains at column i is about 2n / (i + 2n / m0).
+    let two_n = 2. * ctx.n as f64;
+    (two_n / ((ctx.t - 1) as f64 + two_n / ctx.m0 as f64)) as usize
+}
+
+/// Returns the probability that a table generated with this context contains a random password of the search space.
+pub fn expected_success_rate(ctx: &RainbowTableCtx) -> f64 {
+    // from "Making a Faster Cryptanalytic Time-Memory Trade-Off" (Oechslin) section 3.
+    let n = ctx.n as f64;
+    let mut mi = ctx.m0 as f64;
+    let mut miss_probability = 1.;
+
+    for _ in 0..ctx.t {
+        miss_probability *= 1. - mi / n;
+        mi = n * (1. - (-mi / n).exp());
+    }
+
+    1. - miss_probability
+}
+
+/// Returns the probability that at least one of `table_count` tables generated with this context contains a random password of the search space.
+pub fn expected_success_rate_of_tables(ctx: &RainbowTableCtx, table_count: usize) -> f64 {
+    // the tables use different reduction functions, so they are independent.
+    1. - (1. - expected_success_rate(ctx)).powi(table_count as i32)
+}
+
+/// Returns the number of hashes expected to be computed during the generation of a table.
+pub fn expected_hash_count(ctx: &RainbowTableCtx) -> f64 {
+    // the filtrations keep the number of chains computed close to the number of unique chains,
+    // so the sum over all the columns is about the integral of 2n / (i + 2n / m0).
+    let two_n = 2. * ctx.n as f64;
+    let offset = two_n / ctx.m0 as f64;
+
+    two_n * (((ctx.t - 1) as f64 + offset) / offset).ln()
+}
+
+/// Returns the fraction of the hashes of a generation expected to be computed once the chains reach `column`.
+/// The first columns are the most expensive, since most of the chains are merged and removed later on.
+pub(crate) fn expected_work_fraction(ctx: &RainbowTableCtx, column: f64) -> f64 {
+    // same integral as in `expected_hash_count`, between 0 and `column`
+    let offset = 2. * ctx.n as f64 / ctx.m0 as f64;
+    let total = (((ctx.t - 1) as f64 + offset) / offset).ln();
+
+    ((column + offset) / offset).ln() / total
+}
+
+/// Returns the probability that a table with this context and number of unique chains contains a random password of the search space.
+pub fn measured_success_rate(ctx: &RainbowTableCtx, unique_chains: usize) -> f64 {
+    // the chains don't merge, so each column contains `unique_chains` distinct passwords.
+    1. - (1. - unique_chains as f64 / ctx.n as f64).powf(ctx.t as f64)
+}
+
+/// Returns the ranges of columns computed between two filtrations during the generation of a table.
+/// The ranges are contiguous and cover the columns `0..t - 1`.
+pub fn filtration_columns(ctx: &RainbowTableCtx) -> Vec<Range<usize>> {
+    FiltrationIterator::new(*ctx).collect()
+}
+
 /// An iterator to get the columns where a filtration should happen.
 struct FiltrationIterator {
     i: usize,
@@ -197,16 +558,20 @@ impl Iterator for FiltrationIterator {
     type Item = Range<usize>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.i == DEFAULT_FILTER_COUNT {
-            self.i += 1;
-            return Some(self.current_col..self.ctx.t - 1);
-        } else if self.i >= DEFAULT_FILTER_COUNT {
+        let last_col = self.ctx.t - 1;
+        let filter_count = self.ctx.filter_count;
+        if self.current_col >= last_col || self.i > filter_count {
             return None;
+        } else if self.i == filter_count {
+            self.i += 1;
+            return Some(self.current_col..last_col);
         }
 
-        let filter_col = (self.gamma * self.frac.powf(self.i as f64 / DEFAULT_FILTER_COUNT as f64)
+        // the columns are clamped in case the context has more filtrations than columns
+        let filter_col = ((self.gamma * self.frac.powf(self.i as f64 / filter_count as f64)
             - self.gamma) as usize
-            + 2;
+            + 2)
+        .min(last_col);
 
         let col = self.current_col;
 
@@ -221,3 +586,332 @@ impl Iterator for FiltrationIterator {
         Some(col..filter_col)
     }
 }
+
+/// Returns a path in the temporary directory, unique to this test process and to each call,
+/// so that tests running in parallel or from several processes never share a file.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("cugparck_test_{}_{id}_{name}", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::{
+        CompressedPassword, HashType, Password, DEFAULT_CHARSET, MAX_CHARSET_LENGTH_ALLOWED,
+        MAX_FILTER_COUNT,
+    };
+
+    use crate::{
+        ctx_warnings, expected_hash_count, expected_success_rate, expected_success_rate_of_tables,
+        expected_unique_chains, expected_work_fraction, filtration_columns, CtxWarning,
+        CugparckError, RainbowTableCtxBuilder, MIN_CHAIN_LENGTH,
+    };
+
+    #[test]
+    fn test_ctx_warnings() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(10_000)
+            .max_password_length(5)
+            .charset(b"abc")
+            .startpoints(Some(364))
+            .build()
+            .unwrap();
+
+        assert_eq!(364, ctx.n);
+
+        let warnings = ctx_warnings(&ctx);
+        assert!(warnings.contains(&CtxWarning::ChainLengthLargerThanSpace));
+        assert!(warnings
+            .iter()
+            .any(|warning| matches!(warning, CtxWarning::FewUniqueChains { .. })));
+
+        let ctx = RainbowTableCtxBuilder::new().build().unwrap();
+        assert!(ctx_warnings(&ctx).is_empty());
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .hash(HashType::Lm)
+            .charset(b"abcABC")
+            .build()
+            .unwrap();
+        assert!(ctx_warnings(&ctx).contains(&CtxWarning::LmLowercaseCharset));
+    }
+
+    #[test]
+    fn test_min_chain_length() {
+        for t in [0, 1] {
+            assert!(matches!(
+                RainbowTableCtxBuilder::new().chain_length(t).build(),
+                Err(CugparckError::ChainLength(MIN_CHAIN_LENGTH))
+            ));
+        }
+
+        // 1 + 3 + 3^2 + 3^3 = 40 passwords
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .charset(b"abc")
+            .max_password_length(3)
+            .startpoints(Some(40));
+
+        let ctx = ctx_builder.chain_length(MIN_CHAIN_LENGTH).build().unwrap();
+        assert!(!filtration_columns(&ctx).is_empty());
+        // 2n / (t - 1 + 2n / m0) = 80 / 3
+        assert_eq!(26, expected_unique_chains(&ctx));
+        assert!(!ctx_warnings(&ctx).contains(&CtxWarning::ChainLengthLargerThanSpace));
+
+        let ctx = ctx_builder.chain_length(100).build().unwrap();
+        assert!(ctx_warnings(&ctx).contains(&CtxWarning::ChainLengthLargerThanSpace));
+    }
+
+    #[test]
+    fn test_lm_password_length() {
+        let ctx_builder = RainbowTableCtxBuilder::new().hash(HashType::Lm);
+
+        assert!(ctx_builder.max_password_length(7).build().is_ok());
+        assert!(matches!(
+            ctx_builder.max_password_length(8).build(),
+            Err(CugparckError::PasswordTooLong(7))
+        ));
+    }
+
+    #[test]
+    fn test_long_passwords() {
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .hash(HashType::Ntlm)
+            .charset(b"0123456789");
+
+        let ctx = ctx_builder.max_password_length(16).build().unwrap();
+        let password = CompressedPassword::from(ctx.n - 1).into_password(&ctx);
+        assert_eq!("9999999999999999", password.to_string());
+
+        assert!(matches!(
+            ctx_builder.max_password_length(17).build(),
+            Err(CugparckError::PasswordTooLong(16))
+        ));
+
+        // the search space doesn't fit in 64 bits
+        assert!(matches!(
+            ctx_builder
+                .charset(DEFAULT_CHARSET)
+                .max_password_length(16)
+                .build(),
+            Err(CugparckError::Space(_))
+        ));
+    }
+
+    #[test]
+    fn test_expected_estimates() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(1000)
+            .max_password_length(5)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let one_table = expected_success_rate(&ctx);
+        assert_eq!(one_table, expected_success_rate_of_tables(&ctx, 1));
+        assert!(expected_success_rate_of_tables(&ctx, 4) > one_table);
+
+        // each column computes at most m0 chains and at least the unique chains at the end of the generation
+        let hashes = expected_hash_count(&ctx);
+        assert!(hashes < (ctx.m0 * ctx.t) as f64);
+        assert!(hashes > (expected_unique_chains(&ctx) * (ctx.t - 1)) as f64);
+    }
+
+    #[test]
+    fn test_expected_work_fraction() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(1000)
+            .max_password_length(5)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        assert_eq!(0., expected_work_fraction(&ctx, 0.));
+        assert!((expected_work_fraction(&ctx, (ctx.t - 1) as f64) - 1.).abs() < 1e-9);
+
+        // most of the work is done in the first half of the columns
+        let half = expected_work_fraction(&ctx, ctx.t as f64 / 2.);
+        assert!(half > 0.5 && half < 1.);
+    }
+
+    #[test]
+    fn test_filtration_columns() {
+        for (chain_length, max_password_length) in [(10, 2), (100, 4), (10_000, 6), (10_000, 5)] {
+            let ctx = RainbowTableCtxBuilder::new()
+                .chain_length(chain_length)
+                .max_password_length(max_password_length)
+                .charset(b"abcdef")
+                .build()
+                .unwrap();
+
+            let columns = filtration_columns(&ctx);
+
+            assert_eq!(0, columns.first().unwrap().start);
+            assert_eq!(ctx.t - 1, columns.last().unwrap().end);
+            assert!(columns.windows(2).all(|w| w[0].end == w[1].start));
+        }
+
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(10_000)
+            .max_password_length(6)
+            .charset(b"abcdef");
+        for filter_count in [1, 5, 50] {
+            let ctx = ctx_builder.filter_count(filter_count).build().unwrap();
+            let columns = filtration_columns(&ctx);
+
+            assert!(columns.len() <= filter_count + 1);
+            assert_eq!(0, columns.first().unwrap().start);
+            assert_eq!(ctx.t - 1, columns.last().unwrap().end);
+        }
+
+        assert!(matches!(
+            ctx_builder.filter_count(0).build(),
+            Err(CugparckError::FilterCount(_))
+        ));
+
+        // short chains can't have more filtrations than columns
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(10)
+            .max_password_length(4)
+            .charset(b"abcdef");
+        assert!(matches!(
+            ctx_builder.filter_count(9).build(),
+            Err(CugparckError::FilterCount(8))
+        ));
+        assert!(matches!(
+            ctx_builder.filter_count(MAX_FILTER_COUNT).build(),
+            Err(CugparckError::FilterCount(8))
+        ));
+        assert_eq!(8, ctx_builder.build().unwrap().filter_count);
+
+        for t in [2, 3, 10, 100] {
+            let ctx_builder = ctx_builder.chain_length(t);
+            let mut ctxs = vec![ctx_builder.build().unwrap()];
+            ctxs.push(ctx_builder.filter_count((t - 2).max(1)).build().unwrap());
+
+            // contexts built by previous versions may have more filtrations than columns
+            let mut overfiltered = ctxs[0];
+            overfiltered.filter_count = MAX_FILTER_COUNT;
+            ctxs.push(overfiltered);
+
+            for ctx in ctxs {
+                let columns = filtration_columns(&ctx);
+
+                assert_eq!(0, columns.first().unwrap().start);
+                assert_eq!(t - 1, columns.last().unwrap().end);
+                assert!(columns.windows(2).all(|w| w[0].end == w[1].start));
+                assert!(columns.iter().all(|range| !range.is_empty()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_charset_length_boundary() {
+        let charset = (1..=MAX_CHARSET_LENGTH_ALLOWED as u8 + 1).collect::<Vec<_>>();
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .charset(&charset[..MAX_CHARSET_LENGTH_ALLOWED])
+            .build()
+            .unwrap();
+        assert_eq!(MAX_CHARSET_LENGTH_ALLOWED, ctx.charset.len());
+
+        let err = RainbowTableCtxBuilder::new()
+            .charset(&charset)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CugparckError::CharsetTooLong(MAX_CHARSET_LENGTH_ALLOWED)
+        ));
+    }
+
+    #[test]
+    fn test_non_ascii_charset() {
+        let latin1 = RainbowTableCtxBuilder::new()
+            .hash(HashType::Ntlm)
+            .charset(b"abcdet\xe9")
+            .build()
+            .unwrap();
+        let utf8 = RainbowTableCtxBuilder::new()
+            .hash(HashType::Ntlm)
+            .utf8_charset("abcdet\u{e9}")
+            .max_password_length(5)
+            .build()
+            .unwrap();
+
+        // NTLM hashes the same password whatever its encoding
+        assert_eq!(
+            latin1.hash(&Password::new(b"\xe9t\xe9")),
+            utf8.hash(&Password::new("\u{e9}t\u{e9}".as_bytes()))
+        );
+
+        let password = CompressedPassword::from(utf8.n - 1).into_password(&utf8);
+        assert_eq!("\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}", password.to_string());
+        assert!(utf8.could_contain(&password));
+
+        let err = RainbowTableCtxBuilder::new()
+            .utf8_charset("abc\u{e9}")
+            .max_password_length(9)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, CugparckError::PasswordTooLong(8)));
+
+        let err = RainbowTableCtxBuilder::new()
+            .position_charsets(&[b"abc", b"abc"])
+            .utf8_charset("abc\u{e9}")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, CugparckError::Utf8PositionCharsets));
+    }
+
+    #[test]
+    fn test_min_password_length() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .charset(b"abc")
+            .min_password_length(3)
+            .max_password_length(5)
+            .build()
+            .unwrap();
+
+        assert_eq!(27 + 81 + 243, ctx.n);
+        assert_eq!(3, CompressedPassword::from(0).into_password(&ctx).len());
+        assert_eq!(
+            5,
+            CompressedPassword::from(ctx.n - 1)
+                .into_password(&ctx)
+                .len()
+        );
+
+        assert!(matches!(
+            RainbowTableCtxBuilder::new()
+                .min_password_length(4)
+                .max_password_length(3)
+                .build(),
+            Err(CugparckError::MinPasswordLength)
+        ));
+    }
+
+    #[test]
+    fn test_expected_success_rate() {
+        let ctx = RainbowTableCtxBuilder::new().build().unwrap();
+        let success_rate = expected_success_rate(&ctx);
+        assert!(success_rate > 0. && success_rate <= 1.);
+
+        // fewer startpoints should cover less of the search space.
+        let smaller_ctx = RainbowTableCtxBuilder::new()
+            .startpoints(Some(ctx.m0 / 10))
+            .build()
+            .unwrap();
+        assert!(expected_success_rate(&smaller_ctx) < success_rate);
+
+        // a table with only one chain should cover at most t passwords.
+        let ctx = RainbowTableCtxBuilder::new()
+            .startpoints(Some(1))
+            .build()
+            .unwrap();
+        assert!(expected_success_rate(&ctx) <= ctx.t as f64 / ctx.n as f64);
+    }
+}
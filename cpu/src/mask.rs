@@ -0,0 +1,116 @@
+//! Masks describing the charset of each position of a password, using the hashcat syntax.
+
+use crate::error::{CugparckError, CugparckResult};
+
+const LOWERCASE: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"0123456789";
+const SPECIALS: &[u8] = b" !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const LOWER_HEX: &[u8] = b"0123456789abcdef";
+const UPPER_HEX: &[u8] = b"0123456789ABCDEF";
+
+/// Parses a mask into the charset of each position.
+///
+/// The mask uses the hashcat syntax: `?l` is a lowercase letter, `?u` an uppercase letter, `?d` a digit,
+/// `?s` a special character, `?a` any of them, `?h` and `?H` a lowercase and uppercase hexadecimal digit, and `??` a question mark.
+/// Any other character only matches itself.
+/// For instance, `?u?l?l?l?d?d` matches an uppercase letter, followed by three lowercase letters and two digits.
+pub fn parse_mask(mask: &str) -> CugparckResult<Vec<Vec<u8>>> {
+    if !mask.is_ascii() {
+        return Err(CugparckError::InvalidMask(
+            "only ASCII characters are supported".to_owned(),
+        ));
+    }
+
+    let mut charsets = Vec::new();
+    let mut bytes = mask.bytes();
+
+    while let Some(c) = bytes.next() {
+        if c != b'?' {
+            charsets.push(vec![c]);
+            continue;
+        }
+
+        let charset = match bytes.next() {
+            Some(b'l') => LOWERCASE.to_vec(),
+            Some(b'u') => UPPERCASE.to_vec(),
+            Some(b'd') => DIGITS.to_vec(),
+            Some(b's') => SPECIALS.to_vec(),
+            Some(b'a') => [LOWERCASE, UPPERCASE, DIGITS, SPECIALS].concat(),
+            Some(b'h') => LOWER_HEX.to_vec(),
+            Some(b'H') => UPPER_HEX.to_vec(),
+            Some(b'?') => vec![b'?'],
+            Some(c) => {
+                return Err(CugparckError::InvalidMask(format!(
+                    "unknown placeholder ?{}",
+                    c as char
+                )))
+            }
+            None => {
+                return Err(CugparckError::InvalidMask(
+                    "the mask ends with an unfinished placeholder".to_owned(),
+                ))
+            }
+        };
+
+        charsets.push(charset);
+    }
+
+    Ok(charsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::Cpu, parse_mask, RainbowTable, RainbowTableCtxBuilder, SimpleTable};
+
+    #[test]
+    fn test_parse_mask() {
+        let charsets = parse_mask("?u?l-?d??").unwrap();
+
+        assert_eq!(5, charsets.len());
+        assert_eq!(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ", charsets[0].as_slice());
+        assert_eq!(b"abcdefghijklmnopqrstuvwxyz", charsets[1].as_slice());
+        assert_eq!(b"-", charsets[2].as_slice());
+        assert_eq!(b"0123456789", charsets[3].as_slice());
+        assert_eq!(b"?", charsets[4].as_slice());
+        assert_eq!(95, parse_mask("?a").unwrap()[0].len());
+
+        assert!(parse_mask("?x").is_err());
+        assert!(parse_mask("?d?").is_err());
+    }
+
+    #[test]
+    fn test_mask_search_space() {
+        let charsets = parse_mask("?u?l?d").unwrap();
+        let charsets = charsets.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let ctx = RainbowTableCtxBuilder::new()
+            .position_charsets(&charsets)
+            .build()
+            .unwrap();
+
+        assert_eq!(3, ctx.max_password_length);
+        assert_eq!(1 + 26 + 26 * 26 + 26 * 26 * 10, ctx.n);
+        assert!(ctx.has_position_charsets());
+    }
+
+    #[test]
+    fn test_mask_table() {
+        let charsets = parse_mask("?u?l?d").unwrap();
+        let charsets = charsets.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .position_charsets(&charsets)
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        for chain in table.iter() {
+            let endpoint = chain.endpoint.into_password(&ctx);
+            assert!(ctx.could_contain(&endpoint));
+            assert!(endpoint
+                .iter()
+                .zip(&charsets)
+                .all(|(c, charset)| charset.contains(c)));
+        }
+    }
+}
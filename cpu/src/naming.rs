@@ -0,0 +1,183 @@
+//! File names of the tables, built from their parameters.
+
+use std::{fmt, str::FromStr};
+
+use cugparck_commons::RainbowTableCtx;
+
+use crate::{
+    error::{CugparckError, CugparckResult},
+    rcrack::rcrack_hash_name,
+};
+
+/// The placeholders that can be used in a template.
+const PLACEHOLDERS: [&str; 6] = ["hash", "charset", "min", "max", "t", "tn"];
+
+/// A template naming the files of the tables, such as `{hash}_{charset}#{min}-{max}_{t}_{tn}`.
+///
+/// The placeholders are replaced by the parameters of each table:
+/// `{hash}` (the hash function, named as in RainbowCrack), `{charset}` (a hash of the search space),
+/// `{min}` and `{max}` (the password lengths), `{t}` (the chain length) and `{tn}` (the table number).
+/// The template should contain `{tn}`, so the tables of a set don't overwrite each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+}
+
+impl NameTemplate {
+    /// The template naming the tables `table_{tn}`, which doesn't tell apart tables generated with different parameters.
+    pub const DEFAULT: &'static str = "table_{tn}";
+
+    /// A template encoding the parameters of the tables in their name, like RainbowCrack.
+    pub const DESCRIPTIVE: &'static str = "{hash}_{charset}#{min}-{max}_{t}_{tn}";
+
+    /// Returns the file name of a table, without its extension.
+    pub fn file_stem(&self, ctx: &RainbowTableCtx) -> String {
+        let mut stem = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        // the template was validated when it was parsed
+        while let Some((before, after)) = rest.split_once('{') {
+            let (placeholder, after) = after.split_once('}').unwrap();
+            stem.push_str(before);
+            stem.push_str(&placeholder_value(placeholder, ctx));
+            rest = after;
+        }
+        stem.push_str(rest);
+
+        stem
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self {
+            template: Self::DEFAULT.to_owned(),
+        }
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = CugparckError;
+
+    /// Parses a template, checking that its placeholders are valid.
+    fn from_str(s: &str) -> CugparckResult<Self> {
+        let invalid = |reason: &str| CugparckError::InvalidNameTemplate(reason.to_owned());
+
+        if s.contains(['/', '\\']) {
+            return Err(invalid("the template can't contain a path separator"));
+        }
+
+        let mut has_table_number = false;
+        let mut rest = s;
+
+        while let Some((_, after)) = rest.split_once('{') {
+            let (placeholder, after) = after
+                .split_once('}')
+                .ok_or_else(|| invalid("a placeholder is not closed"))?;
+
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(CugparckError::InvalidNameTemplate(format!(
+                    "unknown placeholder `{{{placeholder}}}`"
+                )));
+            }
+
+            has_table_number |= placeholder == "tn";
+            rest = after;
+        }
+
+        if rest.contains('}') {
+            return Err(invalid("a placeholder is not opened"));
+        }
+
+        if !has_table_number {
+            return Err(invalid(
+                "the template should contain the table number `{tn}`",
+            ));
+        }
+
+        Ok(Self {
+            template: s.to_owned(),
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+/// Returns the value of a placeholder for a table.
+fn placeholder_value(placeholder: &str, ctx: &RainbowTableCtx) -> String {
+    match placeholder {
+        "hash" => rcrack_hash_name(ctx.hash_type).to_owned(),
+        "charset" => format!("{:08x}", search_space_hash(ctx)),
+        "min" => ctx.min_password_length.to_string(),
+        "max" => ctx.max_password_length.to_string(),
+        "t" => ctx.t.to_string(),
+        "tn" => ctx.tn.to_string(),
+        _ => unreachable!("unknown placeholder"),
+    }
+}
+
+/// Returns a hash of the charsets, encoding and salt of a table.
+/// Tables with the same hash function, lengths and search space hash can be searched together.
+fn search_space_hash(ctx: &RainbowTableCtx) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+
+    hasher.update(&ctx.charset);
+    for charset in &ctx.charsets[..ctx.max_password_length] {
+        hasher.update(&[charset.len() as u8]);
+        hasher.update(charset);
+    }
+
+    hasher.update(&[ctx.encoding as u8]);
+    for code_point in &ctx.code_points[..ctx.charset.len()] {
+        hasher.update(&code_point.to_le_bytes());
+    }
+
+    hasher.update(&[ctx.salt_position as u8]);
+    hasher.update(&ctx.salt);
+
+    hasher.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::HashType;
+
+    use crate::{NameTemplate, RainbowTableCtxBuilder};
+
+    #[test]
+    fn test_name_template() {
+        let builder = RainbowTableCtxBuilder::new()
+            .hash(HashType::Ntlm)
+            .charset(b"abc")
+            .min_password_length(1)
+            .max_password_length(6)
+            .chain_length(1000)
+            .table_number(2);
+        let ctx = builder.build().unwrap();
+
+        let default = NameTemplate::default();
+        assert_eq!("table_2", default.file_stem(&ctx));
+
+        let descriptive: NameTemplate = NameTemplate::DESCRIPTIVE.parse().unwrap();
+        let stem = descriptive.file_stem(&ctx);
+        assert!(stem.starts_with("ntlm_"), "{stem}");
+        assert!(stem.ends_with("#1-6_1000_2"), "{stem}");
+
+        let other_charset = builder.charset(b"abd").build().unwrap();
+        assert_ne!(stem, descriptive.file_stem(&other_charset));
+
+        for invalid in [
+            "table",
+            "table_{tn",
+            "table_{tn}}",
+            "{foo}_{tn}",
+            "dir/{tn}",
+        ] {
+            assert!(invalid.parse::<NameTemplate>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Parallel iterators.
+//!
+//! Without the `parallel` feature, for targets without threads such as `wasm32-unknown-unknown`,
+//! the same methods are available but run sequentially.
+
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) use sequential::*;
+
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    use std::{
+        hash::{BuildHasher, Hash},
+        ops::RangeFull,
+    };
+
+    use indexmap::{map::Drain, IndexMap};
+
+    pub(crate) trait IntoParallelIterator: IntoIterator + Sized {
+        fn into_par_iter(self) -> Self::IntoIter {
+            self.into_iter()
+        }
+    }
+
+    impl<T: IntoIterator> IntoParallelIterator for T {}
+
+    pub(crate) trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a + ?Sized> IntoParallelRefIterator<'a> for T
+    where
+        &'a T: IntoIterator,
+    {
+        type Iter = <&'a T as IntoIterator>::IntoIter;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub(crate) trait IntoParallelRefMutIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter_mut(&'a mut self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a + ?Sized> IntoParallelRefMutIterator<'a> for T
+    where
+        &'a mut T: IntoIterator,
+    {
+        type Iter = <&'a mut T as IntoIterator>::IntoIter;
+
+        fn par_iter_mut(&'a mut self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub(crate) trait ParallelIterator: Iterator + Sized {
+        fn find_map_any<B>(mut self, f: impl FnMut(Self::Item) -> Option<B>) -> Option<B> {
+            self.find_map(f)
+        }
+
+        fn find_first(mut self, predicate: impl FnMut(&Self::Item) -> bool) -> Option<Self::Item> {
+            self.find(predicate)
+        }
+
+        fn collect_into_vec(self, vec: &mut Vec<Self::Item>) {
+            vec.clear();
+            vec.extend(self);
+        }
+
+        fn unzip_into_vecs<A, B>(self, left: &mut Vec<A>, right: &mut Vec<B>)
+        where
+            Self: Iterator<Item = (A, B)>,
+        {
+            left.clear();
+            right.clear();
+            for (a, b) in self {
+                left.push(a);
+                right.push(b);
+            }
+        }
+    }
+
+    impl<T: Iterator> ParallelIterator for T {}
+
+    pub(crate) trait ParallelExtend<T>: Extend<T> {
+        fn par_extend(&mut self, iter: impl IntoIterator<Item = T>) {
+            self.extend(iter)
+        }
+    }
+
+    impl<T, E: Extend<T>> ParallelExtend<T> for E {}
+
+    pub(crate) trait ParallelSliceMut<T> {
+        fn par_sort_unstable_by_key<K: Ord>(&mut self, f: impl FnMut(&T) -> K);
+    }
+
+    impl<T> ParallelSliceMut<T> for [T] {
+        fn par_sort_unstable_by_key<K: Ord>(&mut self, f: impl FnMut(&T) -> K) {
+            self.sort_unstable_by_key(f)
+        }
+    }
+
+    pub(crate) trait ParallelDrainFull<K, V> {
+        fn par_drain(&mut self, range: RangeFull) -> Drain<'_, K, V>;
+    }
+
+    impl<K: Hash + Eq, V, S: BuildHasher> ParallelDrainFull<K, V> for IndexMap<K, V, S> {
+        fn par_drain(&mut self, range: RangeFull) -> Drain<'_, K, V> {
+            self.drain(range)
+        }
+    }
+}
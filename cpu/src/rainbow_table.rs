@@ -1,17 +1,35 @@
+mod any_table;
 mod compressed_delta_encoding;
+mod disk_chain_map;
 mod simple;
+pub(crate) mod stream;
+mod truncated;
 
-pub use {compressed_delta_encoding::CompressedTable, simple::SimpleTable};
+pub use {
+    any_table::{AnyTable, AnyTableIterator},
+    compressed_delta_encoding::{ArchivedCompressedTable, CompressedTable, EncodingStats},
+    simple::{ArchivedSimpleTable, Checkpoint, GenerationStats, SimpleTable},
+    truncated::{ArchivedTruncatedTable, TruncatedTable},
+};
 
-use std::{fs::File, path::Path};
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    ops::AddAssign,
+    path::Path,
+    rc::Rc,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
 
+use crate::parallel::*;
 use bytecheck::CheckBytes;
 use cugparck_commons::{
-    reduce, CompressedPassword, Digest, Password, RainbowChain, RainbowTableCtx,
+    column_endpoint, reduce, CompressedPassword, Digest, Password, RainbowChain, RainbowTableCtx,
 };
-use rayon::prelude::*;
 use rkyv::{
-    check_archived_root,
+    archived_root, check_archived_root,
     ser::{
         serializers::{
             AllocScratch, CompositeSerializer, FallbackScratch, HeapScratch, SharedSerializeMap,
@@ -23,15 +41,62 @@ use rkyv::{
     Serialize,
 };
 
-use crate::error::{CugparckError, CugparckResult};
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    false_alarm::{checkpoint_columns, FalseAlarmFilter, MAX_CHECKPOINT_COUNT},
+    header::{split_payload, ChecksumWriter, TableHeader, TableStats, FORMAT_VERSION, HEADER_SIZE},
+    renderer::Renderer,
+    shard::Shard,
+};
 
 const MAX_SCRATCH_SPACE: usize = 4096;
 type FileSerializer = CompositeSerializer<
-    WriteSerializer<File>,
+    WriteSerializer<ChecksumWriter<Box<dyn FinishWrite>>>,
     FallbackScratch<HeapScratch<MAX_SCRATCH_SPACE>, AllocScratch>,
     SharedSerializeMap,
 >;
 
+/// A writer to a buffer in memory, which is still available once the writer is dropped.
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FinishWrite for SharedBuffer {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A writer that has to be finished once all the data is written.
+pub trait FinishWrite: Write {
+    /// Writes the data remaining in the buffers of the writer.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl FinishWrite for File {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl FinishWrite for zstd::Encoder<'static, File> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        zstd::Encoder::finish(*self)?;
+        Ok(())
+    }
+}
+
 /// Trait that data structures implement to be used as rainbow tables.
 pub trait RainbowTable: Sized + Sync {
     /// The type of the iterator over the chains of the table.
@@ -55,62 +120,418 @@ pub trait RainbowTable: Sized + Sync {
     /// Returns startpoint of the chain if the password was found in the endpoints.
     fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword>;
 
+    /// Searches the endpoints for several passwords at once.
+    /// Returns the startpoints of the chains ending with the passwords, in the same order.
+    /// Tables whose lookups are cheaper in batches, such as compressed tables, override this method.
+    fn search_endpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Option<CompressedPassword>> {
+        passwords
+            .iter()
+            .map(|&password| self.search_endpoints(password))
+            .collect()
+    }
+
+    /// Searches the endpoints for a password like `search_endpoints`,
+    /// but returns the startpoints of all the chains ending with the password.
+    /// Tables storing their startpoints return at most one startpoint,
+    /// but the chains which merged during the generation of a table only storing its endpoints can't be told apart.
+    fn search_startpoints(&self, password: CompressedPassword) -> Vec<CompressedPassword> {
+        self.search_endpoints(password).into_iter().collect()
+    }
+
+    /// Searches the endpoints for several passwords at once like `search_endpoints_batch`,
+    /// but returns all the startpoints of the chains ending with each password like `search_startpoints`.
+    fn search_startpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Vec<CompressedPassword>> {
+        self.search_endpoints_batch(passwords)
+            .into_iter()
+            .map(|startpoint| startpoint.into_iter().collect())
+            .collect()
+    }
+
+    /// Searches the endpoints for a password like `search_endpoints`,
+    /// but returns the position of the chain ending with the password in the order of `iter`.
+    /// False alarm indexes store the checkpoint bits of the chains at these positions.
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize>;
+
     /// Searches for a password in a given column.
     #[inline]
     fn search_column(&self, column: usize, digest: Digest) -> Option<Password> {
-        let ctx = self.ctx();
-        let hash = ctx.hash_type.hash_function();
-        let mut column_digest = digest;
-        let mut column_counter;
-
-        // get the reduction corresponding to the current column
-        for k in column..ctx.t - 2 {
-            column_counter = reduce(column_digest, k, &ctx);
-            let column_plaintext = column_counter.into_password(&ctx);
-            column_digest = hash(column_plaintext);
-        }
-        column_counter = reduce(column_digest, &ctx.t - 2, &ctx);
+        self.search_column_filtered(column, digest, &())
+    }
 
-        let mut chain_plaintext = match self.search_endpoints(column_counter) {
-            None => return None,
-            Some(found) => found.into_password(&ctx),
-        };
-        let mut chain_digest;
+    /// Searches for a password in a given column, using a filter to skip the false alarms.
+    #[inline]
+    fn search_column_filtered(
+        &self,
+        column: usize,
+        digest: Digest,
+        filter: &dyn FalseAlarmFilter,
+    ) -> Option<Password> {
+        search_column(
+            &self.ctx(),
+            column,
+            digest,
+            |endpoint| self.search_startpoints(endpoint),
+            |endpoint| self.search_chain_position(endpoint),
+            filter,
+            &mut SearchStats::default(),
+        )
+    }
 
-        // we found a matching endpoint, reconstruct the chain
-        for k in 0..column {
-            chain_digest = hash(chain_plaintext);
-            let chain_counter = reduce(chain_digest, k, &ctx);
-            chain_plaintext = chain_counter.into_password(&ctx);
-        }
-        chain_digest = hash(chain_plaintext);
+    /// Searches for a password in a given column like `search_column_filtered`,
+    /// and returns the statistics of the search along with the password.
+    #[inline]
+    fn search_column_stats(
+        &self,
+        column: usize,
+        digest: Digest,
+        filter: &dyn FalseAlarmFilter,
+    ) -> (Option<Password>, SearchStats) {
+        let start = Instant::now();
+        let mut stats = SearchStats::default();
+        let password = search_column(
+            &self.ctx(),
+            column,
+            digest,
+            |endpoint| self.search_startpoints(endpoint),
+            |endpoint| self.search_chain_position(endpoint),
+            filter,
+            &mut stats,
+        );
+        stats.duration = start.elapsed();
 
-        // the digest was indeed present in the chain, we found a plaintext matching the digest
-        if chain_digest == digest {
-            Some(chain_plaintext)
-        } else {
-            None
-        }
+        (password, stats)
     }
 
     /// Searches for a password that hashes to the given digest.
     fn search(&self, digest: Digest) -> Option<Password> {
+        search_by_column(self, digest)
+    }
+
+    /// Searches for a password that hashes to the given digest like `search`,
+    /// and returns the statistics of the search along with the password.
+    fn search_with_stats(&self, digest: Digest) -> (Option<Password>, SearchStats) {
         let ctx = self.ctx();
-        (0..ctx.t - 1)
-            .into_par_iter()
-            .rev()
-            .find_map_any(|i| self.search_column(i, digest))
+        let start = Instant::now();
+        let stats = AtomicSearchStats::default();
+        let password = (0..ctx.t - 1).into_par_iter().rev().find_map_any(|i| {
+            let mut column_stats = SearchStats::default();
+            let password = search_column(
+                &ctx,
+                i,
+                digest,
+                |endpoint| self.search_startpoints(endpoint),
+                |_| None,
+                &(),
+                &mut column_stats,
+            );
+            stats.add(column_stats);
+            password
+        });
+
+        let mut stats = stats.into_inner();
+        stats.duration = start.elapsed();
+        (password, stats)
+    }
+
+    /// Searches for a password that hashes to the given digest, computing the endpoints of each column with a backend.
+    /// The endpoints are then looked up and the false alarms are discarded on the CPU.
+    /// False alarm filters are not used.
+    fn search_gpu<T: Backend>(&self, digest: Digest) -> CugparckResult<Option<SearchHit>> {
+        let ctx = self.ctx();
+        let mut renderer = T::renderer(ctx.t, None).map_err(|err| CugparckError::NoBackend {
+            requested: T::NAME,
+            source: Box::new(err),
+        })?;
+        let endpoints = renderer.column_endpoints(digest, ctx)?;
+
+        Ok(search_column_endpoints(self, digest, &endpoints))
+    }
+
+    /// Searches for the passwords of several digests at once.
+    /// The digests are searched column by column: the endpoints of a column are computed in parallel
+    /// and looked up with `search_endpoints_batch`, which is faster than calling `search` for each digest.
+    fn search_many(&self, digests: &[Digest]) -> Vec<Option<Password>> {
+        let ctx = self.ctx();
+        let mut found = vec![None; digests.len()];
+
+        for column in (0..ctx.t - 1).rev() {
+            let remaining = (0..digests.len())
+                .filter(|&i| found[i].is_none())
+                .collect::<Vec<_>>();
+
+            if remaining.is_empty() {
+                break;
+            }
+
+            let endpoints = remaining
+                .par_iter()
+                .map(|&i| column_endpoint(digests[i], column, &ctx))
+                .collect::<Vec<_>>();
+            let startpoints = self.search_startpoints_batch(&endpoints);
+
+            let passwords = remaining
+                .par_iter()
+                .zip(startpoints)
+                .map(|(&i, startpoints)| {
+                    startpoints
+                        .into_iter()
+                        .find_map(|startpoint| rebuild_chain(&ctx, column, digests[i], startpoint))
+                })
+                .collect::<Vec<_>>();
+
+            for (i, password) in remaining.into_iter().zip(passwords) {
+                found[i] = password;
+            }
+        }
+
+        found
     }
 
     /// Returns the context.
     fn ctx(&self) -> RainbowTableCtx;
 
+    /// Counts how many endpoints fall in each of the `buckets` equal slices of the search space.
+    /// Buckets with few endpoints reveal gaps in the coverage of the table.
+    fn coverage_map(&self, buckets: usize) -> Vec<u64> {
+        let n = self.ctx().n as u128;
+        let mut map = vec![0; buckets];
+
+        if buckets == 0 {
+            return map;
+        }
+
+        for chain in self.iter() {
+            let bucket = chain.endpoint.get() as u128 * buckets as u128 / n;
+            map[bucket as usize] += 1;
+        }
+
+        map
+    }
+
     /// Returns a new rainbow table created from the table passed as a parameter.
-    fn from_rainbow_table<T: RainbowTable>(table: T) -> Self;
+    /// The source table is only borrowed, so it can be an archived table that is still memory-mapped.
+    fn from_rainbow_table<T: RainbowTable>(table: &T) -> Self;
 
     /// Transforms this rainbow table into another rainbow table.
     fn into_rainbow_table<T: RainbowTable>(self) -> T {
-        T::from_rainbow_table(self)
+        T::from_rainbow_table(&self)
+    }
+}
+
+/// A password found in a rainbow table, along with where it was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchHit {
+    /// The password found.
+    pub password: Password,
+    /// The number of the table containing the password.
+    pub table_number: usize,
+    /// The column of the chain containing the password.
+    pub column: usize,
+}
+
+/// Statistics about the searches of a table, to see where an attack spends its time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of columns searched.
+    pub columns: usize,
+    /// The number of matching endpoints whose chain didn't contain the password.
+    pub false_alarms: usize,
+    /// The false alarms skipped by a false alarm filter, without rebuilding their chain.
+    /// They are also counted in `false_alarms`.
+    pub filtered_false_alarms: usize,
+    /// The time spent searching.
+    /// The time of the columns searched in parallel by a `TableCluster` is summed,
+    /// so it can be longer than the search itself.
+    pub duration: Duration,
+}
+
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, other: Self) {
+        self.columns += other.columns;
+        self.false_alarms += other.false_alarms;
+        self.filtered_false_alarms += other.filtered_false_alarms;
+        self.duration += other.duration;
+    }
+}
+
+/// Statistics about the searches of a table, added up by the threads of a search without locking.
+#[derive(Default)]
+pub(crate) struct AtomicSearchStats {
+    columns: AtomicUsize,
+    false_alarms: AtomicUsize,
+    filtered_false_alarms: AtomicUsize,
+    /// The time spent searching, in nanoseconds.
+    nanos: AtomicU64,
+}
+
+impl AtomicSearchStats {
+    /// Adds the statistics of a search.
+    pub(crate) fn add(&self, stats: SearchStats) {
+        // the counters are only read once the search is over
+        self.columns.fetch_add(stats.columns, Ordering::Relaxed);
+        self.false_alarms
+            .fetch_add(stats.false_alarms, Ordering::Relaxed);
+        self.filtered_false_alarms
+            .fetch_add(stats.filtered_false_alarms, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(stats.duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns the statistics added up.
+    pub(crate) fn into_inner(self) -> SearchStats {
+        SearchStats {
+            columns: self.columns.into_inner(),
+            false_alarms: self.false_alarms.into_inner(),
+            filtered_false_alarms: self.filtered_false_alarms.into_inner(),
+            duration: Duration::from_nanos(self.nanos.into_inner()),
+        }
+    }
+}
+
+/// Searches for a password that hashes to the given digest in each column of a table, in parallel.
+/// This is the default implementation of `RainbowTable::search`.
+pub(crate) fn search_by_column<T: RainbowTable>(table: &T, digest: Digest) -> Option<Password> {
+    let ctx = table.ctx();
+    (0..ctx.t - 1)
+        .into_par_iter()
+        .rev()
+        .find_map_any(|i| table.search_column(i, digest))
+}
+
+/// Searches for a password that hashes to the given digest,
+/// from the endpoints of the chains containing the digest at each column, given in column order.
+/// The endpoints are looked up at once with `search_startpoints_batch`.
+pub(crate) fn search_column_endpoints<T: RainbowTable>(
+    table: &T,
+    digest: Digest,
+    endpoints: &[CompressedPassword],
+) -> Option<SearchHit> {
+    let ctx = table.ctx();
+    let startpoints = table.search_startpoints_batch(endpoints);
+
+    startpoints
+        .into_par_iter()
+        .enumerate()
+        .rev()
+        .find_map_any(|(column, startpoints)| {
+            let password = startpoints
+                .into_iter()
+                .find_map(|startpoint| rebuild_chain(&ctx, column, digest, startpoint))?;
+            Some(SearchHit {
+                password,
+                table_number: ctx.tn,
+                column,
+            })
+        })
+}
+
+/// Searches for a password in a given column, using `search_startpoints` to find
+/// the startpoints of the chains ending with a given endpoint.
+/// The chains whose checkpoint bits don't match according to `filter` are not regenerated.
+/// The bits are looked up at the position of the chain given by `chain_position`,
+/// which is only called when the filter has checkpoints.
+/// The column and the false alarms encountered are counted in `stats`, but the time isn't measured.
+#[inline]
+pub(crate) fn search_column<I: IntoIterator<Item = CompressedPassword>>(
+    ctx: &RainbowTableCtx,
+    column: usize,
+    digest: Digest,
+    search_startpoints: impl FnOnce(CompressedPassword) -> I,
+    chain_position: impl FnOnce(CompressedPassword) -> Option<usize>,
+    filter: &dyn FalseAlarmFilter,
+    stats: &mut SearchStats,
+) -> Option<Password> {
+    stats.columns += 1;
+    let hash = ctx.hash_function();
+    let mut column_digest = digest;
+    let mut column_counter;
+
+    let mut checkpoints = [0; MAX_CHECKPOINT_COUNT];
+    let checkpoint_count = filter.checkpoint_count();
+    for (checkpoint, column) in checkpoints
+        .iter_mut()
+        .zip(checkpoint_columns(ctx.t, checkpoint_count))
+    {
+        *checkpoint = column;
+    }
+    let checkpoints = &checkpoints[..checkpoint_count];
+    let mut checkpoint_bits = 0;
+
+    // get the reduction corresponding to the current column
+    for k in column..ctx.t - 2 {
+        column_counter = reduce(column_digest, k, ctx);
+
+        // the password at column k + 1 is known, record its checkpoint bit
+        for (i, _) in checkpoints.iter().enumerate().filter(|(_, c)| **c == k + 1) {
+            checkpoint_bits |= ((column_counter.get() & 1) as u8) << i;
+        }
+
+        let column_plaintext = column_counter.into_password(ctx);
+        column_digest = ctx.hash_with(hash, &column_plaintext);
+    }
+    column_counter = reduce(column_digest, ctx.t - 2, ctx);
+
+    let mut startpoints = search_startpoints(column_counter).into_iter().peekable();
+    startpoints.peek()?;
+
+    // only the checkpoints after the column are known from the digest
+    let mask = checkpoints
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c > column)
+        .fold(0, |mask, (i, _)| mask | 1 << i);
+
+    if checkpoint_count > 0 {
+        let bits = chain_position(column_counter).and_then(|i| filter.checkpoint_bits(i));
+        if let Some(bits) = bits {
+            if (bits ^ checkpoint_bits) & mask != 0 {
+                stats.false_alarms += 1;
+                stats.filtered_false_alarms += 1;
+                return None;
+            }
+        }
+    }
+
+    let password =
+        startpoints.find_map(|startpoint| rebuild_chain(ctx, column, digest, startpoint));
+    if password.is_none() {
+        stats.false_alarms += 1;
+    }
+
+    password
+}
+
+/// Rebuilds the chain starting with `startpoint` up to the given column.
+/// Returns the password at this column if it hashes to the digest, or None if the endpoint match was a false alarm.
+fn rebuild_chain(
+    ctx: &RainbowTableCtx,
+    column: usize,
+    digest: Digest,
+    startpoint: CompressedPassword,
+) -> Option<Password> {
+    let hash = ctx.hash_function();
+    let mut chain_plaintext = startpoint.into_password(ctx);
+    let mut chain_digest;
+
+    // we found a matching endpoint, reconstruct the chain
+    for k in 0..column {
+        chain_digest = ctx.hash_with(hash, &chain_plaintext);
+        let chain_counter = reduce(chain_digest, k, ctx);
+        chain_plaintext = chain_counter.into_password(ctx);
+    }
+    chain_digest = ctx.hash_with(hash, &chain_plaintext);
+
+    // the digest was indeed present in the chain, we found a plaintext matching the digest
+    if chain_digest == digest {
+        Some(chain_plaintext)
+    } else {
+        None
     }
 }
 
@@ -119,30 +540,408 @@ pub trait RainbowTableStorage: Sized + Serialize<FileSerializer>
 where
     for<'a> Self::Archived: CheckBytes<DefaultValidator<'a>>,
 {
+    /// Returns the context written in the header of the stored file.
+    fn header_ctx(&self) -> RainbowTableCtx;
+
+    /// Returns the context of an archived table, which should match the one written in the header.
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx;
+
+    /// Returns the number of chains written in the header of the stored file.
+    fn header_chain_count(&self) -> usize;
+
+    /// Returns the shard written in the header of the stored file, if the table is only a part of a table.
+    fn header_shard(&self) -> Option<Shard> {
+        None
+    }
+
+    /// Returns the generation statistics written in the header of the stored file, if they are known.
+    fn header_stats(&self) -> Option<TableStats> {
+        None
+    }
+
     /// Stores the rainbow table to the given path.
+    /// The data is preceded by a header containing the context and a checksum of the data.
     fn store(&self, path: &Path) -> CugparckResult<()> {
-        let file = File::options()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(path)?;
-
-        let mut serializer = FileSerializer::new(
-            WriteSerializer::new(file),
-            FallbackScratch::default(),
-            SharedSerializeMap::default(),
-        );
+        store_table(self, path, |file| Ok(Box::new(file)))
+    }
 
-        serializer
-            .serialize_value(self)
-            .map_err(|_| CugparckError::Serialize)?;
+    /// Stores the rainbow table to the given path, compressing the data with zstd at the given level.
+    /// The header is not compressed, so it can still be read with `TableHeader::read`.
+    /// The table has to be decompressed with `decompress_zstd` before being loaded.
+    #[cfg(feature = "zstd")]
+    fn store_zstd(&self, path: &Path, level: i32) -> CugparckResult<()> {
+        store_table(self, path, |file| {
+            Ok(Box::new(zstd::Encoder::new(file, level)?))
+        })
+    }
 
-        Ok(())
+    /// Returns the bytes of the rainbow table, as they would be stored by `RainbowTableStorage::store`.
+    /// The table can be loaded back with `RainbowTableStorage::load`, once the bytes are copied to an aligned buffer.
+    fn to_bytes(&self) -> CugparckResult<Vec<u8>> {
+        let buffer = Rc::new(RefCell::new(vec![0; HEADER_SIZE]));
+        let header = serialize_table(self, Box::new(SharedBuffer(buffer.clone())))?;
+
+        let mut bytes = buffer.take();
+        bytes[..HEADER_SIZE].copy_from_slice(&header.to_bytes());
+
+        Ok(bytes)
     }
 
     /// Tries to zero-copy load the rainbow table from a byte slice.
+    /// Files stored without a header by older versions can't be loaded anymore,
+    /// and fail with `CugparckError::UnsupportedVersion(0)`: they have to be generated again.
     #[inline]
     fn load(bytes: &[u8]) -> CugparckResult<&Self::Archived> {
-        check_archived_root::<Self>(bytes).map_err(|_| CugparckError::Check)
+        let (header, payload) = split_payload(bytes)?;
+        let archived = check_archived_root::<Self>(payload).map_err(|_| CugparckError::Check)?;
+
+        // the header is also read on its own, so it shouldn't describe another table than the archived one.
+        if Self::archived_ctx(archived) != header.ctx {
+            return Err(CugparckError::Check);
+        }
+
+        Ok(archived)
+    }
+
+    /// Loads a table from its bytes without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes should have been loaded successfully by `RainbowTableStorage::load` before.
+    unsafe fn load_unchecked(bytes: &[u8]) -> &Self::Archived {
+        archived_root::<Self>(&bytes[HEADER_SIZE..])
+    }
+}
+
+/// Stores a rainbow table to the given path, writing the data through the writer returned by `writer`.
+/// The data is preceded by a header containing the context and a checksum of the data.
+fn store_table<T: RainbowTableStorage>(
+    table: &T,
+    path: &Path,
+    writer: impl FnOnce(File) -> CugparckResult<Box<dyn FinishWrite>>,
+) -> CugparckResult<()>
+where
+    for<'a> T::Archived: CheckBytes<DefaultValidator<'a>>,
+{
+    store_table_with(table, path, writer, |serializer| {
+        serialize_value(serializer, table)
+    })
+}
+
+/// Stores a rainbow table to the given path like `store_table`, but its data is written by `serialize`.
+/// The table only describes the header, so the data can be written from somewhere else than the memory,
+/// as long as it is the archived version of the table.
+fn store_table_with<T: RainbowTableStorage>(
+    table: &T,
+    path: &Path,
+    writer: impl FnOnce(File) -> CugparckResult<Box<dyn FinishWrite>>,
+    serialize: impl FnOnce(&mut FileSerializer) -> CugparckResult<()>,
+) -> CugparckResult<()>
+where
+    for<'a> T::Archived: CheckBytes<DefaultValidator<'a>>,
+{
+    let mut file = File::options()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)?;
+
+    // the header is written once the checksum is known.
+    file.write_all(&[0; HEADER_SIZE])?;
+    let header = serialize_table_with(table, writer(file.try_clone()?)?, serialize)?;
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header.to_bytes())?;
+
+    Ok(())
+}
+
+/// Serializes a rainbow table through `writer`, and returns the header describing the data written.
+fn serialize_table<T: RainbowTableStorage>(
+    table: &T,
+    writer: Box<dyn FinishWrite>,
+) -> CugparckResult<TableHeader>
+where
+    for<'a> T::Archived: CheckBytes<DefaultValidator<'a>>,
+{
+    serialize_table_with(table, writer, |serializer| {
+        serialize_value(serializer, table)
+    })
+}
+
+/// Writes the data of a rainbow table through `writer` with `serialize`, and returns the header describing the data written.
+fn serialize_table_with<T: RainbowTableStorage>(
+    table: &T,
+    writer: Box<dyn FinishWrite>,
+    serialize: impl FnOnce(&mut FileSerializer) -> CugparckResult<()>,
+) -> CugparckResult<TableHeader>
+where
+    for<'a> T::Archived: CheckBytes<DefaultValidator<'a>>,
+{
+    let mut serializer = FileSerializer::new(
+        WriteSerializer::new(ChecksumWriter::new(writer)),
+        FallbackScratch::default(),
+        SharedSerializeMap::default(),
+    );
+
+    serialize(&mut serializer)?;
+
+    let (writer, _, _) = serializer.into_components();
+    let (writer, checksum, payload_len) = writer.into_inner().finalize();
+
+    // the writer has to be done with the data before the header is written.
+    writer.finish()?;
+
+    Ok(TableHeader {
+        version: FORMAT_VERSION,
+        checksum,
+        payload_len,
+        chain_count: table.header_chain_count() as u64,
+        ctx: table.header_ctx(),
+        shard: table.header_shard(),
+        stats: table.header_stats(),
+    })
+}
+
+/// Serializes a value and its dependencies as the root of the archive.
+fn serialize_value<T: Serialize<FileSerializer>>(
+    serializer: &mut FileSerializer,
+    value: &T,
+) -> CugparckResult<()> {
+    serializer
+        .serialize_value(value)
+        .map_err(|_| CugparckError::Serialize)?;
+
+    Ok(())
+}
+
+/// Compresses the bytes of a stored table with zstd at the given level, and stores them to the given path.
+/// The result is the same as with `RainbowTableStorage::store_zstd`,
+/// but the table is compressed as it is read instead of being deserialized first.
+#[cfg(feature = "zstd")]
+pub fn compress_zstd(bytes: &[u8], path: &Path, level: i32) -> CugparckResult<()> {
+    split_payload(bytes)?;
+
+    let mut file = File::create(path)?;
+    file.write_all(&bytes[..HEADER_SIZE])?;
+    zstd::stream::copy_encode(&bytes[HEADER_SIZE..], &mut file, level)?;
+
+    Ok(())
+}
+
+/// Returns the length of a table stored with `RainbowTableStorage::store_zstd` once decompressed, in bytes.
+/// `bytes` should contain the stored table, or at least its header.
+#[cfg(feature = "zstd")]
+pub fn zstd_decompressed_len(bytes: &[u8]) -> CugparckResult<usize> {
+    let header = TableHeader::from_bytes(bytes)?.ok_or(CugparckError::UnsupportedVersion(0))?;
+    Ok(HEADER_SIZE + header.payload_len as usize)
+}
+
+/// Decompresses a table stored with `RainbowTableStorage::store_zstd` to `out`,
+/// which should be exactly `zstd_decompressed_len` bytes long.
+/// The decompressed table can then be loaded with `RainbowTableStorage::load`.
+#[cfg(feature = "zstd")]
+pub fn decompress_zstd(bytes: &[u8], out: &mut [u8]) -> CugparckResult<()> {
+    use std::io::Read;
+
+    if out.len() != zstd_decompressed_len(bytes)? {
+        return Err(CugparckError::Check);
+    }
+
+    let (header, payload) = out.split_at_mut(HEADER_SIZE);
+    header.copy_from_slice(&bytes[..HEADER_SIZE]);
+    zstd::Decoder::new(&bytes[HEADER_SIZE..])?
+        .read_exact(payload)
+        .map_err(|_| CugparckError::Checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::{CompressedPassword, HashType, SaltPosition};
+
+    use crate::{backend::Cpu, RainbowTable, RainbowTableCtxBuilder, SimpleTable};
+
+    #[test]
+    fn test_coverage_map() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(6)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let map = table.coverage_map(4);
+
+        assert_eq!(table.len() as u64, map.iter().sum::<u64>());
+
+        // the reduction function spreads the endpoints uniformly over the search space.
+        let mean = table.len() as f64 / map.len() as f64;
+        for count in map {
+            assert!((count as f64 - mean).abs() < mean * 0.25);
+        }
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        use std::fs;
+
+        use rkyv::AlignedVec;
+
+        use crate::{temp_path, CompressedTable, RainbowTableStorage};
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(5)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        // the bytes are the same as the stored file
+        let path = temp_path("to_bytes.rt");
+        table.store(&path).unwrap();
+        let stored = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(stored, table.to_bytes().unwrap());
+
+        let compressed = CompressedTable::from_rainbow_table(&table);
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&compressed.to_bytes().unwrap());
+        let loaded = CompressedTable::load(&bytes).unwrap();
+        assert_eq!(table.len(), loaded.len());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_store_zstd() {
+        use std::fs;
+
+        use rkyv::AlignedVec;
+
+        use crate::{
+            compress_zstd, decompress_zstd, temp_path, zstd_decompressed_len, RainbowTableStorage,
+            TableHeader,
+        };
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(5)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let path = temp_path("store_zstd.rt");
+        let zstd_path = path.with_extension("rtz");
+        table.store(&path).unwrap();
+        table.store_zstd(&zstd_path, 3).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let zstd_bytes = fs::read(&zstd_path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // the stored table can also be compressed without deserializing it
+        compress_zstd(&bytes, &zstd_path, 3).unwrap();
+        assert_eq!(zstd_bytes, fs::read(&zstd_path).unwrap());
+        fs::remove_file(&zstd_path).unwrap();
+
+        // the header can be read without decompressing the table
+        assert!(zstd_bytes.len() < bytes.len());
+        assert_eq!(
+            TableHeader::from_bytes(&bytes).unwrap(),
+            TableHeader::from_bytes(&zstd_bytes).unwrap()
+        );
+
+        let mut decompressed = AlignedVec::new();
+        decompressed.resize(zstd_decompressed_len(&zstd_bytes).unwrap(), 0);
+        decompress_zstd(&zstd_bytes, &mut decompressed).unwrap();
+        assert_eq!(bytes, decompressed.as_slice());
+
+        let loaded = SimpleTable::load(&decompressed).unwrap();
+        assert_eq!(table.len(), loaded.len());
+
+        // a truncated table can't be decompressed
+        let truncated = &zstd_bytes[..zstd_bytes.len() / 2];
+        assert!(decompress_zstd(truncated, &mut decompressed).is_err());
+    }
+
+    #[test]
+    fn test_search_many() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let digests = (0..ctx.n)
+            .step_by(7)
+            .map(|i| ctx.hash(&CompressedPassword::from(i).into_password(&ctx)))
+            .collect::<Vec<_>>();
+
+        let found = table.search_many(&digests);
+        assert_eq!(digests.len(), found.len());
+
+        for (digest, password) in digests.iter().zip(found) {
+            assert_eq!(table.search(*digest).is_some(), password.is_some());
+
+            if let Some(password) = password {
+                assert_eq!(*digest, ctx.hash(&password));
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_gpu() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        for i in (0..ctx.n).step_by(7) {
+            let digest = ctx.hash(&CompressedPassword::from(i).into_password(&ctx));
+            let found = table.search_gpu::<Cpu>(digest).unwrap();
+            assert_eq!(table.search(digest).is_some(), found.is_some());
+
+            if let Some(hit) = found {
+                assert_eq!(digest, ctx.hash(&hit.password));
+                assert_eq!(ctx.tn, hit.table_number);
+                assert_eq!(Some(hit.password), table.search_column(hit.column, digest));
+            }
+        }
+    }
+
+    #[test]
+    fn test_salted_search() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .hash(HashType::Md5)
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .salt(b"salt", SaltPosition::Prefix)
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let mut found = 0;
+        for i in (0..ctx.n).step_by(7) {
+            let password = CompressedPassword::from(i).into_password(&ctx);
+            let mut salted = b"salt".to_vec();
+            salted.extend_from_slice(&password);
+            let digest = HashType::Md5.hash_function()(&salted);
+
+            if let Some(plaintext) = table.search(digest) {
+                assert_eq!(password, plaintext);
+                found += 1;
+            }
+        }
+
+        assert!(found > 0);
     }
 }
@@ -0,0 +1,246 @@
+use cugparck_commons::{CompressedPassword, Digest, Password, RainbowChain, RainbowTableCtx};
+
+use super::{
+    ArchivedCompressedTable, ArchivedSimpleTable, ArchivedTruncatedTable, CompressedTable,
+    RainbowTable, RainbowTableStorage, SimpleTable, TruncatedTable,
+};
+use crate::error::CugparckResult;
+
+/// An archived rainbow table of any format.
+/// Tables of different formats can then be searched together, for instance in the same `TableCluster`.
+#[derive(Clone, Copy)]
+pub enum AnyTable<'a> {
+    Simple(&'a ArchivedSimpleTable),
+    Compressed(&'a ArchivedCompressedTable),
+    Truncated(&'a ArchivedTruncatedTable),
+}
+
+impl<'a> AnyTable<'a> {
+    /// Loads a simple table from its bytes.
+    pub fn load_simple(bytes: &'a [u8]) -> CugparckResult<Self> {
+        Ok(Self::Simple(SimpleTable::load(bytes)?))
+    }
+
+    /// Loads a compressed delta encoding table from its bytes.
+    pub fn load_compressed(bytes: &'a [u8]) -> CugparckResult<Self> {
+        Ok(Self::Compressed(CompressedTable::load(bytes)?))
+    }
+
+    /// Loads a truncated table from its bytes.
+    pub fn load_truncated(bytes: &'a [u8]) -> CugparckResult<Self> {
+        Ok(Self::Truncated(TruncatedTable::load(bytes)?))
+    }
+
+    /// Loads a simple table from its bytes without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes should have been loaded successfully by `AnyTable::load_simple` before.
+    pub unsafe fn load_simple_unchecked(bytes: &'a [u8]) -> Self {
+        Self::Simple(SimpleTable::load_unchecked(bytes))
+    }
+
+    /// Loads a compressed delta encoding table from its bytes without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes should have been loaded successfully by `AnyTable::load_compressed` before.
+    pub unsafe fn load_compressed_unchecked(bytes: &'a [u8]) -> Self {
+        Self::Compressed(CompressedTable::load_unchecked(bytes))
+    }
+
+    /// Loads a truncated table from its bytes without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes should have been loaded successfully by `AnyTable::load_truncated` before.
+    pub unsafe fn load_truncated_unchecked(bytes: &'a [u8]) -> Self {
+        Self::Truncated(TruncatedTable::load_unchecked(bytes))
+    }
+}
+
+/// An iterator over the chains of a table of any format.
+pub enum AnyTableIterator<'a> {
+    Simple(<ArchivedSimpleTable as RainbowTable>::Iter<'a>),
+    Compressed(<ArchivedCompressedTable as RainbowTable>::Iter<'a>),
+    Truncated(Box<<ArchivedTruncatedTable as RainbowTable>::Iter<'a>>),
+}
+
+impl Iterator for AnyTableIterator<'_> {
+    type Item = RainbowChain;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Simple(iter) => iter.next(),
+            Self::Compressed(iter) => iter.next(),
+            Self::Truncated(iter) => iter.next(),
+        }
+    }
+}
+
+impl RainbowTable for AnyTable<'_> {
+    type Iter<'b>
+        = AnyTableIterator<'b>
+    where
+        Self: 'b;
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Simple(table) => table.len(),
+            Self::Compressed(table) => table.len(),
+            Self::Truncated(table) => table.len(),
+        }
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        match self {
+            Self::Simple(table) => AnyTableIterator::Simple(table.iter()),
+            Self::Compressed(table) => AnyTableIterator::Compressed(table.iter()),
+            Self::Truncated(table) => AnyTableIterator::Truncated(Box::new(table.iter())),
+        }
+    }
+
+    fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword> {
+        match self {
+            Self::Simple(table) => table.search_endpoints(password),
+            Self::Compressed(table) => table.search_endpoints(password),
+            Self::Truncated(table) => table.search_endpoints(password),
+        }
+    }
+
+    fn search_endpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Option<CompressedPassword>> {
+        match self {
+            Self::Simple(table) => table.search_endpoints_batch(passwords),
+            Self::Compressed(table) => table.search_endpoints_batch(passwords),
+            Self::Truncated(table) => table.search_endpoints_batch(passwords),
+        }
+    }
+
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        match self {
+            Self::Simple(table) => table.search_chain_position(password),
+            Self::Compressed(table) => table.search_chain_position(password),
+            Self::Truncated(table) => table.search_chain_position(password),
+        }
+    }
+
+    fn search_startpoints(&self, password: CompressedPassword) -> Vec<CompressedPassword> {
+        match self {
+            Self::Simple(table) => table.search_startpoints(password),
+            Self::Compressed(table) => table.search_startpoints(password),
+            Self::Truncated(table) => table.search_startpoints(password),
+        }
+    }
+
+    fn search_startpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Vec<CompressedPassword>> {
+        match self {
+            Self::Simple(table) => table.search_startpoints_batch(passwords),
+            Self::Compressed(table) => table.search_startpoints_batch(passwords),
+            Self::Truncated(table) => table.search_startpoints_batch(passwords),
+        }
+    }
+
+    fn search(&self, digest: Digest) -> Option<Password> {
+        match self {
+            Self::Simple(table) => table.search(digest),
+            Self::Compressed(table) => table.search(digest),
+            Self::Truncated(table) => table.search(digest),
+        }
+    }
+
+    fn ctx(&self) -> RainbowTableCtx {
+        match self {
+            Self::Simple(table) => table.ctx(),
+            Self::Compressed(table) => table.ctx(),
+            Self::Truncated(table) => table.ctx(),
+        }
+    }
+
+    fn from_rainbow_table<T: RainbowTable>(_: &T) -> Self {
+        panic!("Archived tables cannot be built from other tables")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cugparck_commons::Password;
+    use rkyv::AlignedVec;
+
+    use crate::{
+        backend::Cpu, temp_path, CompressedTable, RainbowTable, RainbowTableCtxBuilder,
+        RainbowTableStorage, SimpleTable, TableCluster, TruncatedTable,
+    };
+
+    use super::AnyTable;
+
+    fn read(path: &std::path::Path) -> AlignedVec {
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(path).unwrap());
+        fs::remove_file(path).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn test_mixed_cluster() {
+        let builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef");
+
+        let simple =
+            SimpleTable::new_blocking::<Cpu>(builder.table_number(1).build().unwrap()).unwrap();
+        let path = temp_path("any_table_1.rt");
+        simple.store(&path).unwrap();
+        let simple_bytes = read(&path);
+
+        let compressed = SimpleTable::new_blocking::<Cpu>(builder.table_number(2).build().unwrap())
+            .unwrap()
+            .into_rainbow_table::<CompressedTable>();
+        let path = temp_path("any_table_2.rtcde");
+        compressed.store(&path).unwrap();
+        let compressed_bytes = read(&path);
+
+        let truncated = SimpleTable::new_blocking::<Cpu>(builder.table_number(3).build().unwrap())
+            .unwrap()
+            .into_rainbow_table::<TruncatedTable>();
+        let path = temp_path("any_table_3.rtt");
+        truncated.store(&path).unwrap();
+        let truncated_bytes = read(&path);
+
+        let tables = [
+            AnyTable::load_simple(&simple_bytes).unwrap(),
+            AnyTable::load_compressed(&compressed_bytes).unwrap(),
+            AnyTable::load_truncated(&truncated_bytes).unwrap(),
+        ];
+        assert_eq!(
+            simple.len() + compressed.len() + truncated.len(),
+            tables
+                .iter()
+                .map(|table| table.iter().count())
+                .sum::<usize>()
+        );
+
+        let ctx = simple.ctx();
+        let tables = tables.iter().collect::<Vec<_>>();
+        let cluster = TableCluster::new(&tables);
+        for password in ["abc", "fed", "aaaa", "cafe"] {
+            let password = Password::new(password.as_bytes());
+            let digest = ctx.hash(&password);
+            let expected = simple
+                .search(digest)
+                .or_else(|| compressed.search(digest))
+                .or_else(|| truncated.search(digest));
+
+            assert_eq!(expected.is_some(), cluster.search(digest).is_some());
+        }
+    }
+}
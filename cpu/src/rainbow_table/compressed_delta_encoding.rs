@@ -1,17 +1,107 @@
-use std::iter::{self, Enumerate};
-
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    iter::{self, Enumerate, Peekable},
+    mem,
+    path::{Path, PathBuf},
+    vec,
+};
+
+use crate::parallel::*;
 use bitvec::prelude::*;
 use bytecheck::CheckBytes;
-use cugparck_commons::{CompressedPassword, RainbowChain, RainbowTableCtx};
+use cugparck_commons::{
+    column_endpoint, ArchivedStartpointOrder, CompressedPassword, Digest, FeistelPermutation,
+    Password, RainbowChain, RainbowTableCtx,
+};
 use itertools::{Itertools, PeekingNext};
-use rayon::prelude::*;
-use rkyv::{Archive, Deserialize, Infallible, Serialize};
-
-use super::{RainbowTable, RainbowTableStorage};
+use rkyv::{
+    ser::{ScratchSpace, Serializer},
+    with::Skip,
+    Archive, Deserialize, Infallible, Serialize,
+};
+
+use super::{
+    search_by_column, search_column_endpoints, store_table_with,
+    stream::{
+        read_chunks, BitSink, BitSpill, SortedChains, StreamBits, StreamVec, StreamVecResolver,
+    },
+    RainbowTable, RainbowTableStorage,
+};
+use crate::{
+    error::{CugparckError, CugparckResult},
+    event::{ProgressCounter, ProgressListener},
+    header::{TableStats, HEADER_SIZE},
+};
 
 /// An arbitrary block size.
 const BLOCK_SIZE: usize = 256;
 
+/// The number of chains between two samples of the endpoints.
+/// A search decodes at most this many endpoints once it has found the closest sample.
+const SAMPLE_INTERVAL: usize = 32;
+
+/// An endpoint stored uncompressed, so that the endpoints can be decoded from the middle of a block.
+#[derive(Archive, Serialize, Deserialize)]
+#[archive_attr(derive(CheckBytes))]
+struct Sample {
+    /// The number of the chain of the endpoint.
+    chain: usize,
+    endpoint: usize,
+    /// The bit address of the next endpoint.
+    bit_address: usize,
+}
+
+/// Samples written to a spill file as they are taken, to be copied to an archive once they are all known.
+struct SampleSpill {
+    path: PathBuf,
+    file: BufWriter<File>,
+    len: usize,
+}
+
+impl SampleSpill {
+    /// Creates a new spill file at the given path.
+    fn new(path: PathBuf) -> CugparckResult<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(&path)?),
+            path,
+            len: 0,
+        })
+    }
+
+    /// Appends samples to the file.
+    fn extend(&mut self, samples: impl Iterator<Item = Sample>) -> CugparckResult<()> {
+        for sample in samples {
+            for value in [sample.chain, sample.endpoint, sample.bit_address] {
+                self.file.write_all(&(value as u64).to_le_bytes())?;
+            }
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the samples to an archive, as a `Vec<Sample>` wrapped with `StreamVec` would be.
+    fn serialize<S>(mut self, serializer: &mut S) -> CugparckResult<StreamVecResolver>
+    where
+        S: Serializer + ScratchSpace + ?Sized,
+    {
+        self.file.flush()?;
+
+        let value = |bytes: &[u8], i: usize| {
+            u64::from_le_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap()) as usize
+        };
+        let samples = read_chunks(&self.path, self.len, 24, |bytes| Sample {
+            chain: value(bytes, 0),
+            endpoint: value(bytes, 1),
+            bit_address: value(bytes, 2),
+        })?;
+
+        StreamVecResolver::from_chunks(samples, serializer)
+    }
+}
+
 /// An index to keep track of the different blocks used to store the endpoints.
 #[derive(Archive, Serialize, Deserialize)]
 #[archive_attr(derive(CheckBytes))]
@@ -29,7 +119,8 @@ impl Index {
             .log2()
             .ceil() as usize;
 
-        let chain_number_size = m.log2().ceil().max(1.) as usize;
+        // the chain numbers go up to m included, for the last entry
+        let chain_number_size = (m + 1.).log2().ceil() as usize;
 
         Self {
             len: 0,
@@ -83,18 +174,130 @@ impl ArchivedIndex {
     }
 }
 
+/// Statistics about how the endpoints of a compressed delta encoding table are distributed and encoded,
+/// to study the behavior of the chains and of the compression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingStats {
+    /// The Rice parameter `k` used to encode the differences between the endpoints.
+    pub rice_parameter: u8,
+    /// The average number of bits per endpoint expected with this Rice parameter.
+    pub expected_bits_per_endpoint: f64,
+    /// The span of the search space covered by each block.
+    pub block_span: usize,
+    /// The number of chains stored in each block.
+    /// The last block holds the endpoints exceeding the other blocks because of the integer rounding.
+    pub block_chains: Vec<usize>,
+    /// How many endpoints are encoded with each number of bits, indexed by the number of bits.
+    /// An endpoint costs `q + 1 + k` bits, where `q` is the quotient of its difference with the previous endpoint by `2^k`.
+    pub bit_costs: Vec<usize>,
+    /// The average difference between two consecutive endpoints of a block, as Rice encoded.
+    pub mean_delta: f64,
+}
+
+impl EncodingStats {
+    /// Computes the statistics from the sorted endpoints of a table.
+    fn from_endpoints(
+        endpoints: impl Iterator<Item = CompressedPassword>,
+        n: usize,
+        l: usize,
+        k: u8,
+        m: usize,
+    ) -> Self {
+        let block_span = n / l;
+        let mut block_chains = vec![0; l + 1];
+        let mut bit_costs = Vec::new();
+        let mut delta_sum = 0.;
+        let mut last = None;
+
+        for endpoint in endpoints {
+            let block = CompressedTable::password_block(endpoint, l, n).min(l);
+            block_chains[block] += 1;
+
+            // the same differences as in `CompressedTable::store_block`
+            let diff = match last {
+                Some((last_block, last_endpoint)) if last_block == block => {
+                    endpoint.get() - last_endpoint - 1
+                }
+                _ => endpoint.get() - block * block_span,
+            };
+            last = Some((block, endpoint.get()));
+            delta_sum += diff as f64;
+
+            let bits = (diff >> k) + 1 + k as usize;
+            if bit_costs.len() <= bits {
+                bit_costs.resize(bits + 1, 0);
+            }
+            bit_costs[bits] += 1;
+        }
+
+        Self {
+            rice_parameter: k,
+            expected_bits_per_endpoint: CompressedTable::optimal_rice_parameter_rate(
+                n as f64, m as f64, k,
+            ),
+            block_span,
+            block_chains,
+            bit_costs,
+            mean_delta: delta_sum / m.max(1) as f64,
+        }
+    }
+
+    /// Returns the total number of bits used to encode the endpoints.
+    pub fn endpoint_bits(&self) -> usize {
+        self.bit_costs
+            .iter()
+            .enumerate()
+            .map(|(bits, count)| bits * count)
+            .sum()
+    }
+
+    /// Returns the average number of bits used to encode an endpoint.
+    pub fn bits_per_endpoint(&self) -> f64 {
+        let count = self.bit_costs.iter().sum::<usize>();
+        self.endpoint_bits() as f64 / count.max(1) as f64
+    }
+}
+
+/// Counts how many endpoints fall in each of the `buckets` equal slices of the search space.
+fn endpoint_histogram(
+    endpoints: impl Iterator<Item = CompressedPassword>,
+    n: usize,
+    buckets: usize,
+) -> Vec<u64> {
+    let mut histogram = vec![0; buckets];
+
+    if buckets == 0 {
+        return histogram;
+    }
+
+    for endpoint in endpoints {
+        let bucket = endpoint.get() as u128 * buckets as u128 / n as u128;
+        histogram[bucket as usize] += 1;
+    }
+
+    histogram
+}
+
 /// A rainbow table using compressed delta encoding.
 #[derive(Archive, Deserialize, Serialize)]
 #[archive_attr(derive(CheckBytes))]
 pub struct CompressedTable {
     ctx: RainbowTableCtx,
     pub index: Index,
+    #[with(StreamVec)]
+    samples: Vec<Sample>,
+    #[with(StreamBits)]
     startpoints: BitVec,
+    #[with(StreamBits)]
     endpoints: BitVec,
     l: usize,
     k: u8,
     m: usize,
     password_bits: u8,
+    /// Statistics about the generation of the table.
+    /// They are stored in the header, so they aren't archived.
+    #[with(Skip)]
+    stats: Option<TableStats>,
 }
 
 impl CompressedTable {
@@ -109,18 +312,21 @@ impl CompressedTable {
 
     /// Rice encodes a number.
     /// The k least significant bits are in Lsb0 order.
-    fn rice_encode(x: usize, k: u8, output: &mut BitVec) {
+    fn rice_encode(x: usize, k: u8, output: &mut impl BitSink) {
         // add q ones
         let m = 1 << k;
-        let q = x / m;
-        let ones = BitVec::<usize, Lsb0>::repeat(true, q);
-        output.extend_from_bitslice(&ones);
+        let mut q = x / m;
+        while q > 0 {
+            let ones = q.min(usize::BITS as usize);
+            output.push_bits(usize::MAX, ones);
+            q -= ones;
+        }
 
         // add the 0 delimiter
-        output.push(false);
+        output.push_bits(0, 1);
 
         // add the k least significant bits
-        output.extend_from_bitslice(&x.view_bits::<Lsb0>()[..k as usize]);
+        output.push_bits(x, k as usize);
     }
 
     /// Gets the number of blocks required.
@@ -159,22 +365,280 @@ impl CompressedTable {
     }
 
     /// Returns the startpoint at the given index.
+    /// The table should store its startpoints.
     #[inline]
     fn startpoint(&self, i: usize) -> CompressedPassword {
+        // the indexes of the startpoints are stored, since they are smaller than the startpoints when they are seeded
         let password_bits = self.password_bits as usize;
-        self.startpoints[i * password_bits..(i + 1) * password_bits]
-            .load::<usize>()
-            .into()
+        self.ctx.startpoint(
+            self.startpoints[i * password_bits..(i + 1) * password_bits].load::<usize>(),
+        )
+    }
+
+    /// Creates a table without chains, with the parameters needed to store `m` chains.
+    fn empty(ctx: RainbowTableCtx, m: usize) -> Self {
+        let l = Self::block_count(m);
+        let k = Self::optimal_rice_parameter(ctx.n as f64, m as f64);
+
+        Self {
+            ctx,
+            index: Index::new(ctx.n as f64, m as f64, k),
+            samples: Vec::new(),
+            startpoints: BitVec::new(),
+            endpoints: BitVec::new(),
+            l,
+            k,
+            m,
+            password_bits: Self::password_bits(ctx.m0),
+            stats: None,
+        }
+    }
+
+    /// Returns an estimation of the size of a stored table with the given context and number of chains, in bytes.
+    pub fn stored_size(ctx: &RainbowTableCtx, chain_count: usize) -> usize {
+        let (n, m) = (ctx.n as f64, chain_count as f64);
+        let k = Self::optimal_rice_parameter(n, m);
+        let index = Index::new(n, m, k);
+
+        let endpoints_bits = Self::optimal_rice_parameter_rate(n, m, k) * m;
+        let startpoints_bits = Self::password_bits(ctx.m0) as usize * chain_count;
+        let index_bits = (Self::block_count(chain_count) + 1)
+            * (index.bit_address_size + index.chain_number_size);
+        let samples_size = chain_count / SAMPLE_INTERVAL * mem::size_of::<ArchivedSample>();
+
+        HEADER_SIZE
+            + (endpoints_bits as usize + startpoints_bits + index_bits) / 8
+            + samples_size
+            + mem::size_of::<ArchivedCompressedTable>()
+    }
+
+    /// Converts a table to a compressed delta encoding table without its startpoints, to save space.
+    /// The startpoints of a chain are then recovered by regenerating the chains of all the startpoints of the context when needed,
+    /// so searching the table becomes a lot slower when an endpoint matches.
+    /// Fails if the startpoints of the table aren't startpoints of its context, as they couldn't be recovered.
+    pub fn from_rainbow_table_endpoints_only<T: RainbowTable>(table: &T) -> CugparckResult<Self> {
+        check_startpoints(table.iter(), &table.ctx())?;
+
+        Ok(Self::from_chains(table, true))
+    }
+
+    /// Converts a table to a compressed delta encoding table, storing the startpoints unless `endpoints_only` is true.
+    fn from_chains<T: RainbowTable>(table: &T, endpoints_only: bool) -> Self {
+        let mut delta_table = Self::empty(table.ctx(), table.len());
+        if endpoints_only {
+            delta_table.password_bits = 0;
+        }
+
+        let mut startpoints =
+            BitVec::with_capacity(delta_table.password_bits as usize * delta_table.m);
+        let mut endpoints = BitVec::new();
+        let mut samples = Vec::with_capacity(delta_table.m / SAMPLE_INTERVAL);
+
+        let mut chains = table.iter().collect_vec();
+        chains.par_sort_unstable_by_key(|chain| chain.endpoint);
+        let mut chains_iter = chains.into_iter().peekable();
+
+        let mut chain_start = 0;
+
+        // store the chains
+        // we add a last block because of the integer rounding some endpoints exceed (n / l) * l.
+        for i in 0..delta_table.l + 1 {
+            delta_table.index.add_entry(endpoints.len(), chain_start);
+
+            chain_start = delta_table.store_block(
+                i,
+                chain_start,
+                &mut chains_iter,
+                &mut endpoints,
+                &mut startpoints,
+                &mut samples,
+            );
+        }
+
+        delta_table.startpoints = startpoints;
+        delta_table.endpoints = endpoints;
+        delta_table.samples = samples;
+
+        delta_table
+    }
+
+    /// Sets the generation statistics stored in the header, usually those of the table it was converted from.
+    pub fn with_stats(mut self, stats: Option<TableStats>) -> Self {
+        self.stats = stats;
+
+        self
+    }
+
+    /// Converts a table to a compressed delta encoding table stored at the given path,
+    /// as `RainbowTable::into_rainbow_table` followed by `RainbowTableStorage::store` would,
+    /// but without holding the chains of either table in memory.
+    ///
+    /// The chains are sorted by endpoint in partition files in `spill_dir`,
+    /// and the blocks are encoded one after the other to spill files which are then copied to the stored table.
+    /// `spill_dir` is removed afterwards. The startpoints are not stored if `endpoints_only` is true,
+    /// as with `CompressedTable::from_rainbow_table_endpoints_only`.
+    /// Fails if the startpoints of the table aren't startpoints of its context, as their indexes couldn't be stored.
+    pub fn store_streamed<T: RainbowTable>(
+        table: &T,
+        path: &Path,
+        spill_dir: &Path,
+        endpoints_only: bool,
+        stats: Option<TableStats>,
+        progress: &dyn ProgressListener,
+    ) -> CugparckResult<()> {
+        // the chains are read once from the table and once from the partitions
+        let progress = ProgressCounter::chains(progress, table.len() * 2);
+        let mut delta_table = Self::empty(table.ctx(), table.len()).with_stats(stats);
+        if endpoints_only {
+            delta_table.password_bits = 0;
+        }
+
+        let (l, n) = (delta_table.l, delta_table.ctx.n);
+        let chains = SortedChains::new(
+            table,
+            spill_dir,
+            l + 1,
+            |endpoint| Self::password_block(endpoint.into(), l, n).min(l),
+            &progress,
+        )?;
+
+        let mut startpoints = BitSpill::new(chains.spill_path("startpoints"))?;
+        let mut endpoints = BitSpill::new(chains.spill_path("endpoints"))?;
+        let mut samples = SampleSpill::new(chains.spill_path("samples"))?;
+        let mut block_samples = Vec::new();
+        let mut chain_start = 0;
+
+        for partition in 0..chains.partition_count() {
+            let partition_chains = chains.load(partition)?;
+            // the startpoints are stored as indexes smaller than m0
+            check_startpoints(partition_chains.iter().copied(), &delta_table.ctx)?;
+            let partition_len = partition_chains.len();
+            let mut chains_iter = partition_chains.into_iter().peekable();
+
+            for i in chains.blocks(partition) {
+                delta_table
+                    .index
+                    .add_entry(endpoints.bit_len(), chain_start);
+
+                chain_start = delta_table.store_block(
+                    i,
+                    chain_start,
+                    &mut chains_iter,
+                    &mut endpoints,
+                    &mut startpoints,
+                    &mut block_samples,
+                );
+                samples.extend(block_samples.drain(..))?;
+            }
+
+            progress.add(partition_len);
+        }
+
+        // the fields are written in the same order as the derived implementation of `Serialize`
+        store_table_with(
+            &delta_table,
+            path,
+            |file| Ok(Box::new(file)),
+            |serializer| {
+                let resolver = CompressedTableResolver {
+                    ctx: delta_table
+                        .ctx
+                        .serialize(serializer)
+                        .map_err(|_| CugparckError::Serialize)?,
+                    index: delta_table
+                        .index
+                        .serialize(serializer)
+                        .map_err(|_| CugparckError::Serialize)?,
+                    samples: samples.serialize(serializer)?,
+                    startpoints: startpoints.serialize(serializer)?,
+                    endpoints: endpoints.serialize(serializer)?,
+                    l: (),
+                    k: (),
+                    m: (),
+                    password_bits: (),
+                    stats: (),
+                };
+
+                serializer
+                    .align_for::<ArchivedCompressedTable>()
+                    .map_err(|_| CugparckError::Serialize)?;
+                // SAFETY: the resolver is the result of serializing the table with its spilled data
+                unsafe { serializer.resolve_aligned(&delta_table, resolver) }
+                    .map_err(|_| CugparckError::Serialize)?;
+
+                Ok(())
+            },
+        )
+    }
+
+    /// Returns true if the table only stores the endpoints of the chains.
+    pub fn is_endpoints_only(&self) -> bool {
+        self.startpoints.is_empty()
     }
 
-    /// Stores a new block of endpoints in the table.
+    /// Returns statistics about the distribution of the endpoints in the blocks and their encoding.
+    /// All the endpoints are decoded, but not the startpoints.
+    pub fn encoding_stats(&self) -> EncodingStats {
+        EncodingStats::from_endpoints(
+            CompressedTableEndpointIterator::new(self),
+            self.ctx.n,
+            self.l,
+            self.k,
+            self.m,
+        )
+    }
+
+    /// Counts how many endpoints fall in each of the `buckets` equal slices of the search space,
+    /// as `RainbowTable::coverage_map` does, but without decoding the startpoints.
+    pub fn endpoint_histogram(&self, buckets: usize) -> Vec<u64> {
+        endpoint_histogram(
+            CompressedTableEndpointIterator::new(self),
+            self.ctx.n,
+            buckets,
+        )
+    }
+
+    /// Returns where the search of a password in the endpoints should start.
+    fn search_start(
+        &self,
+        password: CompressedPassword,
+    ) -> Option<SearchStart<CompressedTableEndpointIterator<'_>>> {
+        let block_number = CompressedTable::password_block(password, self.l, self.ctx.n);
+        let (_, chain_start) = self.index.get_entry(block_number)?;
+
+        // start from the closest sample of the block before the password if there is one
+        let sample_count = self
+            .samples
+            .partition_point(|sample| sample.endpoint <= password.get());
+        let sample = sample_count
+            .checked_sub(1)
+            .map(|i| &self.samples[i])
+            .filter(|sample| sample.chain >= chain_start);
+
+        Some(match sample {
+            Some(sample) if sample.endpoint == password.get() => SearchStart::Sample(sample.chain),
+            Some(sample) => SearchStart::Endpoints(
+                CompressedTableEndpointIterator::from_sample(self, sample),
+                sample.chain + 1,
+            ),
+            None => SearchStart::Endpoints(
+                CompressedTableEndpointIterator::from_block(self, block_number)?,
+                chain_start,
+            ),
+        })
+    }
+
+    /// Stores a new block of endpoints in `endpoints`, and the samples taken from the block in `samples`.
     /// The corresponding startpoints are also stored at the same time.
     /// Returns the number of the first chain to be stored in the next block.
     fn store_block(
-        &mut self,
+        &self,
         i: usize,
         chain_start: usize,
         chains_iter: &mut (impl PeekingNext + Iterator<Item = RainbowChain>),
+        endpoints: &mut impl BitSink,
+        startpoints: &mut impl BitSink,
+        samples: &mut Vec<Sample>,
     ) -> usize {
         let block_span = self.ctx.n / self.l;
         let first_value = i * block_span;
@@ -186,25 +650,31 @@ impl CompressedTable {
 
         // add the startpoints
         for chain in &chains_in_block {
-            self.startpoints.extend_from_bitslice(
-                &chain.startpoint.get().view_bits::<Lsb0>()[..self.password_bits as usize],
+            startpoints.push_bits(
+                self.ctx.startpoint_index(chain.startpoint),
+                self.password_bits as usize,
             );
         }
 
         // add the endpoints
-        let mut delta_iter = iter::once(first_value)
+        let delta_iter = iter::once(first_value)
             .chain(chains_in_block.iter().map(|chain| chain.endpoint.get()))
             .tuple_windows()
             .map(|(last_endpoint, endpoint)| endpoint - last_endpoint);
 
-        // the first difference can't be delta-encoded minus one, in case the first value is equal to the start of the block.
-        if let Some(first_diff) = delta_iter.by_ref().next() {
-            Self::rice_encode(first_diff, self.k, &mut self.endpoints);
-        }
-
-        // encode the endpoints
-        for diff in delta_iter {
-            Self::rice_encode(diff - 1, self.k, &mut self.endpoints);
+        for (j, (chain, diff)) in chains_in_block.iter().zip(delta_iter).enumerate() {
+            // the first difference can't be delta-encoded minus one, in case the first value is equal to the start of the block.
+            let diff = if j == 0 { diff } else { diff - 1 };
+            Self::rice_encode(diff, self.k, endpoints);
+
+            let chain_number = chain_start + j;
+            if chain_number % SAMPLE_INTERVAL == SAMPLE_INTERVAL - 1 {
+                samples.push(Sample {
+                    chain: chain_number,
+                    endpoint: chain.endpoint.get(),
+                    bit_address: endpoints.bit_len(),
+                });
+            }
         }
 
         chain_start + chains_in_block.len()
@@ -222,13 +692,247 @@ impl ArchivedCompressedTable {
     }
 
     /// Returns the startpoint at the given index.
+    /// The table should store its startpoints.
     #[inline]
     fn startpoint(&self, i: usize) -> CompressedPassword {
         let password_bits = self.password_bits as usize;
-        self.startpoints[i * password_bits..(i + 1) * password_bits]
-            .load::<usize>()
-            .into()
+        let index = self.startpoints[i * password_bits..(i + 1) * password_bits].load::<usize>();
+
+        // the context isn't deserialized for each chain
+        match self.ctx.startpoint_order {
+            ArchivedStartpointOrder::Sequential => index.into(),
+            ArchivedStartpointOrder::Seeded => {
+                FeistelPermutation::new(self.ctx.n as usize, self.ctx.startpoint_seed)
+                    .permute(index)
+                    .into()
+            }
+        }
+    }
+
+    /// Returns true if the table only stores the endpoints of the chains.
+    pub fn is_endpoints_only(&self) -> bool {
+        self.startpoints.is_empty()
+    }
+
+    /// Returns statistics about the distribution of the endpoints in the blocks and their encoding.
+    /// All the endpoints are decoded, but not the startpoints.
+    pub fn encoding_stats(&self) -> EncodingStats {
+        EncodingStats::from_endpoints(
+            ArchivedCompressedTableEndpointIterator::new(self),
+            self.ctx.n as usize,
+            self.l as usize,
+            self.k,
+            self.m as usize,
+        )
+    }
+
+    /// Counts how many endpoints fall in each of the `buckets` equal slices of the search space,
+    /// as `RainbowTable::coverage_map` does, but without decoding the startpoints.
+    pub fn endpoint_histogram(&self, buckets: usize) -> Vec<u64> {
+        endpoint_histogram(
+            ArchivedCompressedTableEndpointIterator::new(self),
+            self.ctx.n as usize,
+            buckets,
+        )
+    }
+
+    /// Returns where the search of a password in the endpoints should start.
+    fn search_start(
+        &self,
+        password: CompressedPassword,
+    ) -> Option<SearchStart<ArchivedCompressedTableEndpointIterator<'_>>> {
+        let block_number =
+            CompressedTable::password_block(password, self.l as usize, self.ctx.n as usize);
+        let (_, chain_start) = self.index.get_entry(block_number)?;
+
+        // start from the closest sample of the block before the password if there is one
+        let sample_count = self
+            .samples
+            .partition_point(|sample| sample.endpoint as usize <= password.get());
+        let sample = sample_count
+            .checked_sub(1)
+            .map(|i| &self.samples[i])
+            .filter(|sample| sample.chain as usize >= chain_start);
+
+        Some(match sample {
+            Some(sample) if sample.endpoint as usize == password.get() => {
+                SearchStart::Sample(sample.chain as usize)
+            }
+            Some(sample) => SearchStart::Endpoints(
+                ArchivedCompressedTableEndpointIterator::from_sample(self, sample),
+                sample.chain as usize + 1,
+            ),
+            None => SearchStart::Endpoints(
+                ArchivedCompressedTableEndpointIterator::from_block(self, block_number)?,
+                chain_start,
+            ),
+        })
+    }
+}
+
+/// Where the search of a password in the endpoints of a compressed table starts.
+enum SearchStart<I> {
+    /// The password is the endpoint of a sample, whose chain number is given.
+    Sample(usize),
+    /// The endpoints have to be decoded with the iterator, starting from the given chain number.
+    Endpoints(I, usize),
+}
+
+/// Returns the number of the chain ending with the password, if any.
+fn find_chain(
+    start: SearchStart<impl Iterator<Item = CompressedPassword>>,
+    password: CompressedPassword,
+) -> Option<usize> {
+    match start {
+        SearchStart::Sample(chain) => Some(chain),
+        // the endpoints are sorted, so we can stop as soon as they are past the password
+        SearchStart::Endpoints(endpoints, first_chain) => endpoints
+            .take_while(|&endpoint| endpoint <= password)
+            .position(|endpoint| endpoint == password)
+            .map(|pos| first_chain + pos),
+    }
+}
+
+/// Returns the number of the chain ending with each password, if any.
+///
+/// The passwords are searched in increasing order, so the endpoints already decoded for a password
+/// are not decoded again for the next ones: each block is decoded at most once.
+/// The samples are still used to skip the endpoints between two passwords far apart.
+fn find_chains<I: Iterator<Item = CompressedPassword>>(
+    passwords: &[CompressedPassword],
+    search_start: impl Fn(CompressedPassword) -> Option<SearchStart<I>>,
+) -> Vec<Option<usize>> {
+    let mut order = (0..passwords.len()).collect_vec();
+    order.sort_unstable_by_key(|&i| passwords[i]);
+
+    let mut chains = vec![None; passwords.len()];
+    // the endpoints being decoded, along with the chain number of the next endpoint
+    let mut cursor: Option<(Peekable<I>, usize)> = None;
+
+    for i in order {
+        let password = passwords[i];
+        let (endpoints, first_chain) = match search_start(password) {
+            None => continue,
+            Some(SearchStart::Sample(chain)) => {
+                chains[i] = Some(chain);
+                continue;
+            }
+            Some(SearchStart::Endpoints(endpoints, first_chain)) => (endpoints, first_chain),
+        };
+
+        // the cursor is only moved past the endpoints smaller than the previous passwords,
+        // so it can be reused if it is not behind the start of the search.
+        if !matches!(cursor, Some((_, chain)) if chain >= first_chain) {
+            cursor = Some((endpoints.peekable(), first_chain));
+        }
+        let (endpoints, chain) = cursor.as_mut().unwrap();
+
+        while endpoints.next_if(|&endpoint| endpoint < password).is_some() {
+            *chain += 1;
+        }
+
+        if endpoints.peek() == Some(&password) {
+            chains[i] = Some(*chain);
+        }
+    }
+
+    chains
+}
+
+/// Returns the startpoints of the chains found for each password by `find_chain` or `find_chains`.
+/// If the table only stores its endpoints, the startpoints of all the chains found are recovered at once.
+fn find_startpoints(
+    passwords: &[CompressedPassword],
+    chains: Vec<Option<usize>>,
+    ctx: &RainbowTableCtx,
+    endpoints_only: bool,
+    startpoint: impl Fn(usize) -> CompressedPassword,
+) -> Vec<Vec<CompressedPassword>> {
+    if !endpoints_only {
+        return chains
+            .into_iter()
+            .map(|chain| chain.map(&startpoint).into_iter().collect())
+            .collect();
+    }
+
+    let found = passwords
+        .iter()
+        .zip(&chains)
+        .filter(|(_, chain)| chain.is_some())
+        .map(|(&password, _)| password)
+        .collect_vec();
+    let mut recovered = recover_startpoints(&found, ctx).into_iter();
+
+    chains
+        .into_iter()
+        .map(|chain| chain.and_then(|_| recovered.next()).unwrap_or_default())
+        .collect()
+}
+
+/// Recovers the startpoints of the chains ending with each endpoint, by regenerating the chains of all the startpoints of the context.
+/// The chains are regenerated once for all the endpoints, which takes about as long as the generation of the table.
+///
+/// The chains which merged during the generation can't be told apart, so all their startpoints are returned,
+/// in the order of the startpoints of the context.
+/// No startpoint is returned for an endpoint which isn't produced by the context.
+fn recover_startpoints(
+    endpoints: &[CompressedPassword],
+    ctx: &RainbowTableCtx,
+) -> Vec<Vec<CompressedPassword>> {
+    let mut startpoints = vec![Vec::new(); endpoints.len()];
+    if endpoints.is_empty() {
+        return startpoints;
+    }
+
+    let mut positions = HashMap::<CompressedPassword, Vec<usize>>::new();
+    for (i, &endpoint) in endpoints.iter().enumerate() {
+        positions.entry(endpoint).or_default().push(i);
+    }
+
+    let found = (0..ctx.m0)
+        .into_par_iter()
+        .filter_map(|i| {
+            let startpoint = ctx.startpoint(i);
+            let mut endpoint = startpoint;
+            endpoint.continue_chain(0..ctx.t - 1, ctx);
+            positions
+                .contains_key(&endpoint)
+                .then_some((endpoint, startpoint))
+        })
+        .collect::<Vec<_>>();
+
+    for (endpoint, startpoint) in found {
+        for &i in &positions[&endpoint] {
+            startpoints[i].push(startpoint);
+        }
+    }
+
+    startpoints
+}
+
+/// Searches for a password in a table only storing its endpoints.
+/// The endpoints of all the columns are looked up at once, so that the startpoints are only recovered once.
+fn search_endpoints_only<T: RainbowTable>(table: &T, digest: Digest) -> Option<Password> {
+    let ctx = table.ctx();
+    let endpoints = (0..ctx.t - 1)
+        .into_par_iter()
+        .map(|column| column_endpoint(digest, column, &ctx))
+        .collect::<Vec<_>>();
+
+    search_column_endpoints(table, digest, &endpoints).map(|hit| hit.password)
+}
+
+/// Checks that the startpoints of the chains are startpoints of the context,
+/// so that their indexes can be stored or that they can be recovered from the endpoints.
+pub(super) fn check_startpoints(
+    mut chains: impl Iterator<Item = RainbowChain>,
+    ctx: &RainbowTableCtx,
+) -> CugparckResult<()> {
+    if chains.any(|chain| ctx.startpoint_index(chain.startpoint) >= ctx.m0) {
+        return Err(CugparckError::ForeignStartpoints);
     }
+
+    Ok(())
 }
 
 impl<'a> IntoIterator for &'a CompressedTable {
@@ -262,65 +966,69 @@ impl RainbowTable for CompressedTable {
 
     #[inline]
     fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword> {
-        let password_bits = self.password_bits as usize;
-        let block_number = CompressedTable::password_block(password, self.l, self.ctx.n);
-        let (_, chain_start) = self.index.get_entry(block_number).unwrap();
-
-        let starpoint_index = CompressedTableEndpointIterator::from_block(self, block_number)?
-            .position(|endpoint| endpoint == password)
-            .map(|pos| chain_start + pos);
-
-        starpoint_index.map(|i| {
-            self.startpoints[i * password_bits..(i + 1) * password_bits]
-                .load::<usize>()
-                .into()
-        })
+        self.search_startpoints(password).first().copied()
     }
 
-    fn ctx(&self) -> RainbowTableCtx {
-        self.ctx
+    fn search_endpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Option<CompressedPassword>> {
+        self.search_startpoints_batch(passwords)
+            .into_iter()
+            .map(|startpoints| startpoints.first().copied())
+            .collect()
     }
 
-    fn from_rainbow_table<T: RainbowTable>(table: T) -> Self {
-        let ctx = table.ctx();
-
-        let m = table.len();
-        let l = Self::block_count(m);
-        let k = Self::optimal_rice_parameter(ctx.n as f64, m as f64);
-        let password_bits = Self::password_bits(ctx.m0);
-        let startpoints = BitVec::with_capacity(password_bits as usize * m);
-        let index = Index::new(ctx.n as f64, m as f64, k);
-
-        let mut delta_table = Self {
-            ctx,
-            index,
-            l,
-            k,
-            m,
-            password_bits,
-            startpoints,
-            endpoints: BitVec::new(),
-        };
-
-        let mut chains = table.iter().collect_vec();
-        chains.par_sort_unstable_by_key(|chain| chain.endpoint);
-        let mut chains_iter = chains.into_iter().peekable();
-
-        let mut bit_address = 0;
-        let mut chain_start = 0;
+    #[inline]
+    fn search_startpoints(&self, password: CompressedPassword) -> Vec<CompressedPassword> {
+        match self
+            .search_start(password)
+            .and_then(|start| find_chain(start, password))
+        {
+            Some(chain) => find_startpoints(
+                &[password],
+                vec![Some(chain)],
+                &self.ctx,
+                self.is_endpoints_only(),
+                |i| self.startpoint(i),
+            )
+            .swap_remove(0),
+            None => Vec::new(),
+        }
+    }
 
-        // store the chains
-        // we add a last block because of the integer rounding some endpoints exceed (n / l) * l.
-        for i in 0..delta_table.l + 1 {
-            delta_table.index.add_entry(bit_address, chain_start);
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.search_start(password)
+            .and_then(|start| find_chain(start, password))
+    }
 
-            let next_chain_start = delta_table.store_block(i, chain_start, &mut chains_iter);
+    fn search_startpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Vec<CompressedPassword>> {
+        find_startpoints(
+            passwords,
+            find_chains(passwords, |password| self.search_start(password)),
+            &self.ctx,
+            self.is_endpoints_only(),
+            |i| self.startpoint(i),
+        )
+    }
 
-            bit_address = delta_table.endpoints.len();
-            chain_start = next_chain_start;
+    fn search(&self, digest: Digest) -> Option<Password> {
+        if self.is_endpoints_only() {
+            search_endpoints_only(self, digest)
+        } else {
+            search_by_column(self, digest)
         }
+    }
 
-        delta_table
+    fn ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn from_rainbow_table<T: RainbowTable>(table: &T) -> Self {
+        Self::from_chains(table, false)
     }
 }
 
@@ -335,60 +1043,119 @@ impl RainbowTable for ArchivedCompressedTable {
         self.into_iter()
     }
 
+    #[inline]
     fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword> {
-        let password_bits = self.password_bits as usize;
-        let block_number =
-            CompressedTable::password_block(password, self.l as usize, self.ctx.n as usize);
-        let (_, chain_start) = self.index.get_entry(block_number).unwrap();
+        self.search_startpoints(password).first().copied()
+    }
+
+    fn search_endpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Option<CompressedPassword>> {
+        self.search_startpoints_batch(passwords)
+            .into_iter()
+            .map(|startpoints| startpoints.first().copied())
+            .collect()
+    }
 
-        let starpoint_index =
-            ArchivedCompressedTableEndpointIterator::from_block(self, block_number)?
-                .position(|endpoint| endpoint == password)
-                .map(|pos| chain_start + pos);
+    #[inline]
+    fn search_startpoints(&self, password: CompressedPassword) -> Vec<CompressedPassword> {
+        match self
+            .search_start(password)
+            .and_then(|start| find_chain(start, password))
+        {
+            Some(chain) => find_startpoints(
+                &[password],
+                vec![Some(chain)],
+                &self.ctx(),
+                self.is_endpoints_only(),
+                |i| self.startpoint(i),
+            )
+            .swap_remove(0),
+            None => Vec::new(),
+        }
+    }
 
-        starpoint_index.map(|i| {
-            self.startpoints[i * password_bits..(i + 1) * password_bits]
-                .load::<usize>()
-                .into()
-        })
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.search_start(password)
+            .and_then(|start| find_chain(start, password))
+    }
+
+    fn search_startpoints_batch(
+        &self,
+        passwords: &[CompressedPassword],
+    ) -> Vec<Vec<CompressedPassword>> {
+        find_startpoints(
+            passwords,
+            find_chains(passwords, |password| self.search_start(password)),
+            &self.ctx(),
+            self.is_endpoints_only(),
+            |i| self.startpoint(i),
+        )
+    }
+
+    fn search(&self, digest: Digest) -> Option<Password> {
+        if self.is_endpoints_only() {
+            search_endpoints_only(self, digest)
+        } else {
+            search_by_column(self, digest)
+        }
     }
 
     fn ctx(&self) -> RainbowTableCtx {
         self.ctx.deserialize(&mut Infallible).unwrap()
     }
 
-    fn from_rainbow_table<T: RainbowTable>(_: T) -> Self {
+    fn from_rainbow_table<T: RainbowTable>(_: &T) -> Self {
         panic!("Archived tables cannot be built from other tables")
     }
 }
 
 /// An iterator over the chains of a compressed delta encoding table.
+/// If the table only stores its endpoints, the first startpoint recovered for each endpoint is used,
+/// and the chains whose startpoint can't be recovered are skipped.
 pub struct CompressedTableIterator<'a> {
     table: &'a CompressedTable,
     endpoint_iter: Enumerate<CompressedTableEndpointIterator<'a>>,
+    recovered: Option<vec::IntoIter<Vec<CompressedPassword>>>,
 }
 
 pub struct ArchivedCompressedTableIterator<'a> {
     table: &'a ArchivedCompressedTable,
     endpoint_iter: Enumerate<ArchivedCompressedTableEndpointIterator<'a>>,
+    recovered: Option<vec::IntoIter<Vec<CompressedPassword>>>,
 }
 
 impl<'a> CompressedTableIterator<'a> {
     /// Creates a new iterator over the chains of a compressed delta encoding table.
+    /// If the table only stores its endpoints, the startpoints of all the chains are recovered first.
     pub fn new(table: &'a CompressedTable) -> Self {
+        let recovered = table.is_endpoints_only().then(|| {
+            let endpoints = CompressedTableEndpointIterator::new(table).collect_vec();
+            recover_startpoints(&endpoints, &table.ctx).into_iter()
+        });
+
         Self {
             table,
             endpoint_iter: CompressedTableEndpointIterator::new(table).enumerate(),
+            recovered,
         }
     }
 }
 
 impl<'a> ArchivedCompressedTableIterator<'a> {
     /// Creates a new iterator over the chains of a compressed delta encoding table.
+    /// If the table only stores its endpoints, the startpoints of all the chains are recovered first.
     pub fn new(table: &'a ArchivedCompressedTable) -> Self {
+        let recovered = table.is_endpoints_only().then(|| {
+            let endpoints = ArchivedCompressedTableEndpointIterator::new(table).collect_vec();
+            recover_startpoints(&endpoints, &table.ctx()).into_iter()
+        });
+
         Self {
             table,
             endpoint_iter: ArchivedCompressedTableEndpointIterator::new(table).enumerate(),
+            recovered,
         }
     }
 }
@@ -397,10 +1164,17 @@ impl Iterator for CompressedTableIterator<'_> {
     type Item = RainbowChain;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (i, endpoint) = self.endpoint_iter.next()?;
-        let startpoint = self.table.startpoint(i);
-
-        Some(RainbowChain::from_compressed(startpoint, endpoint))
+        loop {
+            let (i, endpoint) = self.endpoint_iter.next()?;
+            let startpoint = match &mut self.recovered {
+                Some(recovered) => recovered.next()?.first().copied(),
+                None => Some(self.table.startpoint(i)),
+            };
+
+            if let Some(startpoint) = startpoint {
+                return Some(RainbowChain::from_compressed(startpoint, endpoint));
+            }
+        }
     }
 }
 
@@ -408,10 +1182,17 @@ impl Iterator for ArchivedCompressedTableIterator<'_> {
     type Item = RainbowChain;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (i, endpoint) = self.endpoint_iter.next()?;
-        let startpoint = self.table.startpoint(i);
-
-        Some(RainbowChain::from_compressed(startpoint, endpoint))
+        loop {
+            let (i, endpoint) = self.endpoint_iter.next()?;
+            let startpoint = match &mut self.recovered {
+                Some(recovered) => recovered.next()?.first().copied(),
+                None => Some(self.table.startpoint(i)),
+            };
+
+            if let Some(startpoint) = startpoint {
+                return Some(RainbowChain::from_compressed(startpoint, endpoint));
+            }
+        }
     }
 }
 
@@ -448,7 +1229,7 @@ impl<'a> CompressedTableEndpointIterator<'a> {
         let (endpoint_bit_address, i) = table.index.get_entry(block)?;
         let next_switch = table.index.get_entry(block + 1).map(|entry| entry.1);
 
-        Some(Self {
+        let mut iter = Self {
             table,
             next_switch,
             block,
@@ -456,7 +1237,44 @@ impl<'a> CompressedTableEndpointIterator<'a> {
             i,
             endpoint_bit_address,
             last_endpoint: table.ctx.n / table.l * block,
-        })
+        };
+        iter.switch_blocks();
+
+        Some(iter)
+    }
+
+    /// Creates a new iterator starting after the endpoint of a sample.
+    fn from_sample(table: &'a CompressedTable, sample: &Sample) -> Self {
+        let block = CompressedTable::password_block(sample.endpoint.into(), table.l, table.ctx.n);
+        let next_switch = table.index.get_entry(block + 1).map(|entry| entry.1);
+
+        let mut iter = Self {
+            table,
+            next_switch,
+            block,
+            is_first_diff: false,
+            i: sample.chain + 1,
+            endpoint_bit_address: sample.bit_address,
+            last_endpoint: sample.endpoint,
+        };
+        iter.switch_blocks();
+
+        iter
+    }
+
+    /// Moves to the block of the next endpoint if the current block is over.
+    /// The empty blocks are skipped.
+    fn switch_blocks(&mut self) {
+        while self.next_switch == Some(self.i) {
+            self.is_first_diff = true;
+            self.block += 1;
+            self.next_switch = self
+                .table
+                .index
+                .get_entry(self.block + 1)
+                .map(|(_, chain_number)| chain_number);
+            self.last_endpoint = self.table.ctx.n / self.table.l * self.block;
+        }
     }
 }
 
@@ -472,7 +1290,7 @@ impl<'a> ArchivedCompressedTableEndpointIterator<'a> {
         let (endpoint_bit_address, i) = table.index.get_entry(block)?;
         let next_switch = table.index.get_entry(block + 1).map(|entry| entry.1);
 
-        Some(Self {
+        let mut iter = Self {
             table,
             next_switch,
             block,
@@ -480,7 +1298,48 @@ impl<'a> ArchivedCompressedTableEndpointIterator<'a> {
             i,
             endpoint_bit_address,
             last_endpoint: (table.ctx.n / table.l) as usize * block,
-        })
+        };
+        iter.switch_blocks();
+
+        Some(iter)
+    }
+
+    /// Creates a new iterator starting after the endpoint of a sample.
+    fn from_sample(table: &'a ArchivedCompressedTable, sample: &ArchivedSample) -> Self {
+        let block = CompressedTable::password_block(
+            (sample.endpoint as usize).into(),
+            table.l as usize,
+            table.ctx.n as usize,
+        );
+        let next_switch = table.index.get_entry(block + 1).map(|entry| entry.1);
+
+        let mut iter = Self {
+            table,
+            next_switch,
+            block,
+            is_first_diff: false,
+            i: sample.chain as usize + 1,
+            endpoint_bit_address: sample.bit_address as usize,
+            last_endpoint: sample.endpoint as usize,
+        };
+        iter.switch_blocks();
+
+        iter
+    }
+
+    /// Moves to the block of the next endpoint if the current block is over.
+    /// The empty blocks are skipped.
+    fn switch_blocks(&mut self) {
+        while self.next_switch == Some(self.i) {
+            self.is_first_diff = true;
+            self.block += 1;
+            self.next_switch = self
+                .table
+                .index
+                .get_entry(self.block + 1)
+                .map(|(_, chain_number)| chain_number);
+            self.last_endpoint = (self.table.ctx.n / self.table.l) as usize * self.block;
+        }
     }
 }
 
@@ -506,24 +1365,9 @@ impl Iterator for CompressedTableEndpointIterator<'_> {
         self.endpoint_bit_address = self.table.endpoints.len() - rest.len();
 
         self.i += 1;
-
-        match self.next_switch {
-            Some(switch) if self.i == switch => {
-                self.is_first_diff = true;
-                self.block += 1;
-                self.next_switch = self
-                    .table
-                    .index
-                    .get_entry(self.block + 1)
-                    .map(|(_, chain_number)| chain_number);
-                self.last_endpoint = self.table.ctx.n / self.table.l * self.block;
-            }
-
-            _ => {
-                self.is_first_diff = false;
-                self.last_endpoint = endpoint;
-            }
-        }
+        self.is_first_diff = false;
+        self.last_endpoint = endpoint;
+        self.switch_blocks();
 
         Some(endpoint.into())
     }
@@ -551,30 +1395,31 @@ impl Iterator for ArchivedCompressedTableEndpointIterator<'_> {
         self.endpoint_bit_address = self.table.endpoints.len() - rest.len();
 
         self.i += 1;
-
-        match self.next_switch {
-            Some(switch) if self.i == switch => {
-                self.is_first_diff = true;
-                self.block += 1;
-                self.next_switch = self
-                    .table
-                    .index
-                    .get_entry(self.block + 1)
-                    .map(|(_, chain_number)| chain_number);
-                self.last_endpoint = (self.table.ctx.n / self.table.l) as usize * self.block;
-            }
-
-            _ => {
-                self.is_first_diff = false;
-                self.last_endpoint = endpoint;
-            }
-        }
+        self.is_first_diff = false;
+        self.last_endpoint = endpoint;
+        self.switch_blocks();
 
         Some(endpoint.into())
     }
 }
 
-impl RainbowTableStorage for CompressedTable {}
+impl RainbowTableStorage for CompressedTable {
+    fn header_ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx {
+        archived.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn header_chain_count(&self) -> usize {
+        self.len()
+    }
+
+    fn header_stats(&self) -> Option<TableStats> {
+        self.stats.clone()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -585,14 +1430,20 @@ mod tests {
             simple::SimpleTable,
             RainbowTable,
         },
-        RainbowTableCtxBuilder,
+        temp_path, CugparckError, RainbowTableCtxBuilder, RainbowTableStorage,
+    };
+
+    use std::{
+        collections::{BTreeMap, HashSet},
+        fs,
     };
 
     use bitvec::prelude::*;
     use cugparck_commons::{CompressedPassword, Password, RainbowChain};
     use itertools::Itertools;
+    use rkyv::AlignedVec;
 
-    use super::{CompressedTable, BLOCK_SIZE};
+    use super::{CompressedTable, BLOCK_SIZE, SAMPLE_INTERVAL};
 
     /// Builds a table for testing purposes with chains like (startpoint, endpoint = startpoint * 7).
     /// We have n = 5461, m0 = m = 513.
@@ -680,17 +1531,19 @@ mod tests {
 
         let mut index = Index::new(n, m, k);
 
-        // each entry in the index should be 35 bits long
+        // each entry in the index should be 36 bits long
         index.add_entry(0, 0);
         index.add_entry(1000, 50);
         index.add_entry(2000, 100);
+        index.add_entry(3000, 1 << 16);
 
-        assert_eq!(35 * 3, index.entries.len());
+        assert_eq!(36 * 4, index.entries.len());
 
         // we should be able to get all entries back
         assert_eq!((0, 0), index.get_entry(0).unwrap());
         assert_eq!((1000, 50), index.get_entry(1).unwrap());
         assert_eq!((2000, 100), index.get_entry(2).unwrap());
+        assert_eq!((3000, 1 << 16), index.get_entry(3).unwrap());
     }
 
     #[test]
@@ -821,6 +1674,75 @@ mod tests {
         assert_eq!(Some(chain.startpoint), search);
     }
 
+    #[test]
+    fn test_samples() {
+        let (table, chains) = build_table();
+
+        // one sample every SAMPLE_INTERVAL chains
+        assert_eq!(chains.len() / SAMPLE_INTERVAL, table.samples.len());
+
+        for sample in &table.samples {
+            assert_eq!(chains[sample.chain].endpoint.get(), sample.endpoint);
+
+            let endpoints =
+                CompressedTableEndpointIterator::from_sample(&table, sample).collect_vec();
+            assert_eq!(
+                chains[sample.chain + 1..]
+                    .iter()
+                    .map(|chain| chain.endpoint)
+                    .collect_vec(),
+                endpoints
+            );
+        }
+
+        let path = temp_path("samples.rtcde");
+        table.store(&path).unwrap();
+
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        let archived = CompressedTable::load(&bytes).unwrap();
+
+        // every endpoint is found, and the other passwords are not
+        for password in (0..chains.len() * 7 + 7).map(CompressedPassword::from) {
+            let expected = (password.get() % 7 == 0 && password.get() < chains.len() * 7)
+                .then(|| CompressedPassword::from(password.get() / 7));
+
+            assert_eq!(expected, table.search_endpoints(password));
+            assert_eq!(expected, archived.search_endpoints(password));
+        }
+    }
+
+    #[test]
+    fn test_search_endpoints_batch() {
+        let (table, chains) = build_table();
+
+        let path = temp_path("search_endpoints_batch.rtcde");
+        table.store(&path).unwrap();
+
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        let archived = CompressedTable::load(&bytes).unwrap();
+
+        // unsorted passwords with duplicates, some of them far apart
+        let passwords = (0..chains.len() * 7 + 7)
+            .rev()
+            .chain((0..chains.len() * 7).step_by(301))
+            .chain([3, 0, 3])
+            .map(CompressedPassword::from)
+            .collect_vec();
+
+        let expected = passwords
+            .iter()
+            .map(|&password| table.search_endpoints(password))
+            .collect_vec();
+
+        assert_eq!(expected, table.search_endpoints_batch(&passwords));
+        assert_eq!(expected, archived.search_endpoints_batch(&passwords));
+        assert!(table.search_endpoints_batch(&[]).is_empty());
+    }
+
     #[test]
     fn test_search() {
         let ctx = RainbowTableCtxBuilder::new()
@@ -829,14 +1751,13 @@ mod tests {
             .charset(b"abc")
             .build()
             .unwrap();
-        let hash = ctx.hash_type.hash_function();
 
         let table = SimpleTable::new_blocking::<Cpu>(ctx)
             .unwrap()
             .into_rainbow_table::<CompressedTable>();
         let search = Password::new(b"abca");
 
-        let found = table.search(hash(search));
+        let found = table.search(ctx.hash(&search));
         assert_eq!(search, found.unwrap());
     }
 
@@ -848,7 +1769,6 @@ mod tests {
             .charset(b"abcdef")
             .build()
             .unwrap();
-        let hash = ctx.hash_type.hash_function();
 
         let table: CompressedTable = SimpleTable::new_blocking::<Cpu>(ctx)
             .unwrap()
@@ -857,7 +1777,7 @@ mod tests {
         let mut found = 0;
         for i in 0..ctx.n {
             let password = CompressedPassword::from(i).into_password(&ctx);
-            if let Some(plaintext) = table.search(hash(password)) {
+            if let Some(plaintext) = table.search(ctx.hash(&password)) {
                 assert_eq!(password, plaintext);
                 found += 1;
             }
@@ -870,4 +1790,264 @@ mod tests {
             "success rate is only {success_rate}"
         );
     }
+
+    #[test]
+    fn test_stored_size() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(5)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table: CompressedTable = SimpleTable::new_blocking::<Cpu>(ctx)
+            .unwrap()
+            .into_rainbow_table();
+
+        let path = temp_path("stored_size.rtcde");
+        table.store(&path).unwrap();
+        let size = fs::metadata(&path).unwrap().len() as f64;
+        fs::remove_file(&path).unwrap();
+
+        let estimated = CompressedTable::stored_size(&ctx, table.len()) as f64;
+        assert!(
+            (estimated - size).abs() / size < 0.05,
+            "estimated {estimated} bytes instead of {size}"
+        );
+    }
+
+    #[test]
+    fn test_encoding_stats() {
+        let (table, chains) = build_table();
+        let stats = table.encoding_stats();
+
+        // the blocks span 5461 / 3 = 1820 passwords
+        assert_eq!(vec![260, 253, 0, 0], stats.block_chains);
+        assert_eq!(table.endpoints.len(), stats.endpoint_bits());
+        assert_eq!(chains.len(), stats.bit_costs.iter().sum::<usize>());
+        // the endpoints are 7 apart
+        assert!((stats.mean_delta - 6.).abs() < 0.1, "{}", stats.mean_delta);
+
+        let path = temp_path("encoding_stats.rtcde");
+        table.store(&path).unwrap();
+
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let archived = CompressedTable::load(&bytes).unwrap();
+        assert_eq!(stats, archived.encoding_stats());
+        assert_eq!(table.coverage_map(10), table.endpoint_histogram(10));
+        assert_eq!(table.coverage_map(10), archived.endpoint_histogram(10));
+    }
+
+    #[test]
+    fn test_decompress_from_archive() {
+        let (table, _) = build_table();
+
+        let path = temp_path("decompress_from_archive.rtcde");
+        table.store(&path).unwrap();
+
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let archived = CompressedTable::load(&bytes).unwrap();
+        let decompressed = SimpleTable::from_rainbow_table(archived);
+        let in_memory = table.into_rainbow_table::<SimpleTable>();
+
+        assert_eq!(in_memory.len(), decompressed.len());
+        assert_eq!(
+            in_memory
+                .iter()
+                .map(|chain| (chain.startpoint, chain.endpoint))
+                .collect::<HashSet<_>>(),
+            decompressed
+                .iter()
+                .map(|chain| (chain.startpoint, chain.endpoint))
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_seeded_startpoints() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .startpoint_seed(Some(99))
+            .build()
+            .unwrap();
+        let simple = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let table = CompressedTable::from_rainbow_table(&simple);
+
+        let path = temp_path("seeded_startpoints.rtcde");
+        table.store(&path).unwrap();
+
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+
+        // the startpoints are larger than m0, but only their indexes are stored
+        let as_set = |chains: Vec<RainbowChain>| {
+            chains
+                .into_iter()
+                .map(|chain| (chain.startpoint, chain.endpoint))
+                .collect::<HashSet<_>>()
+        };
+        let expected = as_set(simple.iter().collect());
+        assert_eq!(expected, as_set(table.iter().collect()));
+        assert_eq!(
+            expected,
+            as_set(CompressedTable::load(&bytes).unwrap().iter().collect())
+        );
+    }
+
+    #[test]
+    fn test_endpoints_only() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(50)
+            .max_password_length(3)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        // keep the first startpoint of merging chains, like the iterator of the table does
+        let mut chains = BTreeMap::new();
+        for i in 0..ctx.m0 {
+            let mut endpoint = CompressedPassword::from(i);
+            endpoint.continue_chain(0..ctx.t - 1, &ctx);
+            chains.entry(endpoint.get()).or_insert(i);
+        }
+        let chains = chains
+            .into_iter()
+            .map(|(endpoint, startpoint)| {
+                RainbowChain::from_compressed(startpoint.into(), endpoint.into())
+            })
+            .collect_vec();
+
+        let table = SimpleTable::from_vec(chains, ctx);
+        let full = CompressedTable::from_rainbow_table(&table);
+        let endpoints_only = CompressedTable::from_rainbow_table_endpoints_only(&table).unwrap();
+
+        assert!(endpoints_only.is_endpoints_only());
+        assert!(!full.is_endpoints_only());
+        let as_set = |chains: Vec<RainbowChain>| {
+            chains
+                .into_iter()
+                .map(|chain| (chain.startpoint, chain.endpoint))
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(
+            as_set(table.iter().collect()),
+            as_set(endpoints_only.iter().collect())
+        );
+
+        let mut found = 0;
+        for i in 0..ctx.n {
+            let digest = ctx.hash(&CompressedPassword::from(i).into_password(&ctx));
+            let search = full.search(digest);
+            // all the startpoints of the merging chains are tried, so the passwords of the table are still found
+            if search.is_some() {
+                assert_eq!(search, endpoints_only.search(digest));
+            }
+            found += search.is_some() as usize;
+        }
+
+        assert!(found > 0);
+
+        // a startpoint that isn't a startpoint of the context can't be recovered
+        let foreign = SimpleTable::from_vec(
+            vec![RainbowChain::from_compressed(ctx.m0.into(), 0.into())],
+            ctx,
+        );
+        assert!(matches!(
+            CompressedTable::from_rainbow_table_endpoints_only(&foreign),
+            Err(CugparckError::ForeignStartpoints)
+        ));
+    }
+
+    #[test]
+    fn test_endpoints_only_merged_and_rebuilt() {
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(30)
+            .max_password_length(3)
+            .charset(b"abcdef");
+
+        // the merged table keeps whichever startpoint of the merging chains it met first
+        let small =
+            SimpleTable::new_blocking::<Cpu>(ctx_builder.startpoints(Some(50)).build().unwrap())
+                .unwrap();
+        let large =
+            SimpleTable::new_blocking::<Cpu>(ctx_builder.startpoints(Some(150)).build().unwrap())
+                .unwrap();
+        let merged = SimpleTable::merge(&[&small, &large]).unwrap();
+        let rebuilt = SimpleTable::new_blocking::<Cpu>(ctx_builder.build().unwrap())
+            .unwrap()
+            .rebuild_blocking::<Cpu>(60)
+            .unwrap();
+
+        for table in [merged, rebuilt] {
+            let ctx = table.ctx();
+            let full = CompressedTable::from_rainbow_table(&table);
+            let endpoints_only =
+                CompressedTable::from_rainbow_table_endpoints_only(&table).unwrap();
+
+            assert_eq!(table.len(), endpoints_only.iter().count());
+
+            let digests = (0..ctx.n)
+                .map(|i| ctx.hash(&CompressedPassword::from(i).into_password(&ctx)))
+                .collect_vec();
+            let found = endpoints_only.search_many(&digests);
+            for (digest, password) in digests.iter().zip(&found) {
+                if full.search(*digest).is_some() {
+                    assert!(password.is_some());
+                    assert_eq!(*password, endpoints_only.search(*digest));
+                }
+            }
+            assert!(found.iter().any(Option::is_some));
+        }
+    }
+
+    #[test]
+    fn test_store_streamed() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let simple = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let path = temp_path("store_streamed.rtcde");
+        let spill_dir = path.with_extension("spill");
+
+        for endpoints_only in [false, true] {
+            let table = if endpoints_only {
+                CompressedTable::from_rainbow_table_endpoints_only(&simple).unwrap()
+            } else {
+                CompressedTable::from_rainbow_table(&simple)
+            };
+
+            CompressedTable::store_streamed(&simple, &path, &spill_dir, endpoints_only, None, &())
+                .unwrap();
+            assert!(!spill_dir.exists());
+
+            // the streamed table is exactly the table that would have been stored from memory
+            assert_eq!(table.to_bytes().unwrap(), fs::read(&path).unwrap());
+            fs::remove_file(&path).unwrap();
+        }
+
+        // the index of a startpoint outside of the startpoints of the context can't be stored
+        let foreign = SimpleTable::from_vec(
+            vec![RainbowChain::from_compressed(ctx.m0.into(), 0.into())],
+            ctx,
+        );
+        assert!(matches!(
+            CompressedTable::store_streamed(&foreign, &path, &spill_dir, false, None, &()),
+            Err(CugparckError::ForeignStartpoints)
+        ));
+        assert!(!spill_dir.exists());
+        let _ = fs::remove_file(&path);
+    }
 }
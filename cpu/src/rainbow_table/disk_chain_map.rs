@@ -0,0 +1,217 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Seek, SeekFrom, Write},
+    mem,
+    path::{Path, PathBuf},
+};
+
+use cugparck_commons::CompressedPassword;
+
+use super::simple::RainbowMap;
+use crate::{error::CugparckResult, CugparckError};
+
+/// An estimation of the memory used by a chain in a `RainbowMap`, in bytes.
+/// It includes the entry (hash, endpoint and startpoint) and the hash table index.
+pub(crate) const MAP_CHAIN_SIZE: usize = 40;
+
+/// The memory used by a chain in the midpoints and startpoints vectors of the generation, in bytes.
+pub(crate) const VEC_CHAIN_SIZE: usize = 2 * mem::size_of::<CompressedPassword>();
+
+/// The maximum number of partitions.
+/// All the partitions are open at the same time, so it stays below the usual limit of 1024 open files.
+pub(crate) const MAX_PARTITION_COUNT: usize = 512;
+
+/// The size of the buffer of the writer of each partition, in bytes.
+pub(crate) const WRITER_BUFFER_SIZE: usize = 8 * 1024;
+
+/// A chain map keeping its chains in partition files on the disk instead of in memory.
+///
+/// The chains are partitioned by endpoint, so the duplicate endpoints are always in the same partition
+/// and the partitions can be deduplicated one after the other with a fraction of the memory.
+pub(crate) struct DiskChainMap {
+    dir: PathBuf,
+    /// The parent directory, if it was created by the map.
+    created_parent: Option<PathBuf>,
+    partitions: Vec<BufWriter<File>>,
+}
+
+impl DiskChainMap {
+    /// Creates a new disk chain map storing its partitions in a new subdirectory of `dir`.
+    /// Only this subdirectory is removed when the map is dropped, and `dir` too if it was created by the map and is empty.
+    /// The number of partitions is chosen so that the generation of `m0` chains uses at most `max_host_memory` bytes.
+    pub fn new(dir: &Path, m0: usize, max_host_memory: usize) -> CugparckResult<Self> {
+        let vec_size = m0 * VEC_CHAIN_SIZE;
+        // the chains of one partition are deduplicated at a time, but the buffers of all the partitions are kept.
+        let required = |partition_count: usize| {
+            vec_size
+                + partition_count * WRITER_BUFFER_SIZE
+                + (m0 * MAP_CHAIN_SIZE).div_ceil(partition_count)
+        };
+
+        let partition_count = (1..=MAX_PARTITION_COUNT)
+            .find(|&partition_count| required(partition_count) <= max_host_memory)
+            .ok_or_else(|| {
+                let min_required = (1..=MAX_PARTITION_COUNT).map(required).min().unwrap();
+                CugparckError::MaxHostMemoryTooLow(min_required)
+            })?;
+
+        let created_parent = (!dir.exists()).then(|| dir.to_owned());
+        fs::create_dir_all(dir)?;
+
+        // a new directory is created so that nothing else than the partitions is removed with it.
+        let subdir = dir.join(format!("cugparck_chains_{}", std::process::id()));
+        if let Err(err) = fs::create_dir(&subdir) {
+            if let Some(parent) = &created_parent {
+                let _ = fs::remove_dir(parent);
+            }
+            return Err(err.into());
+        }
+
+        let mut map = Self {
+            dir: subdir,
+            created_parent,
+            partitions: Vec::with_capacity(partition_count),
+        };
+        for i in 0..partition_count {
+            let file = File::create(Self::partition_path(&map.dir, i))?;
+            map.partitions
+                .push(BufWriter::with_capacity(WRITER_BUFFER_SIZE, file));
+        }
+
+        Ok(map)
+    }
+
+    /// Returns true if `m0` chains can't be generated in memory with `max_host_memory` bytes.
+    pub fn is_needed(m0: usize, max_host_memory: usize) -> bool {
+        m0 * (VEC_CHAIN_SIZE + MAP_CHAIN_SIZE) > max_host_memory
+    }
+
+    /// Returns the path of the partition `i`.
+    fn partition_path(dir: &Path, i: usize) -> PathBuf {
+        dir.join(format!("partition_{i}"))
+    }
+
+    /// Adds chains to the map.
+    pub fn extend(
+        &mut self,
+        endpoints: &[CompressedPassword],
+        startpoints: &[CompressedPassword],
+    ) -> CugparckResult<()> {
+        let partition_count = self.partitions.len();
+
+        for (endpoint, startpoint) in endpoints.iter().zip(startpoints) {
+            let partition = &mut self.partitions[endpoint.get() % partition_count];
+            partition.write_all(&(endpoint.get() as u64).to_le_bytes())?;
+            partition.write_all(&(startpoint.get() as u64).to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the unique chains of the map to the `endpoints` and `startpoints` vectors, replacing their content.
+    /// The map is empty afterwards.
+    pub fn drain_into(
+        &mut self,
+        endpoints: &mut Vec<CompressedPassword>,
+        startpoints: &mut Vec<CompressedPassword>,
+    ) -> CugparckResult<()> {
+        endpoints.clear();
+        startpoints.clear();
+
+        for (i, partition) in self.partitions.iter_mut().enumerate() {
+            partition.flush()?;
+            let bytes = fs::read(Self::partition_path(&self.dir, i))?;
+
+            let mut chains = RainbowMap::default();
+            chains.extend(bytes.chunks_exact(16).map(|chain| {
+                let (endpoint, startpoint) = chain.split_at(8);
+                (
+                    CompressedPassword::from(
+                        u64::from_le_bytes(endpoint.try_into().unwrap()) as usize
+                    ),
+                    CompressedPassword::from(
+                        u64::from_le_bytes(startpoint.try_into().unwrap()) as usize
+                    ),
+                )
+            }));
+
+            endpoints.extend(chains.keys());
+            startpoints.extend(chains.values());
+
+            let file = partition.get_mut();
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for DiskChainMap {
+    fn drop(&mut self) {
+        self.partitions.clear();
+        let _ = fs::remove_dir_all(&self.dir);
+
+        // the parent is only removed if nothing else was stored in it in the meantime.
+        if let Some(parent) = &self.created_parent {
+            let _ = fs::remove_dir(parent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use cugparck_commons::CompressedPassword;
+
+    use crate::temp_path;
+
+    use super::{DiskChainMap, MAP_CHAIN_SIZE, VEC_CHAIN_SIZE, WRITER_BUFFER_SIZE};
+
+    #[test]
+    fn test_disk_chain_map() {
+        let dir = temp_path("disk_map");
+        let m0 = 100_000;
+
+        assert!(DiskChainMap::new(&dir, m0, m0 * VEC_CHAIN_SIZE).is_err());
+
+        // just enough memory for 4 partitions and their buffers
+        let max_host_memory = m0 * (VEC_CHAIN_SIZE + MAP_CHAIN_SIZE / 4) + 4 * WRITER_BUFFER_SIZE;
+        let mut map = DiskChainMap::new(&dir, m0, max_host_memory).unwrap();
+        assert_eq!(4, map.partitions.len());
+        assert!(map.dir.starts_with(&dir));
+
+        let endpoints = (0..m0)
+            .map(|i| CompressedPassword::from(i % 100))
+            .collect::<Vec<_>>();
+        let startpoints = (0..m0).map(CompressedPassword::from).collect::<Vec<_>>();
+        map.extend(&endpoints, &startpoints).unwrap();
+
+        let (mut unique_endpoints, mut unique_startpoints) = (Vec::new(), Vec::new());
+        map.drain_into(&mut unique_endpoints, &mut unique_startpoints)
+            .unwrap();
+        assert_eq!(100, unique_endpoints.len());
+        assert_eq!(100, unique_startpoints.len());
+
+        for (endpoint, startpoint) in unique_endpoints.iter().zip(&unique_startpoints) {
+            assert_eq!(endpoint.get(), startpoint.get() % 100);
+        }
+
+        // the map is empty once drained
+        map.drain_into(&mut unique_endpoints, &mut unique_startpoints)
+            .unwrap();
+        assert!(unique_endpoints.is_empty());
+
+        drop(map);
+        assert!(!dir.exists());
+
+        // a directory which already exists is kept, with its content.
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("other"), b"").unwrap();
+        drop(DiskChainMap::new(&dir, m0, max_host_memory).unwrap());
+        assert!(dir.join("other").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
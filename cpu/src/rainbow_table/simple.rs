@@ -1,28 +1,126 @@
-use std::{ops::Range, thread};
+use std::{
+    fs, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
+use crate::parallel::*;
 use crate::{
     backend::Backend,
-    event::{Event, SimpleTableHandle},
-    renderer::{BatchInformation, KernelHandle, Renderer, StagingHandleSync},
-    CugparckError, FiltrationIterator,
+    event::{
+        Event, GenerationChannel, GenerationSpeed, ProgressCounter, ProgressListener,
+        SimpleTableHandle,
+    },
+    expected_work_fraction,
+    header::{TableStats, HEADER_SIZE},
+    renderer::{
+        batches_partition, multi_device::MultiDeviceRenderer, BatchInformation, KernelHandle,
+        Renderer, StagingHandleSync,
+    },
+    CugparckError, FiltrationIterator, Shard,
 };
 use bytecheck::CheckBytes;
-use crossbeam_channel::{unbounded, Sender};
 use cugparck_commons::{
     ArchivedCompressedPassword, CompressedPassword, RainbowChain, RainbowTableCtx,
 };
 use indexmap::{map::Iter, IndexMap};
 use nohash_hasher::BuildNoHashHasher;
-use rayon::prelude::*;
-use rkyv::{collections::index_map::Iter as RkyvIter, Archive, Deserialize, Infallible, Serialize};
+use rkyv::{
+    collections::index_map::Iter as RkyvIter, with::Skip, AlignedVec, Archive, Deserialize,
+    Infallible, Serialize,
+};
+use tracing::{field, info_span};
 
-use super::{RainbowTable, RainbowTableStorage};
+use super::{disk_chain_map::DiskChainMap, RainbowTable, RainbowTableStorage};
 use crate::error::CugparckResult;
 
 /// An indexed Hashmap using the endpoint of a rainbow chain as the key (and hash value) and the chain as the value.
-type RainbowMap =
+pub(super) type RainbowMap =
     IndexMap<CompressedPassword, CompressedPassword, BuildNoHashHasher<CompressedPassword>>;
 
+/// Statistics about a generated simple rainbow table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationStats {
+    /// The number of unique chains in the table.
+    pub unique_chains: usize,
+    /// The capacity of the table before releasing the unused memory.
+    pub capacity_before_shrink: usize,
+    /// The capacity of the table after releasing the unused memory.
+    pub capacity_after_shrink: usize,
+    /// An estimation of the size of the table once stored, in bytes.
+    pub bytes: usize,
+}
+
+/// The state of an unfinished generation, used to resume it later.
+#[derive(Archive, Deserialize, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Checkpoint {
+    /// The unique chains computed so far, keyed by their midpoint.
+    chains: RainbowMap,
+    /// The context of the table being generated.
+    ctx: RainbowTableCtx,
+    /// The column the generation should resume from.
+    next_column: usize,
+}
+
+impl Checkpoint {
+    /// Loads a checkpoint from the given path.
+    pub fn load_from(path: &Path) -> CugparckResult<Self> {
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(path)?);
+
+        Ok(Self::load(&bytes)?.deserialize(&mut Infallible).unwrap())
+    }
+
+    /// Stores the chains as a checkpoint, without copying them.
+    /// The checkpoint is first written to a temporary file so that a crash never leaves a corrupted checkpoint.
+    fn store_chains(
+        chains: &mut RainbowMap,
+        ctx: RainbowTableCtx,
+        next_column: usize,
+        path: &Path,
+    ) -> CugparckResult<()> {
+        let checkpoint = Self {
+            chains: mem::take(chains),
+            ctx,
+            next_column,
+        };
+
+        let tmp_path = path.with_extension("tmp");
+        let result = checkpoint
+            .store(&tmp_path)
+            .and_then(|_| Ok(fs::rename(&tmp_path, path)?));
+        *chains = checkpoint.chains;
+
+        result
+    }
+
+    /// Returns the context of the table being generated.
+    pub fn ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    /// Returns the progress of the generation when the checkpoint was made, in percent.
+    pub fn progress(&self) -> f64 {
+        self.next_column as f64 / self.ctx.t as f64 * 100.
+    }
+}
+
+impl RainbowTableStorage for Checkpoint {
+    fn header_ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx {
+        archived.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn header_chain_count(&self) -> usize {
+        self.chains.len()
+    }
+}
+
 /// A simple rainbow table.
 #[derive(Archive, Deserialize, Serialize)]
 #[archive_attr(derive(CheckBytes))]
@@ -31,6 +129,18 @@ pub struct SimpleTable {
     chains: RainbowMap,
     /// The context.
     ctx: RainbowTableCtx,
+    /// The shard of the table, if it is only a part of a table.
+    /// It is stored in the header, so it isn't archived.
+    #[with(Skip)]
+    shard: Option<Shard>,
+    /// Statistics about the generation of the table.
+    /// They are stored in the header, so they aren't archived.
+    #[with(Skip)]
+    stats: Option<TableStats>,
+    /// The capacity of the chains before the memory reserved for the generation was released,
+    /// if the table was generated.
+    #[with(Skip)]
+    capacity_before_shrink: Option<usize>,
 }
 
 impl SimpleTable {
@@ -44,132 +154,609 @@ impl SimpleTable {
                     .map(|chain| (chain.endpoint, chain.startpoint)),
             ),
             ctx,
+            shard: None,
+            stats: None,
+            capacity_before_shrink: None,
         }
     }
 
-    // Returns the startpoints in a vec.
-    fn startpoints(ctx: &RainbowTableCtx) -> CugparckResult<Vec<CompressedPassword>> {
+    /// Converts a table to a simple table like `RainbowTable::from_rainbow_table`,
+    /// reporting the chains converted to `progress`.
+    ///
+    /// All the chains of the simple table are held in memory: its archived hash index can only be built
+    /// once all the endpoints are known, so it can't be written to the disk chain after chain.
+    pub fn from_rainbow_table_with_progress<T: RainbowTable>(
+        table: &T,
+        progress: &dyn ProgressListener,
+    ) -> Self {
+        let progress = ProgressCounter::chains(progress, table.len());
+
+        // the map is allocated once, so that it is never twice in memory while growing.
+        let mut chains = RainbowMap::with_capacity_and_hasher(table.len(), Default::default());
+        chains.extend(
+            table
+                .iter()
+                .inspect(|_| progress.add(1))
+                .map(|chain| (chain.endpoint, chain.startpoint)),
+        );
+
+        Self {
+            ctx: table.ctx(),
+            chains,
+            shard: None,
+            stats: None,
+            capacity_before_shrink: None,
+        }
+    }
+
+    /// Merges tables generated with the same context, except the number of startpoints and of filtrations.
+    /// This is useful to assemble the parts of a table generated on several machines.
+    /// The chains are deduplicated by endpoint, and the merged table uses the largest number of startpoints.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tables` is empty.
+    pub fn merge<T: RainbowTable>(tables: &[&T]) -> CugparckResult<Self> {
+        let mut ctx = tables[0].ctx();
+
+        for table in tables {
+            let other = table.ctx();
+
+            if other.tn != ctx.tn {
+                return Err(CugparckError::IncompatibleTable {
+                    tn: other.tn,
+                    parameter: "table number",
+                });
+            }
+
+            if (RainbowTableCtx {
+                m0: ctx.m0,
+                filter_count: ctx.filter_count,
+                ..other
+            }) != ctx
+            {
+                return Err(CugparckError::IncompatibleTable {
+                    tn: other.tn,
+                    parameter: "context",
+                });
+            }
+
+            ctx.m0 = ctx.m0.max(other.m0);
+        }
+
+        let mut chains = RainbowMap::default();
+        chains
+            .try_reserve(tables.iter().map(|table| table.len()).sum())
+            .map_err(|_| CugparckError::IndexMapOutOfMemory)?;
+
+        for table in tables {
+            for chain in table.iter() {
+                chains.entry(chain.endpoint).or_insert(chain.startpoint);
+            }
+        }
+
+        Ok(Self {
+            chains,
+            ctx,
+            shard: None,
+            stats: None,
+            capacity_before_shrink: None,
+        })
+    }
+
+    // Returns the startpoints of the given indexes in a vec.
+    fn startpoints(
+        indexes: Range<usize>,
+        ctx: &RainbowTableCtx,
+    ) -> CugparckResult<Vec<CompressedPassword>> {
         let mut vec = Vec::new();
-        vec.try_reserve_exact(ctx.m0)?;
+        vec.try_reserve_exact(indexes.len())?;
 
-        (0..ctx.m0)
+        indexes
             .into_par_iter()
-            .map(|i| i.into())
+            .map(|i| ctx.startpoint(i))
             .collect_into_vec(&mut vec);
 
         Ok(vec)
     }
 
+    /// Returns an estimation of the size of a stored table with the given number of chains, in bytes.
+    pub fn stored_size(chain_count: usize) -> usize {
+        HEADER_SIZE
+            + chain_count * 2 * mem::size_of::<ArchivedCompressedPassword>()
+            + mem::size_of::<ArchivedSimpleTable>()
+    }
+
+    /// Releases the memory that is not used by the chains.
+    /// Returns the table along with statistics about it.
+    /// The memory reserved for the generation is already released once the table is generated,
+    /// so the capacity before the shrink is the one the chains had at the end of the generation.
+    pub fn shrink_and_finalize(mut self) -> (Self, GenerationStats) {
+        let capacity_before_shrink = self
+            .capacity_before_shrink
+            .take()
+            .unwrap_or_else(|| self.chains.capacity());
+        self.chains.shrink_to_fit();
+
+        let stats = GenerationStats {
+            unique_chains: self.chains.len(),
+            capacity_before_shrink,
+            capacity_after_shrink: self.chains.capacity(),
+            bytes: Self::stored_size(self.chains.len()),
+        };
+
+        (self, stats)
+    }
+
     /// Creates a new simple rainbow table, asynchronously.
     /// Returns an handle to get events related to the generation and to get the generated table.
     pub fn new_nonblocking<T: Backend>(ctx: RainbowTableCtx) -> CugparckResult<SimpleTableHandle> {
-        let (sender, receiver) = unbounded();
-        let thread_handle = thread::spawn(move || Self::new::<T>(ctx, Some(sender)));
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(ctx, Some(channel), None, None, None, None, None)
+        }))
+    }
+
+    /// Creates a new simple rainbow table, asynchronously.
+    /// A checkpoint is stored at the given path after each filtration, so that the generation can be resumed
+    /// with `SimpleTable::resume` if it is interrupted.
+    /// The checkpoint is not removed once the table is generated.
+    pub fn new_nonblocking_with_checkpoints<T: Backend>(
+        ctx: RainbowTableCtx,
+        checkpoint_path: PathBuf,
+    ) -> CugparckResult<SimpleTableHandle> {
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(
+                ctx,
+                Some(channel),
+                Some(checkpoint_path),
+                None,
+                None,
+                None,
+                None,
+            )
+        }))
+    }
+
+    /// Creates a new simple rainbow table, asynchronously, using about `max_host_memory` bytes of memory for the chains.
+    /// If the chains don't fit in this memory, they are deduplicated on the disk in a subdirectory of `spill_dir`
+    /// after each filtration, which is slower. The subdirectory is removed once the table is generated,
+    /// and so is `spill_dir` if it didn't exist and is empty.
+    pub fn new_nonblocking_with_max_host_memory<T: Backend>(
+        ctx: RainbowTableCtx,
+        max_host_memory: usize,
+        spill_dir: PathBuf,
+    ) -> CugparckResult<SimpleTableHandle> {
+        let disk_map = if DiskChainMap::is_needed(ctx.m0, max_host_memory) {
+            Some(DiskChainMap::new(&spill_dir, ctx.m0, max_host_memory)?)
+        } else {
+            None
+        };
+
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(ctx, Some(channel), None, None, disk_map, None, None)
+        }))
+    }
+
+    /// Creates a part of a simple rainbow table, asynchronously.
+    /// Only the startpoints of the shard are used, and the parts are assembled with `SimpleTable::merge`.
+    pub fn new_nonblocking_shard<T: Backend>(
+        ctx: RainbowTableCtx,
+        shard: Shard,
+    ) -> CugparckResult<SimpleTableHandle> {
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(ctx, Some(channel), None, None, None, Some(shard), None)
+        }))
+    }
+
+    /// Creates a new simple rainbow table, asynchronously, computing at most `max_batch_size` chains at once.
+    /// By default, the size of the batches is computed from the properties of the device.
+    /// This only has an effect on the backends working with batches, such as CUDA.
+    pub fn new_nonblocking_with_batch_size<T: Backend>(
+        ctx: RainbowTableCtx,
+        max_batch_size: usize,
+    ) -> CugparckResult<SimpleTableHandle> {
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(
+                ctx,
+                Some(channel),
+                None,
+                None,
+                None,
+                None,
+                Some(max_batch_size),
+            )
+        }))
+    }
+
+    /// Creates a new simple rainbow table, asynchronously, splitting each batch between several devices of the backend.
+    /// The devices are numbered like in `Backend::devices`, and each of them computes a part of the batch
+    /// proportional to the size of its own batches.
+    pub fn new_nonblocking_with_devices<T: Backend>(
+        ctx: RainbowTableCtx,
+        devices: Vec<usize>,
+    ) -> CugparckResult<SimpleTableHandle>
+    where
+        T::Renderer: Send,
+    {
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            let renderer = MultiDeviceRenderer::new::<T>(&devices, ctx.m0, None)?;
+            Self::generate::<T, _>(renderer, ctx, Some(channel), None, None, None, None)
+        }))
+    }
+
+    /// Extends the chains of the table to `chain_length` columns, asynchronously.
+    /// The chains are continued from their endpoints instead of being computed again from their startpoints,
+    /// and the chains merging in the new columns are removed, so the rebuilt table is usually smaller.
+    pub fn rebuild_nonblocking<T: Backend>(
+        self,
+        chain_length: usize,
+    ) -> CugparckResult<SimpleTableHandle> {
+        let shard = self.shard;
+        let checkpoint = self.into_rebuild_checkpoint(chain_length)?;
+
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(
+                checkpoint.ctx,
+                Some(channel),
+                None,
+                Some(checkpoint),
+                None,
+                shard,
+                None,
+            )
+        }))
+    }
 
-        Ok(SimpleTableHandle {
-            thread_handle,
-            receiver,
+    /// Extends the chains of the table to `chain_length` columns.
+    /// See `SimpleTable::rebuild_nonblocking`.
+    pub fn rebuild_blocking<T: Backend>(self, chain_length: usize) -> CugparckResult<Self> {
+        let shard = self.shard;
+        let checkpoint = self.into_rebuild_checkpoint(chain_length)?;
+
+        Self::new::<T>(
+            checkpoint.ctx,
+            None,
+            None,
+            Some(checkpoint),
+            None,
+            shard,
+            None,
+        )
+    }
+
+    /// Returns a checkpoint continuing the generation of the table up to `chain_length` columns.
+    fn into_rebuild_checkpoint(self, chain_length: usize) -> CugparckResult<Checkpoint> {
+        if chain_length <= self.ctx.t {
+            return Err(CugparckError::ChainLengthTooShort(self.ctx.t));
+        }
+
+        Ok(Checkpoint {
+            chains: self.chains,
+            ctx: RainbowTableCtx {
+                t: chain_length,
+                ..self.ctx
+            },
+            // the endpoints are at the column t - 1
+            next_column: self.ctx.t - 1,
         })
     }
 
+    /// Returns the shard of the table, if it is only a part of a table.
+    pub fn shard(&self) -> Option<Shard> {
+        self.shard
+    }
+
+    /// Returns statistics about the generation of the table, if it was generated and not loaded or merged.
+    pub fn stats(&self) -> Option<&TableStats> {
+        self.stats.as_ref()
+    }
+
+    /// Sets the generation statistics stored in the header, usually those of the table it was converted from.
+    pub fn with_stats(mut self, stats: Option<TableStats>) -> Self {
+        self.stats = stats;
+
+        self
+    }
+
+    /// Resumes the generation of a simple rainbow table from a checkpoint, asynchronously.
+    /// The checkpoint keeps being updated as the generation goes on.
+    pub fn resume<T: Backend>(checkpoint_path: PathBuf) -> CugparckResult<SimpleTableHandle> {
+        let checkpoint = Checkpoint::load_from(&checkpoint_path)?;
+
+        Ok(SimpleTableHandle::spawn(move |channel| {
+            Self::new::<T>(
+                checkpoint.ctx,
+                Some(channel),
+                Some(checkpoint_path),
+                Some(checkpoint),
+                None,
+                None,
+                None,
+            )
+        }))
+    }
+
     /// Creates a new simple rainbow table.
     pub fn new_blocking<T: Backend>(ctx: RainbowTableCtx) -> CugparckResult<Self> {
-        Self::new::<T>(ctx, None)
+        Self::new::<T>(ctx, None, None, None, None, None, None)
     }
 
     fn new<T: Backend>(
         ctx: RainbowTableCtx,
-        sender: Option<Sender<Event>>,
+        channel: Option<GenerationChannel>,
+        checkpoint_path: Option<PathBuf>,
+        checkpoint: Option<Checkpoint>,
+        disk_map: Option<DiskChainMap>,
+        shard: Option<Shard>,
+        max_batch_size: Option<usize>,
     ) -> CugparckResult<Self> {
-        let mut startpoints: Vec<CompressedPassword> = Self::startpoints(&ctx)?;
-        let mut midpoints: Vec<CompressedPassword> = Self::startpoints(&ctx)?;
+        let chains_len = shard.map_or(ctx.m0, |shard| shard.startpoints(ctx.m0).len());
+        let renderer =
+            T::renderer(chains_len, max_batch_size).map_err(|err| CugparckError::NoBackend {
+                requested: T::NAME,
+                source: Box::new(err),
+            })?;
 
-        let mut unique_chains = RainbowMap::default();
-        unique_chains
-            .try_reserve(ctx.m0)
-            .map_err(|_| CugparckError::IndexMapOutOfMemory)?;
+        Self::generate::<T, _>(
+            renderer,
+            ctx,
+            channel,
+            checkpoint_path,
+            checkpoint,
+            disk_map,
+            shard,
+        )
+    }
+
+    /// Generates the table with a renderer of the backend `T`.
+    fn generate<T: Backend, R: Renderer>(
+        mut renderer: R,
+        ctx: RainbowTableCtx,
+        channel: Option<GenerationChannel>,
+        checkpoint_path: Option<PathBuf>,
+        checkpoint: Option<Checkpoint>,
+        mut disk_map: Option<DiskChainMap>,
+        shard: Option<Shard>,
+    ) -> CugparckResult<Self> {
+        let startpoints_range = shard.map_or(0..ctx.m0, |shard| shard.startpoints(ctx.m0));
+        let mut startpoints: Vec<CompressedPassword> =
+            Self::startpoints(startpoints_range.clone(), &ctx)?;
+        let mut midpoints: Vec<CompressedPassword> = Self::startpoints(startpoints_range, &ctx)?;
+
+        // the spans can be recorded with `--profile` in the CLI, to see where the generation spends its time
+        let generation_span = info_span!(
+            "generation",
+            backend = T::NAME,
+            startpoints = startpoints.len(),
+            t = ctx.t,
+            hashes_per_sec = field::Empty,
+        )
+        .entered();
 
-        let mut renderer = T::renderer(startpoints.len())?;
+        let (mut unique_chains, next_column) = match checkpoint {
+            Some(checkpoint) => (checkpoint.chains, checkpoint.next_column),
+            None => (RainbowMap::default(), 0),
+        };
+        // with a disk map, the chains are only kept in memory once the generation is done
+        if disk_map.is_none() {
+            unique_chains
+                .try_reserve(startpoints.len().saturating_sub(unique_chains.len()))
+                .map_err(|_| CugparckError::IndexMapOutOfMemory)?;
+        }
 
         let mut batch_buf: Vec<CompressedPassword> = Vec::new();
         batch_buf.try_reserve_exact(renderer.max_staged_buffer_len(startpoints.len())?)?;
 
-        for columns in FiltrationIterator::new(ctx) {
-            if !unique_chains.is_empty() {
+        // measures the speed of the generation
+        let start = Instant::now();
+        let mut paused = Duration::ZERO;
+        let mut chains_computed = 0;
+        let mut hashes_computed = 0;
+        let start_work_fraction = expected_work_fraction(&ctx, next_column as f64);
+        let mut filtration_curve = Vec::new();
+
+        // the columns before the checkpoint are already computed.
+        // When a table is rebuilt, the checkpoint can be in the middle of a filtration.
+        for columns in FiltrationIterator::new(ctx).filter_map(|columns| {
+            (columns.end > next_column).then(|| columns.start.max(next_column)..columns.end)
+        }) {
+            // the chains of a checkpoint are used even if there are none left
+            if next_column > 0 || !unique_chains.is_empty() {
                 unique_chains
                     .par_drain(..)
                     .unzip_into_vecs(&mut midpoints, &mut startpoints);
             }
 
+            let chains = midpoints.len();
+            let columns_span = info_span!(
+                "columns",
+                start = columns.start,
+                end = columns.end,
+                chains,
+                chains_left = field::Empty,
+            )
+            .entered();
+
+            // each chain should be computed by exactly one batch
+            debug_assert!(batches_partition(
+                renderer.batch_iter(midpoints.len())?,
+                midpoints.len()
+            ));
+
             let batch_iter = renderer.batch_iter(midpoints.len())?.enumerate();
             let batch_count = batch_iter.len();
             let mut previous_batch_range = Range::default();
 
             for (batch_number, batch_info) in batch_iter {
-                if let Some(sender) = &sender {
-                    sender
-                        .send(Event::Batch {
-                            batch_number: batch_number + 1,
-                            batch_count,
-                            columns: columns.clone(),
-                        })
-                        .unwrap();
+                if let Some(channel) = &channel {
+                    // no kernel is running between two batches, so the generation can be paused or stopped here
+                    let wait_start = Instant::now();
+                    channel.wait_between_batches()?;
+                    paused += wait_start.elapsed();
+
+                    channel.send(Event::DeviceStats(renderer.device_memory()?));
+                    channel.send(Event::Batch {
+                        batch_number: batch_number + 1,
+                        batch_count,
+                        columns: columns.clone(),
+                    });
                 }
 
+                let _batch_span = info_span!(
+                    "batch",
+                    number = batch_number + 1,
+                    chains = batch_info.range().len(),
+                    hashes = batch_info.range().len() * columns.len(),
+                )
+                .entered();
+
                 let batch = &mut midpoints[batch_info.range()];
-                let kernel_handle =
-                    renderer.start_kernel(batch, &batch_info, columns.clone(), ctx)?;
+                let kernel_handle = {
+                    let _span = info_span!("start_kernel").entered();
+                    renderer.start_kernel(batch, &batch_info, columns.clone(), ctx)?
+                };
 
                 match kernel_handle {
                     // the kernel is already done and the chains have been modified in place
                     KernelHandle::Sync => {
-                        unique_chains.par_extend(
-                            batch
-                                .par_iter()
-                                .zip(startpoints[batch_info.range()].par_iter()),
-                        );
+                        add_chains(
+                            &mut unique_chains,
+                            &mut disk_map,
+                            batch,
+                            &startpoints[batch_info.range()],
+                        )?;
                     }
 
                     // the kernel is still running and the new midpoints will be available in the staging buffer
                     KernelHandle::Staged(mut staging_handle) => {
                         // add the chains of the previous batch to the HashMap while the kernel is running
-                        unique_chains.par_extend(
-                            batch_buf
-                                .par_iter()
-                                .zip(startpoints[previous_batch_range].par_iter()),
-                        );
+                        add_chains(
+                            &mut unique_chains,
+                            &mut disk_map,
+                            &batch_buf,
+                            &startpoints[previous_batch_range],
+                        )?;
 
-                        staging_handle.sync(&mut batch_buf)?;
+                        {
+                            let _span = info_span!("sync").entered();
+                            staging_handle.sync(&mut batch_buf)?;
+                        }
                         previous_batch_range = batch_info.range();
                     }
                 }
 
-                if let Some(sender) = &sender {
-                    let batch_percent = batch_number as f64 / batch_count as f64;
+                chains_computed += batch_info.range().len();
+                hashes_computed += batch_info.range().len() * columns.len();
+
+                if let Some(channel) = &channel {
+                    // the batch is done at this point
+                    let batch_percent = (batch_number + 1) as f64 / batch_count as f64;
                     let current_col_progress = columns.len() as f64 * batch_percent;
-                    let col_progress = columns.start as f64;
-                    let progress = (col_progress + current_col_progress) / ctx.t as f64 * 100.;
+                    let column = columns.start as f64 + current_col_progress;
+                    let progress = column / ctx.t as f64 * 100.;
 
-                    sender.send(Event::Progress(progress)).unwrap();
+                    channel.send(Event::Progress(progress));
+
+                    let elapsed = start.elapsed().saturating_sub(paused);
+                    let secs = elapsed.as_secs_f64();
+                    let work_fraction = expected_work_fraction(&ctx, column);
+                    let work_done = work_fraction - start_work_fraction;
+                    channel.send(Event::Speed(GenerationSpeed {
+                        chains_per_sec: chains_computed as f64 / secs,
+                        hashes_per_sec: hashes_computed as f64 / secs,
+                        eta: (work_done > 0.)
+                            .then(|| elapsed.mul_f64((1. - work_fraction).max(0.) / work_done)),
+                    }));
                 }
             }
 
             // add the chains of the last batch
-            unique_chains.par_extend(
-                batch_buf
-                    .par_iter()
-                    .zip(startpoints[previous_batch_range].par_iter()),
-            );
+            add_chains(
+                &mut unique_chains,
+                &mut disk_map,
+                &batch_buf,
+                &startpoints[previous_batch_range],
+            )?;
+
+            if let Some(disk_map) = &mut disk_map {
+                let _span = info_span!("disk_filtration").entered();
+                disk_map.drain_into(&mut midpoints, &mut startpoints)?;
+            }
+
+            let chains_left = if disk_map.is_some() {
+                midpoints.len()
+            } else {
+                unique_chains.len()
+            };
+            filtration_curve.push((columns.end as u64, chains_left as u64));
+            columns_span.record("chains_left", chains_left);
+            if let Some(channel) = &channel {
+                channel.send(Event::Filtration {
+                    columns: columns.clone(),
+                    chains,
+                    chains_left,
+                });
+            }
+
+            if let Some(path) = &checkpoint_path {
+                if columns.end < ctx.t - 1 {
+                    let _span = info_span!("checkpoint").entered();
+                    Checkpoint::store_chains(&mut unique_chains, ctx, columns.end, path)?;
+                }
+            }
+        }
+
+        if disk_map.is_some() {
+            unique_chains
+                .try_reserve(midpoints.len())
+                .map_err(|_| CugparckError::IndexMapOutOfMemory)?;
+            unique_chains.extend(midpoints.into_iter().zip(startpoints));
         }
 
+        let duration = start.elapsed().saturating_sub(paused);
+        generation_span.record(
+            "hashes_per_sec",
+            hashes_computed as f64 / duration.as_secs_f64(),
+        );
+
+        let stats = TableStats {
+            unique_chains: unique_chains.len() as u64,
+            filtration_curve,
+            duration,
+            backend: T::NAME.to_owned(),
+            cugparck_version: env!("CARGO_PKG_VERSION").to_owned(),
+        };
+
+        let capacity_before_shrink = unique_chains.capacity();
         unique_chains.shrink_to_fit();
+
         Ok(Self {
             chains: unique_chains,
             ctx,
+            shard,
+            stats: Some(stats),
+            capacity_before_shrink: Some(capacity_before_shrink),
         })
     }
 }
 
+/// Adds chains computed by the kernel to the chain map, or to the disk map if there is one.
+fn add_chains(
+    unique_chains: &mut RainbowMap,
+    disk_map: &mut Option<DiskChainMap>,
+    midpoints: &[CompressedPassword],
+    startpoints: &[CompressedPassword],
+) -> CugparckResult<()> {
+    let _span = info_span!("filtration", chains = midpoints.len()).entered();
+
+    match disk_map {
+        Some(disk_map) => disk_map.extend(midpoints, startpoints)?,
+        None => unique_chains.par_extend(midpoints.par_iter().zip(startpoints.par_iter())),
+    }
+
+    Ok(())
+}
+
 impl RainbowTable for SimpleTable {
     type Iter<'a> = SimpleTableIterator<'a>;
 
@@ -185,18 +772,16 @@ impl RainbowTable for SimpleTable {
         self.chains.get(&password).copied()
     }
 
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.chains.get_index_of(&password)
+    }
+
     fn ctx(&self) -> RainbowTableCtx {
         self.ctx
     }
 
-    fn from_rainbow_table<T: RainbowTable>(table: T) -> Self {
-        Self {
-            ctx: table.ctx(),
-            chains: table
-                .iter()
-                .map(|chain| (chain.endpoint, chain.startpoint))
-                .collect(),
-        }
+    fn from_rainbow_table<T: RainbowTable>(table: &T) -> Self {
+        Self::from_rainbow_table_with_progress(table, &())
     }
 }
 
@@ -217,11 +802,15 @@ impl RainbowTable for ArchivedSimpleTable {
             .map(|ar| ar.deserialize(&mut Infallible).unwrap())
     }
 
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.chains.get_index_of(&password.into())
+    }
+
     fn ctx(&self) -> RainbowTableCtx {
         self.ctx.deserialize(&mut Infallible).unwrap()
     }
 
-    fn from_rainbow_table<T: RainbowTable>(_: T) -> Self {
+    fn from_rainbow_table<T: RainbowTable>(_: &T) -> Self {
         panic!("Archived tables cannot be built from other tables")
     }
 }
@@ -288,7 +877,27 @@ impl Iterator for ArchivedSimpleTableIterator<'_> {
     }
 }
 
-impl RainbowTableStorage for SimpleTable {}
+impl RainbowTableStorage for SimpleTable {
+    fn header_ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx {
+        archived.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn header_chain_count(&self) -> usize {
+        self.len()
+    }
+
+    fn header_shard(&self) -> Option<Shard> {
+        self.shard
+    }
+
+    fn header_stats(&self) -> Option<TableStats> {
+        self.stats.clone()
+    }
+}
 
 impl std::fmt::Debug for SimpleTable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -299,10 +908,363 @@ impl std::fmt::Debug for SimpleTable {
             writeln!(
                 f,
                 "{} -> {}",
-                core::str::from_utf8(&startpoint.into_password(&self.ctx)).unwrap(),
-                core::str::from_utf8(&endpoint.into_password(&self.ctx)).unwrap(),
+                startpoint.into_password(&self.ctx),
+                endpoint.into_password(&self.ctx),
             )?;
         }
         writeln!(f, "...")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use cugparck_commons::{HashType, RainbowTableCtx};
+
+    use crate::{
+        backend::{Backend, Cpu},
+        error::CugparckResult,
+        renderer::cpu::CpuRenderer,
+        temp_path, Checkpoint, CugparckError, RainbowTable, RainbowTableCtxBuilder, Shard,
+        SimpleTable,
+    };
+
+    /// A backend that is never available.
+    struct UnavailableBackend;
+
+    impl Backend for UnavailableBackend {
+        const NAME: &'static str = "unavailable";
+
+        type Renderer = CpuRenderer;
+
+        fn renderer(
+            _chains_len: usize,
+            _max_batch_size: Option<usize>,
+        ) -> CugparckResult<Self::Renderer> {
+            Err(CugparckError::NoGpu)
+        }
+    }
+
+    #[test]
+    fn test_shrink_and_finalize() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        // the generation already released the memory it reserved
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let capacity = table.chains.capacity();
+        let (table, stats) = table.shrink_and_finalize();
+
+        assert_eq!(capacity, stats.capacity_after_shrink);
+        assert_eq!(table.len(), stats.unique_chains);
+        assert!(stats.capacity_after_shrink <= stats.capacity_before_shrink);
+        assert!(stats.capacity_after_shrink >= stats.unique_chains);
+    }
+
+    #[test]
+    fn test_chains_all_hash_types() {
+        for hash_type in HashType::ALL {
+            let ctx = RainbowTableCtxBuilder::new()
+                .hash(hash_type)
+                .chain_length(20)
+                .max_password_length(3)
+                .charset(b"abc")
+                .build()
+                .unwrap();
+
+            let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+            for chain in table.iter() {
+                let mut endpoint = chain.startpoint;
+                endpoint.continue_chain(0..ctx.t - 1, &ctx);
+                assert_eq!(chain.endpoint, endpoint, "{hash_type:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unavailable_backend() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let err = SimpleTable::new_nonblocking::<UnavailableBackend>(ctx)
+            .unwrap()
+            .join()
+            .unwrap_err();
+
+        // the reason why the backend is unavailable is kept.
+        assert!(matches!(
+            err,
+            CugparckError::NoBackend {
+                requested: "unavailable",
+                source,
+            } if matches!(*source, CugparckError::NoGpu)
+        ));
+    }
+
+    #[test]
+    fn test_resume() {
+        let path = temp_path("resume");
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_nonblocking_with_checkpoints::<Cpu>(ctx, path.clone())
+            .unwrap()
+            .join()
+            .unwrap();
+
+        // the last checkpoint is made before the last filtration
+        let checkpoint = Checkpoint::load_from(&path).unwrap();
+        assert!(checkpoint.ctx() == ctx);
+        assert!(checkpoint.progress() > 0. && checkpoint.progress() < 100.);
+
+        let resumed = SimpleTable::resume::<Cpu>(path.clone())
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let endpoints = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(endpoints(&table), endpoints(&resumed));
+
+        for chain in resumed.iter() {
+            let mut endpoint = chain.startpoint;
+            endpoint.continue_chain(0..ctx.t - 1, &ctx);
+            assert_eq!(chain.endpoint, endpoint);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_merge() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        // split the chains in two overlapping halves
+        let chains = table.iter().collect::<Vec<_>>();
+        let first = SimpleTable::from_vec(chains[..chains.len() * 2 / 3].to_vec(), ctx);
+        let second = SimpleTable::from_vec(
+            chains[chains.len() / 3..].to_vec(),
+            RainbowTableCtx { m0: 10, ..ctx },
+        );
+
+        let merged = SimpleTable::merge(&[&first, &second]).unwrap();
+        assert_eq!(table.len(), merged.len());
+        assert!(merged.ctx() == ctx);
+
+        let other_table = SimpleTable::from_vec(Vec::new(), RainbowTableCtx { tn: 2, ..ctx });
+        let err = SimpleTable::merge(&[&first, &other_table]).unwrap_err();
+        assert!(matches!(
+            err,
+            CugparckError::IncompatibleTable {
+                parameter: "table number",
+                ..
+            }
+        ));
+
+        let other_ctx = SimpleTable::from_vec(Vec::new(), RainbowTableCtx { t: 200, ..ctx });
+        let err = SimpleTable::merge(&[&first, &other_ctx]).unwrap_err();
+        assert!(matches!(
+            err,
+            CugparckError::IncompatibleTable {
+                parameter: "context",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_max_host_memory() {
+        let dir = temp_path("spill");
+        // enough chains for the partitions to be bigger than their buffers
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(10)
+            .max_password_length(6)
+            .charset(b"abcdef")
+            .startpoints(Some(50_000))
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        // just enough memory for the midpoints and startpoints, the chains are deduplicated on the disk
+        let spilled =
+            SimpleTable::new_nonblocking_with_max_host_memory::<Cpu>(ctx, ctx.m0 * 24, dir.clone())
+                .unwrap()
+                .join()
+                .unwrap();
+        assert!(!dir.exists());
+
+        let endpoints = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(endpoints(&table), endpoints(&spilled));
+
+        for chain in spilled.iter() {
+            let mut endpoint = chain.startpoint;
+            endpoint.continue_chain(0..ctx.t - 1, &ctx);
+            assert_eq!(chain.endpoint, endpoint);
+        }
+
+        let err = SimpleTable::new_nonblocking_with_max_host_memory::<Cpu>(ctx, ctx.m0, dir)
+            .err()
+            .unwrap();
+        assert!(matches!(err, CugparckError::MaxHostMemoryTooLow(_)));
+    }
+
+    #[test]
+    fn test_shards() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let shards = (1..=3)
+            .map(|i| {
+                let shard = Shard::new(i, 3).unwrap();
+                let table = SimpleTable::new_nonblocking_shard::<Cpu>(ctx, shard)
+                    .unwrap()
+                    .join()
+                    .unwrap();
+                assert_eq!(Some(shard), table.shard());
+
+                for chain in table.iter() {
+                    assert!(shard.startpoints(ctx.m0).contains(&chain.startpoint.get()));
+                }
+
+                table
+            })
+            .collect::<Vec<_>>();
+
+        let merged = SimpleTable::merge(&shards.iter().collect::<Vec<_>>()).unwrap();
+        assert!(merged.ctx() == ctx);
+
+        let endpoints = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(endpoints(&table), endpoints(&merged));
+    }
+
+    #[test]
+    fn test_seeded_startpoints() {
+        let builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .startpoint_seed(Some(1234));
+        let ctx = builder.build().unwrap();
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let startpoints = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| chain.startpoint.get())
+                .collect::<HashSet<_>>()
+        };
+
+        // the startpoints are spread over the search space, and the same seed gives the same table
+        assert!(startpoints(&table)
+            .iter()
+            .any(|&startpoint| startpoint >= ctx.m0));
+        assert!(startpoints(&table)
+            .iter()
+            .all(|&startpoint| startpoint < ctx.n));
+        let again = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        assert_eq!(startpoints(&table), startpoints(&again));
+
+        let other =
+            SimpleTable::new_blocking::<Cpu>(builder.startpoint_seed(Some(4321)).build().unwrap())
+                .unwrap();
+        assert_ne!(startpoints(&table), startpoints(&other));
+
+        // the shards still split the startpoints of the table
+        let shards = (1..=3)
+            .map(|i| {
+                SimpleTable::new_nonblocking_shard::<Cpu>(ctx, Shard::new(i, 3).unwrap())
+                    .unwrap()
+                    .join()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let merged = SimpleTable::merge(&shards.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(
+            table
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>(),
+            merged
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn test_rebuild() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let rebuilt_ctx = RainbowTableCtx { t: 300, ..ctx };
+
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let chain_count = table.len();
+        let rebuilt = table.rebuild_blocking::<Cpu>(300).unwrap();
+        assert!(rebuilt.ctx() == rebuilt_ctx);
+        assert!(rebuilt.len() <= chain_count);
+
+        for chain in rebuilt.iter() {
+            let mut endpoint = chain.startpoint;
+            endpoint.continue_chain(0..rebuilt_ctx.t - 1, &rebuilt_ctx);
+            assert_eq!(chain.endpoint, endpoint);
+        }
+
+        // the same chains are obtained by generating the table with the larger chain length directly
+        let generated = SimpleTable::new_blocking::<Cpu>(rebuilt_ctx).unwrap();
+        let endpoints = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| chain.endpoint)
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(endpoints(&generated), endpoints(&rebuilt));
+
+        let err = rebuilt.rebuild_blocking::<Cpu>(300).err().unwrap();
+        assert!(matches!(err, CugparckError::ChainLengthTooShort(300)));
+    }
+}
@@ -0,0 +1,443 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+};
+
+use crate::parallel::*;
+use bitvec::prelude::*;
+use bytecheck::CheckBytes;
+use cugparck_commons::{CompressedPassword, RainbowChain};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, DeserializeUnsized, Fallible, Serialize,
+};
+
+use super::{disk_chain_map::MAX_PARTITION_COUNT, RainbowTable};
+use crate::{
+    error::{CugparckError, CugparckResult},
+    event::ProgressCounter,
+};
+
+/// The number of chains sorted in memory at once by `SortedChains`, about 16 MiB.
+/// The buffers are much smaller in the tests, so that the small tables tested still use several of them.
+#[cfg(not(test))]
+const PARTITION_CHAINS: usize = 1 << 20;
+#[cfg(test)]
+const PARTITION_CHAINS: usize = 100;
+
+/// The number of bits kept in memory by a `BitSpill` before they are written to its file.
+#[cfg(not(test))]
+const SPILL_BUFFER_BITS: usize = 1 << 16;
+#[cfg(test)]
+const SPILL_BUFFER_BITS: usize = 256;
+
+/// The number of values read at once from a spill file when it is copied to an archive.
+#[cfg(not(test))]
+const SPILL_CHUNK_LEN: usize = 1 << 13;
+#[cfg(test)]
+const SPILL_CHUNK_LEN: usize = 10;
+
+/// An archive wrapper for a `BitVec`.
+/// Unlike `ArchivedBitVec`, its archived version can also be written from bits spilled to the disk,
+/// so tables too large for the memory can be stored block after block.
+pub(crate) struct StreamBits;
+
+/// An archived `BitVec`, written with `StreamBits`.
+#[derive(CheckBytes)]
+#[repr(C)]
+pub struct ArchivedBits {
+    words: ArchivedVec<Archived<usize>>,
+    bit_len: Archived<usize>,
+}
+
+impl Deref for ArchivedBits {
+    type Target = BitSlice<Archived<usize>, Lsb0>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.words.view_bits::<Lsb0>()[..self.bit_len as usize]
+    }
+}
+
+/// The resolver of an `ArchivedBits`.
+pub(crate) struct BitsResolver {
+    words: StreamVecResolver,
+    bit_len: usize,
+}
+
+impl ArchiveWith<BitVec> for StreamBits {
+    type Archived = ArchivedBits;
+    type Resolver = BitsResolver;
+
+    unsafe fn resolve_with(
+        _: &BitVec,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let (fp, fo) = out_field!(out.words);
+        resolver.words.resolve(pos + fp, fo);
+        let (fp, fo) = out_field!(out.bit_len);
+        usize::resolve(&resolver.bit_len, pos + fp, (), fo);
+    }
+}
+
+impl<S: Serializer + ScratchSpace + ?Sized> SerializeWith<BitVec, S> for StreamBits {
+    fn serialize_with(field: &BitVec, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let words = field.as_raw_slice();
+
+        Ok(BitsResolver {
+            words: StreamVecResolver {
+                vec: ArchivedVec::serialize_from_slice(words, serializer)?,
+                len: words.len(),
+            },
+            bit_len: field.len(),
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<ArchivedBits, BitVec, D> for StreamBits {
+    fn deserialize_with(field: &ArchivedBits, _: &mut D) -> Result<BitVec, D::Error> {
+        let mut bits = BitVec::from_vec(field.words.iter().map(|&word| word as usize).collect());
+        bits.truncate(field.bit_len as usize);
+
+        Ok(bits)
+    }
+}
+
+/// An archive wrapper for a `Vec`, whose archived version can also be written from values spilled to the disk.
+/// The archived version is the same as the one of a `Vec`.
+pub(crate) struct StreamVec;
+
+/// The resolver of a vector written with `StreamVec`.
+pub(crate) struct StreamVecResolver {
+    vec: VecResolver,
+    len: usize,
+}
+
+impl StreamVecResolver {
+    /// Writes the values of a vector chunk after chunk, and returns the resolver of the vector.
+    pub fn from_chunks<T, S>(
+        chunks: impl Iterator<Item = CugparckResult<Vec<T>>>,
+        serializer: &mut S,
+    ) -> CugparckResult<Self>
+    where
+        T: Serialize<S>,
+        S: Serializer + ScratchSpace + ?Sized,
+    {
+        let mut resolver: Option<Self> = None;
+
+        // the chunks are aligned like the values, so they are written one after the other
+        for chunk in chunks {
+            let chunk = chunk?;
+            let vec = ArchivedVec::serialize_from_slice(&chunk, serializer)
+                .map_err(|_| CugparckError::Serialize)?;
+
+            match &mut resolver {
+                Some(resolver) => resolver.len += chunk.len(),
+                None => {
+                    resolver = Some(Self {
+                        vec,
+                        len: chunk.len(),
+                    })
+                }
+            }
+        }
+
+        match resolver {
+            Some(resolver) => Ok(resolver),
+            None => Ok(Self {
+                vec: ArchivedVec::serialize_from_slice(&[] as &[T], serializer)
+                    .map_err(|_| CugparckError::Serialize)?,
+                len: 0,
+            }),
+        }
+    }
+
+    unsafe fn resolve<T>(self, pos: usize, out: *mut ArchivedVec<T>) {
+        ArchivedVec::resolve_from_len(self.len, pos, self.vec, out);
+    }
+}
+
+impl<T: Archive> ArchiveWith<Vec<T>> for StreamVec {
+    type Archived = ArchivedVec<T::Archived>;
+    type Resolver = StreamVecResolver;
+
+    unsafe fn resolve_with(
+        _: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        resolver.resolve(pos, out);
+    }
+}
+
+impl<T, S> SerializeWith<Vec<T>, S> for StreamVec
+where
+    T: Serialize<S>,
+    S: Serializer + ScratchSpace + ?Sized,
+{
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(StreamVecResolver {
+            vec: ArchivedVec::serialize_from_slice(field, serializer)?,
+            len: field.len(),
+        })
+    }
+}
+
+impl<T, D> DeserializeWith<ArchivedVec<T::Archived>, Vec<T>, D> for StreamVec
+where
+    T: Archive,
+    [T::Archived]: DeserializeUnsized<[T], D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize_with(
+        field: &ArchivedVec<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<T>, D::Error> {
+        field.deserialize(deserializer)
+    }
+}
+
+/// A destination for the bits of a table, either in memory or in a spill file.
+pub(crate) trait BitSink {
+    /// Appends the `count` least significant bits of `value`, in Lsb0 order.
+    fn push_bits(&mut self, value: usize, count: usize);
+
+    /// Returns the number of bits appended.
+    fn bit_len(&self) -> usize;
+}
+
+impl BitSink for BitVec {
+    #[inline]
+    fn push_bits(&mut self, value: usize, count: usize) {
+        self.extend_from_bitslice(&value.view_bits::<Lsb0>()[..count]);
+    }
+
+    #[inline]
+    fn bit_len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Bits written to a spill file as they are produced, to be copied to an archive once they are all known.
+pub(crate) struct BitSpill {
+    path: PathBuf,
+    file: BufWriter<File>,
+    /// The last bits, which are written to the file once they fill enough words.
+    pending: BitVec,
+    word_count: usize,
+    bit_len: usize,
+    /// The first error that occured while writing to the file, reported by `BitSpill::serialize`.
+    error: Option<io::Error>,
+}
+
+impl BitSpill {
+    /// Creates a new spill file at the given path.
+    pub fn new(path: PathBuf) -> CugparckResult<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(&path)?),
+            path,
+            pending: BitVec::with_capacity(SPILL_BUFFER_BITS + usize::BITS as usize),
+            word_count: 0,
+            bit_len: 0,
+            error: None,
+        })
+    }
+
+    /// Writes the words filled by the pending bits to the file.
+    fn write_words(&mut self) {
+        let words = self.pending.len() / usize::BITS as usize;
+
+        for word in &self.pending.as_raw_slice()[..words] {
+            if let Err(err) = self.file.write_all(&(*word as u64).to_le_bytes()) {
+                self.error.get_or_insert(err);
+            }
+        }
+
+        self.pending = self.pending.split_off(words * usize::BITS as usize);
+        self.word_count += words;
+    }
+
+    /// Writes the bits to an archive, as a `BitVec` wrapped with `StreamBits` would be.
+    /// The spill file is removed afterwards.
+    pub fn serialize<S>(mut self, serializer: &mut S) -> CugparckResult<BitsResolver>
+    where
+        S: Serializer + ScratchSpace + ?Sized,
+    {
+        self.write_words();
+        if !self.pending.is_empty() {
+            self.pending.resize(usize::BITS as usize, false);
+            self.write_words();
+        }
+
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+        self.file.flush()?;
+
+        let words = StreamVecResolver::from_chunks(
+            read_chunks(&self.path, self.word_count, 8, |bytes| {
+                u64::from_le_bytes(bytes.try_into().unwrap()) as usize
+            })?,
+            serializer,
+        )?;
+        fs::remove_file(&self.path)?;
+
+        Ok(BitsResolver {
+            words,
+            bit_len: self.bit_len,
+        })
+    }
+}
+
+impl BitSink for BitSpill {
+    #[inline]
+    fn push_bits(&mut self, value: usize, count: usize) {
+        self.pending
+            .extend_from_bitslice(&value.view_bits::<Lsb0>()[..count]);
+        self.bit_len += count;
+
+        if self.pending.len() >= SPILL_BUFFER_BITS {
+            self.write_words();
+        }
+    }
+
+    #[inline]
+    fn bit_len(&self) -> usize {
+        self.bit_len
+    }
+}
+
+/// Reads the `len` values of `size` bytes stored in a spill file, by chunks of `SPILL_CHUNK_LEN` values.
+pub(crate) fn read_chunks<T>(
+    path: &Path,
+    len: usize,
+    size: usize,
+    parse: impl Fn(&[u8]) -> T,
+) -> CugparckResult<impl Iterator<Item = CugparckResult<Vec<T>>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut buffer = vec![0; SPILL_CHUNK_LEN * size];
+
+    Ok((0..len).step_by(SPILL_CHUNK_LEN).map(move |start| {
+        let bytes = &mut buffer[..(len - start).min(SPILL_CHUNK_LEN) * size];
+        file.read_exact(bytes)?;
+
+        Ok(bytes.chunks_exact(size).map(&parse).collect())
+    }))
+}
+
+/// The chains of a table sorted by endpoint on the disk.
+///
+/// The chains are split into partition files by endpoint, each partition covering consecutive blocks
+/// of the table being written. A partition is small enough to be sorted in memory once it is loaded,
+/// so the blocks of the table can be written one after the other with a fraction of the memory.
+/// The partitions are stored in a spill directory, which is removed when the chains are dropped.
+pub(crate) struct SortedChains {
+    dir: PathBuf,
+    partition_count: usize,
+    block_count: usize,
+}
+
+impl SortedChains {
+    /// Writes the chains of a table to partition files in `dir`.
+    /// `block` returns the block of an endpoint among the `block_count` blocks of the table being written.
+    pub fn new<T: RainbowTable>(
+        table: &T,
+        dir: &Path,
+        block_count: usize,
+        block: impl Fn(usize) -> usize,
+        progress: &ProgressCounter,
+    ) -> CugparckResult<Self> {
+        let partition_count = table
+            .len()
+            .div_ceil(PARTITION_CHAINS)
+            .clamp(1, MAX_PARTITION_COUNT)
+            .min(block_count);
+
+        fs::create_dir_all(dir)?;
+        let chains = Self {
+            dir: dir.to_owned(),
+            partition_count,
+            block_count,
+        };
+
+        let mut partitions = (0..partition_count)
+            .map(|i| Ok(BufWriter::new(File::create(chains.partition_path(i))?)))
+            .collect::<CugparckResult<Vec<_>>>()?;
+
+        for chain in table.iter() {
+            let block = block(chain.endpoint.get()) as u128;
+            let partition = (block * partition_count as u128 / block_count as u128) as usize;
+
+            let partition = &mut partitions[partition];
+            partition.write_all(&(chain.endpoint.get() as u64).to_le_bytes())?;
+            partition.write_all(&(chain.startpoint.get() as u64).to_le_bytes())?;
+            progress.add(1);
+        }
+
+        for mut partition in partitions {
+            partition.flush()?;
+        }
+
+        Ok(chains)
+    }
+
+    /// Returns the path of the partition `i`.
+    fn partition_path(&self, i: usize) -> PathBuf {
+        self.dir.join(format!("partition_{i}"))
+    }
+
+    /// Returns the path of a spill file named `name` in the spill directory.
+    pub fn spill_path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Returns the number of partitions.
+    pub fn partition_count(&self) -> usize {
+        self.partition_count
+    }
+
+    /// Returns the blocks covered by the partition `i`.
+    pub fn blocks(&self, i: usize) -> Range<usize> {
+        let first_block = |i: usize| {
+            (i as u128 * self.block_count as u128).div_ceil(self.partition_count as u128) as usize
+        };
+
+        first_block(i)..first_block(i + 1)
+    }
+
+    /// Loads the chains of the partition `i`, sorted by endpoint.
+    pub fn load(&self, i: usize) -> CugparckResult<Vec<RainbowChain>> {
+        let bytes = fs::read(self.partition_path(i))?;
+
+        let mut chains = bytes
+            .chunks_exact(16)
+            .map(|chain| {
+                let (endpoint, startpoint) = chain.split_at(8);
+                RainbowChain::from_compressed(
+                    CompressedPassword::from(
+                        u64::from_le_bytes(startpoint.try_into().unwrap()) as usize
+                    ),
+                    CompressedPassword::from(
+                        u64::from_le_bytes(endpoint.try_into().unwrap()) as usize
+                    ),
+                )
+            })
+            .collect::<Vec<_>>();
+        chains.par_sort_unstable_by_key(|chain| chain.endpoint);
+
+        Ok(chains)
+    }
+}
+
+impl Drop for SortedChains {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
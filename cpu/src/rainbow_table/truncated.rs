@@ -0,0 +1,682 @@
+use std::{ops::Range, path::Path};
+
+use crate::parallel::*;
+use bitvec::prelude::*;
+use bytecheck::CheckBytes;
+use cugparck_commons::{
+    ArchivedStartpointOrder, CompressedPassword, FeistelPermutation, RainbowChain, RainbowTableCtx,
+    StartpointOrder,
+};
+use itertools::Itertools;
+use rkyv::{ser::Serializer, with::Skip, Archive, Deserialize, Infallible, Serialize};
+
+use super::{
+    compressed_delta_encoding::check_startpoints,
+    store_table_with,
+    stream::{BitSink, BitSpill, SortedChains, StreamBits},
+    RainbowTable, RainbowTableStorage,
+};
+use crate::{
+    error::{CugparckError, CugparckResult},
+    event::{ProgressCounter, ProgressListener},
+    header::{TableStats, HEADER_SIZE},
+};
+
+/// The average number of chains in a block.
+/// Smaller blocks make the index larger, and larger blocks need more bits to tell their endpoints apart.
+const CHAINS_PER_BLOCK: usize = 8;
+
+/// A rainbow table storing truncated endpoints, as described in "Optimal Storage for Rainbow Tables" section 4.
+/// The endpoints are split into blocks by their most significant part, which is given by a prefix index.
+/// Only the least significant bits of their offset in their block are stored,
+/// the fewest bits that still tell apart the endpoints of every block.
+/// A lookup can then match an endpoint that isn't in the table, which is discarded like any other false alarm.
+/// The table is faster to search than a compressed delta encoding table, but larger.
+#[derive(Archive, Deserialize, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct TruncatedTable {
+    ctx: RainbowTableCtx,
+    /// The number of the first chain of each block, followed by the number of chains.
+    #[with(StreamBits)]
+    index: BitVec,
+    /// The truncated offsets of the endpoints in their block, sorted by endpoint.
+    #[with(StreamBits)]
+    endpoints: BitVec,
+    /// The indexes of the startpoints, in the order of the endpoints.
+    #[with(StreamBits)]
+    startpoints: BitVec,
+    m: usize,
+    /// The base 2 logarithm of the number of blocks.
+    block_bits: u8,
+    chain_number_bits: u8,
+    endpoint_bits: u8,
+    password_bits: u8,
+    /// Statistics about the generation of the table.
+    /// They are stored in the header, so they aren't archived.
+    #[with(Skip)]
+    stats: Option<TableStats>,
+}
+
+/// The parameters needed to read a truncated table, shared by the table and its archived version.
+#[derive(Clone, Copy)]
+struct Layout {
+    n: usize,
+    m: usize,
+    block_bits: u8,
+    chain_number_bits: u8,
+    endpoint_bits: u8,
+    password_bits: u8,
+    startpoint_order: StartpointOrder,
+    startpoint_seed: u64,
+}
+
+impl Layout {
+    /// Returns the number of blocks.
+    fn block_count(&self) -> usize {
+        1 << self.block_bits
+    }
+
+    /// Returns the block of an endpoint.
+    fn block(&self, endpoint: usize) -> usize {
+        (endpoint as u128 * self.block_count() as u128 / self.n as u128) as usize
+    }
+
+    /// Returns the first endpoint of a block.
+    fn block_start(&self, block: usize) -> usize {
+        let block_count = self.block_count() as u128;
+        (block as u128 * self.n as u128).div_ceil(block_count) as usize
+    }
+
+    /// Returns the offset of an endpoint in its block, truncated to the number of bits stored.
+    fn truncated_offset(&self, endpoint: usize) -> usize {
+        let offset = endpoint - self.block_start(self.block(endpoint));
+        offset & ((1 << self.endpoint_bits) - 1)
+    }
+
+    /// Creates the layout of a table of `m` chains.
+    /// The number of bits of the endpoints is only known once the chains are sorted, so it is left to 0.
+    fn new(ctx: &RainbowTableCtx, m: usize) -> Self {
+        Self {
+            n: ctx.n,
+            m,
+            block_bits: (m / CHAINS_PER_BLOCK).max(1).ilog2() as u8,
+            chain_number_bits: bits_for(m),
+            endpoint_bits: 0,
+            password_bits: bits_for(ctx.m0.saturating_sub(1)),
+            startpoint_order: ctx.startpoint_order,
+            startpoint_seed: ctx.startpoint_seed,
+        }
+    }
+
+    /// Returns the position of the first chain of each block of `blocks` in `chains`, sorted by endpoint,
+    /// followed by the number of chains.
+    fn block_starts(&self, chains: &[RainbowChain], blocks: Range<usize>) -> Vec<usize> {
+        let mut block_starts = Vec::with_capacity(blocks.len() + 1);
+        for block in blocks {
+            block_starts
+                .push(chains.partition_point(|chain| self.block(chain.endpoint.get()) < block));
+        }
+        block_starts.push(chains.len());
+
+        block_starts
+    }
+
+    /// Returns the number of bits needed to tell apart the endpoints of every block,
+    /// given the chains sorted by endpoint and the position of the first chain of each block.
+    fn endpoint_bits(&self, chains: &[RainbowChain], block_starts: &[usize]) -> u8 {
+        // two offsets are told apart by their lowest bits up to the first one that differs
+        block_starts
+            .par_windows(2)
+            .map(|block| {
+                let chains = &chains[block[0]..block[1]];
+                let offsets = chains
+                    .iter()
+                    .map(|chain| {
+                        let endpoint = chain.endpoint.get();
+                        endpoint - self.block_start(self.block(endpoint))
+                    })
+                    .collect_vec();
+
+                offsets
+                    .iter()
+                    .tuple_combinations()
+                    .map(|(a, b)| (a ^ b).trailing_zeros() as u8 + 1)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Stores the truncated endpoints and the startpoints of chains sorted by endpoint.
+    fn store_chains(
+        &self,
+        ctx: &RainbowTableCtx,
+        chains: &[RainbowChain],
+        endpoints: &mut impl BitSink,
+        startpoints: &mut impl BitSink,
+    ) {
+        for chain in chains {
+            let offset = self.truncated_offset(chain.endpoint.get());
+            endpoints.push_bits(offset, self.endpoint_bits as usize);
+
+            let startpoint = ctx.startpoint_index(chain.startpoint);
+            startpoints.push_bits(startpoint, self.password_bits as usize);
+        }
+    }
+
+    /// Returns the chains of a block.
+    fn block_chains<S: BitStore>(&self, index: &BitSlice<S>, block: usize) -> (usize, usize) {
+        let entry = |i: usize| {
+            let bits = self.chain_number_bits as usize;
+            load(&index[i * bits..(i + 1) * bits])
+        };
+
+        (entry(block), entry(block + 1))
+    }
+
+    /// Returns the startpoint of the chain `i`.
+    fn startpoint<S: BitStore>(&self, startpoints: &BitSlice<S>, i: usize) -> CompressedPassword {
+        let bits = self.password_bits as usize;
+        let index = load(&startpoints[i * bits..(i + 1) * bits]);
+
+        match self.startpoint_order {
+            StartpointOrder::Sequential => index.into(),
+            StartpointOrder::Seeded => FeistelPermutation::new(self.n, self.startpoint_seed)
+                .permute(index)
+                .into(),
+        }
+    }
+
+    /// Searches the truncated endpoints for a password.
+    fn search_endpoints<S: BitStore>(
+        &self,
+        table: (&BitSlice<S>, &BitSlice<S>, &BitSlice<S>),
+        password: CompressedPassword,
+    ) -> Option<CompressedPassword> {
+        let (index, endpoints, startpoints) = table;
+        self.search_chain_position(index, endpoints, password)
+            .map(|i| self.startpoint(startpoints, i))
+    }
+
+    /// Searches the truncated endpoints for a password.
+    /// Returns the number of the first chain whose truncated endpoint matches the password.
+    fn search_chain_position<S: BitStore>(
+        &self,
+        index: &BitSlice<S>,
+        endpoints: &BitSlice<S>,
+        password: CompressedPassword,
+    ) -> Option<usize> {
+        let (first, last) = self.block_chains(index, self.block(password.get()));
+        let offset = self.truncated_offset(password.get());
+        let bits = self.endpoint_bits as usize;
+
+        // the blocks are small, so they are searched linearly
+        (first..last).find(|&i| load(&endpoints[i * bits..(i + 1) * bits]) == offset)
+    }
+}
+
+/// Loads the number stored in a bit slice, which can be empty.
+#[inline]
+fn load<S: BitStore>(bits: &BitSlice<S>) -> usize {
+    if bits.is_empty() {
+        0
+    } else {
+        bits.load_le()
+    }
+}
+
+/// Returns the number of bits needed to store the numbers up to `max`.
+fn bits_for(max: usize) -> u8 {
+    (usize::BITS - max.leading_zeros()) as u8
+}
+
+impl TruncatedTable {
+    fn layout(&self) -> Layout {
+        Layout {
+            n: self.ctx.n,
+            m: self.m,
+            block_bits: self.block_bits,
+            chain_number_bits: self.chain_number_bits,
+            endpoint_bits: self.endpoint_bits,
+            password_bits: self.password_bits,
+            startpoint_order: self.ctx.startpoint_order,
+            startpoint_seed: self.ctx.startpoint_seed,
+        }
+    }
+
+    /// Returns the number of bits used to store each endpoint.
+    pub fn endpoint_bits(&self) -> u8 {
+        self.endpoint_bits
+    }
+
+    /// Returns an estimation of the size of a stored table with the given context and number of chains, in bytes.
+    /// The endpoints are assumed to need as many bits as the number of chains, which is usually close.
+    pub fn stored_size(ctx: &RainbowTableCtx, chain_count: usize) -> usize {
+        let block_count = (chain_count / CHAINS_PER_BLOCK).max(1).next_power_of_two();
+        let index_bits = (block_count + 1) * bits_for(chain_count) as usize;
+        let chain_bits =
+            (bits_for(chain_count) + bits_for(ctx.m0.saturating_sub(1))) as usize * chain_count;
+
+        HEADER_SIZE + (index_bits + chain_bits) / 8 + std::mem::size_of::<ArchivedTruncatedTable>()
+    }
+
+    /// Sets the generation statistics stored in the header, usually those of the table it was converted from.
+    pub fn with_stats(mut self, stats: Option<TableStats>) -> Self {
+        self.stats = stats;
+
+        self
+    }
+
+    /// Returns the chains of the table.
+    /// The endpoints are recomputed from the startpoints in parallel, which is much faster than `RainbowTable::iter`.
+    /// The chains computed are reported to `progress`.
+    pub fn par_chains(&self, progress: &dyn ProgressListener) -> Vec<RainbowChain> {
+        par_chains(self.layout(), &self.startpoints, &self.ctx, progress)
+    }
+
+    /// Converts a table to a truncated table stored at the given path,
+    /// as `RainbowTable::into_rainbow_table` followed by `RainbowTableStorage::store` would,
+    /// but without holding the chains of either table in memory.
+    ///
+    /// The chains are sorted by endpoint in partition files in `spill_dir`, which is removed afterwards.
+    /// See `CompressedTable::store_streamed`.
+    pub fn store_streamed<T: RainbowTable>(
+        table: &T,
+        path: &Path,
+        spill_dir: &Path,
+        stats: Option<TableStats>,
+        progress: &dyn ProgressListener,
+    ) -> CugparckResult<()> {
+        // the chains are read once from the table and twice from the partitions
+        let progress = ProgressCounter::chains(progress, table.len() * 3);
+        let ctx = table.ctx();
+        let mut layout = Layout::new(&ctx, table.len());
+        let chains = SortedChains::new(
+            table,
+            spill_dir,
+            layout.block_count(),
+            |endpoint| layout.block(endpoint),
+            &progress,
+        )?;
+
+        // the endpoints are truncated to the bits needed by every block, so the partitions are read twice
+        for partition in 0..chains.partition_count() {
+            let partition_chains = chains.load(partition)?;
+            // the startpoints are stored as indexes smaller than m0
+            check_startpoints(partition_chains.iter().copied(), &ctx)?;
+            let block_starts = layout.block_starts(&partition_chains, chains.blocks(partition));
+            layout.endpoint_bits = layout
+                .endpoint_bits
+                .max(layout.endpoint_bits(&partition_chains, &block_starts));
+            progress.add(partition_chains.len());
+        }
+
+        let mut index = BitSpill::new(chains.spill_path("index"))?;
+        let mut endpoints = BitSpill::new(chains.spill_path("endpoints"))?;
+        let mut startpoints = BitSpill::new(chains.spill_path("startpoints"))?;
+        let mut chain_start = 0;
+
+        for partition in 0..chains.partition_count() {
+            let partition_chains = chains.load(partition)?;
+            let block_starts = layout.block_starts(&partition_chains, chains.blocks(partition));
+
+            for &block_start in &block_starts[..block_starts.len() - 1] {
+                index.push_bits(chain_start + block_start, layout.chain_number_bits as usize);
+            }
+            layout.store_chains(&ctx, &partition_chains, &mut endpoints, &mut startpoints);
+
+            chain_start += partition_chains.len();
+            progress.add(partition_chains.len());
+        }
+        index.push_bits(chain_start, layout.chain_number_bits as usize);
+
+        let truncated_table = Self {
+            ctx,
+            index: BitVec::new(),
+            endpoints: BitVec::new(),
+            startpoints: BitVec::new(),
+            m: layout.m,
+            block_bits: layout.block_bits,
+            chain_number_bits: layout.chain_number_bits,
+            endpoint_bits: layout.endpoint_bits,
+            password_bits: layout.password_bits,
+            stats,
+        };
+
+        // the fields are written in the same order as the derived implementation of `Serialize`
+        store_table_with(
+            &truncated_table,
+            path,
+            |file| Ok(Box::new(file)),
+            |serializer| {
+                let resolver = TruncatedTableResolver {
+                    ctx: ctx
+                        .serialize(serializer)
+                        .map_err(|_| CugparckError::Serialize)?,
+                    index: index.serialize(serializer)?,
+                    endpoints: endpoints.serialize(serializer)?,
+                    startpoints: startpoints.serialize(serializer)?,
+                    m: (),
+                    block_bits: (),
+                    chain_number_bits: (),
+                    endpoint_bits: (),
+                    password_bits: (),
+                    stats: (),
+                };
+
+                serializer
+                    .align_for::<ArchivedTruncatedTable>()
+                    .map_err(|_| CugparckError::Serialize)?;
+                // SAFETY: the resolver is the result of serializing the table with its spilled data
+                unsafe { serializer.resolve_aligned(&truncated_table, resolver) }
+                    .map_err(|_| CugparckError::Serialize)?;
+
+                Ok(())
+            },
+        )
+    }
+}
+
+impl ArchivedTruncatedTable {
+    fn layout(&self) -> Layout {
+        Layout {
+            n: self.ctx.n as usize,
+            m: self.m as usize,
+            block_bits: self.block_bits,
+            chain_number_bits: self.chain_number_bits,
+            endpoint_bits: self.endpoint_bits,
+            password_bits: self.password_bits,
+            startpoint_order: match self.ctx.startpoint_order {
+                ArchivedStartpointOrder::Sequential => StartpointOrder::Sequential,
+                ArchivedStartpointOrder::Seeded => StartpointOrder::Seeded,
+            },
+            startpoint_seed: self.ctx.startpoint_seed,
+        }
+    }
+
+    /// Returns the chains of the table.
+    /// See `TruncatedTable::par_chains`.
+    pub fn par_chains(&self, progress: &dyn ProgressListener) -> Vec<RainbowChain> {
+        par_chains(self.layout(), &self.startpoints[..], &self.ctx(), progress)
+    }
+}
+
+/// Returns the chain `i` of a table, computing its endpoint from its startpoint.
+fn chain<S: BitStore>(
+    layout: &Layout,
+    startpoints: &BitSlice<S>,
+    ctx: &RainbowTableCtx,
+    i: usize,
+) -> RainbowChain {
+    let startpoint = layout.startpoint(startpoints, i);
+    let mut endpoint = startpoint;
+    endpoint.continue_chain(0..ctx.t - 1, ctx);
+
+    RainbowChain::from_compressed(startpoint, endpoint)
+}
+
+fn par_chains<S: BitStore + Sync>(
+    layout: Layout,
+    startpoints: &BitSlice<S>,
+    ctx: &RainbowTableCtx,
+    progress: &dyn ProgressListener,
+) -> Vec<RainbowChain> {
+    let progress = ProgressCounter::chains(progress, layout.m);
+
+    (0..layout.m)
+        .into_par_iter()
+        .map(|i| {
+            let chain = chain(&layout, startpoints, ctx, i);
+            progress.add(1);
+            chain
+        })
+        .collect()
+}
+
+impl RainbowTable for TruncatedTable {
+    type Iter<'a> = TruncatedTableIterator<'a, usize>;
+
+    fn len(&self) -> usize {
+        self.m
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        TruncatedTableIterator::new(self.layout(), &self.startpoints, self.ctx)
+    }
+
+    fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword> {
+        self.layout()
+            .search_endpoints((&self.index, &self.endpoints, &self.startpoints), password)
+    }
+
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.layout()
+            .search_chain_position(&self.index[..], &self.endpoints[..], password)
+    }
+
+    fn ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn from_rainbow_table<T: RainbowTable>(table: &T) -> Self {
+        let ctx = table.ctx();
+        let m = table.len();
+
+        let mut chains = table.iter().collect_vec();
+        chains.par_sort_unstable_by_key(|chain| chain.endpoint);
+
+        let mut layout = Layout::new(&ctx, m);
+        let block_starts = layout.block_starts(&chains, 0..layout.block_count());
+        layout.endpoint_bits = layout.endpoint_bits(&chains, &block_starts);
+
+        let mut index =
+            BitVec::with_capacity(block_starts.len() * layout.chain_number_bits as usize);
+        for chain_number in block_starts {
+            index.push_bits(chain_number, layout.chain_number_bits as usize);
+        }
+
+        let mut endpoints = BitVec::with_capacity(m * layout.endpoint_bits as usize);
+        let mut startpoints = BitVec::with_capacity(m * layout.password_bits as usize);
+        layout.store_chains(&ctx, &chains, &mut endpoints, &mut startpoints);
+
+        Self {
+            ctx,
+            index,
+            endpoints,
+            startpoints,
+            m,
+            block_bits: layout.block_bits,
+            chain_number_bits: layout.chain_number_bits,
+            endpoint_bits: layout.endpoint_bits,
+            password_bits: layout.password_bits,
+            stats: None,
+        }
+    }
+}
+
+impl RainbowTable for ArchivedTruncatedTable {
+    type Iter<'a> = TruncatedTableIterator<'a, u64>;
+
+    fn len(&self) -> usize {
+        self.m as usize
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        TruncatedTableIterator::new(self.layout(), &self.startpoints[..], self.ctx())
+    }
+
+    fn search_endpoints(&self, password: CompressedPassword) -> Option<CompressedPassword> {
+        self.layout().search_endpoints(
+            (&self.index[..], &self.endpoints[..], &self.startpoints[..]),
+            password,
+        )
+    }
+
+    fn search_chain_position(&self, password: CompressedPassword) -> Option<usize> {
+        self.layout()
+            .search_chain_position(&self.index[..], &self.endpoints[..], password)
+    }
+
+    fn ctx(&self) -> RainbowTableCtx {
+        self.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn from_rainbow_table<T: RainbowTable>(_: &T) -> Self {
+        panic!("Archived tables cannot be built from other tables")
+    }
+}
+
+/// An iterator over the chains of a truncated table.
+/// The endpoints are not stored, so they are recomputed from the startpoints, which is slow.
+pub struct TruncatedTableIterator<'a, S: BitStore> {
+    layout: Layout,
+    startpoints: &'a BitSlice<S>,
+    ctx: RainbowTableCtx,
+    i: usize,
+}
+
+impl<'a, S: BitStore> TruncatedTableIterator<'a, S> {
+    fn new(layout: Layout, startpoints: &'a BitSlice<S>, ctx: RainbowTableCtx) -> Self {
+        Self {
+            layout,
+            startpoints,
+            ctx,
+            i: 0,
+        }
+    }
+}
+
+impl<S: BitStore> Iterator for TruncatedTableIterator<'_, S> {
+    type Item = RainbowChain;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.i == self.layout.m {
+            return None;
+        }
+
+        let chain = chain(&self.layout, self.startpoints, &self.ctx, self.i);
+        self.i += 1;
+
+        Some(chain)
+    }
+}
+
+impl RainbowTableStorage for TruncatedTable {
+    fn header_ctx(&self) -> RainbowTableCtx {
+        self.ctx
+    }
+
+    fn archived_ctx(archived: &Self::Archived) -> RainbowTableCtx {
+        archived.ctx.deserialize(&mut Infallible).unwrap()
+    }
+
+    fn header_chain_count(&self) -> usize {
+        self.m
+    }
+
+    fn header_stats(&self) -> Option<TableStats> {
+        self.stats.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use cugparck_commons::Password;
+    use itertools::Itertools;
+    use rkyv::AlignedVec;
+
+    use crate::{
+        backend::Cpu, temp_path, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage,
+        SimpleTable,
+    };
+
+    use super::{TruncatedTable, CHAINS_PER_BLOCK};
+
+    fn tables() -> (SimpleTable, TruncatedTable) {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let simple = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+        let truncated = TruncatedTable::from_rainbow_table(&simple);
+
+        (simple, truncated)
+    }
+
+    #[test]
+    fn test_search_endpoints() {
+        let (simple, truncated) = tables();
+        assert_eq!(simple.len(), truncated.len());
+        assert!(truncated.len() / (1 << truncated.block_bits) >= CHAINS_PER_BLOCK);
+
+        for chain in simple.iter() {
+            assert_eq!(
+                Some(chain.startpoint),
+                truncated.search_endpoints(chain.endpoint)
+            );
+        }
+
+        // with one bit less, two endpoints of a block would be mixed up
+        let mut layout = truncated.layout();
+        layout.endpoint_bits -= 1;
+        let offsets = simple
+            .iter()
+            .map(|chain| {
+                let endpoint = chain.endpoint.get();
+                (layout.block(endpoint), layout.truncated_offset(endpoint))
+            })
+            .collect::<HashSet<_>>();
+        assert!(offsets.len() < simple.len());
+    }
+
+    #[test]
+    fn test_search() {
+        let (simple, truncated) = tables();
+        let ctx = simple.ctx();
+
+        let path = temp_path("truncated_search.rtt");
+        truncated.store(&path).unwrap();
+        let mut bytes = AlignedVec::new();
+        bytes.extend_from_slice(&fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        let archived = TruncatedTable::load(&bytes).unwrap();
+
+        for password in ["abc", "fed", "aaaa", "cafe", "b"] {
+            let digest = ctx.hash(&Password::new(password.as_bytes()));
+            let expected = simple.search(digest);
+            assert_eq!(expected, truncated.search(digest));
+            assert_eq!(expected, archived.search(digest));
+        }
+    }
+
+    #[test]
+    fn test_chains() {
+        let (simple, truncated) = tables();
+        let chains = simple
+            .iter()
+            .sorted_by_key(|chain| chain.endpoint)
+            .collect_vec();
+
+        assert_eq!(chains, truncated.iter().collect_vec());
+        assert_eq!(chains, truncated.par_chains(&()));
+    }
+
+    #[test]
+    fn test_store_streamed() {
+        let (simple, truncated) = tables();
+
+        let path = temp_path("truncated_streamed.rtt");
+        let spill_dir = path.with_extension("spill");
+
+        TruncatedTable::store_streamed(&simple, &path, &spill_dir, None, &()).unwrap();
+        assert!(!spill_dir.exists());
+
+        assert_eq!(truncated.to_bytes().unwrap(), fs::read(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+}
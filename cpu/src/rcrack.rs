@@ -0,0 +1,427 @@
+//! Conversion of rainbow tables to the file layout of the RainbowCrack formats.
+//!
+//! Note that RainbowCrack uses its own reduction function and password indexing,
+//! so the exported tables are not compatible with RainbowCrack and can only be searched by cugparck.
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+use cugparck_commons::{CompressedPassword, HashType, RainbowChain, RainbowTableCtx};
+
+use crate::{
+    error::CugparckResult, CugparckError, RainbowTable, RainbowTableCtxBuilder, SimpleTable,
+};
+
+/// The charsets of RainbowCrack's `charset.txt`, along with their name.
+const RCRACK_CHARSETS: [(&str, &[u8]); 8] = [
+    ("numeric", b"0123456789"),
+    ("loweralpha", b"abcdefghijklmnopqrstuvwxyz"),
+    ("upperalpha", b"ABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    ("loweralpha-numeric", b"abcdefghijklmnopqrstuvwxyz0123456789"),
+    ("upperalpha-numeric", b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789"),
+    ("mixalpha", b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"),
+    (
+        "mixalpha-numeric",
+        b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789",
+    ),
+    (
+        "ascii-32-95",
+        b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~",
+    ),
+];
+
+/// The size of a chain in a RainbowCrack `.rt` file.
+pub const RCRACK_CHAIN_SIZE: usize = 16;
+
+/// The number of chains recomputed to check that an imported table was exported by cugparck.
+const CHECKED_CHAINS: usize = 16;
+
+/// Returns the name of a hash function in the RainbowCrack file name convention.
+pub(crate) fn rcrack_hash_name(hash_type: HashType) -> &'static str {
+    match hash_type {
+        HashType::Ntlm => "ntlm",
+        HashType::Md4 => "md4",
+        HashType::Md5 => "md5",
+        HashType::Sha1 => "sha1",
+        HashType::Sha2_224 => "sha224",
+        HashType::Sha2_256 => "sha256",
+        HashType::Sha2_384 => "sha384",
+        HashType::Sha2_512 => "sha512",
+        HashType::Sha3_224 => "sha3-224",
+        HashType::Sha3_256 => "sha3-256",
+        HashType::Sha3_384 => "sha3-384",
+        HashType::Sha3_512 => "sha3-512",
+        HashType::Blake2b512 => "blake2b-512",
+        HashType::Blake2s256 => "blake2s-256",
+        HashType::Blake3 => "blake3",
+        HashType::Lm => "lm",
+        HashType::Mysql41 => "mysqlsha1",
+        HashType::Md5Md5 => "md5md5",
+    }
+}
+
+/// Returns the name of a charset in the RainbowCrack file name convention.
+/// Charsets that are not part of RainbowCrack's `charset.txt` are named `custom`.
+pub(crate) fn rcrack_charset_name(charset: &[u8]) -> &'static str {
+    // the charset of a context is sorted
+    let mut charset = charset.to_vec();
+    charset.sort_unstable();
+
+    RCRACK_CHARSETS
+        .iter()
+        .find(|(_, rcrack_charset)| {
+            let mut rcrack_charset = rcrack_charset.to_vec();
+            rcrack_charset.sort_unstable();
+            rcrack_charset == charset
+        })
+        .map(|(name, _)| *name)
+        .unwrap_or("custom")
+}
+
+/// The parameters of a table found in a RainbowCrack file name.
+#[derive(Debug, PartialEq, Eq)]
+struct RcrackFileName {
+    /// `None` if the hash function is not supported.
+    hash_type: Option<HashType>,
+    /// `None` if the charset is not part of RainbowCrack's `charset.txt`.
+    charset: Option<&'static [u8]>,
+    min_password_length: u8,
+    max_password_length: u8,
+    /// The cugparck table number, which is the RainbowCrack table index plus one.
+    table_number: u8,
+    chain_length: usize,
+    chain_count: usize,
+}
+
+/// Parses a RainbowCrack file name, e.g. `ntlm_loweralpha#1-6_1_10000x123456_0.rt`.
+/// Returns `None` if the file name doesn't follow the RainbowCrack convention.
+fn parse_rcrack_file_name(file_name: &str) -> Option<RcrackFileName> {
+    let stem = file_name.strip_suffix(".rt")?;
+    let [hash, charset_and_lengths, table_index, dimensions, _part]: [&str; 5] =
+        stem.split('_').collect::<Vec<_>>().try_into().ok()?;
+
+    let (charset, lengths) = charset_and_lengths.split_once('#')?;
+    let (min_password_length, max_password_length) = lengths.split_once('-')?;
+    let (chain_length, chain_count) = dimensions.split_once('x')?;
+
+    Some(RcrackFileName {
+        hash_type: HashType::ALL
+            .into_iter()
+            .find(|hash_type| rcrack_hash_name(*hash_type) == hash),
+        charset: RCRACK_CHARSETS
+            .iter()
+            .find(|(name, _)| *name == charset)
+            .map(|(_, charset)| *charset),
+        min_password_length: min_password_length.parse().ok()?,
+        max_password_length: max_password_length.parse().ok()?,
+        table_number: table_index.parse::<u8>().ok()?.checked_add(1)?,
+        chain_length: chain_length.parse().ok()?,
+        chain_count: chain_count.parse().ok()?,
+    })
+}
+
+/// Returns the file name of a table in the RainbowCrack convention,
+/// e.g. `ntlm_loweralpha#0-6_0_10000x123456_0.rt` for the table number 1.
+/// RainbowCrack's table indexes start at 0, while cugparck's table numbers start at 1.
+pub fn rcrack_file_name(ctx: &RainbowTableCtx, chain_count: usize) -> String {
+    format!(
+        "{}_{}#{}-{}_{}_{}x{}_0.rt",
+        rcrack_hash_name(ctx.hash_type),
+        rcrack_charset_name(&ctx.charset),
+        ctx.min_password_length,
+        ctx.max_password_length,
+        ctx.tn.saturating_sub(1),
+        ctx.t,
+        chain_count,
+    )
+}
+
+/// Writes the chains of a table in the file layout of the RainbowCrack `.rt` format.
+/// Each chain is stored as its startpoint and endpoint, as little-endian 64-bit integers,
+/// and the chains are sorted by endpoint as RainbowCrack expects.
+///
+/// The chains are still computed with cugparck's reduction function and password indexing,
+/// so the written table can't be searched by RainbowCrack: it can only be imported back by cugparck.
+pub fn export_rcrack<T: RainbowTable>(table: &T, mut writer: impl Write) -> CugparckResult<()> {
+    let mut chains = table
+        .iter()
+        .map(|chain| (chain.endpoint.get() as u64, chain.startpoint.get() as u64))
+        .collect::<Vec<_>>();
+    chains.sort_unstable();
+
+    for (endpoint, startpoint) in chains {
+        writer.write_all(&startpoint.to_le_bytes())?;
+        writer.write_all(&endpoint.to_le_bytes())?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+impl SimpleTable {
+    /// Imports a table in the RainbowCrack `.rt` format.
+    /// The parameters of the table are read from its file name if it follows the RainbowCrack convention.
+    /// `ctx_hints` provides the parameters that can't be found in the file name,
+    /// such as a charset that is not part of RainbowCrack's `charset.txt`.
+    ///
+    /// RainbowCrack uses a different reduction function and password indexing, so only the tables exported by cugparck can be imported.
+    /// A few chains are recomputed to check it, and the other tables are rejected.
+    /// The number of startpoints of the context is raised to cover all the startpoints of the table,
+    /// since the file name only gives the number of chains left after the generation.
+    pub fn from_rcrack(path: &Path, mut ctx_hints: RainbowTableCtxBuilder) -> CugparckResult<Self> {
+        let file_name = path.file_name().and_then(|name| name.to_str());
+
+        if let Some(params) = file_name.and_then(parse_rcrack_file_name) {
+            if let Some(hash_type) = params.hash_type {
+                ctx_hints = ctx_hints.hash(hash_type);
+            }
+
+            if let Some(charset) = params.charset {
+                ctx_hints = ctx_hints.charset(charset);
+            }
+
+            ctx_hints = ctx_hints
+                .min_password_length(params.min_password_length)
+                .max_password_length(params.max_password_length)
+                .table_number(params.table_number)
+                .chain_length(params.chain_length)
+                .startpoints(Some(params.chain_count));
+        }
+
+        let mut ctx = ctx_hints.build()?;
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        if len % RCRACK_CHAIN_SIZE != 0 {
+            return Err(CugparckError::InvalidRcrackTable(
+                "its size is not a multiple of the chain size",
+            ));
+        }
+
+        // the chains are read one by one, so that the file is never entirely in memory along with the chains.
+        let mut reader = BufReader::new(file);
+        let mut chains = Vec::with_capacity(len / RCRACK_CHAIN_SIZE);
+        let mut chunk = [0; RCRACK_CHAIN_SIZE];
+        for _ in 0..len / RCRACK_CHAIN_SIZE {
+            reader.read_exact(&mut chunk)?;
+            let startpoint = u64::from_le_bytes(chunk[..8].try_into().unwrap()) as usize;
+            let endpoint = u64::from_le_bytes(chunk[8..].try_into().unwrap()) as usize;
+
+            if startpoint >= ctx.n || endpoint >= ctx.n {
+                return Err(CugparckError::InvalidRcrackTable(
+                    "a chain is outside of the search space",
+                ));
+            }
+
+            chains.push(RainbowChain::from_compressed(
+                CompressedPassword::from(startpoint),
+                CompressedPassword::from(endpoint),
+            ));
+        }
+
+        // the chains of a table generated by RainbowCrack would only produce false alarms.
+        let step = (chains.len() / CHECKED_CHAINS).max(1);
+        for chain in chains.iter().step_by(step).take(CHECKED_CHAINS) {
+            let mut endpoint = chain.startpoint;
+            endpoint.continue_chain(0..ctx.t - 1, &ctx);
+
+            if endpoint != chain.endpoint {
+                return Err(CugparckError::InvalidRcrackTable(
+                    "its chains weren't computed by cugparck with these parameters. Tables generated by RainbowCrack can't be imported",
+                ));
+            }
+        }
+
+        // the startpoints are indexes of a sequential context
+        if let Some(last) = chains.iter().map(|chain| chain.startpoint.get()).max() {
+            ctx.m0 = ctx.m0.max(last + 1);
+        }
+
+        Ok(SimpleTable::from_vec(chains, ctx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use cugparck_commons::HashType;
+
+    use super::{parse_rcrack_file_name, RcrackFileName};
+    use crate::{
+        backend::Cpu, export_rcrack, rcrack_file_name, temp_path, CugparckError, RainbowTable,
+        RainbowTableCtxBuilder, SimpleTable, RCRACK_CHAIN_SIZE,
+    };
+
+    #[test]
+    fn test_rcrack_file_name() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(1000)
+            .max_password_length(5)
+            .charset(b"abcdefghijklmnopqrstuvwxyz")
+            .table_number(2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            "ntlm_loweralpha#0-5_1_1000x42_0.rt",
+            rcrack_file_name(&ctx, 42)
+        );
+
+        // the charset of the context is sorted, but still matches RainbowCrack's unsorted charset
+        let ctx = RainbowTableCtxBuilder::new()
+            .charset(b"abcdefghijklmnopqrstuvwxyz0123456789")
+            .build()
+            .unwrap();
+        assert!(rcrack_file_name(&ctx, 1).starts_with("ntlm_loweralpha-numeric#0-6_"));
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .charset(b"abc")
+            .build()
+            .unwrap();
+        assert!(rcrack_file_name(&ctx, 1).starts_with("ntlm_custom#0-6_"));
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .charset(b"abc")
+            .min_password_length(3)
+            .build()
+            .unwrap();
+        assert!(rcrack_file_name(&ctx, 1).starts_with("ntlm_custom#3-6_"));
+    }
+
+    #[test]
+    fn test_parse_rcrack_file_name() {
+        assert_eq!(
+            Some(RcrackFileName {
+                hash_type: Some(HashType::Sha3_256),
+                charset: Some(b"0123456789"),
+                min_password_length: 1,
+                max_password_length: 7,
+                table_number: 4,
+                chain_length: 3800,
+                chain_count: 33554432,
+            }),
+            parse_rcrack_file_name("sha3-256_numeric#1-7_3_3800x33554432_0.rt")
+        );
+
+        assert_eq!(
+            Some(RcrackFileName {
+                hash_type: None,
+                charset: None,
+                min_password_length: 1,
+                max_password_length: 7,
+                table_number: 1,
+                chain_length: 2400,
+                chain_count: 100,
+            }),
+            parse_rcrack_file_name("halflmchall_unknown#1-7_0_2400x100_0.rt")
+        );
+
+        assert_eq!(None, parse_rcrack_file_name("table_1.rt"));
+        assert_eq!(
+            None,
+            parse_rcrack_file_name("ntlm_numeric#1-7_255_2400x100_0.rt")
+        );
+        assert_eq!(
+            None,
+            parse_rcrack_file_name("ntlm_numeric#1-7_300_2400x100_0.rt")
+        );
+    }
+
+    #[test]
+    fn test_import_rcrack() {
+        let dir = temp_path("rcrack");
+        fs::create_dir_all(&dir).unwrap();
+
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .table_number(3);
+        let ctx = ctx_builder.build().unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let path = dir.join(rcrack_file_name(&ctx, table.len()));
+        export_rcrack(&table, fs::File::create(&path).unwrap()).unwrap();
+
+        // the custom charset isn't in the file name, the other parameters are
+        let hints = RainbowTableCtxBuilder::new().charset(b"abcdef");
+        let imported = SimpleTable::from_rcrack(&path, hints).unwrap();
+
+        assert_eq!(ctx.tn, imported.ctx().tn);
+        assert_eq!(ctx.t, imported.ctx().t);
+        assert_eq!(ctx.n, imported.ctx().n);
+        let chains = |table: &SimpleTable| {
+            table
+                .iter()
+                .map(|chain| (chain.startpoint, chain.endpoint))
+                .collect::<HashSet<_>>()
+        };
+        assert_eq!(chains(&table), chains(&imported));
+        // the file name only gives the number of chains, which is smaller than the number of startpoints
+        assert!(imported
+            .iter()
+            .all(|chain| chain.startpoint.get() < imported.ctx().m0));
+
+        // truncated file
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(SimpleTable::from_rcrack(&path, hints).is_err());
+
+        // chains that weren't computed with cugparck's reduction function, like in a RainbowCrack table
+        let foreign = bytes
+            .chunks_exact(RCRACK_CHAIN_SIZE)
+            .flat_map(|chunk| [&chunk[..8], &chunk[..8]].concat())
+            .collect::<Vec<_>>();
+        fs::write(&path, foreign).unwrap();
+        assert!(matches!(
+            SimpleTable::from_rcrack(&path, hints),
+            Err(CugparckError::InvalidRcrackTable(_))
+        ));
+
+        // the wrong parameters
+        fs::write(&path, &bytes).unwrap();
+        let wrong_hints = RainbowTableCtxBuilder::new().charset(b"abcdeg");
+        assert!(matches!(
+            SimpleTable::from_rcrack(&path, wrong_hints),
+            Err(CugparckError::InvalidRcrackTable(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_rcrack() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let mut bytes = Vec::new();
+        export_rcrack(&table, &mut bytes).unwrap();
+        assert_eq!(table.len() * RCRACK_CHAIN_SIZE, bytes.len());
+
+        let chains = bytes
+            .chunks_exact(RCRACK_CHAIN_SIZE)
+            .map(|chunk| {
+                let startpoint = u64::from_le_bytes(chunk[..8].try_into().unwrap()) as usize;
+                let endpoint = u64::from_le_bytes(chunk[8..].try_into().unwrap()) as usize;
+                (startpoint, endpoint)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(chains.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        for (startpoint, endpoint) in chains {
+            assert_eq!(
+                Some(startpoint.into()),
+                table.search_endpoints(endpoint.into())
+            );
+        }
+    }
+}
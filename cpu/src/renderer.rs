@@ -3,11 +3,13 @@
 pub mod cpu;
 #[cfg(feature = "cuda")]
 pub mod cuda;
+pub mod multi_device;
 #[cfg(feature = "wgpu")]
 pub mod wgpu;
 
-use crate::error::CugparckResult;
-use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+use crate::parallel::*;
+use crate::{error::CugparckResult, event::DeviceMemory};
+use cugparck_commons::{column_endpoint, CompressedPassword, Digest, RainbowTableCtx};
 use std::ops::Range;
 
 /// A trait that every renderer must implement to generate a rainbow table.
@@ -32,6 +34,11 @@ pub trait Renderer: Sized {
         Ok(0)
     }
 
+    /// Returns the memory usage of the device if the renderer is able to query it.
+    fn device_memory(&self) -> CugparckResult<Option<DeviceMemory>> {
+        Ok(None)
+    }
+
     /// Starts the computation.
     fn start_kernel<'a>(
         &mut self,
@@ -40,6 +47,20 @@ pub trait Renderer: Sized {
         columns: Range<usize>,
         ctx: RainbowTableCtx,
     ) -> CugparckResult<KernelHandle<Self::StagingHandle<'_>>>;
+
+    /// Computes the endpoints of the chains containing the digest at each of the columns `0..t - 1`,
+    /// for the online phase of an attack.
+    /// The renderers without a dedicated kernel compute them on the CPU.
+    fn column_endpoints(
+        &mut self,
+        digest: Digest,
+        ctx: RainbowTableCtx,
+    ) -> CugparckResult<Vec<CompressedPassword>> {
+        Ok((0..ctx.t - 1)
+            .into_par_iter()
+            .map(|column| column_endpoint(digest, column, &ctx))
+            .collect())
+    }
 }
 
 /// A handle to a kernel being run.
@@ -67,3 +88,83 @@ impl StagingHandleSync for () {
 pub trait BatchInformation {
     fn range(&self) -> Range<usize>;
 }
+
+/// Continues the chains of `midpoints` over `columns` with a renderer, batch after batch.
+pub(crate) fn continue_chains<R: Renderer>(
+    renderer: &mut R,
+    midpoints: &mut [CompressedPassword],
+    columns: Range<usize>,
+    ctx: RainbowTableCtx,
+) -> CugparckResult<()> {
+    let mut batch_buf = Vec::new();
+    batch_buf.try_reserve_exact(renderer.max_staged_buffer_len(midpoints.len())?)?;
+
+    for batch_info in renderer.batch_iter(midpoints.len())? {
+        let batch = &mut midpoints[batch_info.range()];
+
+        if let KernelHandle::Staged(mut staging_handle) =
+            renderer.start_kernel(batch, &batch_info, columns.clone(), ctx)?
+        {
+            staging_handle.sync(&mut batch_buf)?;
+            batch.copy_from_slice(&batch_buf[..batch.len()]);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the ranges of the batches are contiguous and cover exactly `0..chains_len`,
+/// so that every chain is computed once and only once.
+pub(crate) fn batches_partition<T: BatchInformation>(
+    batches: impl Iterator<Item = T>,
+    chains_len: usize,
+) -> bool {
+    let mut next_start = 0;
+
+    for batch in batches {
+        let range = batch.range();
+
+        if range.start != next_start || range.end < range.start {
+            return false;
+        }
+
+        next_start = range.end;
+    }
+
+    next_start == chains_len
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{iter, ops::Range};
+
+    use super::{batches_partition, cpu::CpuRenderer, BatchInformation, Renderer};
+
+    impl BatchInformation for Range<usize> {
+        fn range(&self) -> Range<usize> {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_batches_partition() {
+        assert!(batches_partition([0..3, 3..6, 6..7].into_iter(), 7));
+        assert!(batches_partition(iter::once(0..0), 0));
+
+        // overlapping, missing and out of bounds chains
+        assert!(!batches_partition([0..3, 2..7].into_iter(), 7));
+        assert!(!batches_partition([0..3, 4..7].into_iter(), 7));
+        assert!(!batches_partition([0..3, 3..8].into_iter(), 7));
+        assert!(!batches_partition(iter::once(0..3), 7));
+    }
+
+    #[test]
+    fn test_cpu_batches_partition() {
+        let renderer = CpuRenderer::new().unwrap();
+
+        for chains_len in [0, 1, 1000] {
+            let batches = renderer.batch_iter(chains_len).unwrap();
+            assert!(batches_partition(batches, chains_len));
+        }
+    }
+}
@@ -5,8 +5,8 @@ use std::{
     ops::Range,
 };
 
+use crate::parallel::*;
 use cugparck_commons::{CompressedPassword, RainbowTableCtx};
-use rayon::prelude::*;
 
 use crate::{backend::Backend, error::CugparckResult};
 
@@ -60,9 +60,14 @@ impl Renderer for CpuRenderer {
 pub struct Cpu;
 
 impl Backend for Cpu {
+    const NAME: &'static str = "cpu";
+
     type Renderer = CpuRenderer;
 
-    fn renderer(_chains_len: usize) -> CugparckResult<Self::Renderer> {
+    fn renderer(
+        _chains_len: usize,
+        _max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self::Renderer> {
         Self::Renderer::new()
     }
 }
@@ -4,10 +4,18 @@
 const PTX: &str = include_str!("../../../module.ptx");
 
 use super::{BatchInformation, KernelHandle, Renderer, StagingHandleSync};
-use crate::{backend::Backend, error::CugparckResult};
-use cugparck_commons::{CompressedPassword, RainbowTableCtx};
-use cust::{function::FunctionAttribute, prelude::*};
-use std::ops::Range;
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    event::DeviceMemory,
+};
+use cugparck_commons::{CompressedPassword, Digest, RainbowTableCtx};
+use cust::{
+    context::CurrentContext, device::DeviceAttribute, function::FunctionAttribute,
+    memory::DeviceBox, prelude::*,
+};
+use std::{mem, ops::Range};
+use tracing::info_span;
 
 /// Infornations about a batch.
 #[derive(Debug)]
@@ -35,31 +43,42 @@ pub struct BatchIterator {
 
 impl BatchIterator {
     /// Creates a new batch iterator where `chains_len` is the total number of chains to generate.
+    /// The size of the batches is computed from the properties of the device and from the `available_memory`
+    /// of the device in bytes, and can be lowered with `max_batch_size`.
     pub fn new(
         chains_len: usize,
+        max_batch_size: Option<usize>,
+        available_memory: usize,
         device: &Device,
         kernel: &Function,
     ) -> CugparckResult<BatchIterator> {
-        let device_memory = device.total_memory().unwrap() - 50_000;
+        let (_, thread_count) = kernel.suggested_launch_configuration(0, 0.into())?;
 
-        let kernel_memory = kernel.get_attribute(FunctionAttribute::LocalSizeBytes)? as usize;
+        // the batch has to fit in the available memory of the device, along with the staging buffer
+        let device_memory = available_memory.saturating_sub(50_000);
+        let kernel_memory = kernel.get_attribute(FunctionAttribute::LocalSizeBytes)? as usize
+            + mem::size_of::<CompressedPassword>();
         let kernels_per_batch = device_memory / kernel_memory;
 
-        // number of batches to do
-        let mut batches = chains_len / kernels_per_batch;
+        // and the blocks have to fit in the grid of the device
+        let max_block_count = device.get_attribute(DeviceAttribute::MaxGridDimX)? as usize;
+        let kernels_per_grid = max_block_count * thread_count as usize;
 
-        // don't forget the last batch since integer division is rounding down numbers
-        let (batch_size, last_batch_size) = if batches == 0 {
-            (chains_len, chains_len)
-        } else {
-            (chains_len / batches, chains_len % batches)
-        };
-        batches += 1;
+        // the number of kernels of a batch should fit in a u32, and the batches are made of whole blocks
+        let max_u32_batch_size = u32::MAX as usize / thread_count as usize * thread_count as usize;
 
-        let (_, thread_count) = kernel.suggested_launch_configuration(0, 0.into())?;
+        let batch_size = kernels_per_batch
+            .min(kernels_per_grid)
+            .min(max_u32_batch_size)
+            .min(max_batch_size.unwrap_or(usize::MAX))
+            .max(1);
+
+        // don't forget the last batch since integer division is rounding down numbers
+        let batches = ((chains_len + batch_size - 1) / batch_size).max(1);
+        let last_batch_size = chains_len - (batches - 1) * batch_size;
 
         Ok(BatchIterator {
-            batch_size,
+            batch_size: batch_size.min(chains_len),
             last_batch_size,
             batches,
             batch_number: 0,
@@ -82,7 +101,10 @@ impl Iterator for BatchIterator {
             self.batch_size
         };
 
-        let block_count = ((size as u32 + self.thread_count - 1) / self.thread_count).max(1);
+        let thread_count = self.thread_count as usize;
+        let block_count = u32::try_from((size + thread_count - 1) / thread_count)
+            .expect("the batch size is clamped to fit in a u32")
+            .max(1);
         let range = self.batch_number * self.batch_size..self.batch_number * self.batch_size + size;
 
         let batch_info = BatchInfo {
@@ -106,20 +128,34 @@ impl Iterator for BatchIterator {
 
 impl ExactSizeIterator for BatchIterator {}
 
-/// A CUDA renderer.
+/// A CUDA renderer, computing the chains on one device.
 pub struct CudaRenderer {
     device: Device,
     module: Module,
     stream: Stream,
-    _ctx: Context,
+    /// The context of the device, made current before using the device
+    /// since the renderers of several devices can be used from the same thread.
+    context: Context,
     staging_buf: DeviceBuffer<CompressedPassword>,
+    /// The context on the device, uploaded once for all the batches of a table.
+    ctx_buf: Option<(RainbowTableCtx, DeviceBox<RainbowTableCtx>)>,
+    /// The endpoints computed by the last attack, reused by the next digests.
+    endpoints_buf: Option<DeviceBuffer<CompressedPassword>>,
+    max_batch_size: Option<usize>,
 }
 
 impl CudaRenderer {
-    fn new(chains_len: usize) -> CugparckResult<Self> {
+    fn new(
+        device: usize,
+        chains_len: usize,
+        max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self> {
         cust::init(CudaFlags::empty())?;
-        let device = Device::get_device(0)?;
-        let _ctx = Context::new(device)?;
+        if device >= Device::num_devices()? as usize {
+            return Err(CugparckError::NoDevice(device));
+        }
+        let device = Device::get_device(device as u32)?;
+        let context = Context::new(device)?;
         let module = Module::from_ptx(PTX, &[])?;
         let stream = Stream::new(StreamFlags::NON_BLOCKING, None)?;
 
@@ -128,8 +164,11 @@ impl CudaRenderer {
             device,
             module,
             stream,
-            _ctx,
+            context,
             staging_buf: unsafe { DeviceBuffer::uninitialized(0)? },
+            ctx_buf: None,
+            endpoints_buf: None,
+            max_batch_size,
         };
 
         // get the largest batch possible to initialize the staging buffer
@@ -138,8 +177,40 @@ impl CudaRenderer {
         // SAFETY: we're never reading from the staging buffer before initializing it.
         renderer.staging_buf = unsafe { DeviceBuffer::uninitialized(largest_batch)? };
 
+        // the next batches have to fit in the staging buffer, even if more memory becomes available
+        renderer.max_batch_size = Some(largest_batch);
+
         Ok(renderer)
     }
+
+    /// Makes the context of the device current on the calling thread.
+    fn make_current(&self) -> CugparckResult<()> {
+        CurrentContext::set_current(&self.context)?;
+        Ok(())
+    }
+
+    /// Returns a pointer to the context on the device.
+    /// The charsets and search spaces of the context are only uploaded when the context changes,
+    /// instead of being copied as a kernel parameter for each batch.
+    fn device_ctx(
+        &mut self,
+        ctx: RainbowTableCtx,
+    ) -> CugparckResult<DevicePointer<RainbowTableCtx>> {
+        match &mut self.ctx_buf {
+            Some((uploaded, _)) if *uploaded == ctx => (),
+            Some((uploaded, ctx_buf)) => {
+                let _span = info_span!("ctx_upload").entered();
+                ctx_buf.copy_from(&ctx)?;
+                *uploaded = ctx;
+            }
+            None => {
+                let _span = info_span!("ctx_upload").entered();
+                self.ctx_buf = Some((ctx, DeviceBox::new(&ctx)?));
+            }
+        }
+
+        Ok(self.ctx_buf.as_ref().unwrap().1.as_device_ptr())
+    }
 }
 
 impl Renderer for CudaRenderer {
@@ -148,8 +219,20 @@ impl Renderer for CudaRenderer {
     type StagingHandle<'a> = StagingHandle<'a>;
 
     fn batch_iter(&self, chains_len: usize) -> CugparckResult<Self::BatchIterator> {
+        self.make_current()?;
         let kernel = self.module.get_function("chains_kernel")?;
-        BatchIterator::new(chains_len, &self.device, &kernel)
+
+        // the memory of the staging buffer is reused by the batches
+        let (free, _) = cust::memory::mem_get_info()?;
+        let available_memory = free + self.staging_buf.len() * mem::size_of::<CompressedPassword>();
+
+        BatchIterator::new(
+            chains_len,
+            self.max_batch_size,
+            available_memory,
+            &self.device,
+            &kernel,
+        )
     }
 
     fn start_kernel<'a>(
@@ -159,10 +242,16 @@ impl Renderer for CudaRenderer {
         columns: Range<usize>,
         ctx: RainbowTableCtx,
     ) -> CugparckResult<KernelHandle<StagingHandle>> {
-        self.staging_buf.index(..batch.len()).copy_from(batch)?;
+        self.make_current()?;
+        let device_ctx = self.device_ctx(ctx)?;
+
+        // the staging buffer is sized for the largest batch, so it is reused by every batch
+        info_span!("upload", chains = batch.len())
+            .in_scope(|| self.staging_buf.index(..batch.len()).copy_from(batch))?;
         let stream = &self.stream;
         let module = &self.module;
 
+        let _span = info_span!("kernel_launch").entered();
         unsafe {
             launch!(
                 module.chains_kernel<<<batch_info.block_count, batch_info.thread_count, 0, stream>>>(
@@ -170,7 +259,7 @@ impl Renderer for CudaRenderer {
                     columns.end,
                     self.staging_buf.as_device_ptr(),
                     batch.len(),
-                    ctx,
+                    device_ctx,
                 )
             )?
         }
@@ -182,9 +271,65 @@ impl Renderer for CudaRenderer {
         }))
     }
 
+    fn column_endpoints(
+        &mut self,
+        digest: Digest,
+        ctx: RainbowTableCtx,
+    ) -> CugparckResult<Vec<CompressedPassword>> {
+        self.make_current()?;
+        let columns = ctx.t - 1;
+        let device_ctx = self.device_ctx(ctx)?;
+
+        // the digests of an attack have the same number of columns
+        if self
+            .endpoints_buf
+            .as_ref()
+            .map_or(true, |endpoints| endpoints.len() != columns)
+        {
+            // SAFETY: the kernel writes every endpoint before it is read.
+            self.endpoints_buf = Some(unsafe { DeviceBuffer::uninitialized(columns)? });
+        }
+
+        let endpoints = self.endpoints_buf.as_ref().unwrap();
+        let stream = &self.stream;
+        let module = &self.module;
+
+        let kernel = module.get_function("endpoints_kernel")?;
+        let (_, thread_count) = kernel.suggested_launch_configuration(0, 0.into())?;
+        let block_count = (columns as u32 + thread_count - 1) / thread_count;
+
+        unsafe {
+            launch!(
+                module.endpoints_kernel<<<block_count, thread_count, 0, stream>>>(
+                    digest.into_inner(),
+                    digest.len(),
+                    endpoints.as_device_ptr(),
+                    columns,
+                    device_ctx,
+                )
+            )?
+        }
+        stream.synchronize()?;
+
+        let mut host_endpoints = vec![CompressedPassword::default(); columns];
+        endpoints.copy_to(&mut host_endpoints)?;
+
+        Ok(host_endpoints)
+    }
+
     fn max_staged_buffer_len(&self, chains_len: usize) -> CugparckResult<usize> {
         Ok(self.batch_iter(chains_len)?.batch_size)
     }
+
+    fn device_memory(&self) -> CugparckResult<Option<DeviceMemory>> {
+        self.make_current()?;
+        let (free, total) = cust::memory::mem_get_info()?;
+
+        Ok(Some(DeviceMemory {
+            used: total - free,
+            total,
+        }))
+    }
 }
 
 pub struct StagingHandle<'a> {
@@ -195,11 +340,13 @@ pub struct StagingHandle<'a> {
 
 impl StagingHandleSync for StagingHandle<'_> {
     fn sync(&mut self, batch_buf: &mut Vec<CompressedPassword>) -> CugparckResult<()> {
-        self.stream.synchronize()?;
+        // the time waiting for the kernel tells if the generation is GPU-bound
+        info_span!("kernel_wait").in_scope(|| self.stream.synchronize())?;
 
         // SAFETY: the capacity of the staging buffer is always at least as large as the largest batch.
         unsafe { batch_buf.set_len(self.batch_len) }
 
+        let _span = info_span!("readback", chains = self.batch_len).entered();
         self.staging_buf
             .index(..self.batch_len)
             .copy_to(batch_buf)?;
@@ -212,9 +359,30 @@ impl StagingHandleSync for StagingHandle<'_> {
 pub struct Cuda;
 
 impl Backend for Cuda {
+    const NAME: &'static str = "cuda";
+
     type Renderer = CudaRenderer;
 
-    fn renderer(chains_len: usize) -> CugparckResult<Self::Renderer> {
-        Self::Renderer::new(chains_len)
+    fn renderer(
+        chains_len: usize,
+        max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self::Renderer> {
+        Self::Renderer::new(0, chains_len, max_batch_size)
+    }
+
+    fn devices() -> CugparckResult<Vec<String>> {
+        cust::init(CudaFlags::empty())?;
+
+        Ok(Device::devices()?
+            .map(|device| device?.name())
+            .collect::<Result<_, _>>()?)
+    }
+
+    fn device_renderer(
+        device: usize,
+        chains_len: usize,
+        max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self::Renderer> {
+        Self::Renderer::new(device, chains_len, max_batch_size)
     }
 }
@@ -0,0 +1,305 @@
+//! Renderer splitting each batch between several devices of a backend.
+
+use std::{mem, ops::Range, panic, thread, vec};
+
+use cugparck_commons::{CompressedPassword, Digest, RainbowTableCtx};
+
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    event::DeviceMemory,
+};
+
+use super::{continue_chains, BatchInformation, KernelHandle, Renderer};
+
+pub struct BatchInfo {
+    pub range: Range<usize>,
+    /// The part of the batch computed by each device, relative to the start of the batch.
+    pub device_ranges: Vec<Range<usize>>,
+}
+
+impl BatchInformation for BatchInfo {
+    fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+/// A renderer using the renderers of several devices at once, each one from its own thread.
+pub struct MultiDeviceRenderer<R> {
+    renderers: Vec<R>,
+}
+
+impl<R: Renderer> MultiDeviceRenderer<R> {
+    /// Creates the renderers of the given devices of the backend `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `devices` is empty.
+    pub fn new<T: Backend<Renderer = R>>(
+        devices: &[usize],
+        chains_len: usize,
+        max_batch_size: Option<usize>,
+    ) -> CugparckResult<Self> {
+        assert!(!devices.is_empty(), "at least one device is needed");
+
+        let renderers = devices
+            .iter()
+            .map(|&device| {
+                T::device_renderer(device, chains_len, max_batch_size).map_err(|err| match err {
+                    CugparckError::NoDevice(_) => err,
+                    _ => CugparckError::NoBackend {
+                        requested: T::NAME,
+                        source: Box::new(err),
+                    },
+                })
+            })
+            .collect::<CugparckResult<_>>()?;
+
+        Ok(Self { renderers })
+    }
+}
+
+impl<R: Renderer + Send> Renderer for MultiDeviceRenderer<R> {
+    type BatchIterator = vec::IntoIter<BatchInfo>;
+    type BatchInfo = BatchInfo;
+    type StagingHandle<'a> = ();
+
+    fn batch_iter(&self, chains_len: usize) -> CugparckResult<Self::BatchIterator> {
+        // each device computes as many chains at once as it would on its own
+        let device_batch_sizes = self
+            .renderers
+            .iter()
+            .map(|renderer| {
+                let first_batch = renderer.batch_iter(chains_len)?.next();
+                Ok(first_batch.map_or(0, |batch| batch.range().len()).max(1))
+            })
+            .collect::<CugparckResult<Vec<_>>>()?;
+        let batch_size: usize = device_batch_sizes.iter().sum();
+
+        let batches = (0..chains_len.div_ceil(batch_size).max(1))
+            .map(|i| {
+                let range = i * batch_size..((i + 1) * batch_size).min(chains_len);
+
+                // the part of each device is proportional to its batch size
+                let mut device_ranges = Vec::with_capacity(device_batch_sizes.len());
+                let mut cumulated_size = 0;
+                let mut start = 0;
+                for device_batch_size in &device_batch_sizes {
+                    cumulated_size += device_batch_size;
+                    let end = (range.len() as u128 * cumulated_size as u128 / batch_size as u128)
+                        as usize;
+                    device_ranges.push(start..end);
+                    start = end;
+                }
+
+                BatchInfo {
+                    range,
+                    device_ranges,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(batches.into_iter())
+    }
+
+    fn device_memory(&self) -> CugparckResult<Option<DeviceMemory>> {
+        let mut memory = DeviceMemory { used: 0, total: 0 };
+
+        for renderer in &self.renderers {
+            match renderer.device_memory()? {
+                Some(device_memory) => {
+                    memory.used += device_memory.used;
+                    memory.total += device_memory.total;
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(memory))
+    }
+
+    fn start_kernel<'a>(
+        &mut self,
+        batch: &'a mut [CompressedPassword],
+        batch_info: &Self::BatchInfo,
+        columns: Range<usize>,
+        ctx: RainbowTableCtx,
+    ) -> CugparckResult<KernelHandle<()>> {
+        // split the batch in the parts of the devices
+        let mut rest = batch;
+        let mut parts = Vec::with_capacity(batch_info.device_ranges.len());
+        for range in &batch_info.device_ranges {
+            let (part, next) = mem::take(&mut rest).split_at_mut(range.len());
+            parts.push(part);
+            rest = next;
+        }
+
+        thread::scope(|scope| {
+            let handles = self
+                .renderers
+                .iter_mut()
+                .zip(parts)
+                .map(|(renderer, part)| {
+                    let columns = columns.clone();
+                    scope.spawn(move || continue_chains(renderer, part, columns, ctx))
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|err| panic::resume_unwind(err))
+                })
+                .collect::<CugparckResult<()>>()
+        })?;
+
+        Ok(KernelHandle::Sync)
+    }
+
+    fn column_endpoints(
+        &mut self,
+        digest: Digest,
+        ctx: RainbowTableCtx,
+    ) -> CugparckResult<Vec<CompressedPassword>> {
+        self.renderers[0].column_endpoints(digest, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Range, vec};
+
+    use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+
+    use crate::{
+        backend::{Backend, Cpu},
+        error::CugparckResult,
+        renderer::{
+            batches_partition, continue_chains,
+            cpu::{self, CpuRenderer},
+            KernelHandle,
+        },
+        CugparckError, RainbowTableCtxBuilder,
+    };
+
+    use super::{BatchInfo, MultiDeviceRenderer, Renderer};
+
+    /// A CPU renderer computing `batch_size` chains per batch, to test devices with different batch sizes.
+    struct SmallBatchRenderer {
+        batch_size: usize,
+    }
+
+    impl Renderer for SmallBatchRenderer {
+        type BatchIterator = vec::IntoIter<cpu::BatchInfo>;
+        type BatchInfo = cpu::BatchInfo;
+        type StagingHandle<'a> = ();
+
+        fn batch_iter(&self, chains_len: usize) -> CugparckResult<Self::BatchIterator> {
+            Ok((0..chains_len.div_ceil(self.batch_size).max(1))
+                .map(|i| cpu::BatchInfo {
+                    range: i * self.batch_size..((i + 1) * self.batch_size).min(chains_len),
+                })
+                .collect::<Vec<_>>()
+                .into_iter())
+        }
+
+        fn start_kernel<'a>(
+            &mut self,
+            batch: &'a mut [CompressedPassword],
+            batch_info: &Self::BatchInfo,
+            columns: Range<usize>,
+            ctx: RainbowTableCtx,
+        ) -> CugparckResult<KernelHandle<()>> {
+            CpuRenderer.start_kernel(batch, batch_info, columns, ctx)
+        }
+    }
+
+    /// A backend with 3 devices, the device `n` computing `n + 2` chains per batch.
+    struct SmallBatch;
+
+    impl Backend for SmallBatch {
+        const NAME: &'static str = "small batch";
+
+        type Renderer = SmallBatchRenderer;
+
+        fn renderer(
+            chains_len: usize,
+            max_batch_size: Option<usize>,
+        ) -> CugparckResult<Self::Renderer> {
+            Self::device_renderer(0, chains_len, max_batch_size)
+        }
+
+        fn devices() -> CugparckResult<Vec<String>> {
+            Ok((0..3)
+                .map(|device| format!("small batch {device}"))
+                .collect())
+        }
+
+        fn device_renderer(
+            device: usize,
+            _chains_len: usize,
+            _max_batch_size: Option<usize>,
+        ) -> CugparckResult<Self::Renderer> {
+            match device {
+                0..=2 => Ok(SmallBatchRenderer {
+                    batch_size: device + 2,
+                }),
+                _ => Err(CugparckError::NoDevice(device)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_device_batches() {
+        let renderer = MultiDeviceRenderer::new::<SmallBatch>(&[0, 1, 2], 0, None).unwrap();
+
+        for chains_len in [0, 1, 8, 9, 100] {
+            let batches = renderer.batch_iter(chains_len).unwrap().collect::<Vec<_>>();
+            assert_eq!(chains_len.div_ceil(9).max(1), batches.len());
+
+            for batch in &batches {
+                let device_ranges = batch.device_ranges.iter().cloned();
+                assert!(batches_partition(device_ranges, batch.range.len()));
+            }
+            assert!(batches_partition(batches.into_iter(), chains_len));
+        }
+
+        // a full batch is split between the devices like their own batches
+        let BatchInfo { device_ranges, .. } = renderer.batch_iter(9).unwrap().next().unwrap();
+        assert_eq!(vec![0..2, 2..5, 5..9], device_ranges);
+
+        assert!(matches!(
+            MultiDeviceRenderer::new::<SmallBatch>(&[0, 3], 0, None),
+            Err(CugparckError::NoDevice(3))
+        ));
+    }
+
+    #[test]
+    fn test_multi_device_chains() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(20)
+            .max_password_length(4)
+            .charset(b"abc")
+            .build()
+            .unwrap();
+        let startpoints = (0..50).map(|i| ctx.startpoint(i)).collect::<Vec<_>>();
+
+        let mut expected = startpoints.clone();
+        continue_chains(
+            &mut Cpu::renderer(0, None).unwrap(),
+            &mut expected,
+            0..19,
+            ctx,
+        )
+        .unwrap();
+
+        let mut renderer = MultiDeviceRenderer::new::<SmallBatch>(&[2, 0], 0, None).unwrap();
+        let mut midpoints = startpoints;
+        continue_chains(&mut renderer, &mut midpoints, 0..19, ctx).unwrap();
+
+        assert_eq!(expected, midpoints);
+        assert!(renderer.device_memory().unwrap().is_none());
+    }
+}
@@ -211,6 +211,8 @@ pub struct BatchInfo {
 pub struct Vulkan;
 
 impl Backend for Vulkan {
+    const NAME: &'static str = "vulkan";
+
     type Renderer = WgpuRenderer;
 
     fn renderer() -> CugparckResult<Self::Renderer> {
@@ -222,6 +224,8 @@ impl Backend for Vulkan {
 pub struct Dx12;
 
 impl Backend for Dx12 {
+    const NAME: &'static str = "dx12";
+
     type Renderer = WgpuRenderer;
 
     fn renderer() -> CugparckResult<Self::Renderer> {
@@ -233,6 +237,8 @@ impl Backend for Dx12 {
 pub struct Metal;
 
 impl Backend for Metal {
+    const NAME: &'static str = "metal";
+
     type Renderer = WgpuRenderer;
 
     fn renderer() -> CugparckResult<Self::Renderer> {
@@ -244,6 +250,8 @@ impl Backend for Metal {
 pub struct OpenGL;
 
 impl Backend for OpenGL {
+    const NAME: &'static str = "opengl";
+
     type Renderer = WgpuRenderer;
 
     fn renderer() -> CugparckResult<Self::Renderer> {
@@ -255,6 +263,8 @@ impl Backend for OpenGL {
 pub struct Dx11;
 
 impl Backend for Dx11 {
+    const NAME: &'static str = "dx11";
+
     type Renderer = WgpuRenderer;
 
     fn renderer() -> CugparckResult<Self::Renderer> {
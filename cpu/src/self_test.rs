@@ -0,0 +1,170 @@
+//! Runtime checks of the chains kernel of the backends,
+//! to catch the kernels silently computing wrong chains.
+
+use cugparck_commons::{
+    reduce, CompressedPassword, Digest, HashType, Password, RainbowTableCtx, SaltPosition,
+};
+
+use crate::{
+    backend::Backend,
+    error::{CugparckError, CugparckResult},
+    renderer::{continue_chains, Renderer},
+    RainbowTableCtxBuilder,
+};
+
+/// The number of chains computed to compare a backend with the CPU.
+const CHECK_CHAINS: usize = 256;
+
+/// The number of columns computed for each chain to compare a backend with the CPU.
+const CHECK_COLUMNS: usize = 64;
+
+/// The result of a single check of a self-test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelfTestCheck {
+    /// What is checked.
+    pub name: String,
+    pub passed: bool,
+}
+
+/// Checks that a backend can be initialized and computes the same chains as the CPU,
+/// by computing a few short chains on it.
+/// Returns `CugparckError::NoBackend` if the backend can't be initialized,
+/// and `CugparckError::BackendMismatch` if its chains are wrong.
+pub fn probe<T: Backend>() -> CugparckResult<()> {
+    let mut renderer = renderer::<T>()?;
+
+    if same_chains(&mut renderer, RainbowTableCtxBuilder::new().build()?)? {
+        Ok(())
+    } else {
+        Err(CugparckError::BackendMismatch { backend: T::NAME })
+    }
+}
+
+/// Runs the known-answer tests of every hash function and the counter/plaintext round-trips on a backend.
+///
+/// The digest of a known password is computed by the chains kernel for each hash function,
+/// and the round-trips are checked by comparing chains computed by the backend with chains computed on the CPU.
+/// Returns `CugparckError::NoBackend` if the backend can't be initialized.
+pub fn self_test<T: Backend>() -> CugparckResult<Vec<SelfTestCheck>> {
+    let mut renderer = renderer::<T>()?;
+    let mut checks = Vec::new();
+
+    for hash_type in HashType::ALL {
+        checks.push(SelfTestCheck {
+            name: format!("{hash_type:?} known answer"),
+            passed: known_answer(&mut renderer, hash_type)?,
+        });
+    }
+
+    let round_trips = [
+        ("default charset", RainbowTableCtxBuilder::new()),
+        (
+            "minimum password length",
+            RainbowTableCtxBuilder::new()
+                .min_password_length(4)
+                .max_password_length(6),
+        ),
+        (
+            "mask",
+            RainbowTableCtxBuilder::new().position_charsets(&[b"abc", b"0123456789", b"XYZ"]),
+        ),
+        (
+            "UTF-8 charset",
+            RainbowTableCtxBuilder::new()
+                .hash(HashType::Ntlm)
+                .utf8_charset("ab\u{e9}\u{fc}\u{20ac}")
+                .max_password_length(3),
+        ),
+        (
+            "salt",
+            RainbowTableCtxBuilder::new()
+                .hash(HashType::Md5)
+                .salt(b"salt", SaltPosition::Prefix),
+        ),
+    ];
+
+    for (name, builder) in round_trips {
+        let ctx = builder.build()?;
+        checks.push(SelfTestCheck {
+            name: format!("Counter/plaintext round-trip ({name})"),
+            passed: round_trip(&ctx) && same_chains(&mut renderer, ctx)?,
+        });
+    }
+
+    Ok(checks)
+}
+
+/// Returns the renderer of a backend, able to compute the chains of every check.
+fn renderer<T: Backend>() -> CugparckResult<T::Renderer> {
+    T::renderer(CHECK_CHAINS, None).map_err(|err| CugparckError::NoBackend {
+        requested: T::NAME,
+        source: Box::new(err),
+    })
+}
+
+/// Returns true if the renderer computes the same chains as the CPU.
+fn same_chains<R: Renderer>(renderer: &mut R, ctx: RainbowTableCtx) -> CugparckResult<bool> {
+    let startpoints = startpoints(&ctx);
+    let mut midpoints = startpoints.clone();
+    continue_chains(renderer, &mut midpoints, 0..CHECK_COLUMNS, ctx)?;
+
+    Ok(startpoints
+        .into_iter()
+        .zip(midpoints)
+        .all(|(startpoint, midpoint)| {
+            let mut expected = startpoint;
+            expected.continue_chain(0..CHECK_COLUMNS, &ctx);
+            expected == midpoint
+        }))
+}
+
+/// Returns true if the renderer computes the reference digest of the known password of a hash function.
+/// The digest is not returned by the chains kernel, so the reductions of the digests are compared instead.
+/// They use the first 8 bytes of the digests, and the search space is large enough to tell them apart.
+fn known_answer<R: Renderer>(renderer: &mut R, hash_type: HashType) -> CugparckResult<bool> {
+    let (password, expected) = hash_type.known_answer();
+    let ctx = RainbowTableCtxBuilder::new()
+        .hash(hash_type)
+        .max_password_length(password.len() as u8)
+        .build()?;
+
+    let expected = (0..expected.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&expected[i..i + 2], 16).unwrap())
+        .collect::<Digest>();
+
+    let mut midpoint = [CompressedPassword::from_password(
+        Password::new(password),
+        &ctx,
+    )];
+    continue_chains(renderer, &mut midpoint, 0..1, ctx)?;
+
+    Ok(midpoint[0] == reduce(expected, 0, &ctx))
+}
+
+/// Returns true if the startpoints of the checks are converted to plaintexts and back to the same counters.
+fn round_trip(ctx: &RainbowTableCtx) -> bool {
+    startpoints(ctx).into_iter().all(|startpoint| {
+        CompressedPassword::from_password(startpoint.into_password(ctx), ctx) == startpoint
+    })
+}
+
+/// Returns startpoints spread over the whole search space.
+fn startpoints(ctx: &RainbowTableCtx) -> Vec<CompressedPassword> {
+    (0..CHECK_CHAINS)
+        .map(|i| CompressedPassword::from(i * (ctx.n / CHECK_CHAINS)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{backend::Cpu, probe, self_test};
+
+    #[test]
+    fn test_self_test() {
+        assert!(probe::<Cpu>().is_ok());
+
+        let checks = self_test::<Cpu>().unwrap();
+        assert!(checks.iter().all(|check| check.passed), "{checks:?}");
+    }
+}
@@ -0,0 +1,96 @@
+//! Shards of a table, to split its generation across several machines.
+
+use std::{fmt, ops::Range, str::FromStr};
+
+use crate::error::{CugparckError, CugparckResult};
+
+/// A part of the startpoints of a table.
+/// Each shard can be generated on a different machine, and the parts are then merged with `SimpleTable::merge`.
+/// The shards are numbered from 1 to `count`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    index: u16,
+    count: u16,
+}
+
+impl Shard {
+    /// Creates the shard `index` out of `count`.
+    pub fn new(index: u16, count: u16) -> CugparckResult<Self> {
+        if index == 0 || index > count {
+            return Err(CugparckError::InvalidShard(format!(
+                "the shard number should be between 1 and {count}"
+            )));
+        }
+
+        Ok(Self { index, count })
+    }
+
+    /// Returns the number of the shard, starting from 1.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the number of shards the table is split into.
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Returns the indexes of the startpoints generated by this shard, for a table with `m0` startpoints.
+    /// The indexes of all the shards form a partition of `0..m0`, so the shards never share a startpoint.
+    pub fn startpoints(&self, m0: usize) -> Range<usize> {
+        let bound = |i: u16| (m0 as u128 * i as u128 / self.count as u128) as usize;
+        bound(self.index - 1)..bound(self.index)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = CugparckError;
+
+    /// Parses a shard written as `index/count`, such as `2/4`.
+    fn from_str(s: &str) -> CugparckResult<Self> {
+        let invalid = || CugparckError::InvalidShard("the shard should look like `2/4`".to_owned());
+
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let index = index.trim().parse().map_err(|_| invalid())?;
+        let count = count.trim().parse().map_err(|_| invalid())?;
+
+        Self::new(index, count)
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Shard;
+
+    #[test]
+    fn test_shard_startpoints() {
+        let m0 = 1003;
+        let mut next = 0;
+
+        for i in 1..=4 {
+            let startpoints = Shard::new(i, 4).unwrap().startpoints(m0);
+            assert_eq!(next, startpoints.start);
+            next = startpoints.end;
+        }
+
+        assert_eq!(m0, next);
+    }
+
+    #[test]
+    fn test_parse_shard() {
+        let shard = "2/4".parse::<Shard>().unwrap();
+        assert_eq!((2, 4), (shard.index(), shard.count()));
+        assert_eq!("2/4", shard.to_string());
+
+        assert!("0/4".parse::<Shard>().is_err());
+        assert!("5/4".parse::<Shard>().is_err());
+        assert!("2".parse::<Shard>().is_err());
+        assert!("a/b".parse::<Shard>().is_err());
+    }
+}
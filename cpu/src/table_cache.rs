@@ -0,0 +1,278 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use lru::LruCache;
+use memmap2::Mmap;
+
+use crate::{error::CugparckResult, AnyTable, CugparckError};
+
+/// The on-disk format of a cached table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TableFormat {
+    /// A `.rt` table, or a `.rtz` table once decompressed.
+    Simple,
+    /// A `.rtcde` table, using compressed delta encoding.
+    Compressed,
+    /// A `.rtt` table, storing truncated endpoints.
+    Truncated,
+}
+
+impl TableFormat {
+    /// Returns the format of a table from its extension, or `None` if the file isn't a table.
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("rt" | "rtz") => Some(Self::Simple),
+            Some("rtcde") => Some(Self::Compressed),
+            Some("rtt") => Some(Self::Truncated),
+            _ => None,
+        }
+    }
+
+    /// Loads a table of this format from its bytes, checking them.
+    fn load(self, bytes: &[u8]) -> CugparckResult<AnyTable<'_>> {
+        match self {
+            Self::Simple => AnyTable::load_simple(bytes),
+            Self::Compressed => AnyTable::load_compressed(bytes),
+            Self::Truncated => AnyTable::load_truncated(bytes),
+        }
+    }
+
+    /// Loads a table of this format from its bytes without checking them.
+    ///
+    /// # Safety
+    ///
+    /// The bytes should have been loaded successfully by `TableFormat::load` before.
+    unsafe fn load_unchecked(self, bytes: &[u8]) -> AnyTable<'_> {
+        match self {
+            Self::Simple => AnyTable::load_simple_unchecked(bytes),
+            Self::Compressed => AnyTable::load_compressed_unchecked(bytes),
+            Self::Truncated => AnyTable::load_truncated_unchecked(bytes),
+        }
+    }
+}
+
+/// A rainbow table memory mapped from a file by a `TableCache`.
+pub struct CachedTable {
+    path: PathBuf,
+    format: TableFormat,
+    mmap: Mmap,
+}
+
+impl CachedTable {
+    /// Maps the table stored at the given path, and checks it once and for all.
+    /// Tables compressed with zstd are decompressed to an anonymous memory map.
+    fn map(path: &Path) -> CugparckResult<Self> {
+        let format = TableFormat::from_path(path)
+            .ok_or_else(|| CugparckError::UnknownTableFormat(path.to_owned()))?;
+
+        let file = File::open(path)?;
+        // SAFETY: the file exists and is not being modified anywhere else.
+        let mut mmap = unsafe { Mmap::map(&file)? };
+        if path.extension() == Some("rtz".as_ref()) {
+            mmap = decompress(&mmap)?;
+        }
+
+        format.load(&mmap)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            format,
+            mmap,
+        })
+    }
+
+    /// Returns the path of the table.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the size of the mapped table, in bytes.
+    pub fn size(&self) -> usize {
+        self.mmap.len()
+    }
+
+    /// Returns the archived table, without checking it again.
+    pub fn table(&self) -> AnyTable<'_> {
+        // SAFETY: the table was checked when it was mapped, and the memory map is read-only.
+        unsafe { self.format.load_unchecked(&self.mmap) }
+    }
+}
+
+/// Decompresses a table stored with zstd to an anonymous memory map.
+#[cfg(feature = "zstd")]
+fn decompress(bytes: &[u8]) -> CugparckResult<Mmap> {
+    let mut decompressed = memmap2::MmapMut::map_anon(crate::zstd_decompressed_len(bytes)?)?;
+    crate::decompress_zstd(bytes, &mut decompressed)?;
+
+    Ok(decompressed.make_read_only()?)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress(_bytes: &[u8]) -> CugparckResult<Mmap> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Cugparck was built without zstd support",
+    )
+    .into())
+}
+
+/// The tables of a `TableCache`, and their total size.
+struct CacheState {
+    tables: LruCache<PathBuf, Arc<CachedTable>>,
+    size: usize,
+}
+
+/// A cache of memory mapped rainbow tables, which can be shared between threads.
+/// The tables are mapped once and reused by the following searches,
+/// and the least recently used tables are unmapped when the total size of the tables exceeds the budget.
+/// An evicted table stays mapped until the last `Arc` pointing to it is dropped.
+pub struct TableCache {
+    budget: usize,
+    state: Mutex<CacheState>,
+}
+
+impl TableCache {
+    /// Creates a new table cache, keeping at most `budget` bytes of tables mapped.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            state: Mutex::new(CacheState {
+                tables: LruCache::unbounded(),
+                size: 0,
+            }),
+        }
+    }
+
+    /// Returns the table stored at the given path, mapping it if it isn't cached yet.
+    /// A table bigger than the budget is mapped but never cached.
+    pub fn get(&self, path: &Path) -> CugparckResult<Arc<CachedTable>> {
+        if let Some(table) = self.state.lock().unwrap().tables.get(path) {
+            return Ok(table.clone());
+        }
+
+        // the lock isn't held while mapping so that the cached tables can still be used concurrently.
+        let table = Arc::new(CachedTable::map(path)?);
+        if table.size() > self.budget {
+            return Ok(table);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        // another thread may have mapped the same table in the meantime.
+        if let Some(cached) = state.tables.get(path) {
+            return Ok(cached.clone());
+        }
+
+        while state.size + table.size() > self.budget {
+            let (_, evicted) = state.tables.pop_lru().unwrap();
+            state.size -= evicted.size();
+        }
+        state.size += table.size();
+        state.tables.put(path.to_owned(), table.clone());
+
+        Ok(table)
+    }
+
+    /// Returns the number of cached tables.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().tables.len()
+    }
+
+    /// Returns true if no table is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total size of the cached tables, in bytes.
+    pub fn size(&self) -> usize {
+        self.state.lock().unwrap().size
+    }
+
+    /// Unmaps all the cached tables, e.g. after the tables were regenerated.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tables.clear();
+        state.size = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc, thread};
+
+    use crate::{
+        backend::Cpu, temp_path, CugparckError, RainbowTable, RainbowTableCtxBuilder,
+        RainbowTableStorage, SimpleTable,
+    };
+
+    use super::TableCache;
+
+    #[test]
+    fn test_table_cache() {
+        let dir = temp_path("cache");
+        fs::create_dir_all(&dir).unwrap();
+
+        let paths = [1, 2].map(|tn| {
+            let ctx = RainbowTableCtxBuilder::new()
+                .chain_length(10)
+                .max_password_length(3)
+                .charset(b"abc")
+                .table_number(tn)
+                .build()
+                .unwrap();
+            let path = dir.join(format!("{tn}.rt"));
+            SimpleTable::new_blocking::<Cpu>(ctx)
+                .unwrap()
+                .store(&path)
+                .unwrap();
+            path
+        });
+        let sizes = paths
+            .clone()
+            .map(|path| fs::metadata(path).unwrap().len() as usize);
+        let total = sizes[0] + sizes[1];
+
+        // only one table fits in the budget
+        let cache = TableCache::new(total - 1);
+        let first = cache.get(&paths[0]).unwrap();
+        assert_eq!(1, first.table().ctx().tn);
+        assert!(Arc::ptr_eq(&first, &cache.get(&paths[0]).unwrap()));
+        assert_eq!(sizes[0], cache.size());
+
+        // the first table is evicted, but stays usable
+        let second = cache.get(&paths[1]).unwrap();
+        assert_eq!(2, second.table().ctx().tn);
+        assert_eq!(1, cache.len());
+        assert!(!Arc::ptr_eq(&first, &cache.get(&paths[0]).unwrap()));
+        assert_eq!(1, first.table().ctx().tn);
+
+        // the cache can be shared between threads
+        let cache = Arc::new(TableCache::new(total));
+        let handles = (0..4)
+            .map(|i| {
+                let cache = cache.clone();
+                let path = paths[i % 2].clone();
+                thread::spawn(move || cache.get(&path).unwrap().table().ctx().tn)
+            })
+            .collect::<Vec<_>>();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert_eq!(i % 2 + 1, handle.join().unwrap());
+        }
+        assert_eq!(2, cache.len());
+        assert_eq!(total, cache.size());
+
+        // a table bigger than the budget isn't cached
+        let tiny = TableCache::new(sizes[0] - 1);
+        tiny.get(&paths[0]).unwrap();
+        assert!(tiny.is_empty());
+
+        assert!(matches!(
+            cache.get(&dir.join("table.txt")),
+            Err(CugparckError::UnknownTableFormat(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,38 +1,414 @@
-use super::RainbowTable;
-use cugparck_commons::{Digest, Password};
-use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use super::{
+    event::{Progress, ProgressCounter, ProgressListener},
+    false_alarm::FalseAlarmFilter,
+    rainbow_table::{search_column, AtomicSearchStats, SearchHit, SearchStats},
+    RainbowTable,
+};
+use crate::parallel::*;
+use crate::{error::CugparckResult, CugparckError};
+use cugparck_commons::{CompressedPassword, Digest, Password, RainbowTableCtx};
+use lru::LruCache;
+use nohash_hasher::BuildNoHashHasher;
 
 /// A cluster of rainbow tables, to improve the success rate.
 /// If one table has a success rate of 86.5%, then a cluster of 4 tables have a success rate of 99.96%.
 pub struct TableCluster<'a, T: RainbowTable> {
     tables: &'a [&'a T],
+    /// The false alarm filters of the tables, in the same order.
+    filters: Vec<&'a dyn FalseAlarmFilter>,
+    progress: &'a dyn ProgressListener,
 }
 
 impl<'a, T: RainbowTable> TableCluster<'a, T> {
     /// Creates a new table cluster.
     /// The tables inside the cluster should have the same RainbowTableCtx, except the `tn` field.
     pub fn new(tables: &'a [&'a T]) -> Self {
-        Self { tables }
+        Self {
+            tables,
+            filters: vec![&(); tables.len()],
+            progress: &(),
+        }
+    }
+
+    /// Uses false alarm filters to search the tables, one for each table in the same order.
+    pub fn with_false_alarm_filters(mut self, filters: Vec<&'a dyn FalseAlarmFilter>) -> Self {
+        assert_eq!(self.tables.len(), filters.len());
+        self.filters = filters;
+        self
+    }
+
+    /// Reports the columns searched to `progress` during each search.
+    /// A column is done once it is searched for every digest in every table.
+    pub fn with_progress(mut self, progress: &'a dyn ProgressListener) -> Self {
+        self.progress = progress;
+        self
     }
 
     /// Searches for a password in the table cluster.
     pub fn search(&self, digest: Digest) -> Option<Password> {
-        let t = self.tables[0].ctx().t;
+        self.search_batch(&[digest])[0]
+    }
+
+    /// Searches for the passwords of several digests at once in the table cluster.
+    ///
+    /// Each (digest, table, column) triple is a work item of the same rayon pool, so the threads stay busy
+    /// even with a single digest or a single table.
+    /// The columns closest to the endpoints are the cheapest to search and are searched first,
+    /// and the remaining work items of a digest are skipped as soon as its password is found.
+    pub fn search_batch(&self, digests: &[Digest]) -> Vec<Option<Password>> {
+        self.search_batch_hits(digests)
+            .into_iter()
+            .map(|hit| hit.map(|hit| hit.password))
+            .collect()
+    }
+
+    /// Searches for the passwords of several digests at once in the table cluster,
+    /// returning the table and the column where each password was found.
+    pub fn search_batch_hits(&self, digests: &[Digest]) -> Vec<Option<SearchHit>> {
+        self.search_items(digests, |table_index, column, digest| {
+            self.tables[table_index].search_column_filtered(
+                column,
+                digest,
+                self.filters[table_index],
+            )
+        })
+    }
+
+    /// Searches for the passwords of several digests at once in the table cluster like `search_batch_hits`,
+    /// and returns the statistics of the searches of each table, in the same order as the tables.
+    pub fn search_batch_stats(
+        &self,
+        digests: &[Digest],
+    ) -> (Vec<Option<SearchHit>>, Vec<SearchStats>) {
+        let stats = self
+            .tables
+            .iter()
+            .map(|_| AtomicSearchStats::default())
+            .collect::<Vec<_>>();
+
+        let found = self.search_items(digests, |table_index, column, digest| {
+            let (password, column_stats) = self.tables[table_index].search_column_stats(
+                column,
+                digest,
+                self.filters[table_index],
+            );
+            stats[table_index].add(column_stats);
+            password
+        });
+
+        let stats = stats
+            .into_iter()
+            .map(AtomicSearchStats::into_inner)
+            .collect();
+
+        (found, stats)
+    }
+
+    /// Searches each (digest, table, column) triple with `search`,
+    /// which is given the index of the table, the column and the digest.
+    /// Only `search` differs between the searches with and without statistics.
+    fn search_items(
+        &self,
+        digests: &[Digest],
+        search: impl Fn(usize, usize, Digest) -> Option<Password> + Sync,
+    ) -> Vec<Option<SearchHit>> {
+        let columns = self.tables[0].ctx().t - 1;
+        let tables = self.tables.len();
+        let items_per_column = digests.len() * tables;
+
+        let found = digests.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+        let cracked = digests
+            .iter()
+            .map(|_| AtomicBool::new(false))
+            .collect::<Vec<_>>();
+        // the skipped items count as searched, so that the progress reaches the total
+        let progress = ProgressCounter::new(
+            self.progress,
+            columns * items_per_column,
+            items_per_column,
+            |done| Progress::Columns {
+                done: done / items_per_column,
+                total: columns,
+            },
+        );
+
+        (0..columns * items_per_column)
+            .into_par_iter()
+            .for_each(|item| {
+                let column = columns - 1 - item / items_per_column;
+                let digest_index = item % items_per_column / tables;
+                let table_index = item % tables;
+
+                if cracked[digest_index].load(Ordering::Relaxed) {
+                    progress.add(1);
+                    return;
+                }
+
+                if let Some(password) = search(table_index, column, digests[digest_index]) {
+                    cracked[digest_index].store(true, Ordering::Relaxed);
+                    *found[digest_index].lock().unwrap() = Some(SearchHit {
+                        password,
+                        table_number: self.tables[table_index].ctx().tn,
+                        column,
+                    });
+                }
+                progress.add(1);
+            });
+
+        found
+            .into_iter()
+            .map(|hit| hit.into_inner().unwrap())
+            .collect()
+    }
+}
+
+/// The chains of several tables, keyed by endpoint.
+/// An endpoint maps to the startpoints of the chains ending with it, along with their table number.
+type MergedMap = HashMap<
+    CompressedPassword,
+    Vec<(CompressedPassword, usize)>,
+    BuildNoHashHasher<CompressedPassword>,
+>;
+
+/// A cluster of rainbow tables merged in a single structure.
+/// The endpoints of all the tables are looked up in the same map, instead of in each table.
+pub struct MergedTableCluster {
+    chains: MergedMap,
+    ctx: RainbowTableCtx,
+    table_numbers: Vec<usize>,
+}
+
+impl MergedTableCluster {
+    /// Creates a new merged table cluster from the chains of the given tables.
+    /// The tables should have the same RainbowTableCtx, except the `tn` field.
+    pub fn new<T: RainbowTable>(tables: &[&T]) -> Self {
+        let mut chains = MergedMap::default();
+
+        for table in tables {
+            let tn = table.ctx().tn;
+
+            for chain in table.iter() {
+                chains
+                    .entry(chain.endpoint)
+                    .or_default()
+                    .push((chain.startpoint, tn));
+            }
+        }
+
+        Self {
+            chains,
+            ctx: tables[0].ctx(),
+            table_numbers: tables.iter().map(|table| table.ctx().tn).collect(),
+        }
+    }
+
+    /// Returns the number of chains in the cluster.
+    pub fn len(&self) -> usize {
+        self.chains.values().map(Vec::len).sum()
+    }
+
+    /// Returns true if the cluster has no chain.
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Searches for a password in the merged table cluster.
+    pub fn search(&self, digest: Digest) -> Option<Password> {
+        (0..self.ctx.t - 1).into_par_iter().rev().find_map_any(|i| {
+            self.table_numbers.iter().find_map(|&tn| {
+                // the reduction function depends on the table number
+                let ctx = RainbowTableCtx { tn, ..self.ctx };
 
-        (0..t - 1).into_par_iter().rev().find_map_any(|i| {
-            self.tables
-                .iter()
-                .find_map(|table| table.search_column(i, digest))
+                search_column(
+                    &ctx,
+                    i,
+                    digest,
+                    |endpoint| {
+                        self.chains
+                            .get(&endpoint)?
+                            .iter()
+                            .find(|(_, chain_tn)| *chain_tn == tn)
+                            .map(|(startpoint, _)| *startpoint)
+                    },
+                    |_| None,
+                    &(),
+                    &mut SearchStats::default(),
+                )
+            })
         })
     }
 }
 
+/// Checks that a set of tables can be used together in a cluster.
+/// The tables should have the same context, except the number of startpoints and the table number,
+/// which should be different for each table.
+#[derive(Default)]
+pub struct TableSetValidator {
+    ctx: Option<RainbowTableCtx>,
+    table_numbers: HashSet<usize>,
+}
+
+impl TableSetValidator {
+    /// Creates a new validator for an empty set of tables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the context of a table to the set.
+    /// Returns an error describing the problem if the table is not compatible with the tables already added.
+    pub fn add(&mut self, ctx: RainbowTableCtx) -> CugparckResult<()> {
+        let first = *self.ctx.get_or_insert(ctx);
+
+        let incompatible = |parameter| CugparckError::IncompatibleTable {
+            tn: ctx.tn,
+            parameter,
+        };
+
+        if ctx.hash_type != first.hash_type {
+            return Err(incompatible("hash function"));
+        }
+
+        if ctx.charset != first.charset
+            || ctx.charsets != first.charsets
+            || ctx.encoding != first.encoding
+            || ctx.code_points != first.code_points
+        {
+            return Err(incompatible("charset"));
+        }
+
+        if ctx.salt != first.salt || ctx.salt_position != first.salt_position {
+            return Err(incompatible("salt"));
+        }
+
+        if ctx.min_password_length != first.min_password_length {
+            return Err(incompatible("minimum password length"));
+        }
+
+        if ctx.max_password_length != first.max_password_length {
+            return Err(incompatible("maximum password length"));
+        }
+
+        if ctx.t != first.t {
+            return Err(incompatible("chain length"));
+        }
+
+        if !self.table_numbers.insert(ctx.tn) {
+            return Err(CugparckError::DuplicateTableNumber(ctx.tn));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of tables in the set.
+    pub fn len(&self) -> usize {
+        self.table_numbers.len()
+    }
+
+    /// Returns true if no table was added.
+    pub fn is_empty(&self) -> bool {
+        self.table_numbers.is_empty()
+    }
+}
+
+/// A cluster of rainbow tables that can be searched for a digest.
+pub trait ClusterSearch {
+    /// Searches for a password in the cluster.
+    fn search(&self, digest: Digest) -> Option<Password>;
+}
+
+impl<'a, T: RainbowTable> ClusterSearch for TableCluster<'a, T> {
+    fn search(&self, digest: Digest) -> Option<Password> {
+        TableCluster::search(self, digest)
+    }
+}
+
+impl ClusterSearch for MergedTableCluster {
+    fn search(&self, digest: Digest) -> Option<Password> {
+        MergedTableCluster::search(self, digest)
+    }
+}
+
+/// A cluster keeping the results of its most recent searches.
+/// Useful when the same digests are attacked repeatedly, e.g. the digests of common passwords.
+pub struct CachedTableCluster<C: ClusterSearch> {
+    cluster: C,
+    cache: Mutex<LruCache<Digest, Option<Password>>>,
+}
+
+impl<C: ClusterSearch> CachedTableCluster<C> {
+    /// Creates a new cached cluster, keeping the results of at most `capacity` digests.
+    pub fn new(cluster: C, capacity: NonZeroUsize) -> Self {
+        Self {
+            cluster,
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Searches for a password in the cluster, or returns the cached result if this digest was already searched.
+    pub fn search(&self, digest: Digest) -> Option<Password> {
+        if let Some(result) = self.cache.lock().unwrap().get(&digest) {
+            return *result;
+        }
+
+        // the lock isn't held during the search so that other digests can be searched concurrently.
+        let result = self.cluster.search(digest);
+        self.cache.lock().unwrap().put(digest, result);
+
+        result
+    }
+
+    /// Returns the wrapped cluster.
+    pub fn into_inner(self) -> C {
+        self.cluster
+    }
+}
+
+impl<C: ClusterSearch> ClusterSearch for CachedTableCluster<C> {
+    fn search(&self, digest: Digest) -> Option<Password> {
+        CachedTableCluster::search(self, digest)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use cugparck_commons::CompressedPassword;
+    use std::{
+        num::NonZeroUsize,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+
+    use cugparck_commons::{CompressedPassword, Digest, Password};
     use itertools::Itertools;
 
-    use crate::{backend::Cpu, RainbowTableCtxBuilder, SimpleTable, TableCluster};
+    use crate::{
+        backend::Cpu, CachedTableCluster, ClusterSearch, CugparckError, MergedTableCluster,
+        Progress, ProgressListener, RainbowTable, RainbowTableCtxBuilder, SimpleTable,
+        TableCluster, TableSetValidator,
+    };
+
+    /// A cluster counting the searches it receives.
+    #[derive(Default)]
+    struct CountingCluster {
+        searches: AtomicUsize,
+    }
+
+    impl ClusterSearch for CountingCluster {
+        fn search(&self, digest: Digest) -> Option<Password> {
+            self.searches.fetch_add(1, Ordering::Relaxed);
+            (digest[0] == 0).then(|| Password::new(b"found"))
+        }
+    }
 
     #[test]
     fn test_coverage() {
@@ -54,11 +430,10 @@ mod tests {
 
         let mut found = 0;
         let ctx = ctx_builder.build().unwrap();
-        let hash = ctx.hash_type.hash_function();
 
         for i in 0..ctx.n {
             let password = CompressedPassword::from(i).into_password(&ctx);
-            if let Some(plaintext) = cluster.search(hash(password)) {
+            if let Some(plaintext) = cluster.search(ctx.hash(&password)) {
                 assert_eq!(password, plaintext);
                 found += 1;
             }
@@ -73,4 +448,200 @@ mod tests {
             "success rate is only {success_rate}"
         );
     }
+
+    #[test]
+    fn test_merged_coverage() {
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef");
+
+        let tables = (1..5)
+            .map(|i| {
+                let ctx = ctx_builder.table_number(i).build().unwrap();
+                SimpleTable::new_blocking::<Cpu>(ctx).unwrap()
+            })
+            .collect_vec();
+
+        let tables_ref = tables.iter().collect_vec();
+
+        let cluster = TableCluster::new(&tables_ref);
+        let merged = MergedTableCluster::new(&tables_ref);
+
+        assert_eq!(
+            tables.iter().map(|table| table.len()).sum::<usize>(),
+            merged.len()
+        );
+
+        let ctx = ctx_builder.build().unwrap();
+
+        for i in 0..ctx.n {
+            let password = CompressedPassword::from(i).into_password(&ctx);
+            let digest = ctx.hash(&password);
+
+            let found = merged.search(digest);
+            assert_eq!(cluster.search(digest).is_some(), found.is_some());
+
+            if let Some(plaintext) = found {
+                assert_eq!(password, plaintext);
+            }
+        }
+
+        let digests = (0..ctx.n)
+            .map(|i| ctx.hash(&CompressedPassword::from(i).into_password(&ctx)))
+            .collect_vec();
+
+        for ((i, digest), found) in digests
+            .iter()
+            .enumerate()
+            .zip(cluster.search_batch(&digests))
+        {
+            assert_eq!(merged.search(*digest).is_some(), found.is_some());
+
+            if let Some(plaintext) = found {
+                assert_eq!(CompressedPassword::from(i).into_password(&ctx), plaintext);
+            }
+        }
+
+        for (digest, hit) in digests.iter().zip(cluster.search_batch_hits(&digests)) {
+            if let Some(hit) = hit {
+                let table = tables
+                    .iter()
+                    .find(|table| table.ctx().tn == hit.table_number)
+                    .unwrap();
+                assert_eq!(Some(hit.password), table.search_column(hit.column, *digest));
+            }
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<Progress>>);
+
+        impl ProgressListener for Recorder {
+            fn progress(&self, progress: Progress) {
+                self.0.lock().unwrap().push(progress);
+            }
+        }
+
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef");
+
+        let tables = (0..2)
+            .map(|i| {
+                let ctx = ctx_builder.table_number(i).build().unwrap();
+                SimpleTable::new_blocking::<Cpu>(ctx).unwrap()
+            })
+            .collect_vec();
+        let tables_ref = tables.iter().collect_vec();
+
+        let recorder = Recorder::default();
+        let cluster = TableCluster::new(&tables_ref).with_progress(&recorder);
+
+        let ctx = ctx_builder.build().unwrap();
+        let digests = ["abc", "fed", "zzzz"]
+            .map(|password| ctx.hash(&Password::new(password.as_bytes())))
+            .to_vec();
+        cluster.search_batch(&digests);
+
+        // a column is reported once it is searched for every digest in every table,
+        // including the digests already cracked
+        let events = recorder.0.into_inner().unwrap();
+        assert_eq!(ctx.t - 1, events.len());
+        assert!(events.iter().all(|event| matches!(
+            event,
+            Progress::Columns { total, .. } if *total == ctx.t - 1
+        )));
+        assert_eq!(
+            Some(&Progress::Columns {
+                done: ctx.t - 1,
+                total: ctx.t - 1,
+            }),
+            events.iter().max_by_key(|event| match event {
+                Progress::Columns { done, .. }
+                | Progress::Chains { done, .. }
+                | Progress::Passwords { done, .. } => *done,
+            })
+        );
+    }
+
+    #[test]
+    fn test_cached_cluster() {
+        let cached =
+            CachedTableCluster::new(CountingCluster::default(), NonZeroUsize::new(1).unwrap());
+        let found = Digest::from_array_len([0; 64], 16);
+        let not_found = Digest::from_array_len([1; 64], 16);
+
+        assert_eq!(Some(Password::new(b"found")), cached.search(found));
+        assert_eq!(Some(Password::new(b"found")), cached.search(found));
+        assert_eq!(1, cached.cluster.searches.load(Ordering::Relaxed));
+
+        // misses are cached too, and the least recently used digest is evicted.
+        assert_eq!(None, cached.search(not_found));
+        assert_eq!(None, cached.search(not_found));
+        assert_eq!(2, cached.cluster.searches.load(Ordering::Relaxed));
+
+        cached.search(found);
+        assert_eq!(3, cached.into_inner().searches.into_inner());
+    }
+
+    #[test]
+    fn test_table_set_validator() {
+        let ctx_builder = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef");
+
+        let mut validator = TableSetValidator::new();
+        validator
+            .add(ctx_builder.table_number(1).build().unwrap())
+            .unwrap();
+        validator
+            .add(
+                ctx_builder
+                    .table_number(2)
+                    .startpoints(Some(10))
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap();
+        assert_eq!(2, validator.len());
+
+        let err = validator
+            .add(ctx_builder.table_number(2).build().unwrap())
+            .unwrap_err();
+        assert!(matches!(err, CugparckError::DuplicateTableNumber(2)));
+
+        let err = validator
+            .add(ctx_builder.table_number(3).charset(b"abc").build().unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CugparckError::IncompatibleTable {
+                tn: 3,
+                parameter: "charset"
+            }
+        ));
+
+        let err = validator
+            .add(
+                ctx_builder
+                    .table_number(3)
+                    .chain_length(200)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            CugparckError::IncompatibleTable {
+                tn: 3,
+                parameter: "chain length"
+            }
+        ));
+        assert_eq!(2, validator.len());
+    }
 }
@@ -0,0 +1,64 @@
+//! Bindings to attack a rainbow table from JavaScript, when compiling to `wasm32-unknown-unknown`.
+//!
+//! The crate should be built with `--no-default-features --features wasm`, so the search runs without threads.
+//! The tables can't be read from the disk: their bytes are passed to `WasmTable::new` instead,
+//! for example after fetching a file produced by `RainbowTableStorage::store` or `RainbowTableStorage::to_bytes`.
+
+use rkyv::AlignedVec;
+use wasm_bindgen::prelude::*;
+
+use crate::{CompressedTable, CugparckError, RainbowTable, RainbowTableStorage, SimpleTable};
+
+/// A rainbow table loaded from bytes, which can be searched from JavaScript.
+#[wasm_bindgen]
+pub struct WasmTable {
+    bytes: AlignedVec,
+    compressed: bool,
+}
+
+#[wasm_bindgen]
+impl WasmTable {
+    /// Copies the bytes of a stored table, and checks that they contain a valid table.
+    /// `compressed` should be true if the table was compressed with delta encoding.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8], compressed: bool) -> Result<WasmTable, JsError> {
+        // rkyv needs the archived table to be aligned.
+        let mut aligned = AlignedVec::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+
+        let table = Self {
+            bytes: aligned,
+            compressed,
+        };
+        table.check()?;
+
+        Ok(table)
+    }
+
+    /// Searches for a password that hashes to the given digest.
+    /// Returns `undefined` if the password is not in the table.
+    pub fn search(&self, digest: &[u8]) -> Option<String> {
+        let digest = digest.try_into().ok()?;
+
+        let password = if self.compressed {
+            CompressedTable::load(&self.bytes).ok()?.search(digest)
+        } else {
+            SimpleTable::load(&self.bytes).ok()?.search(digest)
+        };
+
+        password.map(|password| password.to_string())
+    }
+}
+
+impl WasmTable {
+    /// Checks that the bytes contain a valid table.
+    fn check(&self) -> Result<(), CugparckError> {
+        if self.compressed {
+            CompressedTable::load(&self.bytes)?;
+        } else {
+            SimpleTable::load(&self.bytes)?;
+        }
+
+        Ok(())
+    }
+}
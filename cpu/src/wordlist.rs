@@ -0,0 +1,124 @@
+//! Dictionary attacks, to find the passwords that are missing from the tables.
+
+use std::collections::HashMap;
+
+use cugparck_commons::{
+    Digest, HashType, PasswordEncoding, RainbowTableCtx, SaltPosition, LM_HALF_LENGTH,
+    MAX_PASSWORD_LENGTH_ALLOWED, MAX_SALT_LENGTH_ALLOWED,
+};
+
+use crate::parallel::*;
+
+/// Returns the maximum length of the words that can be hashed with the hash function and salt of a context.
+/// The words are not limited to the maximum password length of the tables,
+/// but the hash functions hashing the passwords on the stack only accept a salted password of a bounded length.
+pub fn max_word_length(ctx: &RainbowTableCtx) -> usize {
+    match ctx.hash_type {
+        // the LM hashes are split in halves, which are searched separately
+        HashType::Lm => LM_HALF_LENGTH,
+        _ => MAX_PASSWORD_LENGTH_ALLOWED + MAX_SALT_LENGTH_ALLOWED - ctx.salt.len(),
+    }
+}
+
+/// Searches for the passwords of digests in a wordlist, by hashing each word with the hash function and salt of a context.
+/// Returns the index of the first word matching each digest, or `None` if no word matches.
+/// The words longer than `max_word_length` are skipped.
+/// The words are expected to be encoded in UTF-8, so they are hashed as UTF-8 passwords for NTLM.
+pub fn search_wordlist(
+    words: &[&[u8]],
+    digests: &[Digest],
+    ctx: &RainbowTableCtx,
+) -> Vec<Option<usize>> {
+    let mut targets = HashMap::<Digest, Vec<usize>>::new();
+    for (i, digest) in digests.iter().enumerate() {
+        if ctx.could_contain_digest(digest) {
+            targets.entry(*digest).or_default().push(i);
+        }
+    }
+
+    let mut found = vec![None; digests.len()];
+    if targets.is_empty() {
+        return found;
+    }
+
+    let ctx = RainbowTableCtx {
+        encoding: PasswordEncoding::Utf8,
+        ..*ctx
+    };
+    let hash = ctx.hash_function();
+    let max_word_length = max_word_length(&ctx);
+
+    let matches = words
+        .par_iter()
+        .enumerate()
+        .filter(|(_, word)| word.len() <= max_word_length)
+        .filter_map(|(i, word)| {
+            let digest = if ctx.salt.is_empty() {
+                hash(word)
+            } else {
+                let salted = match ctx.salt_position {
+                    SaltPosition::Prefix => [ctx.salt.as_slice(), *word].concat(),
+                    SaltPosition::Suffix => [*word, ctx.salt.as_slice()].concat(),
+                };
+                hash(&salted)
+            };
+
+            targets.contains_key(&digest).then_some((i, digest))
+        })
+        .collect::<Vec<_>>();
+
+    // the matches are in the order of the wordlist, so the first matching word is kept
+    for (word, digest) in matches {
+        for &i in &targets[&digest] {
+            found[i].get_or_insert(word);
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use cugparck_commons::{HashType, Password, SaltPosition};
+
+    use crate::{search_wordlist, RainbowTableCtxBuilder};
+
+    #[test]
+    fn test_search_wordlist() {
+        let words: [&[u8]; 4] = [
+            b"hello",
+            b"a password longer than the tables",
+            b"mot de passe \xc3\xa9",
+            b"hello",
+        ];
+
+        for builder in [
+            RainbowTableCtxBuilder::new().hash(HashType::Md5),
+            RainbowTableCtxBuilder::new().hash(HashType::Ntlm),
+            RainbowTableCtxBuilder::new()
+                .hash(HashType::Sha1)
+                .salt(b"salt", SaltPosition::Suffix),
+        ] {
+            let ctx = builder.build().unwrap();
+            let digests =
+                [b"hello".as_slice(), b"missing", b""].map(|word| ctx.hash(&Password::new(word)));
+            assert_eq!(
+                vec![Some(0), None, None],
+                search_wordlist(&words, &digests, &ctx)
+            );
+        }
+
+        let ctx = RainbowTableCtxBuilder::new()
+            .hash(HashType::Md5)
+            .build()
+            .unwrap();
+        let digests = [
+            ctx.hash_type.hash_function()(words[1]),
+            ctx.hash_type.hash_function()(words[2]),
+        ];
+        assert_eq!(
+            vec![Some(1), Some(2)],
+            search_wordlist(&words, &digests, &ctx)
+        );
+    }
+}
@@ -7,7 +7,9 @@
 #![allow(improper_ctypes_definitions, clippy::missing_safety_doc)]
 
 use cuda_std::{kernel, thread::index_1d};
-use cugparck_commons::{CompressedPassword, RainbowTableCtx};
+use cugparck_commons::{
+    column_endpoint, CompressedPassword, Digest, RainbowTableCtx, MAX_DIGEST_LENGTH_ALLOWED,
+};
 
 #[kernel]
 pub unsafe fn chains_kernel(
@@ -15,7 +17,7 @@ pub unsafe fn chains_kernel(
     col_end: usize,
     midpoints: *mut CompressedPassword,
     midpoints_len: usize,
-    ctx: RainbowTableCtx,
+    ctx: *const RainbowTableCtx,
 ) {
     let index = index_1d() as usize;
 
@@ -24,5 +26,23 @@ pub unsafe fn chains_kernel(
     }
 
     let midpoint = &mut *midpoints.add(index);
-    midpoint.continue_chain(col_start..col_end, &ctx)
+    midpoint.continue_chain(col_start..col_end, &*ctx)
+}
+
+#[kernel]
+pub unsafe fn endpoints_kernel(
+    digest: [u8; MAX_DIGEST_LENGTH_ALLOWED],
+    digest_len: usize,
+    endpoints: *mut CompressedPassword,
+    endpoints_len: usize,
+    ctx: *const RainbowTableCtx,
+) {
+    let column = index_1d() as usize;
+
+    if column >= endpoints_len {
+        return;
+    }
+
+    let digest = Digest::from_array_len(digest, digest_len);
+    *endpoints.add(column) = column_endpoint(digest, column, &*ctx);
 }
@@ -0,0 +1,93 @@
+#ifndef CUGPARCK_H
+#define CUGPARCK_H
+
+/*
+ * C bindings to search digests in rainbow tables generated by cugparck.
+ *
+ * Example:
+ *
+ *     CugparckTables *tables;
+ *     CugparckHit hit;
+ *
+ *     if (cugparck_tables_load("tables", &tables) != CUGPARCK_STATUS_OK)
+ *         return 1;
+ *
+ *     if (cugparck_tables_search(tables, digest, 16, &hit) == CUGPARCK_STATUS_OK)
+ *         printf("%.*s\n", (int)hit.password_len, hit.password);
+ *
+ *     cugparck_tables_free(tables);
+ */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CUGPARCK_MAX_PASSWORD_LENGTH 16
+
+/* The status returned by the functions of the library. */
+typedef enum CugparckStatus {
+    /* The call succeeded. */
+    CUGPARCK_STATUS_OK = 0,
+    /* The password of the digest is not in the tables. */
+    CUGPARCK_STATUS_NOT_FOUND = 1,
+    /* A pointer given to the function is null. */
+    CUGPARCK_STATUS_NULL_POINTER = 2,
+    /* The path is not valid UTF-8. */
+    CUGPARCK_STATUS_INVALID_PATH = 3,
+    /* A file could not be read. */
+    CUGPARCK_STATUS_IO = 4,
+    /* The directory doesn't contain any table. */
+    CUGPARCK_STATUS_NO_TABLE = 5,
+    /* The tables of the directory can't be searched together. */
+    CUGPARCK_STATUS_INCOMPATIBLE_TABLES = 6,
+    /* A table is corrupted, or was stored by an incompatible version of cugparck. */
+    CUGPARCK_STATUS_CORRUPTED_TABLE = 7,
+    /* The digest doesn't have the size of the digests of the tables. */
+    CUGPARCK_STATUS_INVALID_DIGEST = 8,
+    /* An unexpected error occured. */
+    CUGPARCK_STATUS_INTERNAL = 9,
+} CugparckStatus;
+
+/* A password found in the tables. */
+typedef struct CugparckHit {
+    /* The bytes of the password, followed by zeros. The password is not null-terminated. */
+    uint8_t password[CUGPARCK_MAX_PASSWORD_LENGTH];
+    /* The length of the password, in bytes. */
+    size_t password_len;
+    /* The number of the table containing the password. */
+    size_t table_number;
+    /* The column of the chain containing the password. */
+    size_t column;
+} CugparckHit;
+
+/* The rainbow tables of a directory. */
+typedef struct CugparckTables CugparckTables;
+
+/*
+ * Loads the simple (.rt) or compressed (.rtcde) rainbow tables of a directory, and writes a pointer to them in `tables`.
+ * Tables compressed with zstd (.rtz) are ignored, and should be decompressed first.
+ * The tables should be freed with `cugparck_tables_free`.
+ */
+CugparckStatus cugparck_tables_load(const char *dir, CugparckTables **tables);
+
+/*
+ * Searches for the password of a digest in the tables, and writes it in `hit` if it is found.
+ * Returns CUGPARCK_STATUS_NOT_FOUND if the password is not in the tables.
+ * The tables can be searched from several threads at once.
+ */
+CugparckStatus cugparck_tables_search(const CugparckTables *tables, const uint8_t *digest, size_t digest_len, CugparckHit *hit);
+
+/* Frees tables loaded with `cugparck_tables_load`. Does nothing if `tables` is null. */
+void cugparck_tables_free(CugparckTables *tables);
+
+/* Returns a static null-terminated description of a status. */
+const char *cugparck_status_message(CugparckStatus status);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
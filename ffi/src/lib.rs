@@ -0,0 +1,371 @@
+//! C bindings to search digests in rainbow tables from other languages.
+//!
+//! The functions return a `CugparckStatus` and write their results through pointers.
+//! The declarations for C are available in `include/cugparck.h`.
+
+use std::{
+    ffi::CStr,
+    fs,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    ptr, slice,
+};
+
+use cugparck_commons::{Digest, RainbowTableCtx, MAX_PASSWORD_LENGTH_ALLOWED};
+use cugparck_cpu::{
+    ArchivedCompressedTable, ArchivedSimpleTable, CompressedTable, CugparckError, RainbowTable,
+    RainbowTableStorage, SearchHit, SimpleTable, TableCluster, TableSetValidator,
+};
+use memmap2::Mmap;
+
+/// The status returned by the functions of the library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CugparckStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// The password of the digest is not in the tables.
+    NotFound = 1,
+    /// A pointer given to the function is null.
+    NullPointer = 2,
+    /// The path is not valid UTF-8.
+    InvalidPath = 3,
+    /// A file could not be read.
+    Io = 4,
+    /// The directory doesn't contain any table.
+    NoTable = 5,
+    /// The tables of the directory can't be searched together.
+    IncompatibleTables = 6,
+    /// A table is corrupted, or was stored by an incompatible version of cugparck.
+    CorruptedTable = 7,
+    /// The digest doesn't have the size of the digests of the tables.
+    InvalidDigest = 8,
+    /// An unexpected error occured.
+    Internal = 9,
+}
+
+impl From<CugparckError> for CugparckStatus {
+    fn from(err: CugparckError) -> Self {
+        match err {
+            CugparckError::Io(_) => Self::Io,
+            CugparckError::IncompatibleTable { .. } | CugparckError::DuplicateTableNumber(_) => {
+                Self::IncompatibleTables
+            }
+            CugparckError::Check
+            | CugparckError::Checksum
+            | CugparckError::UnsupportedVersion(_) => Self::CorruptedTable,
+            _ => Self::Internal,
+        }
+    }
+}
+
+impl From<std::io::Error> for CugparckStatus {
+    fn from(_: std::io::Error) -> Self {
+        Self::Io
+    }
+}
+
+/// A password found in the tables.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CugparckHit {
+    /// The bytes of the password, followed by zeros.
+    pub password: [u8; MAX_PASSWORD_LENGTH_ALLOWED],
+    /// The length of the password, in bytes.
+    pub password_len: usize,
+    /// The number of the table containing the password.
+    pub table_number: usize,
+    /// The column of the chain containing the password.
+    pub column: usize,
+}
+
+impl From<SearchHit> for CugparckHit {
+    fn from(hit: SearchHit) -> Self {
+        let mut password = [0; MAX_PASSWORD_LENGTH_ALLOWED];
+        password[..hit.password.len()].copy_from_slice(&hit.password);
+
+        Self {
+            password,
+            password_len: hit.password.len(),
+            table_number: hit.table_number,
+            column: hit.column,
+        }
+    }
+}
+
+/// The archived tables of a directory.
+enum Tables {
+    Simple(Vec<&'static ArchivedSimpleTable>),
+    Compressed(Vec<&'static ArchivedCompressedTable>),
+}
+
+impl Tables {
+    /// Returns the contexts of the tables.
+    fn ctxs(&self) -> Vec<RainbowTableCtx> {
+        match self {
+            Self::Simple(tables) => tables.iter().map(|table| table.ctx()).collect(),
+            Self::Compressed(tables) => tables.iter().map(|table| table.ctx()).collect(),
+        }
+    }
+
+    /// Searches for the password of a digest in all the tables.
+    fn search(&self, digest: Digest) -> Option<SearchHit> {
+        match self {
+            Self::Simple(tables) => TableCluster::new(tables).search_batch_hits(&[digest])[0],
+            Self::Compressed(tables) => TableCluster::new(tables).search_batch_hits(&[digest])[0],
+        }
+    }
+}
+
+/// The rainbow tables of a directory, which are opaque to C.
+pub struct CugparckTables {
+    // the tables borrow the memory maps, so they have to be dropped first.
+    tables: Tables,
+    _mmaps: Vec<Mmap>,
+    ctx: RainbowTableCtx,
+}
+
+impl CugparckTables {
+    /// Loads the simple or compressed tables of a directory.
+    /// Tables compressed with zstd are ignored, and should be decompressed first.
+    fn load(dir: &Path) -> Result<Self, CugparckStatus> {
+        let mut simple = Vec::new();
+        let mut compressed = Vec::new();
+
+        for file in fs::read_dir(dir)? {
+            let path = file?.path();
+            let mmaps = match path.extension().and_then(|s| s.to_str()) {
+                Some("rt") => &mut simple,
+                Some("rtcde") => &mut compressed,
+                _ => continue,
+            };
+
+            // SAFETY: the file is not being modified anywhere else.
+            mmaps.push(unsafe { Mmap::map(&fs::File::open(path)?)? });
+        }
+
+        // SAFETY: the memory of a memory map doesn't move when the map is moved,
+        // and the tables are dropped before the memory maps.
+        let (tables, mmaps) = match (simple.is_empty(), compressed.is_empty()) {
+            (true, true) => return Err(CugparckStatus::NoTable),
+            (false, false) => return Err(CugparckStatus::IncompatibleTables),
+            (false, true) => {
+                let tables = simple
+                    .iter()
+                    .map(|mmap| Ok(unsafe { extend_lifetime(SimpleTable::load(mmap)?) }))
+                    .collect::<Result<_, CugparckError>>()?;
+                (Tables::Simple(tables), simple)
+            }
+            (true, false) => {
+                let tables = compressed
+                    .iter()
+                    .map(|mmap| Ok(unsafe { extend_lifetime(CompressedTable::load(mmap)?) }))
+                    .collect::<Result<_, CugparckError>>()?;
+                (Tables::Compressed(tables), compressed)
+            }
+        };
+
+        let ctxs = tables.ctxs();
+        let mut validator = TableSetValidator::new();
+        for ctx in &ctxs {
+            validator.add(*ctx)?;
+        }
+
+        Ok(Self {
+            tables,
+            _mmaps: mmaps,
+            ctx: ctxs[0],
+        })
+    }
+
+    /// Searches for the password of a digest.
+    fn search(&self, digest: &[u8]) -> Result<CugparckHit, CugparckStatus> {
+        if !self.ctx.could_contain_digest(digest) {
+            return Err(CugparckStatus::InvalidDigest);
+        }
+
+        let digest = digest
+            .try_into()
+            .map_err(|_| CugparckStatus::InvalidDigest)?;
+
+        self.tables
+            .search(digest)
+            .map(CugparckHit::from)
+            .ok_or(CugparckStatus::NotFound)
+    }
+}
+
+/// Extends the lifetime of a table loaded from a memory map.
+///
+/// # Safety
+///
+/// The table should not be used once its memory map is dropped.
+unsafe fn extend_lifetime<T>(table: &T) -> &'static T {
+    &*(table as *const T)
+}
+
+/// Runs `f`, turning its result and its panics into a status.
+fn catch_status(f: impl FnOnce() -> Result<(), CugparckStatus>) -> CugparckStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => CugparckStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => CugparckStatus::Internal,
+    }
+}
+
+/// Loads the rainbow tables of a directory, and writes a pointer to them in `tables`.
+/// The tables should be freed with `cugparck_tables_free`.
+///
+/// # Safety
+///
+/// `dir` should be a valid null-terminated string, and `tables` should be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cugparck_tables_load(
+    dir: *const c_char,
+    tables: *mut *mut CugparckTables,
+) -> CugparckStatus {
+    catch_status(|| {
+        if dir.is_null() || tables.is_null() {
+            return Err(CugparckStatus::NullPointer);
+        }
+
+        let dir = CStr::from_ptr(dir)
+            .to_str()
+            .map_err(|_| CugparckStatus::InvalidPath)?;
+
+        let loaded = CugparckTables::load(Path::new(dir))?;
+        *tables = Box::into_raw(Box::new(loaded));
+
+        Ok(())
+    })
+}
+
+/// Searches for the password of a digest in the tables, and writes it in `hit` if it is found.
+/// Returns `CUGPARCK_STATUS_NOT_FOUND` if the password is not in the tables.
+///
+/// # Safety
+///
+/// `tables` should come from `cugparck_tables_load`, `digest` should be valid for `digest_len` bytes,
+/// and `hit` should be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cugparck_tables_search(
+    tables: *const CugparckTables,
+    digest: *const u8,
+    digest_len: usize,
+    hit: *mut CugparckHit,
+) -> CugparckStatus {
+    catch_status(|| {
+        if tables.is_null() || digest.is_null() || hit.is_null() {
+            return Err(CugparckStatus::NullPointer);
+        }
+
+        let digest = slice::from_raw_parts(digest, digest_len);
+        ptr::write(hit, (*tables).search(digest)?);
+
+        Ok(())
+    })
+}
+
+/// Frees tables loaded with `cugparck_tables_load`. Does nothing if `tables` is null.
+///
+/// # Safety
+///
+/// `tables` should come from `cugparck_tables_load`, and should not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cugparck_tables_free(tables: *mut CugparckTables) {
+    if !tables.is_null() {
+        drop(Box::from_raw(tables));
+    }
+}
+
+/// Returns a static null-terminated description of a status.
+#[no_mangle]
+pub extern "C" fn cugparck_status_message(status: CugparckStatus) -> *const c_char {
+    let message: &[u8] = match status {
+        CugparckStatus::Ok => b"Success\0",
+        CugparckStatus::NotFound => b"The password is not in the tables\0",
+        CugparckStatus::NullPointer => b"A pointer given to the function is null\0",
+        CugparckStatus::InvalidPath => b"The path is not valid UTF-8\0",
+        CugparckStatus::Io => b"Unable to read the tables\0",
+        CugparckStatus::NoTable => b"No table found in the given directory\0",
+        CugparckStatus::IncompatibleTables => {
+            b"The tables of the directory can't be used together\0"
+        }
+        CugparckStatus::CorruptedTable => {
+            b"Failed to validate a rainbow table. Is the file corrupted?\0"
+        }
+        CugparckStatus::InvalidDigest => {
+            b"The digest doesn't have the size of the digests of the tables\0"
+        }
+        CugparckStatus::Internal => b"An unexpected error occured\0",
+    };
+
+    message.as_ptr().cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, ffi::CString, fs, ptr};
+
+    use cugparck_commons::{CompressedPassword, Password};
+    use cugparck_cpu::{
+        backend::Cpu, RainbowTable, RainbowTableCtxBuilder, RainbowTableStorage, SimpleTable,
+    };
+
+    use super::{
+        cugparck_tables_free, cugparck_tables_load, cugparck_tables_search, CugparckHit,
+        CugparckStatus,
+    };
+
+    #[test]
+    fn test_search() {
+        let ctx = RainbowTableCtxBuilder::new()
+            .chain_length(100)
+            .max_password_length(4)
+            .charset(b"abcdef")
+            .build()
+            .unwrap();
+        let table = SimpleTable::new_blocking::<Cpu>(ctx).unwrap();
+
+        let dir = env::temp_dir().join(format!("cugparck_test_ffi_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        table.store(&dir.join("table.rt")).unwrap();
+
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let mut tables = ptr::null_mut();
+        let status = unsafe { cugparck_tables_load(path.as_ptr(), &mut tables) };
+        assert_eq!(CugparckStatus::Ok, status);
+
+        let mut hit = CugparckHit {
+            password: Default::default(),
+            password_len: 0,
+            table_number: 0,
+            column: 0,
+        };
+
+        for i in (0..ctx.n).step_by(7) {
+            let digest = ctx.hash(&CompressedPassword::from(i).into_password(&ctx));
+            let status =
+                unsafe { cugparck_tables_search(tables, digest.as_ptr(), digest.len(), &mut hit) };
+
+            match table.search(digest) {
+                Some(_) => {
+                    assert_eq!(CugparckStatus::Ok, status);
+                    let password = &hit.password[..hit.password_len];
+                    assert_eq!(digest, ctx.hash(&Password::new(password)));
+                }
+                None => assert_eq!(CugparckStatus::NotFound, status),
+            }
+        }
+
+        // a SHA-1 digest can't be in NTLM tables
+        let digest = [0; 20];
+        let status =
+            unsafe { cugparck_tables_search(tables, digest.as_ptr(), digest.len(), &mut hit) };
+        assert_eq!(CugparckStatus::InvalidDigest, status);
+
+        unsafe { cugparck_tables_free(tables) };
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+//! Python bindings to generate rainbow tables and search them, so experiments can be scripted without the CLI.
+//!
+//! The module is built with [maturin](https://github.com/PyO3/maturin):
+//!
+//! ```text
+//! cd py && maturin develop --release
+//! ```
+//!
+//! ```python
+//! import cugparck
+//!
+//! ctx = cugparck.RainbowTableCtxBuilder().hash("md5").charset(b"abc").max_password_length(5).build()
+//! table = cugparck.SimpleTable.generate(ctx, backend="cpu")
+//! table.store("table.rt")
+//!
+//! cluster = cugparck.TableCluster([cugparck.Table.load("table.rt")])
+//! password = cluster.search(bytes.fromhex("900150983cd24fb0d6963f7d28e17f72"))
+//! ```
+
+use std::{fs::File, path::PathBuf};
+
+use cugparck_commons::{Digest, HashType, Password, SaltPosition};
+use cugparck_cpu::{
+    backend, ArchivedCompressedTable, ArchivedSimpleTable, CompressedTable, RainbowTable,
+    RainbowTableStorage, SearchHit, TableSetValidator,
+};
+use memmap2::Mmap;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::PyBytes,
+};
+
+create_exception!(
+    cugparck,
+    CugparckError,
+    PyException,
+    "An error raised by cugparck."
+);
+
+/// Converts an error of cugparck to a Python exception.
+fn py_err(err: cugparck_cpu::CugparckError) -> PyErr {
+    CugparckError::new_err(err.to_string())
+}
+
+/// Returns the hash type named like the hash types of the CLI.
+fn parse_hash_type(name: &str) -> PyResult<HashType> {
+    HashType::ALL
+        .into_iter()
+        .find(|hash_type| hash_type_name(*hash_type) == name)
+        .ok_or_else(|| PyValueError::new_err(format!("Unknown hash type: {name}")))
+}
+
+/// Returns the name of a hash type, as used by the CLI.
+fn hash_type_name(hash_type: HashType) -> &'static str {
+    match hash_type {
+        HashType::Ntlm => "ntlm",
+        HashType::Md4 => "md4",
+        HashType::Md5 => "md5",
+        HashType::Sha1 => "sha1",
+        HashType::Sha2_224 => "sha2-224",
+        HashType::Sha2_256 => "sha2-256",
+        HashType::Sha2_384 => "sha2-384",
+        HashType::Sha2_512 => "sha2-512",
+        HashType::Sha3_224 => "sha3-224",
+        HashType::Sha3_256 => "sha3-256",
+        HashType::Sha3_384 => "sha3-384",
+        HashType::Sha3_512 => "sha3-512",
+        HashType::Blake2b512 => "blake2b512",
+        HashType::Blake2s256 => "blake2s256",
+        HashType::Blake3 => "blake3",
+        HashType::Lm => "lm",
+        HashType::Mysql41 => "mysql41",
+        HashType::Md5Md5 => "md5-md5",
+    }
+}
+
+/// Converts the bytes given by Python to a digest.
+fn to_digest(digest: &[u8]) -> PyResult<Digest> {
+    digest
+        .try_into()
+        .map_err(|_| PyValueError::new_err("The digest is too long"))
+}
+
+/// The function generating a table with a backend.
+type GenerateFn = fn(
+    cugparck_commons::RainbowTableCtx,
+) -> Result<cugparck_cpu::SimpleTable, cugparck_cpu::CugparckError>;
+
+/// Returns the function generating a table with the backend named like the backends of the CLI.
+fn generate_fn(backend: &str) -> PyResult<GenerateFn> {
+    use cugparck_cpu::SimpleTable;
+
+    Ok(match backend {
+        "cpu" => SimpleTable::new_blocking::<backend::Cpu>,
+        #[cfg(feature = "cuda")]
+        "cuda" => SimpleTable::new_blocking::<backend::Cuda>,
+        #[cfg(all(feature = "wgpu", any(target_os = "windows", target_os = "linux")))]
+        "vulkan" => SimpleTable::new_blocking::<backend::Vulkan>,
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        "dx12" => SimpleTable::new_blocking::<backend::Dx12>,
+        #[cfg(all(feature = "wgpu", target_os = "windows"))]
+        "dx11" => SimpleTable::new_blocking::<backend::Dx11>,
+        #[cfg(all(feature = "wgpu", target_os = "macos"))]
+        "metal" => SimpleTable::new_blocking::<backend::Metal>,
+        #[cfg(all(feature = "wgpu", target_os = "linux"))]
+        "opengl" => SimpleTable::new_blocking::<backend::OpenGL>,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "The {backend} backend is not available"
+            )))
+        }
+    })
+}
+
+/// A builder for a rainbow table context.
+/// Each method returns the builder, so the calls can be chained.
+#[pyclass]
+#[derive(Default)]
+struct RainbowTableCtxBuilder(cugparck_cpu::RainbowTableCtxBuilder);
+
+#[pymethods]
+impl RainbowTableCtxBuilder {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the hash function, named like in the CLI (`ntlm`, `md5`, `sha2-256`...).
+    fn hash<'a>(mut slf: PyRefMut<'a, Self>, hash_type: &str) -> PyResult<PyRefMut<'a, Self>> {
+        slf.0 = slf.0.hash(parse_hash_type(hash_type)?);
+        Ok(slf)
+    }
+
+    /// Sets the charset.
+    fn charset<'a>(mut slf: PyRefMut<'a, Self>, charset: &[u8]) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.charset(charset);
+        slf
+    }
+
+    /// Sets a charset of Unicode characters, and encodes the passwords in UTF-8.
+    fn utf8_charset<'a>(mut slf: PyRefMut<'a, Self>, charset: &str) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.utf8_charset(charset);
+        slf
+    }
+
+    /// Sets a salt concatenated after the passwords, or before them if `prefix` is true.
+    #[pyo3(signature = (salt, prefix = false))]
+    fn salt<'a>(mut slf: PyRefMut<'a, Self>, salt: &[u8], prefix: bool) -> PyRefMut<'a, Self> {
+        let position = if prefix {
+            SaltPosition::Prefix
+        } else {
+            SaltPosition::Suffix
+        };
+        slf.0 = slf.0.salt(salt, position);
+        slf
+    }
+
+    /// Sets the length of the chains.
+    fn chain_length<'a>(mut slf: PyRefMut<'a, Self>, chain_length: usize) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.chain_length(chain_length);
+        slf
+    }
+
+    /// Sets the minimum password length.
+    fn min_password_length<'a>(mut slf: PyRefMut<'a, Self>, length: u8) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.min_password_length(length);
+        slf
+    }
+
+    /// Sets the maximum password length.
+    fn max_password_length<'a>(mut slf: PyRefMut<'a, Self>, length: u8) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.max_password_length(length);
+        slf
+    }
+
+    /// Sets the table number. Table numbers are 1-indexed.
+    fn table_number<'a>(mut slf: PyRefMut<'a, Self>, table_number: u8) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.table_number(table_number);
+        slf
+    }
+
+    /// Sets the maximality factor, which determines the number of startpoints.
+    fn alpha<'a>(mut slf: PyRefMut<'a, Self>, alpha: f64) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.alpha(alpha);
+        slf
+    }
+
+    /// Chooses the startpoints pseudo-randomly from a seed, so that the table can be reproduced.
+    fn startpoint_seed<'a>(mut slf: PyRefMut<'a, Self>, seed: u64) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.startpoint_seed(Some(seed));
+        slf
+    }
+
+    /// Sets the number of filtrations of the chains during the generation.
+    fn filter_count<'a>(mut slf: PyRefMut<'a, Self>, filter_count: usize) -> PyRefMut<'a, Self> {
+        slf.0 = slf.0.filter_count(filter_count);
+        slf
+    }
+
+    /// Builds the context.
+    fn build(&self) -> PyResult<RainbowTableCtx> {
+        self.0.build().map(RainbowTableCtx).map_err(py_err)
+    }
+}
+
+/// The parameters of a rainbow table.
+#[pyclass]
+#[derive(Clone, Copy)]
+struct RainbowTableCtx(cugparck_commons::RainbowTableCtx);
+
+#[pymethods]
+impl RainbowTableCtx {
+    /// The name of the hash function.
+    #[getter]
+    fn hash_type(&self) -> &'static str {
+        hash_type_name(self.0.hash_type)
+    }
+
+    /// The length of the chains.
+    #[getter]
+    fn chain_length(&self) -> usize {
+        self.0.t
+    }
+
+    /// The number of the table.
+    #[getter]
+    fn table_number(&self) -> usize {
+        self.0.tn
+    }
+
+    /// The size of the search space.
+    #[getter]
+    fn search_space(&self) -> usize {
+        self.0.n
+    }
+
+    /// Returns the digest of a password, with the salt of the context.
+    fn digest<'py>(&self, py: Python<'py>, password: &[u8]) -> PyResult<&'py PyBytes> {
+        if password.len() > cugparck_commons::MAX_PASSWORD_LENGTH_ALLOWED {
+            return Err(PyValueError::new_err("The password is too long"));
+        }
+
+        Ok(PyBytes::new(py, &self.0.hash(&Password::new(password))))
+    }
+}
+
+/// A rainbow table in memory, as generated.
+#[pyclass]
+struct SimpleTable(cugparck_cpu::SimpleTable);
+
+#[pymethods]
+impl SimpleTable {
+    /// Generates a table with a backend named like in the CLI (`cpu`, `cuda`, `vulkan`...).
+    /// The GIL is released during the generation.
+    #[staticmethod]
+    #[pyo3(signature = (ctx, backend = "cpu"))]
+    fn generate(py: Python<'_>, ctx: RainbowTableCtx, backend: &str) -> PyResult<Self> {
+        let generate = generate_fn(backend)?;
+
+        py.allow_threads(|| generate(ctx.0))
+            .map(Self)
+            .map_err(py_err)
+    }
+
+    /// The context of the table.
+    #[getter]
+    fn ctx(&self) -> RainbowTableCtx {
+        RainbowTableCtx(self.0.ctx())
+    }
+
+    /// Stores the table to a file, which can be loaded with `Table.load`.
+    fn store(&self, path: PathBuf) -> PyResult<()> {
+        self.0.store(&path).map_err(py_err)
+    }
+
+    /// Stores the table compressed with delta encoding to a file, which can be loaded with `Table.load`.
+    fn store_compressed(&self, path: PathBuf) -> PyResult<()> {
+        CompressedTable::from_rainbow_table(&self.0)
+            .store(&path)
+            .map_err(py_err)
+    }
+
+    /// Searches for the password of a digest, and returns `None` if it is not in the table.
+    fn search<'py>(&self, py: Python<'py>, digest: &[u8]) -> PyResult<Option<&'py PyBytes>> {
+        let digest = to_digest(digest)?;
+        let password = py.allow_threads(|| self.0.search(digest));
+
+        Ok(password.map(|password| PyBytes::new(py, &password)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// A reference to a table archived in a memory map.
+#[derive(Clone, Copy)]
+enum ArchivedTable {
+    Simple(&'static ArchivedSimpleTable),
+    Compressed(&'static ArchivedCompressedTable),
+}
+
+impl ArchivedTable {
+    fn ctx(&self) -> cugparck_commons::RainbowTableCtx {
+        match self {
+            Self::Simple(table) => table.ctx(),
+            Self::Compressed(table) => table.ctx(),
+        }
+    }
+}
+
+/// A rainbow table stored in a file, simple (`.rt`) or compressed with delta encoding (`.rtcde`).
+/// The file is memory mapped, so large tables can be loaded quickly.
+#[pyclass]
+struct Table {
+    // the table borrows the memory map, so it has to be dropped first.
+    table: ArchivedTable,
+    _mmap: Mmap,
+}
+
+#[pymethods]
+impl Table {
+    /// Loads a table from a file.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let compressed = match path.extension().and_then(|s| s.to_str()) {
+            Some("rt") => false,
+            Some("rtcde") => true,
+            _ => {
+                return Err(PyValueError::new_err(
+                    "Only .rt and .rtcde tables can be loaded",
+                ))
+            }
+        };
+
+        // SAFETY: the file is not being modified anywhere else.
+        let mmap = unsafe { Mmap::map(&File::open(path)?)? };
+
+        // SAFETY: the memory of a memory map doesn't move when the map is moved,
+        // and the table is dropped before the memory map.
+        let table = unsafe {
+            if compressed {
+                ArchivedTable::Compressed(
+                    &*(CompressedTable::load(&mmap).map_err(py_err)?
+                        as *const ArchivedCompressedTable),
+                )
+            } else {
+                ArchivedTable::Simple(
+                    &*(cugparck_cpu::SimpleTable::load(&mmap).map_err(py_err)?
+                        as *const ArchivedSimpleTable),
+                )
+            }
+        };
+
+        Ok(Self { table, _mmap: mmap })
+    }
+
+    /// The context of the table.
+    #[getter]
+    fn ctx(&self) -> RainbowTableCtx {
+        RainbowTableCtx(self.table.ctx())
+    }
+
+    /// Searches for the password of a digest, and returns `None` if it is not in the table.
+    fn search<'py>(&self, py: Python<'py>, digest: &[u8]) -> PyResult<Option<&'py PyBytes>> {
+        let digest = to_digest(digest)?;
+        let table = self.table;
+        let password = py.allow_threads(|| match table {
+            ArchivedTable::Simple(table) => table.search(digest),
+            ArchivedTable::Compressed(table) => table.search(digest),
+        });
+
+        Ok(password.map(|password| PyBytes::new(py, &password)))
+    }
+
+    fn __len__(&self) -> usize {
+        match self.table {
+            ArchivedTable::Simple(table) => table.len(),
+            ArchivedTable::Compressed(table) => table.len(),
+        }
+    }
+}
+
+/// The archived tables of a cluster, which are all of the same kind.
+enum ClusterTables {
+    Simple(Vec<&'static ArchivedSimpleTable>),
+    Compressed(Vec<&'static ArchivedCompressedTable>),
+}
+
+/// A cluster of tables generated with the same parameters, to improve the success rate.
+#[pyclass]
+struct TableCluster {
+    // the tables borrow the memory maps of the `Table` objects, which are kept alive.
+    tables: ClusterTables,
+    _owners: Vec<Py<Table>>,
+}
+
+#[pymethods]
+impl TableCluster {
+    /// Creates a cluster from tables which only differ by their table number.
+    #[new]
+    fn new(tables: Vec<Py<Table>>, py: Python<'_>) -> PyResult<Self> {
+        let archived = tables
+            .iter()
+            .map(|table| table.borrow(py).table)
+            .collect::<Vec<_>>();
+
+        let mut validator = TableSetValidator::new();
+        for table in &archived {
+            validator.add(table.ctx()).map_err(py_err)?;
+        }
+
+        let cluster_tables = match archived.first() {
+            None => return Err(PyValueError::new_err("A cluster needs at least one table")),
+            Some(ArchivedTable::Simple(_)) => ClusterTables::Simple(
+                archived
+                    .iter()
+                    .map(|table| match table {
+                        ArchivedTable::Simple(table) => Ok(*table),
+                        ArchivedTable::Compressed(_) => Err(()),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| mixed_tables())?,
+            ),
+            Some(ArchivedTable::Compressed(_)) => ClusterTables::Compressed(
+                archived
+                    .iter()
+                    .map(|table| match table {
+                        ArchivedTable::Compressed(table) => Ok(*table),
+                        ArchivedTable::Simple(_) => Err(()),
+                    })
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| mixed_tables())?,
+            ),
+        };
+
+        Ok(Self {
+            tables: cluster_tables,
+            _owners: tables,
+        })
+    }
+
+    /// Searches for the password of a digest, and returns `None` if it is not in the tables.
+    fn search<'py>(&self, py: Python<'py>, digest: &[u8]) -> PyResult<Option<&'py PyBytes>> {
+        Ok(self.search_batch(py, vec![digest])?.remove(0))
+    }
+
+    /// Searches for the passwords of several digests at once, which is faster than searching them one by one.
+    fn search_batch<'py>(
+        &self,
+        py: Python<'py>,
+        digests: Vec<&[u8]>,
+    ) -> PyResult<Vec<Option<&'py PyBytes>>> {
+        let digests = digests
+            .into_iter()
+            .map(to_digest)
+            .collect::<PyResult<Vec<_>>>()?;
+
+        let hits: Vec<Option<SearchHit>> = py.allow_threads(|| match &self.tables {
+            ClusterTables::Simple(tables) => {
+                cugparck_cpu::TableCluster::new(tables).search_batch_hits(&digests)
+            }
+            ClusterTables::Compressed(tables) => {
+                cugparck_cpu::TableCluster::new(tables).search_batch_hits(&digests)
+            }
+        });
+
+        Ok(hits
+            .into_iter()
+            .map(|hit| hit.map(|hit| PyBytes::new(py, &hit.password)))
+            .collect())
+    }
+
+    fn __len__(&self) -> usize {
+        match &self.tables {
+            ClusterTables::Simple(tables) => tables.len(),
+            ClusterTables::Compressed(tables) => tables.len(),
+        }
+    }
+}
+
+/// The error raised when simple and compressed tables are mixed in a cluster.
+fn mixed_tables() -> PyErr {
+    PyValueError::new_err("All the tables of a cluster should be of the same type")
+}
+
+#[pymodule]
+#[pyo3(name = "cugparck")]
+fn cugparck_py(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("CugparckError", py.get_type::<CugparckError>())?;
+    m.add_class::<RainbowTableCtxBuilder>()?;
+    m.add_class::<RainbowTableCtx>()?;
+    m.add_class::<SimpleTable>()?;
+    m.add_class::<Table>()?;
+    m.add_class::<TableCluster>()?;
+
+    Ok(())
+}